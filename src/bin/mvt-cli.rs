@@ -0,0 +1,197 @@
+// mvt-cli.rs  mvt command-line tool.
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+#![forbid(unsafe_code)]
+
+use flate2::read::GzDecoder;
+use geojson::{JsonObject, JsonValue};
+use mvt::{Compression, Error, Tile, TileId};
+use std::io::Read;
+use std::process::ExitCode;
+
+/// Usage help text
+const USAGE: &str = "\
+Usage: mvt-cli <command> [args]
+
+Commands:
+  show <tile>                      Describe layers and features
+  stats <tile>                     Show per-layer statistics
+  json <tile>                      Dump tile (in tile coördinates) to JSON
+  geojson <tile> <z/x/y>           Dump tile (in WGS-84) to GeoJSON
+  compress <tile> <out> <codec>    Re-compress tile (gzip, zstd, brotli)
+                                   or decompress (none)
+
+Input tiles may be gzip or zstd compressed.";
+
+/// Command-line error
+#[derive(Debug)]
+enum CliError {
+    /// Invalid arguments
+    Usage,
+    /// Tile error
+    Tile(Error),
+}
+
+impl From<Error> for CliError {
+    fn from(err: Error) -> Self {
+        CliError::Tile(err)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(err: std::io::Error) -> Self {
+        CliError::Tile(Error::Io(err))
+    }
+}
+
+/// Read a tile file, decompressing if necessary
+fn read_tile(path: &str) -> Result<Tile, CliError> {
+    let data = std::fs::read(path)?;
+    let data = match data.as_slice() {
+        [0x1f, 0x8b, ..] => {
+            let mut buf = Vec::new();
+            GzDecoder::new(data.as_slice()).read_to_end(&mut buf)?;
+            buf
+        }
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => zstd::decode_all(data.as_slice())?,
+        _ => data,
+    };
+    Ok(Tile::from_bytes(&data)?)
+}
+
+/// Parse a tile ID (z/x/y)
+fn parse_tid(tid: &str) -> Result<TileId, CliError> {
+    let v: Vec<u32> = tid
+        .split('/')
+        .map(|n| n.parse().map_err(|_| CliError::Usage))
+        .collect::<Result<_, _>>()?;
+    match v[..] {
+        [z, x, y] => Ok(TileId::new(x, y, z)?),
+        _ => Err(CliError::Usage),
+    }
+}
+
+/// Parse a compression codec
+fn parse_codec(codec: &str) -> Result<Option<Compression>, CliError> {
+    match codec {
+        "none" => Ok(None),
+        "gzip" => Ok(Some(Compression::Gzip(9))),
+        "zstd" => Ok(Some(Compression::Zstd(19))),
+        "brotli" => Ok(Some(Compression::Brotli(11))),
+        _ => Err(CliError::Usage),
+    }
+}
+
+/// Convert a tile to JSON, with geometry in tile coördinates
+fn tile_json(tile: &Tile) -> Result<JsonValue, CliError> {
+    let mut layers = Vec::new();
+    for layer in tile.layers() {
+        let mut features = Vec::new();
+        for feature in layer.features() {
+            let mut obj = JsonObject::new();
+            if let Some(id) = feature.id() {
+                obj.insert("id".into(), id.into());
+            }
+            if let (Some(tp), Some(geom)) =
+                (feature.geom_type(), feature.geom_data())
+            {
+                obj.insert("type".into(), format!("{tp:?}").into());
+                let parts: Vec<JsonValue> = geom
+                    .decode()?
+                    .into_iter()
+                    .map(|part| {
+                        part.into_iter()
+                            .map(|(x, y)| JsonValue::from(vec![x, y]))
+                            .collect()
+                    })
+                    .collect();
+                obj.insert("geometry".into(), parts.into());
+            }
+            let props: JsonObject = feature
+                .tags()
+                .map(|(k, v)| (k.to_string(), JsonValue::from(v)))
+                .collect();
+            obj.insert("properties".into(), props.into());
+            features.push(JsonValue::from(obj));
+        }
+        let mut obj = JsonObject::new();
+        obj.insert("name".into(), layer.name().into());
+        obj.insert("version".into(), layer.version().into());
+        obj.insert("extent".into(), layer.extent().into());
+        obj.insert("features".into(), features.into());
+        layers.push(JsonValue::from(obj));
+    }
+    let mut obj = JsonObject::new();
+    obj.insert("layers".into(), layers.into());
+    Ok(obj.into())
+}
+
+/// Print tile statistics
+fn print_stats(tile: &Tile) {
+    let stats = tile.stats();
+    println!(
+        "{:<24} {:>9} {:>9} {:>6} {:>6} {:>9}",
+        "layer", "features", "vertices", "keys", "values", "bytes"
+    );
+    for l in &stats.layers {
+        println!(
+            "{:<24} {:>9} {:>9} {:>6} {:>6} {:>9}",
+            l.name,
+            l.num_features,
+            l.num_vertices,
+            l.num_keys,
+            l.num_values,
+            l.encoded_size
+        );
+    }
+    println!(
+        "{:<24} {:>9} {:>9} {:>6} {:>6} {:>9}",
+        "(total)",
+        stats.num_features,
+        stats.num_vertices,
+        stats.num_keys,
+        stats.num_values,
+        stats.encoded_size
+    );
+}
+
+/// Run a command
+fn run(args: &[String]) -> Result<(), CliError> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args[..] {
+        ["show", path] => print!("{}", read_tile(path)?.describe()),
+        ["stats", path] => print_stats(&read_tile(path)?),
+        ["json", path] => println!("{:#}", tile_json(&read_tile(path)?)?),
+        ["geojson", path, tid] => {
+            let tid = parse_tid(tid)?;
+            println!("{}", read_tile(path)?.to_geojson(tid)?);
+        }
+        ["compress", path, out, codec] => {
+            let codec = parse_codec(codec)?;
+            let tile = read_tile(path)?;
+            let data = match codec {
+                Some(codec) => tile.to_bytes_compressed(codec)?,
+                None => tile.to_bytes()?,
+            };
+            std::fs::write(out, data)?;
+        }
+        _ => return Err(CliError::Usage),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage) => {
+            eprintln!("{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(CliError::Tile(err)) => {
+            eprintln!("mvt-cli: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// clip.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Clipping geometry to bounding boxes.
+//!
+use pointy::{BBox, Float, Pt, Seg};
+
+/// Clip points to a bounding box.
+pub(crate) fn clip_points<F>(pts: &[Pt<F>], bbox: BBox<F>) -> Vec<Pt<F>>
+where
+    F: Float,
+{
+    pts.iter()
+        .filter(|p| {
+            p.x >= bbox.x_min()
+                && p.x <= bbox.x_max()
+                && p.y >= bbox.y_min()
+                && p.y <= bbox.y_max()
+        })
+        .copied()
+        .collect()
+}
+
+/// Clip a linestring to a bounding box.
+///
+/// Returns one linestring for each run within the bounding box.
+pub(crate) fn clip_line<F>(pts: &[Pt<F>], bbox: BBox<F>) -> Vec<Vec<Pt<F>>>
+where
+    F: Float,
+{
+    let mut lines = Vec::new();
+    let mut line: Vec<Pt<F>> = Vec::new();
+    for w in pts.windows(2) {
+        let seg = Seg::new(w[0], w[1]);
+        match seg.clip(bbox) {
+            Some(clipped) if clipped.p0 != clipped.p1 => {
+                if line.last() != Some(&clipped.p0) {
+                    if line.len() > 1 {
+                        lines.push(std::mem::take(&mut line));
+                    }
+                    line.clear();
+                    line.push(clipped.p0);
+                }
+                line.push(clipped.p1);
+                if clipped.p1 != seg.p1 && line.len() > 1 {
+                    // segment exits the bounding box
+                    lines.push(std::mem::take(&mut line));
+                }
+            }
+            _ => (),
+        }
+    }
+    if line.len() > 1 {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Clip a polygon ring to a bounding box (Sutherland–Hodgman).
+///
+/// Each point has a tag, which is copied to clipped points.  Boundary points
+/// get a tag from `lerp`, called with the tags of the clipped segment and
+/// the position of the boundary point along it (0 to 1).
+pub(crate) fn clip_ring<F, T>(
+    pts: &[(Pt<F>, T)],
+    bbox: BBox<F>,
+    lerp: impl Fn(T, T, F) -> T,
+) -> Vec<(Pt<F>, T)>
+where
+    F: Float,
+    T: Copy,
+{
+    let mut ring = pts.to_vec();
+    for edge in 0..4 {
+        let inside = |(p, _): (Pt<F>, T)| match edge {
+            0 => p.x >= bbox.x_min(),
+            1 => p.x <= bbox.x_max(),
+            2 => p.y >= bbox.y_min(),
+            _ => p.y <= bbox.y_max(),
+        };
+        let intersect = |(p0, tag0): (Pt<F>, T), (p1, tag1): (Pt<F>, T)| {
+            let (v, v0, v1) = match edge {
+                0 => (bbox.x_min(), p0.x, p1.x),
+                1 => (bbox.x_max(), p0.x, p1.x),
+                2 => (bbox.y_min(), p0.y, p1.y),
+                _ => (bbox.y_max(), p0.y, p1.y),
+            };
+            let t = (v - v0) / (v1 - v0);
+            let mut p = p0 + (p1 - p0) * t;
+            // snap exactly onto the edge
+            if edge < 2 {
+                p.x = v;
+            } else {
+                p.y = v;
+            }
+            (p, lerp(tag0, tag1, t))
+        };
+        let input = std::mem::take(&mut ring);
+        let n = input.len();
+        for i in 0..n {
+            let p0 = input[(i + n - 1) % n];
+            let p1 = input[i];
+            match (inside(p0), inside(p1)) {
+                (true, true) => ring.push(p1),
+                (true, false) => ring.push(intersect(p0, p1)),
+                (false, true) => {
+                    ring.push(intersect(p0, p1));
+                    ring.push(p1);
+                }
+                (false, false) => (),
+            }
+        }
+    }
+    ring
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn line() {
+        let bbox = BBox::new([(0.0, 0.0), (10.0, 10.0)]);
+        let pts = [
+            Pt::new(-5.0, 5.0),
+            Pt::new(5.0, 5.0),
+            Pt::new(15.0, 5.0),
+            Pt::new(15.0, 8.0),
+            Pt::new(5.0, 8.0),
+        ];
+        let lines = clip_line(&pts, bbox);
+        assert_eq!(
+            lines,
+            vec![
+                vec![Pt::new(0.0, 5.0), Pt::new(5.0, 5.0), Pt::new(10.0, 5.0)],
+                vec![Pt::new(10.0, 8.0), Pt::new(5.0, 8.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn ring() {
+        let bbox = BBox::new([(0.0, 0.0), (10.0, 10.0)]);
+        let pts = [
+            Pt::new(5.0, 5.0),
+            Pt::new(15.0, 5.0),
+            Pt::new(15.0, 15.0),
+            Pt::new(5.0, 15.0),
+        ];
+        let pts: Vec<_> = pts.into_iter().zip(0..).collect();
+        let ring = clip_ring(&pts, bbox, |_t0, t1, _t| t1);
+        assert_eq!(
+            ring,
+            vec![
+                (Pt::new(5.0, 10.0), 0),
+                (Pt::new(5.0, 5.0), 0),
+                (Pt::new(10.0, 5.0), 1),
+                (Pt::new(10.0, 10.0), 3),
+            ]
+        );
+    }
+}
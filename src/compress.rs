@@ -0,0 +1,136 @@
+// compress.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Compressed tile output.
+//!
+use crate::error::Result;
+use crate::tile::Tile;
+#[cfg(feature = "flate2")]
+use flate2::write::GzEncoder;
+
+/// Compression codec and level for tile output.
+///
+/// See [Tile::to_bytes_compressed].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Compression {
+    /// Gzip, with level from 0 (none) to 9 (best)
+    #[cfg(feature = "flate2")]
+    Gzip(u32),
+
+    /// Zstandard, with level from 1 (fastest) to 22 (best); 0 is the
+    /// default level
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+
+    /// Brotli, with quality from 0 (fastest) to 11 (best)
+    #[cfg(feature = "brotli")]
+    Brotli(u32),
+}
+
+impl Tile {
+    /// Encode the tile and compress it.
+    ///
+    /// * `compression` Compression codec and level.
+    ///
+    /// The tile is streamed into the compressor, so the uncompressed tile
+    /// is never buffered.
+    pub fn to_bytes_compressed(
+        &self,
+        compression: Compression,
+    ) -> Result<Vec<u8>> {
+        // compressed tiles are typically less than half the size
+        let buf = Vec::with_capacity(self.compute_size() / 2);
+        match compression {
+            #[cfg(feature = "flate2")]
+            Compression::Gzip(level) => {
+                let level = flate2::Compression::new(level.min(9));
+                let mut enc = GzEncoder::new(buf, level);
+                self.write_to(&mut enc)?;
+                Ok(enc.finish()?)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => {
+                let mut enc = zstd::Encoder::new(buf, level)?;
+                self.write_to(&mut enc)?;
+                Ok(enc.finish()?)
+            }
+            #[cfg(feature = "brotli")]
+            Compression::Brotli(quality) => {
+                const BUFFER_SIZE: usize = 4096;
+                const WINDOW_BITS: u32 = 22;
+                let mut enc = brotli::CompressorWriter::new(
+                    buf,
+                    BUFFER_SIZE,
+                    quality.min(11),
+                    WINDOW_BITS,
+                );
+                self.write_to(&mut enc)?;
+                Ok(enc.into_inner())
+            }
+        }
+    }
+
+    /// Encode the tile and compress it with gzip.
+    ///
+    /// * `level` Compression level, from 0 (none) to 9 (best).
+    ///
+    /// The result is ready for storage or an HTTP response with
+    /// `Content-Encoding: gzip`.
+    #[cfg(feature = "flate2")]
+    pub fn to_bytes_gzip(&self, level: u32) -> Result<Vec<u8>> {
+        self.to_bytes_compressed(Compression::Gzip(level))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomType;
+    use crate::fixture::make_tile;
+
+    /// Make a tile for testing
+    fn line_tile() -> Tile {
+        let pts: Vec<_> =
+            (0..100).map(|i| (f64::from(i) * 10.0, 20.0)).collect();
+        make_tile("First Layer", GeomType::Linestring, &pts, |f| {
+            f.add_tag_string("key", "value")
+        })
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gzip() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+        let tile = line_tile();
+        let gz = tile.to_bytes_gzip(6).unwrap();
+        assert_eq!(&gz[..2], &[0x1F, 0x8B]);
+        let mut bytes = Vec::new();
+        GzDecoder::new(&gz[..]).read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, tile.to_bytes().unwrap());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd() {
+        let tile = line_tile();
+        let z = tile.to_bytes_compressed(Compression::Zstd(3)).unwrap();
+        let bytes = zstd::decode_all(&z[..]).unwrap();
+        assert_eq!(bytes, tile.to_bytes().unwrap());
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn brotli() {
+        use std::io::Read;
+        let tile = line_tile();
+        let br = tile.to_bytes_compressed(Compression::Brotli(5)).unwrap();
+        let mut bytes = Vec::new();
+        brotli::Decompressor::new(&br[..], 4096)
+            .read_to_end(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, tile.to_bytes().unwrap());
+    }
+}
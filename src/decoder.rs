@@ -0,0 +1,652 @@
+// decoder.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Decoding of MVT tiles.
+//!
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::{Error, Result};
+use crate::simplify::signed_area;
+use crate::tile::Tile;
+#[cfg(feature = "v3")]
+use crate::v3::AttrValue;
+use crate::value::TagValue;
+use crate::vector_tile::Tile as VecTile;
+use crate::vector_tile::tile::{
+    Feature as VtFeature, GeomType as VtGeomType, Layer as VtLayer, Value,
+};
+use protobuf::Message;
+use std::collections::BTreeMap;
+
+/// Read-only view of a layer in a [Tile].
+///
+/// Obtain with [Tile::layers] or [Tile::layer].
+#[derive(Clone, Copy)]
+pub struct LayerRef<'a> {
+    layer: &'a VtLayer,
+}
+
+/// Read-only view of a feature in a layer.
+///
+/// Obtain with [LayerRef::features].
+#[derive(Clone, Copy)]
+pub struct FeatureRef<'a> {
+    layer: &'a VtLayer,
+    feature: &'a VtFeature,
+}
+
+impl Tile {
+    /// Decode a tile from bytes.
+    ///
+    /// * `bytes` Encoded tile data.
+    ///
+    /// Returns an error if the protobuf data is invalid, if the layers do
+    /// not have matching extents, or if any layer is not version 2
+    /// (`Error::UnsupportedVersion`).  See [from_bytes_v1] for legacy tiles.
+    ///
+    /// [from_bytes_v1]: #method.from_bytes_v1
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut vec_tile = VecTile::parse_from_bytes(bytes)?;
+        check_versions(&mut vec_tile, false)?;
+        Tile::from_vec_tile(vec_tile)
+    }
+
+    /// Decode a tile from bytes, accepting version 1 layers.
+    ///
+    /// * `bytes` Encoded tile data.
+    ///
+    /// Version 1 layers are normalized to version 2 semantics:
+    /// * Polygon rings are rewound so that exterior rings have positive area
+    ///   (the winding of the first ring of each feature is taken as
+    ///   exterior)
+    /// * Linestring and polygon command streams are re-encoded with one
+    ///   `MoveTo` per part
+    /// * The layer version is set to 2
+    ///
+    /// This allows legacy tilesets to be migrated by re-encoding each tile.
+    pub fn from_bytes_v1(bytes: &[u8]) -> Result<Self> {
+        let mut vec_tile = VecTile::parse_from_bytes(bytes)?;
+        check_versions(&mut vec_tile, true)?;
+        Tile::from_vec_tile(vec_tile)
+    }
+
+    /// Decode a tile from bytes, keeping only some features.
+    ///
+    /// * `bytes` Encoded tile data.
+    /// * `filter` Predicate called with the layer name and each feature;
+    ///   features are kept only if it returns `true`.
+    ///
+    /// Geometry of rejected features is never decoded, and unused entries
+    /// are removed from layer key/value tables.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::Error;
+    /// # fn main() -> Result<(), Error> {
+    /// use mvt::{TagValue, Tile};
+    ///
+    /// # let bytes = Tile::new(4096).to_bytes()?;
+    /// let tile = Tile::from_bytes_filtered(&bytes, |layer, feature| {
+    ///     layer != "debug"
+    ///         && feature.tags().any(|(k, v)| {
+    ///             k == "class" && v == TagValue::String("major".into())
+    ///         })
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_bytes_filtered<F>(bytes: &[u8], mut filter: F) -> Result<Self>
+    where
+        F: FnMut(&str, FeatureRef) -> bool,
+    {
+        let mut vec_tile = VecTile::parse_from_bytes(bytes)?;
+        check_versions(&mut vec_tile, false)?;
+        for layer in vec_tile.layers.iter_mut() {
+            let features = std::mem::take(&mut layer.features);
+            let keep: Vec<bool> = features
+                .iter()
+                .map(|feature| {
+                    let feature = FeatureRef { layer, feature };
+                    filter(layer.name(), feature)
+                })
+                .collect();
+            layer.features = features
+                .into_iter()
+                .zip(keep)
+                .filter_map(|(f, keep)| keep.then_some(f))
+                .collect();
+            // keys of version 3 layers can be used by attributes
+            if layer.version() == 2 {
+                compact_tables(layer);
+            }
+        }
+        Tile::from_vec_tile(vec_tile)
+    }
+}
+
+/// Get the geometry type of a feature
+fn geom_type(feature: &VtFeature) -> Option<GeomType> {
+    match feature.type_.map(|tp| tp.enum_value()) {
+        Some(Ok(VtGeomType::POINT)) => Some(GeomType::Point),
+        Some(Ok(VtGeomType::LINESTRING)) => Some(GeomType::Linestring),
+        Some(Ok(VtGeomType::POLYGON)) => Some(GeomType::Polygon),
+        _ => None,
+    }
+}
+
+/// Check layer versions, normalizing version 1 layers if allowed
+fn check_versions(vec_tile: &mut VecTile, v1: bool) -> Result<()> {
+    for layer in vec_tile.layers.iter_mut() {
+        match layer.version() {
+            2 => (),
+            #[cfg(feature = "v3")]
+            3 => (),
+            1 if v1 => {
+                normalize_v1(layer).map_err(|e| e.with_layer(layer.name()))?
+            }
+            version => {
+                log::debug!("unsupported layer version: {version}");
+                return Err(
+                    Error::UnsupportedVersion(version).with_layer(layer.name())
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Normalize a version 1 layer to version 2 semantics
+fn normalize_v1(layer: &mut VtLayer) -> Result<()> {
+    for (i, feature) in layer.features.iter_mut().enumerate() {
+        let geom_tp = match geom_type(feature) {
+            Some(GeomType::Point) | None => continue,
+            Some(geom_tp) => geom_tp,
+        };
+        let data = std::mem::take(&mut feature.geometry);
+        let geom = GeomData::from_vec(geom_tp, data);
+        feature.geometry = normalize_geometry(geom_tp, &geom)
+            .map_err(|e| e.with_feature(i))?
+            .into_vec();
+    }
+    layer.set_version(2);
+    Ok(())
+}
+
+/// Re-encode version 1 linestring / polygon geometry
+fn normalize_geometry(geom_tp: GeomType, geom: &GeomData) -> Result<GeomData> {
+    let mut parts = geom.decode()?;
+    if geom_tp == GeomType::Polygon {
+        let first = parts
+            .iter()
+            .map(|ring| signed_area(ring))
+            .find(|area| *area != 0.0);
+        if first.is_some_and(|area| area < 0.0) {
+            for ring in parts.iter_mut() {
+                ring[1..].reverse();
+            }
+        }
+    }
+    let mut enc = GeomEncoder::<f64>::new(geom_tp).verbatim(true);
+    for part in parts {
+        for (x, y) in part {
+            enc.add_point_i32(x, y)?;
+        }
+        enc.complete_geom()?;
+    }
+    enc.encode()
+}
+
+/// Remove unused keys and values from a layer, re-indexing feature tags
+fn compact_tables(layer: &mut VtLayer) {
+    let mut keys = vec![None; layer.keys.len()];
+    let mut values = vec![None; layer.values.len()];
+    let (mut n_keys, mut n_values) = (0u32, 0u32);
+    for feature in layer.features.iter_mut() {
+        let tags = std::mem::take(&mut feature.tags);
+        for kv in tags.chunks_exact(2) {
+            let (k, v) = (kv[0] as usize, kv[1] as usize);
+            // tags with invalid indices are dropped
+            if k < keys.len() && v < values.len() {
+                feature.tags.push(*keys[k].get_or_insert_with(|| {
+                    n_keys += 1;
+                    n_keys - 1
+                }));
+                feature.tags.push(*values[v].get_or_insert_with(|| {
+                    n_values += 1;
+                    n_values - 1
+                }));
+            }
+        }
+    }
+    let old_keys = std::mem::take(&mut layer.keys);
+    let mut new_keys = vec![String::new(); n_keys as usize];
+    for (key, pos) in old_keys.into_iter().zip(keys) {
+        if let Some(pos) = pos {
+            new_keys[pos as usize] = key;
+        }
+    }
+    layer.keys = new_keys;
+    let old_values = std::mem::take(&mut layer.values);
+    let mut new_values = vec![Default::default(); n_values as usize];
+    for (value, pos) in old_values.into_iter().zip(values) {
+        if let Some(pos) = pos {
+            new_values[pos as usize] = value;
+        }
+    }
+    layer.values = new_values;
+}
+
+impl<'a> LayerRef<'a> {
+    /// Create a new layer view
+    pub(crate) fn new(layer: &'a VtLayer) -> Self {
+        LayerRef { layer }
+    }
+
+    /// Get the layer name.
+    pub fn name(&self) -> &'a str {
+        self.layer.name()
+    }
+
+    /// Get the layer version.
+    pub fn version(&self) -> u32 {
+        self.layer.version()
+    }
+
+    /// Get the layer extent.
+    pub fn extent(&self) -> u32 {
+        self.layer.extent()
+    }
+
+    /// Get number of features (count).
+    pub fn num_features(&self) -> usize {
+        self.layer.features.len()
+    }
+
+    /// Get number of keys in the key table.
+    pub fn keys_len(&self) -> usize {
+        self.layer.keys.len()
+    }
+
+    /// Get number of values in the value table.
+    pub fn values_len(&self) -> usize {
+        self.layer.values.len()
+    }
+
+    /// Get the encoded size in bytes, including the field header.
+    pub(crate) fn encoded_size(&self) -> usize {
+        let len = self.layer.compute_size();
+        let header = 1 + protobuf::rt::compute_raw_varint64_size(len);
+        (header + len) as usize
+    }
+
+    /// Get an iterator of features.
+    pub fn features(&self) -> impl Iterator<Item = FeatureRef<'a>> + use<'a> {
+        let layer = self.layer;
+        layer
+            .features
+            .iter()
+            .map(move |feature| FeatureRef { layer, feature })
+    }
+}
+
+impl<'a> FeatureRef<'a> {
+    /// Get the feature ID (if set).
+    pub fn id(&self) -> Option<u64> {
+        self.feature.id
+    }
+
+    /// Get the geometry type.
+    ///
+    /// Returns `None` for unknown geometry.
+    pub fn geom_type(&self) -> Option<GeomType> {
+        geom_type(self.feature)
+    }
+
+    /// Get the geometry data.
+    ///
+    /// Returns `None` for unknown geometry.
+    pub fn geom_data(&self) -> Option<GeomData> {
+        let geom_tp = self.geom_type()?;
+        Some(GeomData::from_vec(geom_tp, self.feature.geometry.clone()))
+    }
+
+    /// Get the attributes (`v3` feature).
+    ///
+    /// Attributes use the experimental MVT 3.0 draft encoding (see
+    /// [Feature::add_attribute](crate::Feature::add_attribute)).  Returns
+    /// `Error::InvalidData` if the attributes are malformed.
+    #[cfg(feature = "v3")]
+    pub fn attributes(&self) -> Result<Vec<(String, AttrValue)>> {
+        crate::v3::decode_attributes(self.layer, self.feature)
+    }
+
+    /// Get the elevation of each vertex (`v3` feature).
+    ///
+    /// Elevations use the experimental MVT 3.0 draft encoding (see
+    /// [GeomEncoder::add_point_3d]).  Returns an empty `Vec` for features
+    /// without elevations, or `Error::InvalidData` if they are malformed.
+    ///
+    /// [GeomEncoder::add_point_3d]: crate::GeomEncoder::add_point_3d
+    #[cfg(feature = "v3")]
+    pub fn elevations(&self) -> Result<Vec<f64>> {
+        crate::v3::decode_elevations(self.layer, self.feature)
+    }
+
+    /// Get the tags (key / value pairs).
+    ///
+    /// Tags with invalid key or value indices are skipped.
+    pub fn tags(&self) -> impl Iterator<Item = (&'a str, TagValue)> + use<'a> {
+        let layer = self.layer;
+        self.feature.tags.chunks_exact(2).filter_map(move |kv| {
+            let key = layer.keys.get(kv[0] as usize);
+            let value = layer.values.get(kv[1] as usize);
+            match (key, value.and_then(TagValue::from_value)) {
+                (Some(key), Some(value)) => Some((key.as_str(), value)),
+                _ => {
+                    log::warn!("Invalid tag indices: {kv:?}");
+                    None
+                }
+            }
+        })
+    }
+
+    /// Get the value of a tag.
+    ///
+    /// * `key` Tag key.
+    ///
+    /// If a key is repeated, the first value is used.
+    pub fn get(&self, key: &str) -> Option<TagValue> {
+        TagValue::from_value(self.value(key)?)
+    }
+
+    /// Get a string tag value.
+    ///
+    /// * `key` Tag key.
+    pub fn get_str(&self, key: &str) -> Option<&'a str> {
+        self.value(key)?.string_value.as_deref()
+    }
+
+    /// Get a boolean tag value.
+    ///
+    /// * `key` Tag key.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key)?.as_bool()
+    }
+
+    /// Get a numeric tag value as `i64`.
+    ///
+    /// * `key` Tag key.
+    ///
+    /// Other numeric types are converted only if lossless.
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get(key)?.as_i64()
+    }
+
+    /// Get a numeric tag value as `u64`.
+    ///
+    /// * `key` Tag key.
+    ///
+    /// Other numeric types are converted only if lossless.
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get(key)?.as_u64()
+    }
+
+    /// Get a numeric tag value as `f64`.
+    ///
+    /// * `key` Tag key.
+    ///
+    /// Other numeric types are converted only if lossless.
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key)?.as_f64()
+    }
+
+    /// Look up the raw value of a tag
+    fn value(&self, key: &str) -> Option<&'a Value> {
+        let layer = self.layer;
+        self.feature.tags.chunks_exact(2).find_map(|kv| {
+            if layer.keys.get(kv[0] as usize)? == key {
+                layer.values.get(kv[1] as usize)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Get the tags as a map of properties.
+    ///
+    /// If a key is repeated, the last value is used.
+    pub fn properties(&self) -> BTreeMap<&'a str, TagValue> {
+        self.tags().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomEncoder;
+
+    #[test]
+    fn roundtrip() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("First Layer");
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .point(0.0, 0.0)
+            .unwrap()
+            .point(1024.0, 2048.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.set_id(7);
+        feature.add_tag_string("key", "value");
+        feature.add_tag_uint("count", 3);
+        let layer = feature.into_layer();
+        tile.add_layer(layer).unwrap();
+        let layer = tile.create_layer("Second Layer");
+        tile.add_layer(layer).unwrap();
+        let bytes = tile.to_bytes().unwrap();
+
+        let tile = Tile::from_bytes(&bytes).unwrap();
+        assert_eq!(tile.extent(), 4096);
+        assert_eq!(tile.num_layers(), 2);
+        let layer = tile.layer("First Layer").unwrap();
+        assert_eq!(layer.version(), 2);
+        assert_eq!(layer.num_features(), 1);
+        let feature = layer.features().next().unwrap();
+        assert_eq!(feature.id(), Some(7));
+        assert_eq!(feature.geom_type(), Some(GeomType::Linestring));
+        let tags: Vec<_> = feature.tags().collect();
+        assert_eq!(
+            tags,
+            vec![
+                ("key", TagValue::String("value".to_string())),
+                ("count", TagValue::U64(3)),
+            ]
+        );
+        let geom = feature.geom_data().unwrap();
+        assert_eq!(geom.decode().unwrap(), vec![vec![(0, 0), (1024, 2048)]]);
+        let names: Vec<_> = tile.layers().map(|l| l.name()).collect();
+        assert_eq!(names, vec!["First Layer", "Second Layer"]);
+
+        // decoded layers can be modified and re-encoded
+        let mut layers = tile.into_layers();
+        let layer = layers.remove(0);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(5.0, 5.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.add_tag_string("key", "value");
+        let layer = feature.into_layer();
+        assert_eq!(layer.num_features(), 2);
+        let mut tile = Tile::new(4096);
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layer("First Layer").unwrap();
+        let feature = layer.features().nth(1).unwrap();
+        assert_eq!(
+            feature.tags().collect::<Vec<_>>(),
+            vec![("key", TagValue::String("value".to_string()))]
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Tile::from_bytes(&[0xFF, 0xFF]).is_err());
+        let mut tile = VecTile::new();
+        let mut layer = VtLayer::new();
+        layer.set_name("a".to_string());
+        layer.set_version(2);
+        layer.set_extent(4096);
+        tile.layers.push(layer);
+        let mut layer = VtLayer::new();
+        layer.set_name("b".to_string());
+        layer.set_version(2);
+        layer.set_extent(512);
+        tile.layers.push(layer);
+        let bytes = tile.write_to_bytes().unwrap();
+        assert!(Tile::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn version_1() {
+        let mut tile = VecTile::new();
+        let mut layer = VtLayer::new();
+        layer.set_name("legacy".to_string());
+        layer.set_version(1);
+        layer.set_extent(4096);
+        // counter-clockwise exterior ring, with clockwise hole
+        let mut feature = VtFeature::new();
+        feature.set_type(VtGeomType::POLYGON);
+        feature.geometry = vec![
+            9, 0, 0, 26, 0, 20, 20, 0, 0, 19, 15, 9, 15, 4, 26, 12, 0, 0, 12,
+            11, 0, 15,
+        ];
+        layer.features.push(feature);
+        // linestring with two LineTo commands
+        let mut feature = VtFeature::new();
+        feature.set_type(VtGeomType::LINESTRING);
+        feature.geometry = vec![9, 0, 0, 10, 20, 0, 10, 0, 20];
+        layer.features.push(feature);
+        tile.layers.push(layer);
+        let bytes = tile.write_to_bytes().unwrap();
+        assert!(matches!(
+            Tile::from_bytes(&bytes).map_err(Error::into_root),
+            Err(Error::UnsupportedVersion(1))
+        ));
+        let tile = Tile::from_bytes_v1(&bytes).unwrap();
+        let layer = tile.layer("legacy").unwrap();
+        assert_eq!(layer.version(), 2);
+        let mut features = layer.features();
+        let geom = features.next().unwrap().geom_data().unwrap();
+        assert!(geom.validate(GeomType::Polygon).is_ok());
+        assert_eq!(
+            geom.decode().unwrap(),
+            [
+                vec![(0, 0), (10, 0), (10, 10), (0, 10)],
+                vec![(2, 2), (2, 8), (8, 8), (8, 2)],
+            ]
+        );
+        let geom = features.next().unwrap().geom_data().unwrap();
+        assert_eq!(geom.into_vec(), [9, 0, 0, 18, 20, 0, 0, 20]);
+        // normalized tiles can be re-encoded
+        let bytes = tile.to_bytes().unwrap();
+        assert_eq!(Tile::from_bytes(&bytes).unwrap().num_layers(), 1);
+    }
+
+    #[test]
+    fn filtered() {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("roads");
+        for (i, class) in ["major", "minor", "major"].iter().enumerate() {
+            let geom = GeomEncoder::new(GeomType::Point)
+                .point(i as f64, 0.0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            let mut feature = layer.into_feature(geom);
+            feature.set_id(i as u64);
+            feature.add_tag_string("name", &format!("road {i}"));
+            feature.add_tag_string("class", class);
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer).unwrap();
+        let layer = tile.create_layer("debug");
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 1.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.add_tag_string("class", "major");
+        tile.add_layer(feature.into_layer()).unwrap();
+        let bytes = tile.to_bytes().unwrap();
+
+        let major = TagValue::String("major".to_string());
+        let tile = Tile::from_bytes_filtered(&bytes, |layer, feature| {
+            layer == "roads"
+                && feature.tags().any(|(k, v)| k == "class" && v == major)
+        })
+        .unwrap();
+        assert_eq!(tile.num_layers(), 2);
+        assert_eq!(tile.layer("debug").unwrap().num_features(), 0);
+        let layer = tile.layer("roads").unwrap();
+        let ids: Vec<_> = layer.features().map(|f| f.id()).collect();
+        assert_eq!(ids, [Some(0), Some(2)]);
+        let feature = layer.features().nth(1).unwrap();
+        assert_eq!(
+            feature.tags().collect::<Vec<_>>(),
+            [
+                ("name", TagValue::String("road 2".to_string())),
+                ("class", major.clone()),
+            ]
+        );
+        // unused keys and values are removed
+        assert_eq!(layer.layer.keys, ["name", "class"]);
+        assert_eq!(layer.layer.values.len(), 3);
+        let debug = tile.layer("debug").unwrap();
+        assert!(debug.layer.keys.is_empty());
+        assert!(debug.layer.values.is_empty());
+    }
+
+    #[test]
+    fn typed_getters() {
+        let mut tile = Tile::new(4096);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("x").into_feature(geom);
+        feature.add_tag_string("name", "a");
+        feature.add_tag_uint("population", 1200);
+        feature.add_tag_sint("depth", -30);
+        feature.add_tag_double("area", 2.0);
+        feature.add_tag_float("ratio", 0.5);
+        feature.add_tag_uint("big", u64::MAX);
+        feature.add_tag_int("huge", i64::MAX);
+        feature.add_tag_bool("open", true);
+        tile.add_layer(feature.into_layer()).unwrap();
+        let layer = tile.layer("x").unwrap();
+        let f = layer.features().next().unwrap();
+        assert_eq!(f.get_str("name"), Some("a"));
+        assert_eq!(f.get_str("population"), None);
+        assert_eq!(f.get_str("missing"), None);
+        assert_eq!(f.get("population"), Some(TagValue::U64(1200)));
+        assert_eq!(f.get_i64("population"), Some(1200));
+        assert_eq!(f.get_f64("population"), Some(1200.0));
+        assert_eq!(f.get_i64("depth"), Some(-30));
+        assert_eq!(f.get_u64("depth"), None);
+        assert_eq!(f.get_u64("area"), Some(2));
+        assert_eq!(f.get_f64("ratio"), Some(0.5));
+        assert_eq!(f.get_i64("ratio"), None);
+        assert_eq!(f.get_i64("big"), None);
+        assert_eq!(f.get_u64("big"), Some(u64::MAX));
+        assert_eq!(f.get_f64("big"), None);
+        assert_eq!(f.get_f64("huge"), None);
+        assert_eq!(f.get_bool("open"), Some(true));
+        assert_eq!(f.get_bool("name"), None);
+        assert_eq!(f.get_i64("name"), None);
+    }
+}
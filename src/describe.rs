@@ -0,0 +1,133 @@
+// describe.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Human-readable tile descriptions.
+//!
+use crate::decoder::{FeatureRef, LayerRef};
+use crate::encoder::GeomType;
+use crate::tile::Tile;
+use crate::value::TagValue;
+use std::fmt::{Result, Write};
+
+/// Format a tag value with its type
+fn fmt_value(value: &TagValue) -> String {
+    match value {
+        TagValue::String(v) => format!("{v:?} (string)"),
+        TagValue::F64(v) => format!("{v} (double)"),
+        TagValue::F32(v) => format!("{v} (float)"),
+        TagValue::I64(v) => format!("{v} (int)"),
+        TagValue::U64(v) => format!("{v} (uint)"),
+        TagValue::Sint(v) => format!("{v} (sint)"),
+        TagValue::Bool(v) => format!("{v} (bool)"),
+    }
+}
+
+/// Get the name of a geometry type
+fn geom_name(geom_tp: Option<GeomType>) -> &'static str {
+    match geom_tp {
+        Some(GeomType::Point) => "point",
+        Some(GeomType::Linestring) => "linestring",
+        Some(GeomType::Polygon) => "polygon",
+        None => "unknown",
+    }
+}
+
+/// Describe a feature
+fn describe_feature(
+    out: &mut String,
+    i: usize,
+    feature: &FeatureRef<'_>,
+) -> Result {
+    writeln!(out, "  feature: {i}")?;
+    match feature.id() {
+        Some(id) => writeln!(out, "    id: {id}")?,
+        None => writeln!(out, "    id: (none)")?,
+    }
+    writeln!(out, "    geomtype: {}", geom_name(feature.geom_type()))?;
+    if let Some(geom) = feature.geom_data() {
+        let stats = geom.stats();
+        writeln!(
+            out,
+            "    geometry: {} parts, {} vertices",
+            stats.num_parts, stats.num_vertices
+        )?;
+    }
+    writeln!(out, "    tags:")?;
+    for (key, value) in feature.tags() {
+        writeln!(out, "      {key} = {}", fmt_value(&value))?;
+    }
+    Ok(())
+}
+
+/// Describe a layer
+fn describe_layer(out: &mut String, layer: &LayerRef<'_>) -> Result {
+    writeln!(out, "layer: {}", layer.name())?;
+    writeln!(out, "  version: {}", layer.version())?;
+    writeln!(out, "  extent: {}", layer.extent())?;
+    writeln!(
+        out,
+        "  features: {}, keys: {}, values: {}",
+        layer.num_features(),
+        layer.keys_len(),
+        layer.values_len()
+    )?;
+    for (i, feature) in layer.features().enumerate() {
+        describe_feature(out, i, &feature)?;
+    }
+    Ok(())
+}
+
+impl Tile {
+    /// Describe the tile in a human-readable format.
+    ///
+    /// Each layer is listed with its features, including IDs, tags and a
+    /// summary of the geometry (type, parts and vertex count), similar to
+    /// `vtzero-show`.  This is useful for debugging encoder output in tests
+    /// and logs.
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        for layer in self.layers() {
+            // writing to a String cannot fail
+            let _ = describe_layer(&mut out, &layer);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomEncoder;
+
+    #[test]
+    fn describe() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("roads");
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .points([(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)])
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.set_id(7);
+        feature.add_tag_string("name", "Main St");
+        feature.add_tag_uint("lanes", 2);
+        tile.add_layer(feature.into_layer()).unwrap();
+        assert_eq!(
+            tile.describe(),
+            "layer: roads
+  version: 2
+  extent: 4096
+  features: 1, keys: 2, values: 2
+  feature: 0
+    id: 7
+    geomtype: linestring
+    geometry: 1 parts, 3 vertices
+    tags:
+      name = \"Main St\" (string)
+      lanes = 2 (uint)
+"
+        );
+    }
+}
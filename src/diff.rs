@@ -0,0 +1,315 @@
+// diff.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Semantic comparison of tiles.
+//!
+use crate::decoder::{FeatureRef, LayerRef};
+use crate::tile::Tile;
+use crate::value::TagValue;
+use std::collections::HashMap;
+
+/// Key identifying a feature within a layer.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FeatureKey {
+    /// Feature ID
+    Id(u64),
+
+    /// Position among features without an ID
+    Index(usize),
+}
+
+/// Kind of change to a feature.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Change {
+    /// Feature only in the other tile
+    Added,
+
+    /// Feature only in this tile
+    Removed,
+
+    /// Feature in both tiles, with differences
+    Changed {
+        /// Geometry type or coördinates differ
+        geometry: bool,
+
+        /// Tags differ
+        tags: bool,
+    },
+}
+
+/// Difference of one feature between two tiles.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeatureDiff {
+    /// Layer name
+    pub layer: String,
+
+    /// Feature key
+    pub key: FeatureKey,
+
+    /// Kind of change
+    pub change: Change,
+}
+
+/// Differences between two tiles.
+///
+/// Created by [Tile::diff].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TileDiff {
+    features: Vec<FeatureDiff>,
+}
+
+impl TileDiff {
+    /// Check if the tiles are equivalent.
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// Get an iterator of all feature differences.
+    pub fn iter(&self) -> impl Iterator<Item = &FeatureDiff> {
+        self.features.iter()
+    }
+
+    /// Get an iterator of added features.
+    pub fn added(&self) -> impl Iterator<Item = &FeatureDiff> {
+        self.iter().filter(|f| f.change == Change::Added)
+    }
+
+    /// Get an iterator of removed features.
+    pub fn removed(&self) -> impl Iterator<Item = &FeatureDiff> {
+        self.iter().filter(|f| f.change == Change::Removed)
+    }
+
+    /// Get an iterator of changed features.
+    pub fn changed(&self) -> impl Iterator<Item = &FeatureDiff> {
+        self.iter()
+            .filter(|f| matches!(f.change, Change::Changed { .. }))
+    }
+
+    /// Add a feature difference
+    fn push(&mut self, layer: &str, key: FeatureKey, change: Change) {
+        self.features.push(FeatureDiff {
+            layer: layer.to_string(),
+            key,
+            change,
+        });
+    }
+
+    /// Compare features of one layer
+    fn diff_layer(
+        &mut self,
+        name: &str,
+        layer: Option<LayerRef>,
+        other: Option<LayerRef>,
+    ) {
+        let other_features = keyed_features(other);
+        let mut others: HashMap<FeatureKey, Vec<usize>> = HashMap::new();
+        // reversed, so duplicate IDs are matched in order by popping
+        for (i, (key, _feature)) in other_features.iter().enumerate().rev() {
+            others.entry(*key).or_default().push(i);
+        }
+        let mut matched = vec![false; other_features.len()];
+        for (key, feature) in keyed_features(layer) {
+            match others.get_mut(&key).and_then(Vec::pop) {
+                Some(i) => {
+                    matched[i] = true;
+                    let other = &other_features[i].1;
+                    let geometry = !geometry_eq(&feature, other);
+                    let tags = sorted_tags(&feature) != sorted_tags(other);
+                    if geometry || tags {
+                        self.push(
+                            name,
+                            key,
+                            Change::Changed { geometry, tags },
+                        );
+                    }
+                }
+                None => self.push(name, key, Change::Removed),
+            }
+        }
+        for ((key, _feature), matched) in other_features.iter().zip(matched) {
+            if !matched {
+                self.push(name, *key, Change::Added);
+            }
+        }
+    }
+}
+
+/// Get features of a layer with keys
+fn keyed_features(layer: Option<LayerRef>) -> Vec<(FeatureKey, FeatureRef)> {
+    let mut index = 0;
+    layer
+        .iter()
+        .flat_map(|layer| layer.features())
+        .map(|feature| match feature.id() {
+            Some(id) => (FeatureKey::Id(id), feature),
+            None => {
+                index += 1;
+                (FeatureKey::Index(index - 1), feature)
+            }
+        })
+        .collect()
+}
+
+/// Check if feature geometries are equivalent
+fn geometry_eq(a: &FeatureRef, b: &FeatureRef) -> bool {
+    if a.geom_type() != b.geom_type() {
+        return false;
+    }
+    let (Some(ga), Some(gb)) = (a.geom_data(), b.geom_data()) else {
+        return true;
+    };
+    match (ga.decode(), gb.decode()) {
+        (Ok(pa), Ok(pb)) => pa == pb,
+        // malformed geometry must be identical
+        _ => ga.into_vec() == gb.into_vec(),
+    }
+}
+
+/// Get feature tags, sorted by key
+fn sorted_tags<'a>(feature: &FeatureRef<'a>) -> Vec<(&'a str, TagValue)> {
+    let mut tags: Vec<_> = feature.tags().collect();
+    tags.sort_by(|a, b| a.0.cmp(b.0));
+    tags
+}
+
+impl Tile {
+    /// Compare with another tile.
+    ///
+    /// * `other` Tile to compare.
+    ///
+    /// Layers are matched by name.  Within a layer, features with an ID are
+    /// matched by ID, and others by their order among features without an
+    /// ID.  Geometry is compared by decoded coördinates and tags by key and
+    /// value, so differences in key/value table ordering or command
+    /// encoding are ignored.
+    pub fn diff(&self, other: &Tile) -> TileDiff {
+        let mut diff = TileDiff::default();
+        for layer in self.layers() {
+            let name = layer.name();
+            diff.diff_layer(name, Some(layer), other.layer(name));
+        }
+        for layer in other.layers() {
+            let name = layer.name();
+            if self.layer(name).is_none() {
+                diff.diff_layer(name, None, Some(layer));
+            }
+        }
+        diff
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Layer;
+
+    fn add_feature(layer: Layer, id: Option<u64>, x: f64, tags: bool) -> Layer {
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(x, 1.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        if let Some(id) = id {
+            feature.set_id(id);
+        }
+        if tags {
+            feature.add_tag_string("a", "1");
+            feature.add_tag_uint("b", 2);
+        } else {
+            feature.add_tag_uint("b", 2);
+            feature.add_tag_string("a", "1");
+        }
+        feature.into_layer()
+    }
+
+    #[test]
+    fn same() {
+        let mut a = Tile::new(4096);
+        let layer = add_feature(a.create_layer("x"), Some(1), 1.0, true);
+        let layer = add_feature(layer, None, 2.0, true);
+        a.add_layer(layer).unwrap();
+        let mut b = Tile::new(4096);
+        // different key/value table order
+        let layer = add_feature(b.create_layer("x"), None, 2.0, false);
+        let layer = add_feature(layer, Some(1), 1.0, false);
+        b.add_layer(layer).unwrap();
+        assert!(a.diff(&b).is_empty());
+        assert!(b.diff(&a).is_empty());
+    }
+
+    #[test]
+    fn changes() {
+        let mut a = Tile::new(4096);
+        let layer = add_feature(a.create_layer("x"), Some(1), 1.0, true);
+        let layer = add_feature(layer, Some(2), 2.0, true);
+        let layer = add_feature(layer, None, 3.0, true);
+        a.add_layer(layer).unwrap();
+        let layer = add_feature(a.create_layer("gone"), Some(9), 1.0, true);
+        a.add_layer(layer).unwrap();
+        let mut b = Tile::new(4096);
+        let layer = add_feature(b.create_layer("x"), Some(1), 5.0, true);
+        let mut layer = add_feature(layer, None, 3.0, true);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(3.0, 1.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        layer = layer.into_feature(geom).into_layer();
+        b.add_layer(layer).unwrap();
+        let layer = add_feature(b.create_layer("new"), Some(4), 1.0, true);
+        b.add_layer(layer).unwrap();
+        let diff = a.diff(&b);
+        let d = |layer: &str, key, change| FeatureDiff {
+            layer: layer.to_string(),
+            key,
+            change,
+        };
+        assert_eq!(
+            diff.iter().cloned().collect::<Vec<_>>(),
+            [
+                d(
+                    "x",
+                    FeatureKey::Id(1),
+                    Change::Changed {
+                        geometry: true,
+                        tags: false,
+                    }
+                ),
+                d("x", FeatureKey::Id(2), Change::Removed),
+                d("x", FeatureKey::Index(1), Change::Added),
+                d("gone", FeatureKey::Id(9), Change::Removed),
+                d("new", FeatureKey::Id(4), Change::Added),
+            ]
+        );
+        assert_eq!(diff.added().count(), 2);
+        assert_eq!(diff.removed().count(), 2);
+        assert_eq!(diff.changed().count(), 1);
+    }
+
+    #[test]
+    fn tags_changed() {
+        let mut a = Tile::new(4096);
+        let layer = add_feature(a.create_layer("x"), None, 1.0, true);
+        a.add_layer(layer).unwrap();
+        let mut b = Tile::new(4096);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 1.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = b.create_layer("x").into_feature(geom);
+        feature.add_tag_string("a", "1");
+        b.add_layer(feature.into_layer()).unwrap();
+        let diff = a.diff(&b);
+        assert_eq!(
+            diff.changed().next().unwrap().change,
+            Change::Changed {
+                geometry: false,
+                tags: true,
+            }
+        );
+    }
+}
@@ -4,8 +4,25 @@
 //
 //! Encoder for Mapbox Vector Tile (MVT) geometry.
 //!
+use crate::clip::clip_ring;
 use crate::error::{Error, Result};
-use pointy::{BBox, Float, Pt, Seg, Transform};
+use crate::label::{
+    label_position, path_midpoint, paths_centroid, points_centroid,
+    polygons_representative, rings_centroid,
+};
+use crate::simplify::{
+    douglas_peucker, path_length, signed_area, visvalingam_area,
+    visvalingam_count,
+};
+use crate::validate::{
+    RingInfo, classify_rings, find_ring_crossing, group_polygons,
+    repair_ring_crossings, ring_within,
+};
+use ahash::HashSet;
+use pointy::{BBox, Bounded, Bounds, Float, Pt, Seg, Transform};
+
+/// Parts with the encoded index of each vertex
+type VertexParts = Vec<Vec<(usize, (i32, i32))>>;
 
 /// Path commands
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -20,6 +37,30 @@ enum Command {
     ClosePath = 7,
 }
 
+/// Geometry command, decoded from [GeomData].
+///
+/// Parameters are relative to the previous position (delta encoded).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GeomCommand {
+    /// Move to new position
+    MoveTo { dx: i32, dy: i32 },
+
+    /// Line to new position
+    LineTo { dx: i32, dy: i32 },
+
+    /// Close current path
+    ClosePath,
+}
+
+/// Iterator of geometry commands
+struct Commands<'a> {
+    /// Encoded geometry data
+    data: &'a [u32],
+
+    /// Current command
+    cmd: Option<CommandInt>,
+}
+
 /// Integer command
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 struct CommandInt {
@@ -51,6 +92,58 @@ pub enum GeomType {
     Polygon,
 }
 
+/// Handling of negative tile coordinates.
+///
+/// Negative coordinates are above or left of the tile.  Depending on the tile
+/// buffer convention, these may be valid or invalid.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NegMode {
+    /// Allow negative coordinates (limited only by the bounding box)
+    #[default]
+    Allow,
+
+    /// Clamp negative coordinates to zero
+    Clamp,
+
+    /// Return [Error::NegativeCoordinate](enum.Error.html)
+    Error,
+}
+
+/// Geometry validation strictness.
+///
+/// See [GeomEncoder::strictness].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Strictness {
+    /// Encode geometry without checking specification rules
+    #[default]
+    Normal,
+
+    /// Reject geometry which violates "MUST" rules of the specification
+    Strict,
+
+    /// Drop degenerate parts: linestrings with fewer than 2 vertices, and
+    /// polygon rings with fewer than 3 vertices or zero area (such as
+    /// sub-pixel rings collapsed by rounding).  Dropped parts are counted in
+    /// the [EncodeReport].
+    Lenient,
+}
+
+/// Simplification algorithm for linestrings and polygon rings.
+///
+/// See [GeomEncoder::simplify].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SimplifyMode {
+    /// Douglas–Peucker: tolerance is the maximum distance (tile units) of
+    /// removed vertices
+    #[default]
+    DouglasPeucker,
+
+    /// Visvalingam–Whyatt: tolerance is the minimum triangle area (tile
+    /// units squared) of kept vertices.  This often works better for natural
+    /// features, such as coastlines and rivers.
+    Visvalingam,
+}
+
 /// Encoder for [Feature](struct.Feature.html) geometry.
 ///
 /// This can consist of Point, Linestring or Polygon data.
@@ -66,7 +159,7 @@ pub enum GeomType {
 ///     .encode()?;
 /// # Ok(()) }
 /// ```
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct GeomEncoder<F>
 where
     F: Float,
@@ -74,15 +167,28 @@ where
     /// Geometry type
     geom_tp: GeomType,
 
+    /// X,Y position at start of linestring/polygon geometry
+    xy_start: Option<Pt<F>>,
+
     /// X,Y position at end of linestring/polygon geometry
     xy_end: Option<Pt<F>>,
 
     /// Transform to MVT coordinates
     transform: Transform<F>,
 
+    /// Reprojection from source CRS (before transform)
+    #[cfg(feature = "proj")]
+    reprojection: Option<crate::proj::Reprojection>,
+
     /// Bounding box
     bbox: BBox<F>,
 
+    /// Clip buffer (tile units)
+    clip_buffer: i32,
+
+    /// Bounding box for clipping, expanded by clip buffer
+    clip_bbox: BBox<F>,
+
     /// Minimum X value
     x_min: i32,
 
@@ -95,6 +201,36 @@ where
     /// Maximum Y value
     y_max: i32,
 
+    /// Negative coordinate handling
+    neg_mode: NegMode,
+
+    /// Verbatim mode (no redundant point removal or simplification)
+    verbatim: bool,
+
+    /// Simplification tolerance (tile units)
+    simplify: Option<F>,
+
+    /// Simplification algorithm
+    simplify_mode: SimplifyMode,
+
+    /// Repair ring crossings caused by rounding
+    snap_rounding: bool,
+
+    /// Maximum total vertices
+    max_total_vertices: Option<u32>,
+
+    /// Tolerance for welding polygon ring endpoints
+    weld_tolerance: Option<F>,
+
+    /// Valid coordinate range (min, max) for extent plus buffer
+    valid_range: Option<(i32, i32)>,
+
+    /// Tile range (min, max) for skipping geometry outside of it
+    skip_range: Option<(i32, i32)>,
+
+    /// Automatic polygon winding order correction
+    auto_winding: bool,
+
     /// Previous tile point
     pt0: Option<(i32, i32)>,
 
@@ -104,9 +240,84 @@ where
     /// Command offset
     cmd_offset: usize,
 
+    /// Data offset of current part (if it started with a MoveTo)
+    part_offset: Option<usize>,
+
+    /// Tile point before current part
+    part_pen: Option<(i32, i32)>,
+
+    /// Data offset of current part (including continuation parts)
+    part_data: usize,
+
+    /// Index of current part
+    part_index: usize,
+
     /// Count of geometry data
     count: u32,
 
+    /// Total count of vertices
+    num_vertices: usize,
+
+    /// Tile-space bounds of vertices (x_min, y_min, x_max, y_max)
+    tile_bounds: Option<(i32, i32, i32, i32)>,
+
+    /// Tile bounds may include removed vertices
+    bounds_stale: bool,
+
+    /// Tile points of current part (linestring / polygon)
+    part: Vec<(i32, i32)>,
+
+    /// Source points and input indices of current ring (for clipping)
+    ring: Vec<(Pt<F>, usize)>,
+
+    /// Count of input points
+    num_input: usize,
+
+    /// Input index and tile point of each retained vertex (if recording)
+    vertex_map: Option<Vec<(usize, (i32, i32))>>,
+
+    /// Elevation of each input point (if any were added)
+    #[cfg(feature = "v3")]
+    elevations: Option<Vec<F>>,
+
+    /// Elevation of each retained vertex (if any were added)
+    #[cfg(feature = "v3")]
+    vertex_z: Vec<F>,
+
+    /// Elevation of the next tile point
+    #[cfg(feature = "v3")]
+    next_z: Option<F>,
+
+    /// Spacing of label points along linestrings (tile units)
+    label_spacing: Option<F>,
+
+    /// Previous source point and distance to next label point
+    label_pen: Option<(Pt<F>, F)>,
+
+    /// Label points along linestrings (tile coördinates, not rounded)
+    label_points: Vec<(F, F)>,
+
+    /// Skip duplicate points (multipoint)
+    dedupe_points: bool,
+
+    /// Tile points already emitted (for deduplication)
+    seen_points: HashSet<(i32, i32)>,
+
+    /// Check for duplicate parts
+    warn_duplicate_parts: bool,
+
+    /// Validation strictness
+    strictness: Strictness,
+
+    /// Most recent exterior ring (for validation / winding correction)
+    exterior: Option<Vec<(i32, i32)>>,
+
+    /// Tile points of completed parts (for duplicate checks)
+    parts: HashSet<Vec<(i32, i32)>>,
+
+    /// Encoding report
+    report: EncodeReport,
+
     /// Encoded geometry data
     data: Vec<u32>,
 }
@@ -126,12 +337,63 @@ where
 ///     .encode()?;
 /// # Ok(()) }
 /// ```
+#[derive(Default)]
 pub struct GeomData {
     /// Geometry type
     geom_tp: GeomType,
 
     /// Encoded geometry data
     data: Vec<u32>,
+
+    /// Encoding report
+    report: EncodeReport,
+
+    /// Tile-space bounds (x_min, y_min, x_max, y_max), if known
+    bounds: Option<(i32, i32, i32, i32)>,
+
+    /// Elevation of each vertex
+    #[cfg(feature = "v3")]
+    elevations: Vec<f64>,
+}
+
+/// Statistics of encoded geometry.
+///
+/// See [GeomData::stats].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GeomStats {
+    /// Number of vertices (not including closing vertices of rings)
+    pub num_vertices: usize,
+
+    /// Number of parts: points, linestrings or polygon rings
+    pub num_parts: usize,
+
+    /// Encoded size of the geometry field (bytes)
+    pub encoded_size: usize,
+}
+
+/// Report of issues found while encoding geometry.
+///
+/// These issues do not prevent encoding, but may indicate problems with the
+/// source data.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EncodeReport {
+    /// Two or more linestring parts have identical vertices
+    /// (see [GeomEncoder::warn_duplicate_parts]).
+    pub duplicate_parts: bool,
+
+    /// Count of degenerate linestring parts dropped
+    /// (see [Strictness::Lenient]).
+    pub dropped_lines: usize,
+
+    /// Count of degenerate polygon rings dropped
+    /// (see [Strictness::Lenient]).
+    pub dropped_rings: usize,
+
+    /// Geometry was skipped for being outside of the tile
+    /// (see [GeomEncoder::skip_outside]).
+    pub skipped_outside: bool,
 }
 
 impl CommandInt {
@@ -147,15 +409,43 @@ impl CommandInt {
     }
 
     /// Decode command
-    fn decode(code: u32) -> Self {
+    fn decode(code: u32) -> Result<Self> {
         let id = match code & 0x7 {
             1 => Command::MoveTo,
             2 => Command::LineTo,
             7 => Command::ClosePath,
-            _ => panic!("Invalid code: {code}"),
+            _ => {
+                log::debug!("invalid command: {code}");
+                return Err(Error::InvalidCommand(code));
+            }
         };
         let count = code >> 3;
-        CommandInt { id, count }
+        Ok(CommandInt { id, count })
+    }
+
+    /// Get the end of the command's parameters in geometry data.
+    ///
+    /// * `data` Geometry data.
+    /// * `i` Index of the first parameter.
+    ///
+    /// Returns `Error::CountOverflow` if the count is larger than the whole
+    /// data, or `Error::TruncatedParameters` if the data ends early.
+    fn params_end(&self, data: &[u32], i: usize) -> Result<usize> {
+        if self.id == Command::ClosePath {
+            return Ok(i);
+        }
+        let count = self.count as usize;
+        if count > data.len() {
+            log::debug!("command count overflow: {}", self.count);
+            return Err(Error::CountOverflow(self.count));
+        }
+        match i.checked_add(2 * count) {
+            Some(end) if end <= data.len() => Ok(end),
+            _ => {
+                log::debug!("truncated command parameters");
+                Err(Error::TruncatedParameters())
+            }
+        }
     }
 }
 
@@ -169,6 +459,12 @@ impl ParamInt {
     fn encode(&self) -> u32 {
         ((self.value << 1) ^ (self.value >> 31)) as u32
     }
+
+    /// Decode the parameter
+    fn decode(code: u32) -> Self {
+        let value = ((code >> 1) as i32) ^ -((code & 1) as i32);
+        ParamInt { value }
+    }
 }
 
 impl<F> GeomEncoder<F>
@@ -189,35 +485,319 @@ where
         }
     }
 
+    /// Create a new geometry encoder with capacity for a number of points.
+    ///
+    /// * `geom_tp` Geometry type.
+    /// * `n_points` Number of points expected.
+    ///
+    /// This avoids repeated reallocation when encoding large geometries.
+    pub fn with_capacity(geom_tp: GeomType, n_points: usize) -> Self {
+        let mut enc = GeomEncoder::new(geom_tp);
+        // two parameters per point, plus MoveTo, LineTo and ClosePath
+        enc.data
+            .reserve(n_points.saturating_mul(2).saturating_add(3));
+        if geom_tp != GeomType::Point {
+            enc.part.reserve(n_points);
+        }
+        enc
+    }
+
+    /// Get the geometry type
+    pub fn geom_type(&self) -> GeomType {
+        self.geom_tp
+    }
+
     /// Adjust min/max values
     fn adjust_minmax(mut self) -> Self {
         if self.bbox != BBox::default() {
-            let p = self.transform * (self.bbox.x_min(), self.bbox.y_min());
-            let x0 = p.x.round().to_i32().unwrap_or(i32::MIN);
-            let y0 = p.y.round().to_i32().unwrap_or(i32::MIN);
-            let p = self.transform * (self.bbox.x_max(), self.bbox.y_max());
-            let x1 = p.x.round().to_i32().unwrap_or(i32::MAX);
-            let y1 = p.y.round().to_i32().unwrap_or(i32::MAX);
-            self.x_min = x0.min(x1);
-            self.y_min = y0.min(y1);
-            self.x_max = x0.max(x1);
-            self.y_max = y0.max(y1);
+            let p0 = self.transform * (self.bbox.x_min(), self.bbox.y_min());
+            let p1 = self.transform * (self.bbox.x_max(), self.bbox.y_max());
+            let x0 = p0.x.round().to_i32().unwrap_or(i32::MIN);
+            let y0 = p0.y.round().to_i32().unwrap_or(i32::MIN);
+            let x1 = p1.x.round().to_i32().unwrap_or(i32::MAX);
+            let y1 = p1.y.round().to_i32().unwrap_or(i32::MAX);
+            let buf = self.clip_buffer;
+            self.x_min = x0.min(x1).saturating_sub(buf);
+            self.y_min = y0.min(y1).saturating_sub(buf);
+            self.x_max = x0.max(x1).saturating_add(buf);
+            self.y_max = y0.max(y1).saturating_add(buf);
+            // convert buffer to source units
+            let buf = F::from(buf).unwrap_or(F::zero());
+            let bx = buf * self.bbox.x_span() / (p1.x - p0.x).abs();
+            let by = buf * self.bbox.y_span() / (p1.y - p0.y).abs();
+            let bx = if bx.is_finite() { bx } else { F::zero() };
+            let by = if by.is_finite() { by } else { F::zero() };
+            self.clip_bbox = BBox::new([
+                (self.bbox.x_min() - bx, self.bbox.y_min() - by),
+                (self.bbox.x_max() + bx, self.bbox.y_max() + by),
+            ]);
         }
         self
     }
 
-    /// Add a bounding box
+    /// Add a bounding box.
+    ///
+    /// Geometry is clipped to the bounding box.  Linestrings are split into
+    /// a new part each time they re-enter it.  Polygon rings are clipped
+    /// when completed, so they are closed correctly along its edges.
     pub fn bbox(mut self, bbox: BBox<F>) -> Self {
         self.bbox = bbox;
         self.adjust_minmax()
     }
 
+    /// Set a buffer around the bounding box for clipping.
+    ///
+    /// * `buffer` Buffer width, in tile units.
+    ///
+    /// Geometry is clipped to the bounding box expanded by the buffer,
+    /// instead of clamping hard to its edges.  This lets renderers draw
+    /// geometry slightly past the tile edge, avoiding seams.  A negative
+    /// buffer is treated as zero.
+    pub fn clip_buffer(mut self, buffer: i32) -> Self {
+        self.clip_buffer = buffer.max(0);
+        self.adjust_minmax()
+    }
+
     /// Add a transform
     pub fn transform(mut self, transform: Transform<F>) -> Self {
         self.transform = transform;
         self.adjust_minmax()
     }
 
+    /// Add a reprojection from a source CRS.
+    ///
+    /// Points are reprojected before the [transform](#method.transform),
+    /// which (along with the bounding box) is in the reprojected CRS.
+    #[cfg(feature = "proj")]
+    pub fn reproject(
+        mut self,
+        reprojection: crate::proj::Reprojection,
+    ) -> Self {
+        self.reprojection = Some(reprojection);
+        self
+    }
+
+    /// Set handling of negative tile coordinates.
+    pub fn negative_coords(mut self, neg_mode: NegMode) -> Self {
+        self.neg_mode = neg_mode;
+        self
+    }
+
+    /// Validate coordinates against a tile extent and buffer.
+    ///
+    /// * `extent` Height / width of tile bounds.
+    /// * `buffer` Buffer around tile bounds.
+    ///
+    /// Adding a point with tile coördinates outside of `-buffer` to
+    /// `extent + buffer` results in
+    /// [Error::OutOfBounds](enum.Error.html).
+    pub fn validate_extent(mut self, extent: u32, buffer: u32) -> Self {
+        let buffer = i32::try_from(buffer).unwrap_or(i32::MAX);
+        let extent = i32::try_from(extent).unwrap_or(i32::MAX);
+        self.valid_range = Some((-buffer, extent.saturating_add(buffer)));
+        self
+    }
+
+    /// Skip geometry entirely outside of a tile extent and buffer.
+    ///
+    /// * `extent` Height / width of tile bounds.
+    /// * `buffer` Buffer around tile bounds.
+    ///
+    /// When the bounds of the encoded geometry do not overlap `-buffer` to
+    /// `extent + buffer`, the geometry data is cleared on encode, and the
+    /// [report](#method.report) flags it as skipped.  Check
+    /// [GeomData::is_empty] before adding a feature.
+    pub fn skip_outside(mut self, extent: u32, buffer: u32) -> Self {
+        let buffer = i32::try_from(buffer).unwrap_or(i32::MAX);
+        let extent = i32::try_from(extent).unwrap_or(i32::MAX);
+        self.skip_range = Some((-buffer, extent.saturating_add(buffer)));
+        self
+    }
+
+    /// Set verbatim mode.
+    ///
+    /// In verbatim mode, every point is encoded exactly as added: redundant
+    /// points are not removed and polygon rings are not simplified.
+    pub fn verbatim(mut self, verbatim: bool) -> Self {
+        self.verbatim = verbatim;
+        self
+    }
+
+    /// Set validation strictness.
+    ///
+    /// With [Strictness::Strict], completing a part which violates any of
+    /// these rules results in an error naming the rule:
+    ///
+    /// * Linestring parts must have at least 2 vertices
+    ///   ([Error::TooFewPoints])
+    /// * Linestring parts must not duplicate a previous part
+    ///   ([Error::DuplicatePart])
+    /// * Polygon rings must have at least 3 distinct vertices (4 including
+    ///   the closing vertex) ([Error::TooFewPoints])
+    /// * Polygon rings must not have zero area ([Error::ZeroAreaRing])
+    /// * The first ring must be an exterior ring (positive area, clockwise
+    ///   in tile coördinates) ([Error::InteriorBeforeExterior])
+    /// * Interior rings must be contained by the preceding exterior ring
+    ///   ([Error::InteriorNotWithin])
+    ///
+    /// Rings are always closed by the encoder.  Strict mode is the
+    /// counterpart to [verbatim](#method.verbatim) mode.
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Enable strict validation.
+    ///
+    /// This is a shorthand for
+    /// [strictness](#method.strictness)`(Strictness::Strict)`.
+    pub fn strict(self) -> Self {
+        self.strictness(Strictness::Strict)
+    }
+
+    /// Set automatic polygon winding order correction.
+    ///
+    /// When enabled, each polygon ring is classified when completed: a ring
+    /// within the preceding exterior ring is an interior ring; otherwise it
+    /// is an exterior ring.  Rings with the wrong winding order for their
+    /// classification are reversed, so exterior rings are clockwise and
+    /// interior rings are counter-clockwise in tile coördinates.
+    ///
+    /// Note: islands within holes are classified as interior rings.
+    pub fn auto_winding(mut self, auto_winding: bool) -> Self {
+        self.auto_winding = auto_winding;
+        self
+    }
+
+    /// Set a tolerance for simplifying linestrings and polygon rings.
+    ///
+    /// * `tolerance` Tolerance, depending on
+    ///   [simplify_mode](#method.simplify_mode).
+    ///
+    /// Each part is simplified when completed.  Linestring endpoints are
+    /// always kept, and polygon rings keep at least 3 vertices.  A part
+    /// starting at the last vertex of the previous part always keeps that
+    /// vertex.  This has no effect in [verbatim](#method.verbatim) mode.
+    pub fn simplify(mut self, tolerance: F) -> Self {
+        self.simplify = Some(tolerance);
+        self
+    }
+
+    /// Set the simplification algorithm.
+    pub fn simplify_mode(mut self, mode: SimplifyMode) -> Self {
+        self.simplify_mode = mode;
+        self
+    }
+
+    /// Set the maximum total vertex count for the feature.
+    ///
+    /// When the geometry has more vertices, all parts are simplified on
+    /// encode.  The vertex budget is distributed by length (linestrings) or
+    /// area (polygons).
+    pub fn max_total_vertices(mut self, n: u32) -> Self {
+        self.max_total_vertices = Some(n);
+        self
+    }
+
+    /// Set tolerance for welding polygon ring endpoints.
+    ///
+    /// When a ring is completed and its last point is within `tol` (source
+    /// units) of the first point, the last point is snapped to the first,
+    /// closing the ring.
+    pub fn weld_tolerance(mut self, tol: F) -> Self {
+        self.weld_tolerance = Some(tol);
+        self
+    }
+
+    /// Record a mapping from input points to tile vertices.
+    ///
+    /// See [vertex_map](#method.vertex_map).
+    pub fn record_vertex_map(mut self, record: bool) -> Self {
+        self.vertex_map = record.then(Vec::new);
+        self
+    }
+
+    /// Get the mapping from input points to tile vertices.
+    ///
+    /// Each retained vertex is paired with the index of the input point
+    /// which produced it (in order of [add_point](#method.add_point) calls).
+    /// Boundary vertices from clipping have the index of the point ending
+    /// the clipped segment.  This is empty unless
+    /// [record_vertex_map](#method.record_vertex_map) is enabled.
+    pub fn vertex_map(&self) -> &[(usize, (i32, i32))] {
+        self.vertex_map.as_deref().unwrap_or_default()
+    }
+
+    /// Compute evenly spaced label points along linestrings.
+    ///
+    /// * `spacing` Distance between label points (tile units).
+    ///
+    /// Points are placed along each part, starting at half the spacing from
+    /// its first vertex.  They are calculated from the transformed
+    /// coördinates before rounding, clipping or simplification, and points
+    /// outside of the bounding box are skipped.  Use
+    /// [label_points](#method.label_points) to get them before encoding,
+    /// e.g. for a road name label layer.
+    pub fn label_spacing(mut self, spacing: F) -> Self {
+        self.label_spacing = Some(spacing);
+        self
+    }
+
+    /// Get label points along linestrings.
+    ///
+    /// See [label_spacing](#method.label_spacing).
+    pub fn label_points(&self) -> &[(F, F)] {
+        &self.label_points
+    }
+
+    /// Encode label points along linestrings as point geometry.
+    ///
+    /// See [label_spacing](#method.label_spacing).
+    pub fn encode_label_points(&self) -> Result<GeomData> {
+        let mut enc = GeomEncoder::<F>::new(GeomType::Point);
+        for (x, y) in &self.label_points {
+            let x = x.round().to_i32().ok_or(Error::InvalidValue())?;
+            let y = y.round().to_i32().ok_or(Error::InvalidValue())?;
+            enc.add_point_i32(x, y)?;
+        }
+        enc.encode()
+    }
+
+    /// Skip duplicate points in a multipoint.
+    ///
+    /// When enabled, a point is skipped if its tile coördinates match any
+    /// point already added to the feature.  This is useful at low zoom
+    /// levels, where many points can collapse to the same tile coördinates.
+    pub fn dedupe_points(mut self, dedupe: bool) -> Self {
+        self.dedupe_points = dedupe;
+        self
+    }
+
+    /// Check for duplicate linestring parts.
+    ///
+    /// When enabled, parts with identical vertices are flagged in the
+    /// [report](#method.report).  The geometry is still encoded.
+    pub fn warn_duplicate_parts(mut self, warn: bool) -> Self {
+        self.warn_duplicate_parts = warn;
+        self
+    }
+
+    /// Repair polygon ring crossings caused by rounding.
+    ///
+    /// Rounding to tile coördinates can make a valid polygon ring cross
+    /// itself.  When enabled, rings are checked on encode, and for each
+    /// crossing the vertex nearest to the crossed segment is removed.
+    /// Crossings between separate rings are not repaired.
+    pub fn snap_rounding(mut self, snap_rounding: bool) -> Self {
+        self.snap_rounding = snap_rounding;
+        self
+    }
+
+    /// Get the encoding report
+    pub fn report(&self) -> &EncodeReport {
+        &self.report
+    }
+
     /// Push a Command
     fn push_command(&mut self, cmd: Command) {
         log::trace!("push_command: {cmd:?}");
@@ -228,9 +808,8 @@ where
     /// Set count of the most recent Command.
     fn set_command_count(&mut self, count: u32) {
         let off = self.cmd_offset;
-        let mut cmd = CommandInt::decode(self.data[off]);
-        cmd.count = count;
-        self.data[off] = cmd.encode();
+        debug_assert!(count <= 0x1FFF_FFFF);
+        self.data[off] = (self.data[off] & 0x7) | (count << 3);
     }
 
     /// Push one point with relative coörindates.
@@ -241,7 +820,25 @@ where
         self.data.push(ParamInt::new(x.saturating_sub(px)).encode());
         self.data.push(ParamInt::new(y.saturating_sub(py)).encode());
         self.pt1 = Some((x, y));
+        // point parts are never completed, so they are not recorded
+        if self.geom_tp != GeomType::Point {
+            self.part.push((x, y));
+        }
+        if let Some(vertex_map) = &mut self.vertex_map {
+            vertex_map.push((self.num_input, (x, y)));
+        }
+        #[cfg(feature = "v3")]
+        if let Some(z) = self.next_z {
+            self.vertex_z.push(z);
+        }
         self.count += 1;
+        self.num_vertices += 1;
+        self.tile_bounds = Some(match self.tile_bounds {
+            Some((x0, y0, x1, y1)) => {
+                (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+            }
+            None => (x, y, x, y),
+        });
     }
 
     /// Pop most recent point.
@@ -250,7 +847,16 @@ where
         self.data.pop();
         self.data.pop();
         self.pt1 = self.pt0;
+        self.part.pop();
+        if let Some(vertex_map) = &mut self.vertex_map {
+            vertex_map.pop();
+        }
+        #[cfg(feature = "v3")]
+        self.vertex_z.pop();
         self.count -= 1;
+        // popped points are always between their neighbors, so the
+        // bounds do not need to be adjusted
+        self.num_vertices -= 1;
     }
 
     /// Add a point, taking ownership (for method chaining).
@@ -260,328 +866,3108 @@ where
     }
 
     /// Add a point.
+    ///
+    /// Errors include the input vertex index as [context](Error::context).
     pub fn add_point(&mut self, x: F, y: F) -> Result<()> {
-        self.add_boundary_points(x, y)?;
-        self.add_tile_point(x, y)
+        let vertex = self.num_input;
+        self.check_no_elevation()
+            .and_then(|_| self.add_source_point(x, y))
+            .map_err(|e| e.with_vertex(vertex))
     }
 
-    /// Add one or two boundary points (if needed).
-    fn add_boundary_points(&mut self, x: F, y: F) -> Result<()> {
-        if let Some(pxy) = self.xy_end {
-            let xy = Pt::from((x, y));
-            let seg = Seg::new(pxy, xy);
-            if let Some(seg) = seg.clip(self.bbox) {
-                if seg.p0 != pxy {
-                    self.add_tile_point(seg.p0.x, seg.p0.y)?;
-                }
-                if seg.p1 != xy {
-                    self.add_tile_point(seg.p1.x, seg.p1.y)?;
-                }
-            }
+    /// Add a point with elevation, taking ownership (for method chaining).
+    ///
+    /// See [add_point_3d](#method.add_point_3d).
+    #[cfg(feature = "v3")]
+    pub fn point_3d(mut self, x: F, y: F, z: F) -> Result<Self> {
+        self.add_point_3d(x, y, z)?;
+        Ok(self)
+    }
+
+    /// Add a point with elevation (`v3` feature).
+    ///
+    /// * `x` X coördinate.
+    /// * `y` Y coördinate.
+    /// * `z` Elevation, which is not transformed.
+    ///
+    /// Elevations are stored per vertex using the experimental MVT 3.0
+    /// draft encoding, when the geometry is added to a
+    /// [Feature](crate::Feature).  Boundary vertices from clipping get an
+    /// elevation interpolated along the clipped segment.
+    ///
+    /// Either all points of a geometry have elevations, or none do.  Returns
+    /// `Error::InvalidGeometry` if called after points without elevation
+    /// were added, or if a point without elevation is added after this.
+    #[cfg(feature = "v3")]
+    pub fn add_point_3d(&mut self, x: F, y: F, z: F) -> Result<()> {
+        let vertex = self.num_input;
+        if self.elevations.is_none() && vertex > 0 {
+            log::debug!("point with elevation after points without");
+            return Err(Error::InvalidGeometry().with_vertex(vertex));
         }
-        match self.geom_tp {
-            GeomType::Linestring | GeomType::Polygon => {
-                self.xy_end = Some(Pt::from((x, y)));
-            }
-            _ => (),
+        let elevations = self.elevations.get_or_insert_with(Vec::new);
+        elevations.truncate(vertex);
+        elevations.push(z);
+        self.add_source_point(x, y)
+            .map_err(|e| e.with_vertex(vertex))
+    }
+
+    /// Check that a point without elevation can be added.
+    fn check_no_elevation(&self) -> Result<()> {
+        #[cfg(feature = "v3")]
+        if self.elevations.is_some() {
+            log::debug!("point without elevation after points with");
+            return Err(Error::InvalidGeometry());
         }
         Ok(())
     }
 
-    /// Add a tile point.
-    fn add_tile_point(&mut self, x: F, y: F) -> Result<()> {
-        let pt = self.make_point(x, y)?;
-        if let Some((px, py)) = self.pt1 {
-            if pt.0 == px && pt.1 == py {
-                if self.count == 0 {
-                    // If the first point of a line in a multilinestring (or multipolygon) is the same as the last of the previous line,
-                    // we skip the MoveTo command and increase the count so the next point correctly gets a LineTo.
-                    self.count += 1;
-                } else {
-                    // Redundant points other than the first are unexpected, and entirely skipped.
-                    log::trace!("redundant point: {px},{py}");
-                }
-                return Ok(());
-            }
+    /// Get the elevation of an input point.
+    #[cfg_attr(not(feature = "v3"), allow(unused_variables))]
+    fn input_z(&self, i: usize) -> Option<F> {
+        #[cfg(feature = "v3")]
+        if let Some(elevations) = &self.elevations {
+            return elevations.get(i).copied();
         }
-        match self.geom_tp {
-            GeomType::Point => {
-                if self.count == 0 {
-                    self.push_command(Command::MoveTo);
-                }
+        None
+    }
+
+    /// Set the elevation of the next tile point.
+    #[cfg_attr(not(feature = "v3"), allow(unused_variables))]
+    fn set_next_z(&mut self, z: Option<F>) {
+        #[cfg(feature = "v3")]
+        {
+            self.next_z = z;
+        }
+    }
+
+    /// Set the elevation of the next tile point, at `p` on the segment from
+    /// the previous input point `p0` to the current one `p1`.
+    fn set_segment_z(&mut self, p0: Pt<F>, p1: Pt<F>, p: Pt<F>) {
+        let i = self.num_input;
+        let z0 = i.checked_sub(1).and_then(|i| self.input_z(i));
+        let z = z0.zip(self.input_z(i)).map(|(z0, z1)| {
+            let len = p0.distance(p1);
+            if len > F::zero() {
+                z0 + (z1 - z0) * (p0.distance(p) / len)
+            } else {
+                z1
             }
-            GeomType::Linestring => match self.count {
-                0 => self.push_command(Command::MoveTo),
+        });
+        self.set_next_z(z);
+    }
+
+    /// Get elevation of each encoded vertex.
+    #[cfg(feature = "v3")]
+    fn vertex_elevations(&self) -> Vec<f64> {
+        self.vertex_z
+            .iter()
+            .map(|z| z.to_f64().unwrap_or_default())
+            .collect()
+    }
+
+    /// Add a point in source coördinates.
+    fn add_source_point(&mut self, x: F, y: F) -> Result<()> {
+        #[cfg(feature = "proj")]
+        let (x, y) = self.reproject_point(x, y)?;
+        if self.label_spacing.is_some() {
+            self.add_label_points(x, y);
+        }
+        if self.clips_rings() {
+            // rings are clipped when completed
+            self.ring.push((Pt::from((x, y)), self.num_input));
+            self.num_input += 1;
+            return Ok(());
+        }
+        if self.clips_lines() {
+            self.add_clipped_line_point(x, y)?;
+            self.num_input += 1;
+            return Ok(());
+        }
+        self.add_boundary_points(x, y)?;
+        self.set_next_z(self.input_z(self.num_input));
+        self.add_tile_point(x, y)?;
+        self.num_input += 1;
+        Ok(())
+    }
+
+    /// Add label points along a linestring segment ending at a point.
+    fn add_label_points(&mut self, x: F, y: F) {
+        let Some(spacing) = self.label_spacing else {
+            return;
+        };
+        if self.geom_tp != GeomType::Linestring || spacing <= F::zero() {
+            return;
+        }
+        let two = F::one() + F::one();
+        let p1 = Pt::from((x, y));
+        let Some((p0, mut next)) = self.label_pen else {
+            self.label_pen = Some((p1, spacing / two));
+            return;
+        };
+        let (t0, t1) = (self.transform * p0, self.transform * p1);
+        let len = t0.distance(t1);
+        while next <= len {
+            let t = next / len;
+            let p = p0 + (p1 - p0) * t;
+            if self.bbox == BBox::default()
+                || self.bbox.check(p.x, p.y) == Bounds::Within
+            {
+                let tp = t0 + (t1 - t0) * t;
+                self.label_points.push((tp.x, tp.y));
+            }
+            next = next + spacing;
+        }
+        self.label_pen = Some((p1, next - len));
+    }
+
+    /// Reproject a point from the source CRS
+    #[cfg(feature = "proj")]
+    fn reproject_point(&self, x: F, y: F) -> Result<(F, F)> {
+        let Some(reprojection) = &self.reprojection else {
+            return Ok((x, y));
+        };
+        let x = x.to_f64().ok_or(Error::InvalidValue())?;
+        let y = y.to_f64().ok_or(Error::InvalidValue())?;
+        let (x, y) = reprojection.project(x, y)?;
+        let x = F::from(x).ok_or(Error::InvalidValue())?;
+        let y = F::from(y).ok_or(Error::InvalidValue())?;
+        Ok((x, y))
+    }
+
+    /// Add a point in tile coördinates, taking ownership (for method
+    /// chaining).
+    ///
+    /// See [add_point_i32](#method.add_point_i32).
+    pub fn point_i32(mut self, x: i32, y: i32) -> Result<Self> {
+        self.add_point_i32(x, y)?;
+        Ok(self)
+    }
+
+    /// Add a point in tile coördinates.
+    ///
+    /// The transform is not applied, so there is no float rounding.  The
+    /// point is not clipped to the bounding box, but negative mode, clip
+    /// buffer and extent validation still apply.  Points added this way
+    /// should not be mixed with float points in one geometry.
+    pub fn add_point_i32(&mut self, x: i32, y: i32) -> Result<()> {
+        let vertex = self.num_input;
+        let pt = self
+            .check_no_elevation()
+            .and_then(|_| self.check_tile_xy(x, y))
+            .map_err(|e| e.with_vertex(vertex))?;
+        self.add_tile_xy(pt);
+        self.num_input += 1;
+        Ok(())
+    }
+
+    /// Add points in tile coördinates, taking ownership (for method
+    /// chaining).
+    ///
+    /// * `pts` Points, as `(x, y)` tuples.
+    pub fn points_i32<I>(mut self, pts: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (i32, i32)>,
+    {
+        self.add_points_i32(pts)?;
+        Ok(self)
+    }
+
+    /// Add points in tile coördinates.
+    ///
+    /// * `pts` Points, as `(x, y)` tuples.
+    ///
+    /// See [add_point_i32](#method.add_point_i32).
+    pub fn add_points_i32<I>(&mut self, pts: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (i32, i32)>,
+    {
+        let pts = pts.into_iter();
+        // each point is encoded as two parameters
+        self.data.reserve(pts.size_hint().0 * 2);
+        for (x, y) in pts {
+            self.add_point_i32(x, y)?;
+        }
+        Ok(())
+    }
+
+    /// Add points, taking ownership (for method chaining).
+    ///
+    /// * `pts` Points, as `(x, y)` tuples or [Pt] values.
+    pub fn points<I>(mut self, pts: I) -> Result<Self>
+    where
+        I: IntoIterator,
+        I::Item: Into<Pt<F>>,
+    {
+        self.add_points(pts)?;
+        Ok(self)
+    }
+
+    /// Add points.
+    ///
+    /// * `pts` Points, as `(x, y)` tuples or [Pt] values.
+    pub fn add_points<I>(&mut self, pts: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Into<Pt<F>>,
+    {
+        let pts = pts.into_iter();
+        // each point is encoded as two parameters
+        self.data.reserve(pts.size_hint().0 * 2);
+        for p in pts {
+            let p = p.into();
+            self.add_point(p.x, p.y)?;
+        }
+        Ok(())
+    }
+
+    /// Add geometry parts, taking ownership (for method chaining).
+    ///
+    /// * `parts` Linestring parts or polygon rings, each an iterator of
+    ///   points.
+    ///
+    /// Each part is completed after its points are added.
+    pub fn geometries<I>(mut self, parts: I) -> Result<Self>
+    where
+        I: IntoIterator,
+        I::Item: IntoIterator,
+        <I::Item as IntoIterator>::Item: Into<Pt<F>>,
+    {
+        self.add_geometries(parts)?;
+        Ok(self)
+    }
+
+    /// Add geometry parts.
+    ///
+    /// * `parts` Linestring parts or polygon rings, each an iterator of
+    ///   points.
+    ///
+    /// Each part is completed after its points are added.
+    pub fn add_geometries<I>(&mut self, parts: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: IntoIterator,
+        <I::Item as IntoIterator>::Item: Into<Pt<F>>,
+    {
+        for part in parts {
+            self.add_points(part)?;
+            self.complete_geom()?;
+        }
+        Ok(())
+    }
+
+    /// Add a rounded rectangle (polygon only).
+    ///
+    /// * `bbox` Bounds of the rectangle.
+    /// * `radius` Corner radius, clamped to half the smaller side.
+    /// * `segments_per_corner` Number of line segments for each corner arc.
+    ///
+    /// The ring is completed, so more rings can be added afterwards.  It has
+    /// positive area with an identity transform (exterior ring).
+    pub fn add_rounded_rect(
+        &mut self,
+        bbox: BBox<F>,
+        radius: F,
+        segments_per_corner: u32,
+    ) -> Result<()> {
+        if self.geom_tp != GeomType::Polygon {
+            return Err(Error::WrongGeomType(GeomType::Polygon));
+        }
+        let two = F::one() + F::one();
+        let half = (bbox.x_span() / two).min(bbox.y_span() / two);
+        let r = radius.max(F::zero()).min(half);
+        let (x0, x1) = (bbox.x_min() + r, bbox.x_max() - r);
+        let (y0, y1) = (bbox.y_min() + r, bbox.y_max() - r);
+        let pi = F::PI();
+        let pi_2 = F::FRAC_PI_2();
+        let corners = [
+            (Pt::new(x0, y0), pi),
+            (Pt::new(x1, y0), pi + pi_2),
+            (Pt::new(x1, y1), F::zero()),
+            (Pt::new(x0, y1), pi_2),
+        ];
+        let mut pts: Vec<Pt<F>> = corners
+            .into_iter()
+            .flat_map(|(center, start)| {
+                arc_points(center, r, start, start + pi_2, segments_per_corner)
+            })
+            .collect();
+        // with sides of zero length, the last arc ends at the first point
+        if let (Some(first), Some(last)) = (pts.first(), pts.last())
+            && pts.len() > 1
+            && first.distance(*last) <= F::epsilon() * (half + F::one())
+        {
+            pts.pop();
+        }
+        self.add_points(pts)?;
+        self.complete_geom()
+    }
+
+    /// Add one or two boundary points (if needed).
+    fn add_boundary_points(&mut self, x: F, y: F) -> Result<()> {
+        if let Some(pxy) = self.xy_end {
+            let xy = Pt::from((x, y));
+            let seg = Seg::new(pxy, xy);
+            if let Some(seg) = seg.clip(self.clip_bbox) {
+                if seg.p0 != pxy {
+                    let p = self.snap_edge(seg.p0);
+                    self.set_segment_z(pxy, xy, p);
+                    self.add_tile_point(p.x, p.y)?;
+                }
+                if seg.p1 != xy {
+                    let p = self.snap_edge(seg.p1);
+                    self.set_segment_z(pxy, xy, p);
+                    self.add_tile_point(p.x, p.y)?;
+                }
+            }
+        }
+        match self.geom_tp {
+            GeomType::Linestring | GeomType::Polygon => {
+                if self.xy_start.is_none() {
+                    self.xy_start = Some(Pt::from((x, y)));
+                }
+                self.xy_end = Some(Pt::from((x, y)));
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Snap a clipped point exactly onto the nearest bounding box edge.
+    ///
+    /// Clipped intersections are calculated in floating point, so they can
+    /// land slightly off the edge.  Snapping the crossed coordinate ensures
+    /// that adjacent tiles share boundary vertices exactly.
+    fn snap_edge(&self, mut p: Pt<F>) -> Pt<F> {
+        let bbox = self.clip_bbox;
+        let edges = [
+            (p.x - bbox.x_min()).abs(),
+            (p.x - bbox.x_max()).abs(),
+            (p.y - bbox.y_min()).abs(),
+            (p.y - bbox.y_max()).abs(),
+        ];
+        let mut nearest = 0;
+        for (i, d) in edges.iter().enumerate() {
+            if *d < edges[nearest] {
+                nearest = i;
+            }
+        }
+        match nearest {
+            0 => p.x = bbox.x_min(),
+            1 => p.x = bbox.x_max(),
+            2 => p.y = bbox.y_min(),
+            _ => p.y = bbox.y_max(),
+        }
+        p
+    }
+
+    /// Add a tile point.
+    fn add_tile_point(&mut self, x: F, y: F) -> Result<()> {
+        let pt = self.make_point(x, y)?;
+        self.add_tile_xy(pt);
+        Ok(())
+    }
+
+    /// Add a point with tile coördinates.
+    fn add_tile_xy(&mut self, pt: (i32, i32)) {
+        if let Some((px, py)) = self.pt1
+            && !self.verbatim
+            && pt.0 == px
+            && pt.1 == py
+        {
+            if self.count == 0 {
+                // If the first point of a line in a multilinestring (or multipolygon) is the same as the last of the previous line,
+                // we skip the MoveTo command and increase the count so the next point correctly gets a LineTo.
+                self.part_data = self.data.len();
+                self.part_pen = self.pt1;
+                self.count += 1;
+                self.part.push(pt);
+            } else {
+                // Redundant points other than the first are unexpected, and entirely skipped.
+                log::trace!("redundant point: {px},{py}");
+            }
+            return;
+        }
+        match self.geom_tp {
+            GeomType::Point => {
+                if self.dedupe_points && !self.seen_points.insert(pt) {
+                    log::trace!("duplicate point: {},{}", pt.0, pt.1);
+                    return;
+                }
+                if self.count == 0 {
+                    self.push_command(Command::MoveTo);
+                }
+            }
+            GeomType::Linestring => match self.count {
+                0 => {
+                    self.part_offset = Some(self.data.len());
+                    self.part_data = self.data.len();
+                    self.part_pen = self.pt1;
+                    self.push_command(Command::MoveTo);
+                }
                 1 => self.push_command(Command::LineTo),
                 _ => (),
             },
             GeomType::Polygon => {
                 match self.count {
-                    0 => self.push_command(Command::MoveTo),
+                    0 => {
+                        self.part_offset = Some(self.data.len());
+                        self.part_data = self.data.len();
+                        self.part_pen = self.pt1;
+                        self.push_command(Command::MoveTo);
+                    }
                     1 => self.push_command(Command::LineTo),
                     _ => (),
                 }
-                if self.count >= 2 && self.should_simplify_point(pt.0, pt.1) {
+                if self.count >= 2
+                    && !self.verbatim
+                    && self.should_simplify_point(pt.0, pt.1)
+                {
                     self.pop_point();
                 }
             }
         }
         self.push_point(pt.0, pt.1);
+    }
+
+    /// Make point with tile coörindates.
+    fn make_point(&self, x: F, y: F) -> Result<(i32, i32)> {
+        let p = self.transform * (x, y);
+        let x = p.x.round().to_i32().ok_or(Error::InvalidValue())?;
+        let y = p.y.round().to_i32().ok_or(Error::InvalidValue())?;
+        self.check_tile_xy(x, y)
+    }
+
+    /// Check tile coördinates, applying negative mode and clamping
+    fn check_tile_xy(&self, mut x: i32, mut y: i32) -> Result<(i32, i32)> {
+        match self.neg_mode {
+            NegMode::Allow => (),
+            NegMode::Clamp => {
+                x = x.max(0);
+                y = y.max(0);
+            }
+            NegMode::Error => {
+                if x < 0 || y < 0 {
+                    return Err(Error::NegativeCoordinate());
+                }
+            }
+        }
+        x = x.clamp(self.x_min, self.x_max);
+        y = y.clamp(self.y_min, self.y_max);
+        if let Some((min, max)) = self.valid_range
+            && (x < min || x > max || y < min || y > max)
+        {
+            return Err(Error::OutOfBounds());
+        }
+        Ok((x, y))
+    }
+
+    /// Check if point should be simplified.
+    fn should_simplify_point(&self, x: i32, y: i32) -> bool {
+        if let (Some((p0x, p0y)), Some((p1x, p1y))) = (self.pt0, self.pt1) {
+            if p0x == p1x && p1x == x {
+                return (p0y < p1y && p1y < y) || (p0y > p1y && p1y > y);
+            }
+            if p0y == p1y && p1y == y {
+                return (p0x < p1x && p1x < x) || (p0x > p1x && p1x > x);
+            }
+        }
+        false
+    }
+
+    /// Get a complexity score for the geometry.
+    ///
+    /// This is the number of vertices per tile unit of the bounding box
+    /// diagonal.  Detailed (jagged) geometry has a higher score than smooth
+    /// geometry covering the same extent, which can be used for allocating
+    /// simplification budgets.
+    pub fn complexity_score(&self) -> f64 {
+        match self.tile_bounds {
+            Some((x0, y0, x1, y1)) => {
+                let dx = f64::from(x1) - f64::from(x0);
+                let dy = f64::from(y1) - f64::from(y0);
+                let diag = dx.hypot(dy);
+                if diag > 0.0 {
+                    self.num_vertices as f64 / diag
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Complete a closed polygon ring, dropping its closing vertex.
+    ///
+    /// The closing vertex is only dropped if it matches the first vertex in
+    /// tile coördinates.
+    #[cfg(feature = "geozero")]
+    pub(crate) fn complete_ring(&mut self) -> Result<()> {
+        if self.ring.len() > 1
+            && self.ring.first().map(|p| p.0) == self.ring.last().map(|p| p.0)
+        {
+            self.ring.pop();
+        }
+        if self.geom_tp == GeomType::Polygon
+            && self.part.len() > 1
+            && self.part.first() == self.part.last()
+        {
+            self.pop_point();
+        }
+        self.complete_geom()
+    }
+
+    /// Complete the current geometry (for multilinestring / multipolygon).
+    ///
+    /// Completing an empty part (no points added since the previous
+    /// completion) does nothing.  Errors include the part index as
+    /// [context](Error::context).
+    pub fn complete_geom(&mut self) -> Result<()> {
+        let part = self.part_index;
+        self.complete_part().map_err(|e| e.with_part(part))
+    }
+
+    /// Complete the current part.
+    fn complete_part(&mut self) -> Result<()> {
+        self.label_pen = None;
+        if !self.ring.is_empty() {
+            self.add_clipped_ring()?;
+        }
+        if self.count == 0 {
+            log::trace!("complete_geom: empty part");
+            self.reset_part();
+            return Ok(());
+        }
+        match self.geom_tp {
+            GeomType::Point => {
+                self.set_command_count(self.count);
+                // early return skips geometry reset
+                return Ok(());
+            }
+            GeomType::Linestring => {
+                self.simplify_part(false);
+                if self.strictness == Strictness::Lenient && self.part.len() < 2
+                {
+                    self.drop_part();
+                    self.report.dropped_lines += 1;
+                } else {
+                    self.complete_line()?;
+                }
+            }
+            GeomType::Polygon => {
+                if self.should_weld() {
+                    log::trace!("weld ring endpoints");
+                    self.pop_point();
+                    self.bounds_stale = true;
+                }
+                self.simplify_part(true);
+                if self.strictness == Strictness::Lenient
+                    && (self.part.len() < 3 || signed_area(&self.part) == 0.0)
+                {
+                    self.drop_part();
+                    self.report.dropped_rings += 1;
+                } else {
+                    self.complete_ring_part()?;
+                }
+            }
+        }
+        self.part_index += 1;
+        self.reset_part();
+        Ok(())
+    }
+
+    /// Reset linestring / polygon part state.
+    fn reset_part(&mut self) {
+        self.part.clear();
+        self.part_offset = None;
+        self.count = 0;
+        self.xy_start = None;
+        self.xy_end = None;
+        self.pt0 = None;
+    }
+
+    /// Complete the current linestring part.
+    fn complete_line(&mut self) -> Result<()> {
+        if self.count > 1 {
+            self.set_command_count(self.count - 1);
+        }
+        let strict = self.strictness == Strictness::Strict;
+        if strict && self.part.len() < 2 {
+            return Err(Error::TooFewPoints {
+                needed: 2,
+                got: self.part.len(),
+            });
+        }
+        if self.warn_duplicate_parts || strict {
+            let duplicate = self.check_duplicate_part();
+            if duplicate && strict {
+                return Err(Error::DuplicatePart());
+            }
+        }
+        Ok(())
+    }
+
+    /// Complete the current polygon ring part.
+    fn complete_ring_part(&mut self) -> Result<()> {
+        if self.auto_winding {
+            self.correct_winding();
+        }
+        if self.strictness == Strictness::Strict {
+            self.validate_ring()?;
+        }
+        if self.count > 1 {
+            self.set_command_count(self.count - 1);
+            self.push_command(Command::ClosePath);
+        }
+        Ok(())
+    }
+
+    /// Drop the current part (lenient mode).
+    fn drop_part(&mut self) {
+        log::debug!("dropping degenerate part: {:?}", self.part);
+        // continuation parts share their first point with the previous part
+        let shared = usize::from(self.part_offset.is_none());
+        let n = self.part.len().saturating_sub(shared);
+        self.data.truncate(self.part_data);
+        if let Some(vertex_map) = &mut self.vertex_map {
+            vertex_map.truncate(vertex_map.len() - n);
+        }
+        #[cfg(feature = "v3")]
+        self.vertex_z
+            .truncate(self.vertex_z.len().saturating_sub(n));
+        self.num_vertices -= n;
+        self.pt1 = self.part_pen;
+        self.bounds_stale = true;
+    }
+
+    /// Check if polygon rings are clipped to the bounding box
+    fn clips_rings(&self) -> bool {
+        self.geom_tp == GeomType::Polygon && self.bbox != BBox::default()
+    }
+
+    /// Check if linestrings are clipped to the bounding box
+    fn clips_lines(&self) -> bool {
+        self.geom_tp == GeomType::Linestring && self.bbox != BBox::default()
+    }
+
+    /// Add a linestring point, clipped to the bounding box.
+    ///
+    /// A new part is started each time the line enters the bounding box.
+    fn add_clipped_line_point(&mut self, x: F, y: F) -> Result<()> {
+        let xy = Pt::from((x, y));
+        let xy_start = self.xy_start.or(Some(xy));
+        match self.xy_end {
+            None => {
+                if xy.bounded_by(self.clip_bbox) {
+                    self.set_next_z(self.input_z(self.num_input));
+                    self.add_tile_point(x, y)?;
+                }
+            }
+            Some(pxy) => {
+                if let Some(seg) = Seg::new(pxy, xy).clip(self.clip_bbox)
+                    && seg.p0 != seg.p1
+                {
+                    if seg.p0 != pxy {
+                        // entering bounding box: start a new part
+                        self.complete_geom()?;
+                        let p = self.snap_edge(seg.p0);
+                        self.set_segment_z(pxy, xy, p);
+                        self.add_tile_point(p.x, p.y)?;
+                    }
+                    if seg.p1 != xy {
+                        // exiting bounding box
+                        let p = self.snap_edge(seg.p1);
+                        self.set_segment_z(pxy, xy, p);
+                        self.add_tile_point(p.x, p.y)?;
+                        self.complete_geom()?;
+                    } else {
+                        self.set_next_z(self.input_z(self.num_input));
+                        self.add_tile_point(x, y)?;
+                    }
+                }
+            }
+        }
+        self.xy_start = xy_start;
+        self.xy_end = Some(xy);
+        Ok(())
+    }
+
+    /// Clip the current ring to the bounding box, and add tile points.
+    fn add_clipped_ring(&mut self) -> Result<()> {
+        let mut ring = std::mem::take(&mut self.ring);
+        if let (Some(tol), Some(first), Some(last)) =
+            (self.weld_tolerance, ring.first(), ring.last())
+            && ring.len() > 1
+            && first.0.distance(last.0) <= tol
+        {
+            log::trace!("weld ring endpoints");
+            ring.pop();
+        }
+        let num_input = self.num_input;
+        // boundary points get the input index of the point ending the
+        // clipped segment, and an elevation interpolated along it
+        let ring: Vec<_> = ring
+            .into_iter()
+            .map(|(p, i)| (p, (i, self.input_z(i))))
+            .collect();
+        let lerp = |(_i0, z0): (usize, Option<F>), (i1, z1), t| {
+            (i1, z0.zip(z1).map(|(z0, z1)| z0 + (z1 - z0) * t))
+        };
+        for (p, (idx, z)) in clip_ring(&ring, self.clip_bbox, lerp) {
+            // vertex map uses the input index of each point
+            self.num_input = idx;
+            self.set_next_z(z);
+            let res = self.add_tile_point(p.x, p.y);
+            if res.is_err() {
+                self.num_input = num_input;
+                return res;
+            }
+        }
+        self.num_input = num_input;
         Ok(())
     }
 
-    /// Make point with tile coörindates.
-    fn make_point(&self, x: F, y: F) -> Result<(i32, i32)> {
-        let p = self.transform * (x, y);
-        let mut x = p.x.round().to_i32().ok_or(Error::InvalidValue())?;
-        let mut y = p.y.round().to_i32().ok_or(Error::InvalidValue())?;
-        x = x.clamp(self.x_min, self.x_max);
-        y = y.clamp(self.y_min, self.y_max);
-        Ok((x, y))
+    /// Check if the ring endpoints should be welded.
+    fn should_weld(&self) -> bool {
+        match (self.weld_tolerance, self.xy_start, self.xy_end) {
+            (Some(tol), Some(p0), Some(p1)) => {
+                self.part.len() > 1 && p0.distance(p1) <= tol
+            }
+            _ => false,
+        }
+    }
+
+    /// Check if current part duplicates a previously completed part.
+    fn check_duplicate_part(&mut self) -> bool {
+        if self.part.is_empty() {
+            return false;
+        }
+        if self.parts.insert(self.part.clone()) {
+            false
+        } else {
+            log::debug!("duplicate part: {:?}", self.part);
+            self.report.duplicate_parts = true;
+            true
+        }
+    }
+
+    /// Correct winding order of the current polygon ring.
+    fn correct_winding(&mut self) {
+        // continuation parts have no MoveTo, and can't be rewritten
+        let Some(offset) = self.part_offset else {
+            return;
+        };
+        if self.part.len() < 3 {
+            return;
+        }
+        let area = signed_area(&self.part);
+        let interior = match &self.exterior {
+            Some(exterior) => ring_within(&self.part, exterior),
+            None => false,
+        };
+        if (area < 0.0) != interior && area != 0.0 {
+            log::debug!("reversing ring winding order");
+            let n = self.part.len();
+            let order: Vec<usize> =
+                std::iter::once(0).chain((1..n).rev()).collect();
+            self.rewrite_part(offset, &order);
+        }
+        if !interior {
+            self.exterior = Some(self.part.clone());
+        }
+    }
+
+    /// Simplify the current part.
+    fn simplify_part(&mut self, closed: bool) {
+        let Some(tol) = self.simplify else {
+            return;
+        };
+        if self.verbatim {
+            return;
+        }
+        let tol = tol.to_f64().unwrap_or_default();
+        // continuation parts must keep their first vertex, which is shared
+        // with the previous part, so they are simplified as open paths
+        let closed = closed && self.part_offset.is_some();
+        let keep = match self.simplify_mode {
+            SimplifyMode::DouglasPeucker => {
+                douglas_peucker(&self.part, closed, tol)
+            }
+            SimplifyMode::Visvalingam => {
+                visvalingam_area(&self.part, closed, tol)
+            }
+        };
+        if keep.len() < self.part.len() {
+            log::trace!(
+                "simplified part: {} to {}",
+                self.part.len(),
+                keep.len()
+            );
+            self.rewrite_part(self.part_data, &keep);
+            self.bounds_stale = true;
+        }
+    }
+
+    /// Rewrite the current part from its data offset.
+    ///
+    /// * `offset` Data offset of the part.
+    /// * `order` Indices of part vertices to keep, in new order.  For
+    ///   continuation parts, the first index must be 0.
+    fn rewrite_part(&mut self, offset: usize, order: &[usize]) {
+        // continuation parts share their first point with the previous part
+        let shared = usize::from(self.part_offset.is_none());
+        debug_assert!(shared == 0 || order.first() == Some(&0));
+        let n = self.part.len() - shared;
+        let pts = std::mem::take(&mut self.part);
+        let mut vertex_map = self.vertex_map.take();
+        if let Some(vertex_map) = &mut vertex_map {
+            reorder_tail(vertex_map, n, order, shared);
+        }
+        #[cfg(feature = "v3")]
+        let mut vertex_z = std::mem::take(&mut self.vertex_z);
+        #[cfg(feature = "v3")]
+        if !vertex_z.is_empty() {
+            reorder_tail(&mut vertex_z, n, order, shared);
+        }
+        self.data.truncate(offset);
+        self.pt0 = None;
+        self.pt1 = self.part_pen;
+        self.count = 0;
+        self.num_vertices -= n;
+        if shared > 0 {
+            self.part.push(pts[0]);
+            self.count = 1;
+        }
+        for (i, idx) in order.iter().enumerate().skip(shared) {
+            match i {
+                0 => self.push_command(Command::MoveTo),
+                1 => self.push_command(Command::LineTo),
+                _ => (),
+            }
+            let pt = pts[*idx];
+            self.push_point(pt.0, pt.1);
+        }
+        self.vertex_map = vertex_map;
+        #[cfg(feature = "v3")]
+        {
+            self.vertex_z = vertex_z;
+        }
+    }
+
+    /// Validate the current polygon ring (strict mode).
+    fn validate_ring(&mut self) -> Result<()> {
+        if self.part.len() < 3 {
+            log::debug!("ring with too few vertices: {:?}", self.part);
+            return Err(Error::TooFewPoints {
+                needed: 4,
+                got: self.part.len() + 1,
+            });
+        }
+        let area = signed_area(&self.part);
+        if area > 0.0 {
+            self.exterior = Some(self.part.clone());
+            return Ok(());
+        }
+        if area == 0.0 {
+            log::debug!("zero-area ring: {:?}", self.part);
+            return Err(Error::ZeroAreaRing());
+        }
+        match &self.exterior {
+            Some(exterior) if ring_within(&self.part, exterior) => Ok(()),
+            Some(_) => {
+                log::debug!("interior ring not within exterior");
+                Err(Error::InteriorNotWithin())
+            }
+            None => {
+                log::debug!("interior ring before exterior ring");
+                Err(Error::InteriorBeforeExterior())
+            }
+        }
+    }
+
+    /// Limit the total vertex count by simplifying all parts.
+    fn limit_vertices(&mut self, limit: usize) {
+        if self.num_vertices <= limit {
+            return;
+        }
+        // data was encoded here, so it can always be decoded
+        let parts = decode_vertices(&self.data).unwrap_or_default();
+        let parts = match self.geom_tp {
+            GeomType::Point => {
+                let pts = parts.concat();
+                let n = pts.len();
+                let pts = (0..limit).map(|i| pts[i * n / limit]).collect();
+                vec![pts]
+            }
+            GeomType::Linestring => budget_parts(parts, false, limit),
+            GeomType::Polygon => budget_parts(parts, true, limit),
+        };
+        self.rebuild(parts);
+    }
+
+    /// Repair ring crossings caused by rounding.
+    fn repair_crossings(&mut self) {
+        // data was encoded here, so it can always be decoded
+        let mut parts = decode_vertices(&self.data).unwrap_or_default();
+        let mut changed = false;
+        for part in &mut parts {
+            let ring: Vec<_> = part.iter().map(|v| v.1).collect();
+            let keep = repair_ring_crossings(&ring);
+            if keep.len() < part.len() {
+                *part = keep.into_iter().map(|i| part[i]).collect();
+                changed = true;
+            }
+        }
+        if changed {
+            self.rebuild(parts);
+        }
+    }
+
+    /// Rebuild the geometry data from finished parts.
+    ///
+    /// * `parts` Parts, with the index of each encoded vertex.
+    ///
+    /// The parts were already simplified and validated, so they are written
+    /// directly.  The vertex map and elevations are kept for the remaining
+    /// vertices.
+    fn rebuild(&mut self, parts: VertexParts) {
+        let vertex_map = self.vertex_map.take();
+        #[cfg(feature = "v3")]
+        let vertex_z = std::mem::take(&mut self.vertex_z);
+        let mut keep = Vec::with_capacity(self.num_vertices);
+        self.data.clear();
+        self.pt0 = None;
+        self.pt1 = None;
+        self.num_vertices = 0;
+        self.tile_bounds = None;
+        self.bounds_stale = false;
+        for mut part in parts {
+            if self.geom_tp != GeomType::Point && !self.verbatim {
+                // removing vertices can leave repeated points
+                part.dedup_by_key(|v| v.1);
+                if self.geom_tp == GeomType::Polygon
+                    && part.len() > 1
+                    && part.first().map(|v| v.1) == part.last().map(|v| v.1)
+                {
+                    part.pop();
+                }
+            }
+            let min = match self.geom_tp {
+                GeomType::Point => 1,
+                GeomType::Linestring => 2,
+                GeomType::Polygon => 3,
+            };
+            if part.len() < min {
+                continue;
+            }
+            for (i, (_v, (x, y))) in part.iter().enumerate() {
+                match (self.geom_tp, i) {
+                    (_, 0) => self.push_command(Command::MoveTo),
+                    (GeomType::Point, _) => (),
+                    (_, 1) => self.push_command(Command::LineTo),
+                    _ => (),
+                }
+                self.push_point(*x, *y);
+            }
+            match self.geom_tp {
+                GeomType::Point => self.set_command_count(part.len() as u32),
+                _ => self.set_command_count(part.len() as u32 - 1),
+            }
+            if self.geom_tp == GeomType::Polygon {
+                self.push_command(Command::ClosePath);
+            }
+            keep.extend(part.iter().map(|v| v.0));
+        }
+        self.part.clear();
+        self.count = 0;
+        self.vertex_map = vertex_map.map(|map| {
+            keep.iter().filter_map(|v| map.get(*v).copied()).collect()
+        });
+        #[cfg(feature = "v3")]
+        {
+            self.vertex_z = keep
+                .iter()
+                .filter_map(|v| vertex_z.get(*v).copied())
+                .collect();
+        }
+    }
+
+    /// Complete the current geometry (for multilinestring / multipolygon).
+    pub fn complete(mut self) -> Result<Self> {
+        self.complete_geom()?;
+        Ok(self)
+    }
+
+    /// Finish encoding the geometry data.
+    fn finish(&mut self) -> Result<()> {
+        self.complete_geom()?;
+        if let Some(limit) = self.max_total_vertices {
+            self.limit_vertices(limit as usize);
+        }
+        if self.snap_rounding && self.geom_tp == GeomType::Polygon {
+            self.repair_crossings();
+        }
+        if self.bounds_stale {
+            // data was encoded here, so it can always be decoded
+            let parts = decode_parts(&self.data).unwrap_or_default();
+            self.tile_bounds = parts_bounds(&parts);
+            self.bounds_stale = false;
+        }
+        if let (Some((min, max)), Some((x0, y0, x1, y1))) =
+            (self.skip_range, self.tile_bounds)
+            && (x1 < min || x0 >= max || y1 < min || y0 >= max)
+        {
+            log::debug!("skipping geometry outside of tile");
+            self.data.clear();
+            self.tile_bounds = None;
+            self.report.skipped_outside = true;
+        }
+        Ok(())
+    }
+
+    /// Encode the geometry data, consuming the encoder.
+    pub fn encode(mut self) -> Result<GeomData> {
+        self.finish()?;
+        #[cfg(feature = "v3")]
+        let elevations = self.vertex_elevations();
+        let mut geom = GeomData::new(self.geom_tp, self.data, self.report);
+        geom.bounds = self.tile_bounds;
+        #[cfg(feature = "v3")]
+        {
+            geom.elevations = elevations;
+        }
+        Ok(geom)
+    }
+
+    /// Encode the geometry data into existing geometry data.
+    ///
+    /// * `geom` Geometry data to replace.
+    ///
+    /// The data buffers are swapped, and the encoder is [reset] with the
+    /// same geometry type.  Reusing both the encoder and geometry data
+    /// avoids allocating a new buffer for each feature.  After an error,
+    /// the encoder should be reset before reuse.
+    ///
+    /// [reset]: #method.reset
+    pub fn encode_into(&mut self, geom: &mut GeomData) -> Result<()> {
+        self.finish()?;
+        #[cfg(feature = "v3")]
+        {
+            geom.elevations = self.vertex_elevations();
+        }
+        geom.geom_tp = self.geom_tp;
+        std::mem::swap(&mut geom.data, &mut self.data);
+        geom.report = std::mem::take(&mut self.report);
+        geom.bounds = self.tile_bounds;
+        self.reset(self.geom_tp);
+        Ok(())
+    }
+
+    /// Reset the encoder for a new feature.
+    ///
+    /// * `geom_tp` Geometry type.
+    ///
+    /// All geometry is cleared, but options (transform, bounding box, etc.)
+    /// are kept, and allocated buffers are reused.
+    pub fn reset(&mut self, geom_tp: GeomType) {
+        self.geom_tp = geom_tp;
+        self.xy_start = None;
+        self.xy_end = None;
+        self.pt0 = None;
+        self.pt1 = None;
+        self.cmd_offset = 0;
+        self.part_offset = None;
+        self.part_pen = None;
+        self.part_data = 0;
+        self.part_index = 0;
+        self.count = 0;
+        self.num_vertices = 0;
+        self.tile_bounds = None;
+        self.bounds_stale = false;
+        self.part.clear();
+        self.ring.clear();
+        self.num_input = 0;
+        if let Some(vertex_map) = &mut self.vertex_map {
+            vertex_map.clear();
+        }
+        #[cfg(feature = "v3")]
+        {
+            self.elevations = None;
+            self.vertex_z.clear();
+            self.next_z = None;
+        }
+        self.exterior = None;
+        self.parts.clear();
+        self.label_pen = None;
+        self.label_points.clear();
+        self.seen_points.clear();
+        self.report = EncodeReport::default();
+        self.data.clear();
+    }
+}
+
+impl Iterator for Commands<'_> {
+    type Item = GeomCommand;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cmd = loop {
+            match self.cmd {
+                Some(cmd) if cmd.count > 0 => break cmd,
+                _ => {
+                    let (code, data) = self.data.split_first()?;
+                    self.data = data;
+                    let cmd = CommandInt::decode(*code).ok()?;
+                    if cmd.id == Command::ClosePath {
+                        self.cmd = None;
+                        return Some(GeomCommand::ClosePath);
+                    }
+                    // a count of zero has no parameters
+                    self.cmd = Some(cmd);
+                }
+            }
+        };
+        let [x, y, data @ ..] = self.data else {
+            log::debug!("truncated command parameters");
+            self.data = &[];
+            return None;
+        };
+        self.data = data;
+        self.cmd = Some(CommandInt::new(cmd.id, cmd.count.saturating_sub(1)));
+        let dx = ParamInt::decode(*x).value;
+        let dy = ParamInt::decode(*y).value;
+        match cmd.id {
+            Command::MoveTo => Some(GeomCommand::MoveTo { dx, dy }),
+            _ => Some(GeomCommand::LineTo { dx, dy }),
+        }
+    }
+}
+
+/// Calculate the bounds of parts with tile coördinates.
+fn parts_bounds(parts: &[Vec<(i32, i32)>]) -> Option<(i32, i32, i32, i32)> {
+    parts.iter().flatten().fold(None, |b, &(x, y)| {
+        Some(match b {
+            Some((x0, y0, x1, y1)) => {
+                (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+            }
+            None => (x, y, x, y),
+        })
+    })
+}
+
+/// Decode geometry data into parts with tile coördinates.
+///
+/// Point data is decoded into a single part.
+fn decode_parts(data: &[u32]) -> Result<Vec<Vec<(i32, i32)>>> {
+    let mut parts = Vec::new();
+    let mut part = Vec::new();
+    let (mut x, mut y) = (0i32, 0i32);
+    let mut i = 0;
+    while i < data.len() {
+        let cmd = CommandInt::decode(data[i])?;
+        i += 1;
+        match cmd.id {
+            Command::MoveTo if !part.is_empty() => {
+                parts.push(std::mem::take(&mut part));
+            }
+            Command::ClosePath => continue,
+            _ => (),
+        }
+        let end = cmd.params_end(data, i)?;
+        for xy in data[i..end].chunks_exact(2) {
+            x = x.wrapping_add(ParamInt::decode(xy[0]).value);
+            y = y.wrapping_add(ParamInt::decode(xy[1]).value);
+            part.push((x, y));
+        }
+        i = end;
+    }
+    if !part.is_empty() {
+        parts.push(part);
+    }
+    Ok(parts)
+}
+
+/// Reorder the last `n` values of per-vertex data (for rewriting a part).
+///
+/// * `order` Indices of part vertices to keep, in new order.
+/// * `shared` Number of shared vertices at the start of the part, which
+///   have no values in the tail.
+fn reorder_tail<T: Copy>(
+    values: &mut Vec<T>,
+    n: usize,
+    order: &[usize],
+    shared: usize,
+) {
+    let tail = values.split_off(values.len() - n);
+    values.extend(order.iter().skip(shared).map(|i| tail[*i - shared]));
+}
+
+/// Decode geometry data into parts, with the index of each vertex.
+///
+/// Vertices are indexed in encoded order.  A polygon ring continuing after
+/// a ClosePath (without a MoveTo) starts with the last vertex of the
+/// previous ring.
+fn decode_vertices(data: &[u32]) -> Result<VertexParts> {
+    let mut parts = Vec::new();
+    let mut part: Vec<(usize, (i32, i32))> = Vec::new();
+    let (mut x, mut y) = (0i32, 0i32);
+    let mut v = 0;
+    let mut closed = false;
+    let mut i = 0;
+    while i < data.len() {
+        let cmd = CommandInt::decode(data[i])?;
+        i += 1;
+        match cmd.id {
+            Command::MoveTo if !part.is_empty() => {
+                parts.push(std::mem::take(&mut part));
+            }
+            Command::LineTo if closed => {
+                let pen = part.last().copied();
+                parts.push(std::mem::take(&mut part));
+                part.extend(pen);
+            }
+            Command::ClosePath => {
+                closed = true;
+                continue;
+            }
+            _ => (),
+        }
+        closed = false;
+        let end = cmd.params_end(data, i)?;
+        for xy in data[i..end].chunks_exact(2) {
+            x = x.wrapping_add(ParamInt::decode(xy[0]).value);
+            y = y.wrapping_add(ParamInt::decode(xy[1]).value);
+            part.push((v, (x, y)));
+            v += 1;
+        }
+        i = end;
+    }
+    if !part.is_empty() {
+        parts.push(part);
+    }
+    Ok(parts)
+}
+
+/// Check that each polygon ring is closed with a ClosePath command.
+fn check_rings_closed(data: &[u32]) -> Result<()> {
+    let mut open = false;
+    let mut i = 0;
+    while i < data.len() {
+        let cmd = CommandInt::decode(data[i])?;
+        i += 1;
+        match cmd.id {
+            Command::MoveTo if open => {
+                log::debug!("ring not closed");
+                return Err(Error::UnclosedRing());
+            }
+            Command::MoveTo => open = true,
+            Command::ClosePath => open = false,
+            Command::LineTo => (),
+        }
+        if cmd.id != Command::ClosePath {
+            i = i.saturating_add(2 * cmd.count as usize);
+        }
+    }
+    if open {
+        log::debug!("ring not closed");
+        return Err(Error::UnclosedRing());
+    }
+    Ok(())
+}
+
+/// Check the command sequence of geometry data (MVT 2.1, 4.3.4).
+fn check_commands(data: &[u32], geom_tp: GeomType) -> Result<()> {
+    let mut i = 0;
+    let mut part = 0;
+    while i < data.len() {
+        if geom_tp == GeomType::Point && part > 0 {
+            log::debug!("point geometry with multiple MoveTo");
+            return Err(Error::InvalidCommand(data[i]).with_part(part));
+        }
+        check_part(data, &mut i, geom_tp).map_err(|e| e.with_part(part))?;
+        part += 1;
+    }
+    Ok(())
+}
+
+/// Check the commands of one part, advancing the data index.
+fn check_part(data: &[u32], i: &mut usize, geom_tp: GeomType) -> Result<()> {
+    let code = data[*i];
+    let cmd = CommandInt::decode(code)?;
+    let multi = geom_tp == GeomType::Point;
+    if cmd.id != Command::MoveTo || cmd.count == 0 || (!multi && cmd.count > 1)
+    {
+        log::debug!("expected MoveTo: {code}");
+        return Err(Error::InvalidCommand(code));
+    }
+    *i = cmd.params_end(data, *i + 1)?;
+    if multi {
+        return Ok(());
+    }
+    let mut count = 0;
+    if let Some(&code) = data.get(*i) {
+        let cmd = CommandInt::decode(code)?;
+        if cmd.id == Command::LineTo {
+            *i = cmd.params_end(data, *i + 1)?;
+            count = cmd.count as usize;
+        }
+    }
+    let (needed, got) = match geom_tp {
+        GeomType::Polygon => (4, count + 2),
+        _ => (2, count + 1),
+    };
+    if got < needed {
+        log::debug!("part with too few points: {got}");
+        return Err(Error::TooFewPoints { needed, got });
+    }
+    if geom_tp == GeomType::Polygon {
+        let close = CommandInt::new(Command::ClosePath, 1).encode();
+        match data.get(*i) {
+            Some(&code) if code == close => *i += 1,
+            Some(&code)
+                if CommandInt::decode(code)?.id == Command::ClosePath =>
+            {
+                log::debug!("invalid ClosePath: {code}");
+                return Err(Error::InvalidCommand(code));
+            }
+            _ => {
+                log::debug!("ring not closed");
+                return Err(Error::UnclosedRing());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Distribute a vertex budget across parts and simplify them.
+///
+/// * `parts` Linestring parts or polygon rings, with vertex indices.
+/// * `closed` Whether the parts are closed rings.
+/// * `limit` Total vertex limit.
+///
+/// Each part gets a minimum number of vertices, with the remaining budget
+/// distributed by length (linestrings) or area (polygons).  If the limit is
+/// too small for all minimums, the smallest parts are dropped.
+fn budget_parts(parts: VertexParts, closed: bool, limit: usize) -> VertexParts {
+    let min = if closed { 3 } else { 2 };
+    // tile points of each part, for weights and simplification
+    let mut parts: Vec<(Vec<(i32, i32)>, _)> = parts
+        .into_iter()
+        .map(|p| (p.iter().map(|v| v.1).collect(), p))
+        .collect();
+    let weight = |p: &[(i32, i32)]| {
+        if closed {
+            signed_area(p).abs()
+        } else {
+            path_length(p)
+        }
+    };
+    while parts.len() * min > limit && !parts.is_empty() {
+        let smallest = parts
+            .iter()
+            .enumerate()
+            .min_by(|a, b| weight(&a.1.0).total_cmp(&weight(&b.1.0)))
+            .map(|(i, _)| i)
+            .unwrap_or_default();
+        parts.remove(smallest);
+    }
+    let total: f64 = parts.iter().map(|p| weight(&p.0)).sum();
+    let spare = limit.saturating_sub(parts.len() * min) as f64;
+    parts
+        .iter()
+        .map(|(pts, part)| {
+            let share = if total > 0.0 {
+                (spare * weight(pts) / total).floor() as usize
+            } else {
+                0
+            };
+            visvalingam_count(pts, closed, min + share)
+                .into_iter()
+                .map(|i| part[i])
+                .collect()
+        })
+        .collect()
+}
+
+/// Tessellate a circular arc into points.
+///
+/// * `center` Center of the arc.
+/// * `radius` Radius of the arc.
+/// * `start` Start angle (radians).
+/// * `end` End angle (radians).
+/// * `segments` Number of line segments (0 for a single point at `center`).
+fn arc_points<F>(
+    center: Pt<F>,
+    radius: F,
+    start: F,
+    end: F,
+    segments: u32,
+) -> impl Iterator<Item = Pt<F>>
+where
+    F: Float,
+{
+    let n = F::from(segments).unwrap_or_else(F::one);
+    let step = (end - start) / n.max(F::one());
+    let count = if segments > 0 && radius > F::zero() {
+        segments + 1
+    } else {
+        1
+    };
+    (0..count).map(move |i| {
+        let angle = start + step * F::from(i).unwrap_or_else(F::zero);
+        center + Pt::from_angle(angle) * radius
+    })
+}
+
+impl GeomData {
+    /// Create new geometry data.
+    ///
+    /// * `geom_tp` Geometry type.
+    /// * `data` Validated geometry.
+    /// * `report` Encoding report.
+    fn new(geom_tp: GeomType, data: Vec<u32>, report: EncodeReport) -> Self {
+        GeomData {
+            geom_tp,
+            data,
+            report,
+            bounds: None,
+            #[cfg(feature = "v3")]
+            elevations: Vec::new(),
+        }
+    }
+
+    /// Create geometry data from a command stream (not validated).
+    pub(crate) fn from_vec(geom_tp: GeomType, data: Vec<u32>) -> Self {
+        GeomData::new(geom_tp, data, EncodeReport::default())
+    }
+
+    /// Get the geometry type
+    pub(crate) fn geom_type(&self) -> GeomType {
+        self.geom_tp
+    }
+
+    /// Check if data is empty
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Get length of data
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Get the encoding report
+    pub fn report(&self) -> &EncodeReport {
+        &self.report
+    }
+
+    /// Get the elevation of each vertex (`v3` feature).
+    ///
+    /// This is empty unless points were added with
+    /// [GeomEncoder::add_point_3d].
+    #[cfg(feature = "v3")]
+    pub fn elevations(&self) -> &[f64] {
+        &self.elevations
+    }
+
+    /// Get the geometry data
+    pub(crate) fn into_vec(self) -> Vec<u32> {
+        self.data
+    }
+
+    /// Decode into parts with tile coördinates.
+    ///
+    /// Each part is a linestring or polygon ring, with delta encoding removed.
+    /// Polygon rings are not explicitly closed (the first vertex is not
+    /// repeated).  Point data is decoded into a single part containing all
+    /// points.
+    ///
+    /// Returns an error if the command stream is malformed:
+    /// * an unknown command ID (`Error::InvalidCommand`)
+    /// * a command count larger than the data (`Error::CountOverflow`)
+    /// * data ending before all parameters (`Error::TruncatedParameters`)
+    pub fn decode(&self) -> Result<Vec<Vec<(i32, i32)>>> {
+        decode_parts(&self.data)
+    }
+
+    /// Get the tile-space bounds of the geometry.
+    ///
+    /// Returns `(x_min, y_min, x_max, y_max)`, or `None` if the geometry is
+    /// empty.  For encoded geometry, the bounds are recorded while encoding,
+    /// so the command stream does not need to be decoded.
+    pub fn bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        self.bounds.or_else(|| parts_bounds(&self.decode().ok()?))
+    }
+
+    /// Get statistics of the geometry.
+    ///
+    /// This can be used to enforce per-feature complexity budgets before
+    /// adding a feature to a layer.
+    pub fn stats(&self) -> GeomStats {
+        let mut stats = GeomStats {
+            encoded_size: protobuf::rt::vec_packed_uint32_size(4, &self.data)
+                as usize,
+            ..Default::default()
+        };
+        for cmd in self.commands() {
+            match cmd {
+                GeomCommand::MoveTo { .. } => {
+                    stats.num_vertices += 1;
+                    stats.num_parts += 1;
+                }
+                GeomCommand::LineTo { .. } => stats.num_vertices += 1,
+                GeomCommand::ClosePath => (),
+            }
+        }
+        stats
+    }
+
+    /// Get an iterator of geometry commands.
+    ///
+    /// Commands with a count are expanded into one item per position.
+    /// Commands with a count of zero are skipped.  Iteration stops at the
+    /// first malformed command.
+    pub fn commands(&self) -> impl Iterator<Item = GeomCommand> + '_ {
+        Commands {
+            data: &self.data,
+            cmd: None,
+        }
+    }
+
+    /// Classify polygon rings by signed area.
+    ///
+    /// Returns an error if:
+    /// * the geometry is not a polygon (`Error::WrongGeomType`)
+    /// * a ring is not closed (`Error::UnclosedRing`)
+    /// * a ring has fewer than 4 points (`Error::TooFewPoints`)
+    /// * a ring has zero area (`Error::ZeroAreaRing`)
+    /// * an interior ring appears before any exterior ring
+    ///   (`Error::InteriorBeforeExterior`)
+    pub fn rings(&self) -> Result<Vec<RingInfo>> {
+        if self.geom_tp != GeomType::Polygon {
+            return Err(Error::WrongGeomType(GeomType::Polygon));
+        }
+        check_rings_closed(&self.data)?;
+        classify_rings(&self.decode()?)
+    }
+
+    /// Check polygon rings for self-intersections.
+    ///
+    /// Each ring is swept for pairs of non-adjacent segments which cross,
+    /// touch or overlap, and for adjacent segments which fold back into a
+    /// zero-width spike.
+    /// Consumers such as Mapbox GL can misrender self-intersecting polygons.
+    /// Intersections between separate rings are not checked.
+    ///
+    /// Returns `Error::SelfIntersection` with the offending ring and segment
+    /// indices, or `Error::WrongGeomType` if the geometry is not a polygon.
+    pub fn validate_topology(&self) -> Result<()> {
+        if self.geom_tp != GeomType::Polygon {
+            return Err(Error::WrongGeomType(GeomType::Polygon));
+        }
+        for (r, ring) in self.decode()?.iter().enumerate() {
+            if let Some((i, j)) = find_ring_crossing(ring) {
+                log::debug!("ring {r} self-intersects: segments {i}, {j}");
+                return Err(Error::SelfIntersection(r, i, j));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the geometry against the MVT 2.1 geometry rules.
+    ///
+    /// * `geom_tp` Expected geometry type.
+    ///
+    /// This is useful for checking geometry data received from elsewhere
+    /// before adding it to a [Feature](crate::Feature).  Empty geometry is
+    /// valid.  Returns an error if:
+    /// * the geometry type does not match (`Error::WrongGeomType`)
+    /// * a command is unexpected or has the wrong count
+    ///   (`Error::InvalidCommand`), or the command stream is malformed
+    /// * a part has too few points (`Error::TooFewPoints`)
+    /// * a ring is not closed (`Error::UnclosedRing`)
+    /// * a ring has zero area (`Error::ZeroAreaRing`), an interior ring
+    ///   appears before any exterior ring (`Error::InteriorBeforeExterior`),
+    ///   or a ring intersects itself (`Error::SelfIntersection`)
+    ///
+    /// Coördinates outside of the tile bounds are allowed, since the
+    /// specification does not limit the buffer size.  Errors in a specific
+    /// part include its index as [context](Error::context).
+    pub fn validate(&self, geom_tp: GeomType) -> Result<()> {
+        if self.geom_tp != geom_tp {
+            return Err(Error::WrongGeomType(geom_tp));
+        }
+        check_commands(&self.data, geom_tp)?;
+        if geom_tp == GeomType::Polygon {
+            classify_rings(&self.decode()?)?;
+            self.validate_topology()?;
+        }
+        Ok(())
+    }
+
+    /// Calculate the centroid of the geometry (tile coördinates).
+    ///
+    /// * Points: mean of all points
+    /// * Linestrings: length-weighted mean of segment midpoints
+    /// * Polygons: area-weighted centroid, with holes subtracted
+    ///
+    /// The centroid may fall outside of the geometry; see
+    /// [representative_point](#method.representative_point).  Returns
+    /// `None` if the geometry is empty.
+    pub fn centroid(&self) -> Result<Option<(f64, f64)>> {
+        let parts = self.decode()?;
+        Ok(match self.geom_tp {
+            GeomType::Point => points_centroid(&parts.concat()),
+            GeomType::Linestring => paths_centroid(&parts),
+            GeomType::Polygon => rings_centroid(&parts),
+        })
+    }
+
+    /// Find a representative point on the geometry (tile coördinates).
+    ///
+    /// Unlike the [centroid](#method.centroid), the point is guaranteed to
+    /// be on the geometry (within rounding):
+    ///
+    /// * Points: the first point
+    /// * Linestrings: halfway along the longest part
+    /// * Polygons: the centroid if it is inside, otherwise the [label
+    ///   position](#method.label_position)
+    ///
+    /// This is useful for reducing polygons to points at low zoom levels.
+    /// Returns `None` if the geometry is empty.
+    pub fn representative_point(&self) -> Result<Option<(i32, i32)>> {
+        let parts = self.decode()?;
+        let round = |(x, y): (f64, f64)| (x.round() as i32, y.round() as i32);
+        Ok(match self.geom_tp {
+            GeomType::Point => parts.first().and_then(|p| p.first()).copied(),
+            GeomType::Linestring => path_midpoint(&parts).map(round),
+            GeomType::Polygon => {
+                polygons_representative(&group_polygons(parts)?)
+            }
+        })
+    }
+
+    /// Find a label position for polygon geometry.
+    ///
+    /// * `precision` Precision of the search (tile units).
+    ///
+    /// The position is the pole of inaccessibility (polylabel): the interior
+    /// point farthest from any ring edge.  For multipolygons, the polygon
+    /// with the largest area is used.
+    ///
+    /// Returns `None` if the geometry has no polygon with positive area, or
+    /// `Error::WrongGeomType` if the geometry is not a polygon.
+    pub fn label_position(&self, precision: f64) -> Result<Option<(f64, f64)>> {
+        if self.geom_tp != GeomType::Polygon {
+            return Err(Error::WrongGeomType(GeomType::Polygon));
+        }
+        let polys = group_polygons(self.decode()?)?;
+        Ok(label_position(&polys, precision))
+    }
+
+    /// Encode a label point for polygon geometry.
+    ///
+    /// * `precision` Precision of the search (tile units).
+    ///
+    /// The result is point geometry at the [label position], for adding as
+    /// a companion feature (typically in a separate label layer).  It is
+    /// empty if the geometry has no polygon with positive area.
+    ///
+    /// [label position]: #method.label_position
+    pub fn label_point(&self, precision: f64) -> Result<GeomData> {
+        let mut enc = GeomEncoder::<f64>::new(GeomType::Point);
+        if let Some((x, y)) = self.label_position(precision)? {
+            enc.add_point_i32(x.round() as i32, y.round() as i32)?;
+        }
+        enc.encode()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::validate::RingType;
+
+    /// Decode absolute tile points from geometry data
+    fn decode_points(v: &[u32]) -> Vec<(i32, i32)> {
+        decode_parts(v).unwrap().concat()
+    }
+
+    // Examples from MVT spec:
+    #[test]
+    fn test_point() {
+        let v = GeomEncoder::new(GeomType::Point)
+            .point(25.0, 17.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 50, 34));
+    }
+
+    #[test]
+    fn test_multipoint() {
+        let enc = GeomEncoder::new(GeomType::Point)
+            .point(5.0, 7.0)
+            .unwrap()
+            .point(3.0, 2.0)
+            .unwrap();
+        assert!(enc.part.is_empty());
+        let v = enc.encode().unwrap().into_vec();
+        assert_eq!(v, vec!(17, 10, 14, 3, 9));
+    }
+
+    #[test]
+    fn test_linestring() {
+        let v = GeomEncoder::new(GeomType::Linestring)
+            .point(2.0, 2.0)
+            .unwrap()
+            .point(2.0, 10.0)
+            .unwrap()
+            .point(10.0, 10.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 4, 4, 18, 0, 16, 16, 0));
+    }
+
+    #[test]
+    fn test_multilinestring() {
+        let v = GeomEncoder::new(GeomType::Linestring)
+            .point(2.0, 2.0)
+            .unwrap()
+            .point(2.0, 10.0)
+            .unwrap()
+            .point(10.0, 10.0)
+            .unwrap()
+            .complete()
+            .unwrap()
+            .point(1.0, 1.0)
+            .unwrap()
+            .point(3.0, 5.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 4, 4, 18, 0, 16, 16, 0, 9, 17, 17, 10, 4, 8));
+    }
+
+    #[test]
+    fn test_multilinestring_with_redundant_points() {
+        let v = GeomEncoder::new(GeomType::Linestring)
+            .point(2.0, 2.0)
+            .unwrap()
+            .point(2.0, 2.0)
+            .unwrap()
+            .point(10.0, 10.0)
+            .unwrap()
+            .complete()
+            .unwrap()
+            .point(10.0, 10.0)
+            .unwrap()
+            .point(13.0, 15.0)
+            .unwrap()
+            .point(10.0, 10.0)
+            .unwrap()
+            .complete()
+            .unwrap()
+            .point(2.0, 2.0)
+            .unwrap()
+            .point(10.0, 10.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(
+            v,
+            vec!(9, 4, 4, 10, 16, 16, 18, 6, 10, 5, 9, 9, 15, 15, 10, 16, 16)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_parts() {
+        let enc = GeomEncoder::new(GeomType::Linestring)
+            .warn_duplicate_parts(true)
+            .point(2.0, 2.0)
+            .unwrap()
+            .point(2.0, 10.0)
+            .unwrap()
+            .complete()
+            .unwrap()
+            .point(5.0, 5.0)
+            .unwrap()
+            .point(8.0, 5.0)
+            .unwrap()
+            .complete()
+            .unwrap();
+        assert!(!enc.report().duplicate_parts);
+        assert_eq!(enc.parts.len(), 2);
+        let geom = enc
+            .point(2.0, 2.0)
+            .unwrap()
+            .point(2.0, 10.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert!(geom.report().duplicate_parts);
+        assert_eq!(
+            geom.into_vec(),
+            vec!(9, 4, 4, 10, 0, 16, 9, 6, 9, 10, 6, 0, 9, 11, 5, 10, 0, 16)
+        );
+    }
+
+    #[test]
+    fn test_negative_coords() {
+        let enc = GeomEncoder::new(GeomType::Point)
+            .transform(Transform::with_translate(-10.0, 0.0));
+        let v = enc
+            .clone()
+            .negative_coords(NegMode::Allow)
+            .point(5.0, 3.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 9, 6));
+        let v = enc
+            .clone()
+            .negative_coords(NegMode::Clamp)
+            .point(5.0, 3.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 0, 6));
+        let res = enc.negative_coords(NegMode::Error).point(5.0, 3.0);
+        assert!(matches!(
+            res.map_err(Error::into_root),
+            Err(Error::NegativeCoordinate())
+        ));
+    }
+
+    #[test]
+    fn test_rounded_rect() {
+        let mut enc = GeomEncoder::new(GeomType::Polygon);
+        let bbox = BBox::new([(0.0, 0.0), (100.0, 60.0)]);
+        enc.add_rounded_rect(bbox, 50.0, 4).unwrap();
+        let pts = decode_points(&enc.encode().unwrap().into_vec());
+        // radius clamped to 30, so top and bottom arc ends are shared
+        assert_eq!(pts.len(), 18);
+        assert_eq!(pts[0], (0, 30));
+        assert_eq!(pts[4], (30, 0));
+        assert_eq!(pts[5], (70, 0));
+        assert_eq!(pts[9], (100, 30));
+        // corners are rounded
+        assert!(!pts.contains(&(0, 0)));
+        assert!(!pts.contains(&(100, 60)));
+        assert!(pts.contains(&(9, 9)));
+        let mut enc = GeomEncoder::new(GeomType::Linestring);
+        assert!(enc.add_rounded_rect(bbox, 5.0, 4).is_err());
+    }
+
+    #[test]
+    fn test_max_total_vertices() {
+        let mut enc = GeomEncoder::new(GeomType::Polygon)
+            .max_total_vertices(100)
+            .record_vertex_map(true);
+        // rings with radius 4000, 2000, 1000 and 500
+        for (i, r) in [4000.0, 2000.0, 1000.0, 500.0].into_iter().enumerate() {
+            let cx = 10_000.0 * i as f64 + 5000.0;
+            for n in 0..250 {
+                let a = std::f64::consts::TAU * f64::from(n) / 250.0;
+                enc.add_point(cx + r * a.cos(), 500.0 + r * a.sin())
+                    .unwrap();
+            }
+            enc.complete_geom().unwrap();
+        }
+        assert!(enc.num_vertices > 990);
+        enc.finish().unwrap();
+        // vertex map is kept for remaining vertices
+        let map = enc.vertex_map().to_vec();
+        let parts = enc.encode().unwrap().decode().unwrap();
+        let pts: Vec<_> = map.iter().map(|v| v.1).collect();
+        assert_eq!(pts, parts.concat());
+        assert!(map.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(parts.len(), 4);
+        let counts: Vec<usize> = parts.iter().map(|p| p.len()).collect();
+        assert!(counts.iter().sum::<usize>() <= 100);
+        assert!(counts.iter().all(|c| *c >= 3));
+        assert!(counts[0] > counts[1]);
+        assert!(counts[1] > counts[2]);
+        for part in parts {
+            assert!(signed_area(&part) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_empty_parts() {
+        for geom_tp in
+            [GeomType::Point, GeomType::Linestring, GeomType::Polygon]
+        {
+            let mut enc = GeomEncoder::new(geom_tp);
+            enc.complete_geom().unwrap();
+            for (x, y) in [(2.0, 2.0), (2.0, 10.0), (10.0, 10.0)] {
+                enc.add_point(x, y).unwrap();
+            }
+            enc.complete_geom().unwrap();
+            enc.complete_geom().unwrap();
+            enc.complete_geom().unwrap();
+            let v = enc.encode().unwrap().into_vec();
+            let expected = match geom_tp {
+                GeomType::Point => vec![25, 4, 4, 0, 16, 16, 0],
+                GeomType::Linestring => vec![9, 4, 4, 18, 0, 16, 16, 0],
+                GeomType::Polygon => vec![9, 4, 4, 18, 0, 16, 16, 0, 15],
+            };
+            assert_eq!(v, expected);
+        }
+        let geom = GeomEncoder::<f64>::new(GeomType::Point).encode().unwrap();
+        assert!(geom.is_empty());
+        // failed point, then a new part
+        let mut enc = GeomEncoder::new(GeomType::Linestring)
+            .bbox(BBox::new([(0.0, 0.0), (10.0, 10.0)]))
+            .validate_extent(8, 0);
+        enc.add_point(-5.0, 9.0).unwrap();
+        // clipped boundary point is out of bounds
+        assert!(enc.add_point(5.0, 9.0).is_err());
+        enc.complete_geom().unwrap();
+        enc.add_point(2.0, 2.0).unwrap();
+        enc.add_point(4.0, 6.0).unwrap();
+        let parts = enc.encode().unwrap().decode().unwrap();
+        assert_eq!(parts, vec![vec![(2, 2), (4, 6)]]);
+    }
+
+    #[test]
+    fn test_weld_tolerance() {
+        let ring = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let enc = |tol| {
+            let mut enc =
+                GeomEncoder::new(GeomType::Polygon).weld_tolerance(tol);
+            for (x, y) in ring {
+                enc.add_point(x, y).unwrap();
+            }
+            enc.add_point(0.001, 0.0).unwrap();
+            enc.encode().unwrap().into_vec()
+        };
+        // unwelded: last vertex duplicates the first
+        assert_eq!(
+            enc(0.0001),
+            vec!(9, 0, 0, 34, 20, 0, 0, 20, 19, 0, 0, 19, 15)
+        );
+        assert_eq!(enc(0.01), vec!(9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15));
+    }
+
+    #[test]
+    fn test_vertex_map() {
+        let mut enc =
+            GeomEncoder::new(GeomType::Polygon).record_vertex_map(true);
+        for (x, y) in [(0.0, 0.0), (5.0, 0.0), (10.0, 0.0), (10.0, 10.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.add_point(0.0, 10.0).unwrap();
+        assert_eq!(
+            enc.vertex_map(),
+            [(0, (0, 0)), (2, (10, 0)), (3, (10, 10)), (4, (0, 10))]
+        );
+        let enc = GeomEncoder::new(GeomType::Point).point(1.0, 2.0).unwrap();
+        assert!(enc.vertex_map().is_empty());
+    }
+
+    #[test]
+    fn test_validate_extent() {
+        let mut enc =
+            GeomEncoder::new(GeomType::Point).validate_extent(4096, 64);
+        enc.add_point(-64.0, 4160.0).unwrap();
+        enc.add_point(2048.0, 2048.0).unwrap();
+        let err = enc.add_point(4161.0, 0.0).unwrap_err();
+        assert_eq!(err.context().and_then(|c| c.vertex), Some(2));
+        assert!(matches!(err.root(), Error::OutOfBounds()));
+        assert!(matches!(
+            enc.add_point(0.0, -65.0).map_err(Error::into_root),
+            Err(Error::OutOfBounds())
+        ));
+        assert_eq!(
+            enc.encode().unwrap().into_vec(),
+            vec!(17, 127, 8320, 4224, 4223)
+        );
+    }
+
+    #[test]
+    fn test_strict() {
+        // interior ring first
+        let ring = [(0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0)];
+        let enc = |strict: bool| {
+            let mut enc = GeomEncoder::new(GeomType::Polygon);
+            if strict {
+                enc = enc.strict();
+            }
+            for (x, y) in ring {
+                enc.add_point(x, y)?;
+            }
+            enc.encode()
+        };
+        assert!(enc(false).is_ok());
+        assert!(matches!(
+            enc(true).map_err(Error::into_root),
+            Err(Error::InteriorBeforeExterior())
+        ));
+        // zero-area ring
+        let res = GeomEncoder::new(GeomType::Polygon)
+            .strict()
+            .point(0.0, 0.0)
+            .and_then(|e| e.point(5.0, 5.0))
+            .and_then(|e| e.point(10.0, 10.0))
+            .and_then(|e| e.encode());
+        assert!(matches!(
+            res.map_err(Error::into_root),
+            Err(Error::ZeroAreaRing())
+        ));
+        // interior ring outside of exterior
+        let res = GeomEncoder::new(GeomType::Polygon)
+            .strict()
+            .point(0.0, 0.0)
+            .and_then(|e| e.point(10.0, 0.0))
+            .and_then(|e| e.point(10.0, 10.0))
+            .and_then(|e| e.complete())
+            .and_then(|e| e.point(20.0, 20.0))
+            .and_then(|e| e.point(20.0, 30.0))
+            .and_then(|e| e.point(30.0, 30.0))
+            .and_then(|e| e.encode());
+        let err = res.err().unwrap();
+        assert_eq!(err.context().and_then(|c| c.part), Some(1));
+        assert!(matches!(err.into_root(), Error::InteriorNotWithin()));
+        // single point linestring
+        let res = GeomEncoder::new(GeomType::Linestring)
+            .strict()
+            .point(1.0, 1.0)
+            .and_then(|e| e.encode());
+        assert!(matches!(
+            res.map_err(Error::into_root),
+            Err(Error::TooFewPoints { needed: 2, got: 1 })
+        ));
+        // valid polygon with hole
+        let res = GeomEncoder::new(GeomType::Polygon)
+            .strict()
+            .point(0.0, 0.0)
+            .and_then(|e| e.point(10.0, 0.0))
+            .and_then(|e| e.point(10.0, 10.0))
+            .and_then(|e| e.point(0.0, 10.0))
+            .and_then(|e| e.complete())
+            .and_then(|e| e.point(2.0, 2.0))
+            .and_then(|e| e.point(2.0, 8.0))
+            .and_then(|e| e.point(8.0, 8.0))
+            .and_then(|e| e.encode());
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_polygon() {
+        let v = GeomEncoder::new(GeomType::Polygon)
+            .point(3.0, 6.0)
+            .unwrap()
+            .point(8.0, 12.0)
+            .unwrap()
+            .point(20.0, 34.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 6, 12, 18, 10, 12, 24, 44, 15));
+    }
+
+    #[test]
+    fn test_multipolygon() {
+        let v = GeomEncoder::new(GeomType::Polygon)
+            // positive area => exterior ring
+            .point(0.0, 0.0)
+            .unwrap()
+            .point(10.0, 0.0)
+            .unwrap()
+            .point(10.0, 10.0)
+            .unwrap()
+            .point(0.0, 10.0)
+            .unwrap()
+            .complete()
+            .unwrap()
+            // positive area => exterior ring
+            .point(11.0, 11.0)
+            .unwrap()
+            .point(20.0, 11.0)
+            .unwrap()
+            .point(20.0, 20.0)
+            .unwrap()
+            .point(11.0, 20.0)
+            .unwrap()
+            .complete()
+            .unwrap()
+            // negative area => interior ring
+            .point(13.0, 13.0)
+            .unwrap()
+            .point(13.0, 17.0)
+            .unwrap()
+            .point(17.0, 17.0)
+            .unwrap()
+            .point(17.0, 13.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(
+            v,
+            vec!(
+                9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15, 9, 22, 2, 26, 18, 0, 0,
+                18, 17, 0, 15, 9, 4, 13, 26, 0, 8, 8, 0, 0, 7, 15
+            )
+        );
+    }
+
+    #[test]
+    fn test_clip_snap_edge() {
+        let bbox = BBox::new([(0.1, 0.1), (0.4, 0.4)]);
+        let t = Transform::with_translate(-0.1, -0.1)
+            .scale(4096.0 / 0.3, 4096.0 / 0.3);
+        let v = GeomEncoder::new(GeomType::Linestring)
+            .bbox(bbox)
+            .transform(t)
+            .point(0.0, 0.13)
+            .unwrap()
+            .point(0.3, 0.37)
+            .unwrap()
+            .point(0.7, 0.31)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        let pts = decode_points(&v);
+        // boundary vertices snapped to x = 0 and x = 4096
+        assert_eq!(pts.len(), 3);
+        assert_eq!(pts[0], (0, 1502));
+        assert_eq!(pts[2], (4096, 3482));
+    }
+
+    #[test]
+    fn test_complexity_score() {
+        let smooth = GeomEncoder::new(GeomType::Linestring)
+            .point(0.0, 0.0)
+            .unwrap()
+            .point(50.0, 50.0)
+            .unwrap()
+            .point(100.0, 100.0)
+            .unwrap();
+        let mut jagged = GeomEncoder::new(GeomType::Linestring);
+        for i in 0..=20 {
+            let x = f64::from(i) * 5.0;
+            let y = if i % 2 == 0 { x } else { 100.0 - x };
+            jagged.add_point(x, y).unwrap();
+        }
+        jagged.add_point(100.0, 100.0).unwrap();
+        let s0 = smooth.complexity_score();
+        let s1 = jagged.complexity_score();
+        assert!(s0 > 0.0);
+        assert!(s1 > s0 * 5.0);
+        assert_eq!(
+            GeomEncoder::<f64>::new(GeomType::Point).complexity_score(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        // polygon example from MVT spec
+        let geom = GeomData::from_vec(
+            GeomType::Polygon,
+            vec![9, 6, 12, 18, 10, 12, 24, 44, 15],
+        );
+        assert_eq!(
+            geom.decode().unwrap(),
+            vec![vec![(3, 6), (8, 12), (20, 34)]]
+        );
+        // invalid command
+        let geom = GeomData::from_vec(GeomType::Point, vec![11, 4, 4]);
+        assert!(matches!(geom.decode(), Err(Error::InvalidCommand(11))));
+        // truncated parameters
+        let geom =
+            GeomData::from_vec(GeomType::Linestring, vec![9, 4, 4, 18, 2]);
+        assert!(matches!(geom.decode(), Err(Error::TruncatedParameters())));
+        // count overflow
+        let geom = GeomData::from_vec(GeomType::Point, vec![u32::MAX - 6, 2]);
+        assert!(matches!(
+            geom.decode(),
+            Err(Error::CountOverflow(0x1FFF_FFFF))
+        ));
+        let geom = GeomData::from_vec(GeomType::Linestring, vec![9, 2]);
+        assert!(matches!(geom.decode(), Err(Error::TruncatedParameters())));
+        assert_eq!(geom.commands().count(), 0);
+    }
+
+    #[test]
+    fn test_auto_winding() {
+        let square = |enc: &mut GeomEncoder<f64>, pts: &[(f64, f64)]| {
+            for (x, y) in pts {
+                enc.add_point(*x, *y).unwrap();
+            }
+            enc.complete_geom().unwrap();
+        };
+        let mut enc = GeomEncoder::new(GeomType::Polygon)
+            .auto_winding(true)
+            .record_vertex_map(true);
+        // counter-clockwise exterior
+        square(
+            &mut enc,
+            &[(0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0)],
+        );
+        // clockwise interior
+        square(&mut enc, &[(2.0, 2.0), (8.0, 2.0), (8.0, 8.0), (2.0, 8.0)]);
+        // clockwise exterior (unchanged)
+        square(
+            &mut enc,
+            &[(20.0, 0.0), (30.0, 0.0), (30.0, 10.0), (20.0, 10.0)],
+        );
+        assert_eq!(
+            enc.vertex_map()[..4]
+                .iter()
+                .map(|v| v.0)
+                .collect::<Vec<_>>(),
+            [0, 3, 2, 1]
+        );
+        let parts = enc.encode().unwrap().decode().unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                vec![(0, 0), (10, 0), (10, 10), (0, 10)],
+                vec![(2, 2), (2, 8), (8, 8), (8, 2)],
+                vec![(20, 0), (30, 0), (30, 10), (20, 10)],
+            ]
+        );
+        assert!(signed_area(&parts[0]) > 0.0);
+        assert!(signed_area(&parts[1]) < 0.0);
+    }
+
+    #[test]
+    fn test_simplify() {
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .simplify(2.0)
+            .point(0.0, 0.0)
+            .unwrap()
+            .point(5.0, 1.0)
+            .unwrap()
+            .point(10.0, 0.0)
+            .unwrap()
+            .point(15.0, 8.0)
+            .unwrap()
+            .point(20.0, 0.0)
+            .unwrap()
+            .complete()
+            .unwrap()
+            .point(30.0, 0.0)
+            .unwrap()
+            .point(31.0, 1.0)
+            .unwrap()
+            .point(40.0, 0.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(
+            geom.decode().unwrap(),
+            vec![
+                vec![(0, 0), (10, 0), (15, 8), (20, 0)],
+                vec![(30, 0), (40, 0)],
+            ]
+        );
+        let mut enc = GeomEncoder::new(GeomType::Polygon)
+            .simplify(2.0)
+            .record_vertex_map(true);
+        for (x, y) in [(0.0, 0.0), (5.0, 1.0), (10.0, 0.0), (10.0, 10.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.add_point(0.0, 10.0).unwrap();
+        enc.complete_geom().unwrap();
+        let map: Vec<_> = enc.vertex_map().iter().map(|v| v.0).collect();
+        assert_eq!(map, [0, 2, 3, 4]);
+        assert_eq!(
+            enc.encode().unwrap().decode().unwrap(),
+            vec![vec![(0, 0), (10, 0), (10, 10), (0, 10)]]
+        );
+        // continuation part, starting at the end of the previous part
+        let mut enc = GeomEncoder::new(GeomType::Linestring)
+            .simplify(2.0)
+            .record_vertex_map(true);
+        for (x, y) in [(0.0, 0.0), (10.0, 0.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        for (x, y) in [(10.0, 0.0), (15.0, 1.0), (20.0, 0.0), (30.0, 0.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        let map: Vec<_> = enc.vertex_map().iter().map(|v| v.0).collect();
+        assert_eq!(map, [0, 1, 5]);
+        assert_eq!(
+            enc.encode().unwrap().into_vec(),
+            vec![9, 0, 0, 10, 20, 0, 10, 40, 0]
+        );
+        let mut enc = GeomEncoder::new(GeomType::Linestring)
+            .simplify(10.0)
+            .simplify_mode(SimplifyMode::Visvalingam);
+        for (x, y) in [(0.0, 0.0), (5.0, 1.0), (10.0, 0.0), (15.0, 8.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.add_point(20.0, 0.0).unwrap();
+        assert_eq!(
+            enc.encode().unwrap().decode().unwrap(),
+            vec![vec![(0, 0), (10, 0), (15, 8), (20, 0)]]
+        );
+    }
+
+    #[test]
+    fn test_snap_rounding() {
+        let ring = [(0.0, 0.0), (20.0, 3.0), (20.0, 10.0), (9.6, 1.49)];
+        let mut enc = GeomEncoder::new(GeomType::Polygon)
+            .snap_rounding(true)
+            .record_vertex_map(true);
+        for (x, y) in ring {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.add_point(0.0, 10.0).unwrap();
+        enc.finish().unwrap();
+        let map: Vec<usize> = enc.vertex_map().iter().map(|v| v.0).collect();
+        assert_eq!(map, [0, 1, 2, 4]);
+        assert_eq!(
+            enc.encode().unwrap().decode().unwrap(),
+            vec![vec![(0, 0), (20, 3), (20, 10), (0, 10)]]
+        );
+    }
+
+    #[test]
+    fn test_decode_vertices() {
+        let enc = GeomEncoder::new(GeomType::Polygon)
+            .points([(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)])
+            .and_then(|e| e.complete())
+            .and_then(|e| e.points([(0.0, 10.0), (10.0, 20.0), (0.0, 20.0)]))
+            .unwrap();
+        let parts = decode_vertices(&enc.encode().unwrap().into_vec()).unwrap();
+        // second ring continues from the last vertex of the first
+        assert_eq!(
+            parts,
+            vec![
+                vec![(0, (0, 0)), (1, (10, 0)), (2, (10, 10)), (3, (0, 10))],
+                vec![(3, (0, 10)), (4, (10, 20)), (5, (0, 20))],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lenient() {
+        let mut enc = GeomEncoder::new(GeomType::Linestring)
+            .strictness(Strictness::Lenient)
+            .record_vertex_map(true);
+        enc.add_point(0.0, 0.0).unwrap();
+        enc.add_point(5.0, 5.0).unwrap();
+        enc.complete_geom().unwrap();
+        // collapses to a single point
+        enc.add_point(10.0, 10.0).unwrap();
+        enc.add_point(10.2, 10.1).unwrap();
+        enc.complete_geom().unwrap();
+        enc.add_point(20.0, 20.0).unwrap();
+        enc.add_point(25.0, 20.0).unwrap();
+        assert_eq!(enc.vertex_map().len(), 4);
+        let geom = enc.encode().unwrap();
+        assert_eq!(geom.report().dropped_lines, 1);
+        assert_eq!(
+            geom.decode().unwrap(),
+            vec![vec![(0, 0), (5, 5)], vec![(20, 20), (25, 20)]]
+        );
+        let mut enc =
+            GeomEncoder::new(GeomType::Polygon).strictness(Strictness::Lenient);
+        for (x, y) in [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        // sub-pixel ring
+        for (x, y) in [(20.0, 20.0), (20.3, 20.0), (20.3, 20.3)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        // zero-area ring
+        for (x, y) in [(30.0, 30.0), (35.0, 35.0), (40.0, 40.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        let geom = enc.encode().unwrap();
+        assert_eq!(geom.report().dropped_rings, 2);
+        assert_eq!(
+            geom.decode().unwrap(),
+            vec![vec![(0, 0), (10, 0), (10, 10), (0, 10)]]
+        );
+        assert!(geom.rings().is_ok());
     }
 
-    /// Check if point should be simplified.
-    fn should_simplify_point(&self, x: i32, y: i32) -> bool {
-        if let (Some((p0x, p0y)), Some((p1x, p1y))) = (self.pt0, self.pt1) {
-            if p0x == p1x && p1x == x {
-                return (p0y < p1y && p1y < y) || (p0y > p1y && p1y > y);
-            }
-            if p0y == p1y && p1y == y {
-                return (p0x < p1x && p1x < x) || (p0x > p1x && p1x > x);
-            }
+    #[test]
+    fn test_validate_topology() {
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let bowtie = [(20.0, 0.0), (30.0, 10.0), (30.0, 0.0), (20.0, 10.0)];
+        let mut enc = GeomEncoder::new(GeomType::Polygon);
+        for (x, y) in square {
+            enc.add_point(x, y).unwrap();
         }
-        false
+        enc.complete_geom().unwrap();
+        let mut valid = enc.clone();
+        for (x, y) in bowtie {
+            enc.add_point(x, y).unwrap();
+        }
+        let geom = enc.encode().unwrap();
+        assert!(matches!(
+            geom.validate_topology(),
+            Err(Error::SelfIntersection(1, 0, 2))
+        ));
+        valid.add_point(20.0, 0.0).unwrap();
+        valid.add_point(30.0, 0.0).unwrap();
+        valid.add_point(25.0, 10.0).unwrap();
+        assert!(valid.encode().unwrap().validate_topology().is_ok());
     }
 
-    /// Complete the current geometry (for multilinestring / multipolygon).
-    pub fn complete_geom(&mut self) -> Result<()> {
-        // FIXME: return Error::InvalidGeometry
-        //        if "MUST" rules in the spec are violated
-        match self.geom_tp {
-            GeomType::Point => {
-                self.set_command_count(self.count);
-                // early return skips geometry reset
-                return Ok(());
-            }
-            GeomType::Linestring => {
-                if self.count > 1 {
-                    self.set_command_count(self.count - 1);
-                }
-            }
-            GeomType::Polygon => {
-                if self.count > 1 {
-                    self.set_command_count(self.count - 1);
-                    self.push_command(Command::ClosePath);
-                }
-            }
-        }
-        // reset linestring / polygon geometry state
-        self.count = 0;
-        self.xy_end = None;
-        self.pt0 = None;
-        Ok(())
+    #[test]
+    fn test_validate() {
+        let line = GeomEncoder::new(GeomType::Linestring)
+            .points([(0.0, 0.0), (10.0, 0.0), (10.0, 30.0)])
+            .and_then(|e| e.complete())
+            .and_then(|e| e.points([(5.0, 5.0), (6.0, 5.0)]))
+            .and_then(|e| e.encode())
+            .unwrap();
+        assert!(line.validate(GeomType::Linestring).is_ok());
+        assert!(matches!(
+            line.validate(GeomType::Polygon),
+            Err(Error::WrongGeomType(GeomType::Polygon))
+        ));
+        // large buffer
+        let line = GeomData::from_vec(
+            GeomType::Linestring,
+            vec![9, 32767, 32767, 10, 65534, 0],
+        );
+        assert!(line.validate(GeomType::Linestring).is_ok());
+        let points = GeomData::from_vec(GeomType::Point, vec![17, 4, 4, 2, 2]);
+        assert!(points.validate(GeomType::Point).is_ok());
+        let points =
+            GeomData::from_vec(GeomType::Point, vec![9, 4, 4, 9, 2, 2]);
+        assert!(matches!(
+            points.validate(GeomType::Point).map_err(Error::into_root),
+            Err(Error::InvalidCommand(9))
+        ));
+        // linestring with two LineTo commands
+        let line = GeomData::from_vec(
+            GeomType::Linestring,
+            vec![9, 0, 0, 10, 20, 0, 10, 0, 20],
+        );
+        let err = line.validate(GeomType::Linestring).unwrap_err();
+        assert_eq!(err.context().and_then(|c| c.part), Some(1));
+        assert!(matches!(err.root(), Error::InvalidCommand(10)));
+        // linestring with LineTo count of 0
+        let line = GeomData::from_vec(GeomType::Linestring, vec![9, 0, 0, 2]);
+        assert!(matches!(
+            line.validate(GeomType::Linestring)
+                .map_err(Error::into_root),
+            Err(Error::TooFewPoints { needed: 2, got: 1 })
+        ));
+        // square polygon
+        let poly = GeomData::from_vec(
+            GeomType::Polygon,
+            vec![9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15],
+        );
+        assert!(poly.validate(GeomType::Polygon).is_ok());
+        let poly = GeomData::from_vec(
+            GeomType::Polygon,
+            vec![9, 0, 0, 26, 20, 0, 0, 20, 19, 0],
+        );
+        assert!(matches!(
+            poly.validate(GeomType::Polygon).map_err(Error::into_root),
+            Err(Error::UnclosedRing())
+        ));
+        let poly =
+            GeomData::from_vec(GeomType::Polygon, vec![9, 0, 0, 10, 20, 0, 15]);
+        assert!(matches!(
+            poly.validate(GeomType::Polygon).map_err(Error::into_root),
+            Err(Error::TooFewPoints { needed: 4, got: 3 })
+        ));
+        // counter-clockwise (interior) ring only
+        let poly = GeomData::from_vec(
+            GeomType::Polygon,
+            vec![9, 0, 0, 26, 0, 20, 20, 0, 0, 19, 15],
+        );
+        assert!(matches!(
+            poly.validate(GeomType::Polygon),
+            Err(Error::InteriorBeforeExterior())
+        ));
     }
 
-    /// Complete the current geometry (for multilinestring / multipolygon).
-    pub fn complete(mut self) -> Result<Self> {
-        self.complete_geom()?;
-        Ok(self)
+    #[test]
+    fn test_centroid() {
+        let points = GeomEncoder::new(GeomType::Point)
+            .points([(0.0, 0.0), (10.0, 0.0), (20.0, 30.0)])
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(points.centroid().unwrap(), Some((10.0, 10.0)));
+        assert_eq!(points.representative_point().unwrap(), Some((0, 0)));
+        let line = GeomEncoder::new(GeomType::Linestring)
+            .points([(0.0, 0.0), (10.0, 0.0), (10.0, 30.0)])
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(line.centroid().unwrap(), Some((8.75, 11.25)));
+        assert_eq!(line.representative_point().unwrap(), Some((10, 10)));
+        // L shape: centroid is outside of the polygon
+        let poly = GeomEncoder::new(GeomType::Polygon)
+            .points([
+                (0.0, 0.0),
+                (100.0, 0.0),
+                (100.0, 10.0),
+                (10.0, 10.0),
+                (10.0, 100.0),
+                (0.0, 100.0),
+            ])
+            .unwrap()
+            .encode()
+            .unwrap();
+        let (x, y) = poly.centroid().unwrap().unwrap();
+        assert!(x > 10.0 && y > 10.0);
+        let (x, y) = poly.representative_point().unwrap().unwrap();
+        assert!(x <= 10 || y <= 10);
+        let empty =
+            GeomEncoder::<f64>::new(GeomType::Polygon).encode().unwrap();
+        assert_eq!(empty.centroid().unwrap(), None);
+        assert_eq!(empty.representative_point().unwrap(), None);
     }
 
-    /// Encode the geometry data, consuming the encoder.
-    pub fn encode(mut self) -> Result<GeomData> {
-        // FIXME: return Error::InvalidGeometry
-        //        if "MUST" rules in the spec are violated
-        self = self.complete()?;
-        Ok(GeomData::new(self.geom_tp, self.data))
+    #[test]
+    fn test_label_point() {
+        let geom = GeomEncoder::new(GeomType::Polygon)
+            .points([(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)])
+            .unwrap()
+            .complete()
+            .unwrap()
+            .points([(20.0, 0.0), (60.0, 0.0), (60.0, 40.0), (20.0, 40.0)])
+            .unwrap()
+            .encode()
+            .unwrap();
+        let label = geom.label_point(1.0).unwrap();
+        assert_eq!(label.geom_type(), GeomType::Point);
+        let pts = label.decode().unwrap();
+        assert_eq!(pts.len(), 1);
+        let (x, y) = pts[0][0];
+        assert!((39..=41).contains(&x) && (19..=21).contains(&y));
+        let line = GeomEncoder::new(GeomType::Linestring)
+            .points([(0.0, 0.0), (10.0, 0.0)])
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert!(line.label_point(1.0).is_err());
     }
-}
 
-impl GeomData {
-    /// Create new geometry data.
-    ///
-    /// * `geom_tp` Geometry type.
-    /// * `data` Validated geometry.
-    fn new(geom_tp: GeomType, data: Vec<u32>) -> Self {
-        GeomData { geom_tp, data }
+    #[test]
+    fn test_label_spacing() {
+        let enc = GeomEncoder::new(GeomType::Linestring)
+            .transform(Transform::with_scale(2.0, 2.0))
+            .label_spacing(40.0)
+            .points([(0.0, 0.0), (50.0, 0.0), (50.0, 20.0)])
+            .unwrap()
+            .complete()
+            .unwrap()
+            .points([(0.0, 10.0), (10.0, 10.0)])
+            .unwrap();
+        assert_eq!(
+            enc.label_points(),
+            &[
+                (20.0, 0.0),
+                (60.0, 0.0),
+                (100.0, 0.0),
+                (100.0, 40.0),
+                (20.0, 20.0)
+            ]
+        );
+        let labels = enc.encode_label_points().unwrap();
+        assert_eq!(labels.geom_type(), GeomType::Point);
+        assert_eq!(labels.decode().unwrap()[0].len(), 5);
+        let bbox = BBox::new([(0.0, 0.0), (20.0, 20.0)]);
+        let enc = GeomEncoder::new(GeomType::Linestring)
+            .bbox(bbox)
+            .label_spacing(10.0)
+            .points([(0.0, 5.0), (40.0, 5.0)])
+            .unwrap();
+        assert_eq!(enc.label_points(), &[(5.0, 5.0), (15.0, 5.0)]);
     }
 
-    /// Get the geometry type
-    pub(crate) fn geom_type(&self) -> GeomType {
-        self.geom_tp
+    #[test]
+    fn test_dedupe_points() {
+        let pts = [(1.0, 1.0), (5.0, 5.0), (1.2, 0.9), (5.0, 5.0), (9.0, 1.0)];
+        let mut enc = GeomEncoder::new(GeomType::Point).dedupe_points(true);
+        for (x, y) in pts {
+            enc.add_point(x, y).unwrap();
+        }
+        assert_eq!(
+            enc.encode().unwrap().decode().unwrap(),
+            vec![vec![(1, 1), (5, 5), (9, 1)]]
+        );
+        let mut enc = GeomEncoder::new(GeomType::Point);
+        for (x, y) in pts {
+            enc.add_point(x, y).unwrap();
+        }
+        assert_eq!(enc.encode().unwrap().decode().unwrap()[0].len(), 5);
     }
 
-    /// Check if data is empty
-    pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+    #[test]
+    fn test_points() {
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .points([(2.0, 2.0), (2.0, 10.0)])
+            .unwrap()
+            .points(vec![Pt::new(10.0, 10.0)])
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.into_vec(), vec!(9, 4, 4, 18, 0, 16, 16, 0));
+        let mut enc = GeomEncoder::new(GeomType::Point);
+        let pts = [(1.0, 1.0), (2.0, 2.0)];
+        enc.add_points(pts.iter().copied()).unwrap();
+        assert_eq!(enc.encode().unwrap().decode().unwrap()[0].len(), 2);
     }
 
-    /// Get length of data
-    pub fn len(&self) -> usize {
-        self.data.len()
+    #[test]
+    fn test_geometries() {
+        let parts = vec![
+            vec![(2.0, 2.0), (2.0, 10.0), (10.0, 10.0)],
+            vec![(1.0, 1.0), (3.0, 5.0)],
+        ];
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .geometries(parts)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(
+            geom.into_vec(),
+            vec!(9, 4, 4, 18, 0, 16, 16, 0, 9, 17, 17, 10, 4, 8)
+        );
     }
 
-    /// Get the geometry data
-    pub(crate) fn into_vec(self) -> Vec<u32> {
-        self.data
+    #[test]
+    fn test_reset() {
+        let mut enc = GeomEncoder::new(GeomType::Point)
+            .transform(Transform::with_scale(2.0, 2.0))
+            .record_vertex_map(true);
+        let mut geom = GeomData::default();
+        enc.add_point(1.0, 1.0).unwrap();
+        enc.add_point(2.0, 3.0).unwrap();
+        enc.encode_into(&mut geom).unwrap();
+        assert_eq!(geom.decode().unwrap(), vec![vec![(2, 2), (4, 6)]]);
+        assert!(enc.vertex_map().is_empty());
+        enc.add_point(5.0, 5.0).unwrap();
+        enc.encode_into(&mut geom).unwrap();
+        assert_eq!(geom.decode().unwrap(), vec![vec![(10, 10)]]);
+        enc.add_point(7.0, 7.0).unwrap();
+        enc.reset(GeomType::Linestring);
+        enc.add_points([(1.0, 1.0), (2.0, 1.0)]).unwrap();
+        assert_eq!(enc.vertex_map().len(), 2);
+        enc.encode_into(&mut geom).unwrap();
+        assert_eq!(geom.geom_type(), GeomType::Linestring);
+        assert_eq!(geom.decode().unwrap(), vec![vec![(2, 2), (4, 2)]]);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[test]
+    fn test_with_capacity() {
+        let enc = GeomEncoder::<f64>::with_capacity(GeomType::Linestring, 100);
+        assert!(enc.data.capacity() >= 203);
+        let geom = enc.points([(1.0, 1.0), (2.0, 2.0)]).unwrap().encode();
+        assert_eq!(geom.unwrap().into_vec(), vec!(9, 2, 2, 10, 2, 2));
+    }
 
-    // Examples from MVT spec:
     #[test]
-    fn test_point() {
-        let v = GeomEncoder::new(GeomType::Point)
-            .point(25.0, 17.0)
+    fn test_commands() {
+        let geom = GeomEncoder::new(GeomType::Polygon)
+            .points([(3.0, 6.0), (8.0, 12.0), (20.0, 34.0)])
             .unwrap()
             .encode()
-            .unwrap()
-            .into_vec();
-        assert_eq!(v, vec!(9, 50, 34));
+            .unwrap();
+        let cmds: Vec<_> = geom.commands().collect();
+        assert_eq!(
+            cmds,
+            vec![
+                GeomCommand::MoveTo { dx: 3, dy: 6 },
+                GeomCommand::LineTo { dx: 5, dy: 6 },
+                GeomCommand::LineTo { dx: 12, dy: 22 },
+                GeomCommand::ClosePath,
+            ]
+        );
+        // truncated
+        let geom = GeomData::from_vec(GeomType::Point, vec![17, 2, 2, 4]);
+        assert_eq!(geom.commands().count(), 1);
+        // zero count
+        let geom = GeomData::from_vec(
+            GeomType::Linestring,
+            vec![9, 6, 12, 2, 10, 18, 10],
+        );
+        let cmds: Vec<_> = geom.commands().collect();
+        assert_eq!(
+            cmds,
+            vec![
+                GeomCommand::MoveTo { dx: 3, dy: 6 },
+                GeomCommand::LineTo { dx: 9, dy: 5 },
+            ]
+        );
     }
 
     #[test]
-    fn test_multipoint() {
-        let v = GeomEncoder::new(GeomType::Point)
-            .point(5.0, 7.0)
-            .unwrap()
-            .point(3.0, 2.0)
+    fn test_stats() {
+        let geom = GeomEncoder::new(GeomType::Polygon)
+            .geometries([
+                [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)],
+                [(20.0, 20.0), (30.0, 20.0), (30.0, 30.0)],
+            ])
             .unwrap()
             .encode()
-            .unwrap()
-            .into_vec();
-        assert_eq!(v, vec!(17, 10, 14, 3, 9));
+            .unwrap();
+        let stats = geom.stats();
+        assert_eq!(stats.num_vertices, 6);
+        assert_eq!(stats.num_parts, 2);
+        assert_eq!(stats.encoded_size, 2 + geom.len());
+        assert_eq!(GeomData::default().stats().encoded_size, 0);
     }
 
     #[test]
-    fn test_linestring() {
-        let v = GeomEncoder::new(GeomType::Linestring)
-            .point(2.0, 2.0)
-            .unwrap()
-            .point(2.0, 10.0)
-            .unwrap()
-            .point(10.0, 10.0)
+    fn test_bounds() {
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .points([(5.0, 8.0), (-3.0, 2.0), (7.0, 4.0)])
             .unwrap()
             .encode()
+            .unwrap();
+        assert_eq!(geom.bounds(), Some((-3, 2, 7, 8)));
+        let decoded = GeomData::from_vec(GeomType::Linestring, geom.into_vec());
+        assert_eq!(decoded.bounds(), Some((-3, 2, 7, 8)));
+        assert_eq!(GeomData::default().bounds(), None);
+        // simplified vertices are not included
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .simplify(2.0)
+            .points([(0.0, 0.0), (5.0, -1.0), (10.0, 0.0)])
             .unwrap()
-            .into_vec();
-        assert_eq!(v, vec!(9, 4, 4, 18, 0, 16, 16, 0));
+            .encode()
+            .unwrap();
+        assert_eq!(geom.bounds(), Some((0, 0, 10, 0)));
     }
 
     #[test]
-    fn test_multilinestring() {
-        let v = GeomEncoder::new(GeomType::Linestring)
-            .point(2.0, 2.0)
-            .unwrap()
-            .point(2.0, 10.0)
+    fn test_skip_outside() {
+        let enc = GeomEncoder::new(GeomType::Linestring).skip_outside(4096, 64);
+        let geom = enc
+            .clone()
+            .points([(4200.0, 10.0), (4300.0, 20.0)])
             .unwrap()
-            .point(10.0, 10.0)
-            .unwrap()
-            .complete()
-            .unwrap()
-            .point(1.0, 1.0)
-            .unwrap()
-            .point(3.0, 5.0)
+            .encode()
+            .unwrap();
+        assert!(geom.is_empty());
+        assert!(geom.report().skipped_outside);
+        assert_eq!(geom.bounds(), None);
+        let geom = enc
+            .clone()
+            .points([(4100.0, 10.0), (4300.0, 20.0)])
             .unwrap()
             .encode()
+            .unwrap();
+        assert!(!geom.is_empty());
+        // crossing the tile, with no vertices inside
+        let geom = enc
+            .points([(-100.0, 10.0), (4300.0, 20.0)])
             .unwrap()
-            .into_vec();
-        assert_eq!(v, vec!(9, 4, 4, 18, 0, 16, 16, 0, 9, 17, 17, 10, 4, 8));
+            .encode()
+            .unwrap();
+        assert!(!geom.report().skipped_outside);
     }
 
     #[test]
-    fn test_multilinestring_with_redundant_points() {
-        let v = GeomEncoder::new(GeomType::Linestring)
-            .point(2.0, 2.0)
+    fn test_rings() {
+        let geom = GeomEncoder::new(GeomType::Polygon)
+            .point(0.0, 0.0)
             .unwrap()
-            .point(2.0, 2.0)
+            .point(10.0, 0.0)
             .unwrap()
             .point(10.0, 10.0)
             .unwrap()
+            .point(0.0, 10.0)
+            .unwrap()
             .complete()
             .unwrap()
-            .point(10.0, 10.0)
+            .point(2.0, 2.0)
             .unwrap()
-            .point(13.0, 15.0)
+            .point(2.0, 8.0)
             .unwrap()
-            .point(10.0, 10.0)
+            .point(8.0, 8.0)
             .unwrap()
-            .complete()
+            .point(8.0, 2.0)
             .unwrap()
+            .encode()
+            .unwrap();
+        let rings = geom.rings().unwrap();
+        assert_eq!(rings.len(), 2);
+        assert_eq!(rings[0].ring_type, RingType::Exterior);
+        assert_eq!(rings[1].ring_type, RingType::Interior);
+        assert_eq!(rings[1].area, -36.0);
+        // interior ring first
+        let geom = GeomEncoder::new(GeomType::Polygon)
             .point(2.0, 2.0)
             .unwrap()
-            .point(10.0, 10.0)
+            .point(2.0, 8.0)
             .unwrap()
-            .encode()
+            .point(8.0, 8.0)
             .unwrap()
-            .into_vec();
+            .encode()
+            .unwrap();
+        assert!(matches!(geom.rings(), Err(Error::InteriorBeforeExterior())));
+        // unclosed ring
+        let geom = GeomData::from_vec(
+            GeomType::Polygon,
+            vec![9, 0, 0, 26, 20, 0, 0, 20],
+        );
+        assert!(matches!(geom.rings(), Err(Error::UnclosedRing())));
+    }
+
+    #[test]
+    fn test_clip_polygon() {
+        let bbox = BBox::new([(0.0, 0.0), (10.0, 10.0)]);
+        let mut enc = GeomEncoder::new(GeomType::Polygon)
+            .bbox(bbox)
+            .record_vertex_map(true);
+        // triangle with a tile corner inside it
+        for (x, y) in [(5.0, 5.0), (20.0, 8.0), (8.0, 20.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        // ring entirely outside
+        for (x, y) in [(20.0, 20.0), (30.0, 20.0), (30.0, 30.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        let map: Vec<usize> = enc.vertex_map().iter().map(|v| v.0).collect();
+        assert_eq!(map, [0, 0, 1, 2]);
+        let parts = enc.encode().unwrap().decode().unwrap();
+        assert_eq!(parts, vec![vec![(6, 10), (5, 5), (10, 6), (10, 10)]]);
+    }
+
+    #[test]
+    fn test_clip_linestring() {
+        let bbox = BBox::new([(0.0, 0.0), (10.0, 10.0)]);
+        let mut enc = GeomEncoder::new(GeomType::Linestring).bbox(bbox);
+        // zig-zag crossing the right edge three times
+        for (x, y) in
+            [(5.0, 1.0), (15.0, 3.0), (5.0, 5.0), (15.0, 7.0), (5.0, 9.0)]
+        {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        // entirely outside
+        for (x, y) in [(20.0, 20.0), (30.0, 20.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        // crossing the whole tile
+        for (x, y) in [(-5.0, 5.0), (15.0, 5.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        let parts = enc.encode().unwrap().decode().unwrap();
         assert_eq!(
-            v,
-            vec!(9, 4, 4, 10, 16, 16, 18, 6, 10, 5, 9, 9, 15, 15, 10, 16, 16)
+            parts,
+            vec![
+                vec![(5, 1), (10, 2)],
+                vec![(10, 4), (5, 5), (10, 6)],
+                vec![(10, 8), (5, 9)],
+                vec![(0, 5), (10, 5)],
+            ]
         );
     }
 
     #[test]
-    fn test_polygon() {
-        let v = GeomEncoder::new(GeomType::Polygon)
-            .point(3.0, 6.0)
-            .unwrap()
-            .point(8.0, 12.0)
+    fn test_clip_buffer() {
+        // source units are half of tile units
+        let bbox = BBox::new([(0.0, 0.0), (50.0, 50.0)]);
+        let t = Transform::with_scale(2.0, 2.0);
+        let enc = |buffer| {
+            GeomEncoder::new(GeomType::Polygon)
+                .bbox(bbox)
+                .transform(t)
+                .clip_buffer(buffer)
+                .point(-40.0, -40.0)
+                .unwrap()
+                .point(40.0, -40.0)
+                .unwrap()
+                .point(40.0, 40.0)
+                .unwrap()
+                .point(-40.0, 40.0)
+                .unwrap()
+                .encode()
+                .unwrap()
+                .decode()
+                .unwrap()
+        };
+        assert_eq!(enc(0), vec![vec![(0, 0), (80, 0), (80, 80), (0, 80)]]);
+        assert_eq!(enc(-10), enc(0));
+        assert_eq!(
+            enc(10),
+            vec![vec![(-10, -10), (80, -10), (80, 80), (-10, 80)]]
+        );
+        let parts = GeomEncoder::new(GeomType::Linestring)
+            .bbox(bbox)
+            .transform(t)
+            .clip_buffer(16)
+            .point(25.0, 25.0)
             .unwrap()
-            .point(20.0, 34.0)
+            .point(100.0, 25.0)
             .unwrap()
             .encode()
             .unwrap()
-            .into_vec();
-        assert_eq!(v, vec!(9, 6, 12, 18, 10, 12, 24, 44, 15));
+            .decode()
+            .unwrap();
+        assert_eq!(parts, vec![vec![(50, 50), (116, 50)]]);
     }
 
     #[test]
-    fn test_multipolygon() {
-        let v = GeomEncoder::new(GeomType::Polygon)
-            // positive area => exterior ring
-            .point(0.0, 0.0)
+    fn test_point_i32() {
+        let pts = [(3, 6), (8, 12), (20, 34), (3, 6)];
+        let v = GeomEncoder::<f64>::new(GeomType::Polygon)
+            .points_i32(pts)
             .unwrap()
-            .point(10.0, 0.0)
+            .encode()
             .unwrap()
-            .point(10.0, 10.0)
+            .into_vec();
+        let w = GeomEncoder::new(GeomType::Polygon)
+            .points(pts.map(|(x, y)| (f64::from(x), f64::from(y))))
             .unwrap()
-            .point(0.0, 10.0)
+            .encode()
             .unwrap()
-            .complete()
+            .into_vec();
+        assert_eq!(v, w);
+        // transform is not applied
+        let v = GeomEncoder::new(GeomType::Point)
+            .transform(Transform::with_scale(2.0, 2.0))
+            .point_i32(25, 17)
             .unwrap()
-            // positive area => exterior ring
-            .point(11.0, 11.0)
+            .encode()
             .unwrap()
-            .point(20.0, 11.0)
+            .into_vec();
+        assert_eq!(v, vec!(9, 50, 34));
+        // negative mode still applies
+        let res = GeomEncoder::<f64>::new(GeomType::Point)
+            .negative_coords(NegMode::Error)
+            .point_i32(-1, 2);
+        assert!(matches!(
+            res.map_err(Error::into_root),
+            Err(Error::NegativeCoordinate())
+        ));
+        let res = GeomEncoder::<f64>::new(GeomType::Point)
+            .validate_extent(4096, 0)
+            .point_i32(4097, 2);
+        assert!(matches!(
+            res.map_err(Error::into_root),
+            Err(Error::OutOfBounds())
+        ));
+    }
+
+    #[cfg(feature = "v3")]
+    #[test]
+    fn test_point_3d() {
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .point_3d(0.0, 0.0, 250.0)
             .unwrap()
-            .point(20.0, 20.0)
+            .point_3d(10.0, 0.0, 260.0)
             .unwrap()
-            .point(11.0, 20.0)
+            .point_3d(10.0, 10.0, 275.5)
             .unwrap()
-            .complete()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.elevations(), [250.0, 260.0, 275.5]);
+        // points without elevation can't be mixed in
+        let mut enc = GeomEncoder::new(GeomType::Linestring)
+            .point_3d(0.0, 0.0, 250.0)
+            .unwrap();
+        let err = enc.add_point(10.0, 0.0).unwrap_err();
+        assert_eq!(err.context().and_then(|c| c.vertex), Some(1));
+        assert!(matches!(err.root(), Error::InvalidGeometry()));
+        assert!(matches!(
+            enc.add_point_i32(10, 0).map_err(Error::into_root),
+            Err(Error::InvalidGeometry())
+        ));
+        let res = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 2.0)
             .unwrap()
-            // negative area => interior ring
-            .point(13.0, 13.0)
+            .point_3d(3.0, 4.0, 5.0);
+        assert!(matches!(
+            res.map_err(Error::into_root),
+            Err(Error::InvalidGeometry())
+        ));
+    }
+
+    #[cfg(feature = "v3")]
+    #[test]
+    fn test_point_3d_clipped() {
+        // linestring crossing the boundary halfway
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .bbox(BBox::new([(0.0, 0.0), (20.0, 20.0)]))
+            .point_3d(-10.0, 10.0, 100.0)
             .unwrap()
-            .point(13.0, 17.0)
+            .point_3d(10.0, 10.0, 200.0)
             .unwrap()
-            .point(17.0, 17.0)
+            .encode()
+            .unwrap();
+        assert_eq!(geom.decode().unwrap(), vec![vec![(0, 10), (10, 10)]]);
+        assert_eq!(geom.elevations(), [150.0, 200.0]);
+        // linestring exiting the bounding box
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .bbox(BBox::new([(0.0, 0.0), (20.0, 20.0)]))
+            .point_3d(5.0, 10.0, 0.0)
             .unwrap()
-            .point(17.0, 13.0)
+            .point_3d(25.0, 10.0, 200.0)
             .unwrap()
             .encode()
+            .unwrap();
+        assert_eq!(geom.decode().unwrap(), vec![vec![(5, 10), (20, 10)]]);
+        assert_eq!(geom.elevations(), [0.0, 150.0]);
+        // polygon ring
+        let geom = GeomEncoder::new(GeomType::Polygon)
+            .bbox(BBox::new([(0.0, 0.0), (10.0, 10.0)]))
+            .point_3d(5.0, 5.0, 0.0)
             .unwrap()
-            .into_vec();
+            .point_3d(15.0, 5.0, 100.0)
+            .unwrap()
+            .point_3d(15.0, 15.0, 200.0)
+            .unwrap()
+            .point_3d(5.0, 15.0, 300.0)
+            .unwrap()
+            .encode()
+            .unwrap();
         assert_eq!(
-            v,
-            vec!(
-                9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15, 9, 22, 2, 26, 18, 0, 0,
-                18, 17, 0, 15, 9, 4, 13, 26, 0, 8, 8, 0, 0, 7, 15
-            )
+            geom.decode().unwrap(),
+            vec![vec![(5, 10), (5, 5), (10, 5), (10, 10)]]
         );
+        assert_eq!(geom.elevations(), [150.0, 0.0, 50.0, 150.0]);
+    }
+
+    #[cfg(feature = "v3")]
+    #[test]
+    fn test_point_3d_rebuilt() {
+        let mut enc =
+            GeomEncoder::new(GeomType::Linestring).max_total_vertices(3);
+        for i in 0..10 {
+            let x = f64::from(i) * 10.0;
+            let y = if i == 5 { 50.0 } else { 0.0 };
+            enc.add_point_3d(x, y, x).unwrap();
+        }
+        let geom = enc.encode().unwrap();
+        // elevation matches X coördinate of each remaining vertex
+        let xs: Vec<f64> = geom.decode().unwrap()[0]
+            .iter()
+            .map(|(x, _y)| f64::from(*x))
+            .collect();
+        assert_eq!(xs.len(), 3);
+        assert_eq!(geom.elevations(), xs);
+        let ring = [(0.0, 0.0), (20.0, 3.0), (20.0, 10.0), (9.6, 1.49)];
+        let mut enc = GeomEncoder::new(GeomType::Polygon).snap_rounding(true);
+        for (i, (x, y)) in ring.into_iter().enumerate() {
+            enc.add_point_3d(x, y, i as f64).unwrap();
+        }
+        enc.add_point_3d(0.0, 10.0, 4.0).unwrap();
+        assert_eq!(enc.encode().unwrap().elevations(), [0.0, 1.0, 2.0, 4.0]);
     }
 }
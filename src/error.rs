@@ -2,15 +2,53 @@
 //
 // Copyright (c) 2019-2022  Minnesota Department of Transportation
 //
+use crate::encoder::GeomType;
 use protobuf::Error as ProtobufError;
+use std::fmt;
+
+/// Context of where an [Error] occurred.
+///
+/// See [Error::context].
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ErrorContext {
+    /// Layer name
+    pub layer: Option<String>,
+
+    /// Feature index within the layer (or source feature index, for
+    /// [Tiler](crate::Tiler))
+    pub feature: Option<usize>,
+
+    /// Part (linestring or polygon ring) index within the geometry
+    pub part: Option<usize>,
+
+    /// Vertex (input point) index within the geometry
+    pub vertex: Option<usize>,
+}
 
 /// MVT Error types
 #[non_exhaustive]
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     /// The tile already contains a layer with the specified name.
-    #[error("Duplicate name")]
-    DuplicateName(),
+    #[error("Duplicate layer name {0:?}")]
+    DuplicateName(String),
+
+    /// The layer name is empty.
+    #[error("Empty layer name")]
+    EmptyName(),
+
+    /// The layer contains more than one feature with the same ID.
+    #[error("Duplicate feature ID {0}")]
+    DuplicateId(u64),
+
+    /// The layer has more features than its limit.
+    #[error("Too many features (limit {0})")]
+    TooManyFeatures(usize),
+
+    /// The layer encoded size is larger than its limit.
+    #[error("Layer too large (limit {0} bytes)")]
+    LayerTooLarge(usize),
 
     /// The layer extent does not match the tile extent.
     #[error("Wrong layer extent")]
@@ -24,14 +62,243 @@ pub enum Error {
     #[error("Invalid geometry data")]
     InvalidGeometry(),
 
+    /// Geometry command integer has an unknown command ID.
+    #[error("Invalid geometry command {0}")]
+    InvalidCommand(u32),
+
+    /// Geometry command count is larger than the geometry data.
+    #[error("Geometry command count {0} overflows data")]
+    CountOverflow(u32),
+
+    /// Geometry data ends before all command parameters.
+    #[error("Truncated geometry parameters")]
+    TruncatedParameters(),
+
+    /// Linestring duplicates a previous part.
+    #[error("Duplicate linestring part")]
+    DuplicatePart(),
+
+    /// Part has too few points.
+    ///
+    /// Linestrings need at least 2 points, and polygon rings need at least
+    /// 4 (including the closing point).
+    #[error("Too few points ({got}, needed {needed})")]
+    TooFewPoints {
+        /// Minimum number of points
+        needed: usize,
+
+        /// Actual number of points
+        got: usize,
+    },
+
+    /// Polygon ring is not closed with a ClosePath command.
+    #[error("Ring not closed")]
+    UnclosedRing(),
+
+    /// Polygon ring has zero area.
+    #[error("Ring has zero area")]
+    ZeroAreaRing(),
+
+    /// Interior ring appears before any exterior ring.
+    #[error("Interior ring before exterior ring")]
+    InteriorBeforeExterior(),
+
+    /// Interior ring is not within the preceding exterior ring.
+    #[error("Interior ring not within exterior ring")]
+    InteriorNotWithin(),
+
+    /// Polygon ring intersects itself.
+    ///
+    /// Contains the ring index and the indices of two intersecting segments
+    /// (segment `i` runs from vertex `i` to the next vertex).
+    #[error("Ring {0} self-intersects at segments {1} and {2}")]
+    SelfIntersection(usize, usize, usize),
+
+    /// Geometry type does not match the expected type.
+    #[error("Wrong geometry type (expected {0:?})")]
+    WrongGeomType(GeomType),
+
     /// Invalid float value
     #[error("Invalid float value")]
     InvalidValue(),
 
+    /// Tile coordinate outside of extent plus buffer.
+    #[error("Coordinate out of bounds")]
+    OutOfBounds(),
+
+    /// Negative tile coordinate (with `NegMode::Error`).
+    #[error("Negative coordinate")]
+    NegativeCoordinate(),
+
+    /// Tile archive is malformed or has an unsupported version.
+    #[error("Invalid archive")]
+    InvalidArchive(),
+
+    /// Tile data is not a valid protobuf message.
+    #[error("Invalid tile data")]
+    InvalidData(),
+
+    /// Layer has an unsupported version.
+    #[error("Unsupported layer version {0}")]
+    UnsupportedVersion(u32),
+
+    /// Tile archive uses an unsupported compression type.
+    #[error("Unsupported compression {0}")]
+    UnsupportedCompression(u8),
+
+    /// I/O error while reading or writing a tile.
+    #[error("I/O error {0}")]
+    Io(#[from] std::io::Error),
+
+    /// SQLite error while writing an MBTiles file.
+    #[cfg(feature = "mbtiles")]
+    #[error("SQLite error {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    /// Error while reading Arrow data.
+    #[cfg(feature = "geoarrow")]
+    #[error("Arrow error {0}")]
+    Arrow(String),
+
+    /// Error while reading FlatGeobuf data.
+    #[cfg(feature = "flatgeobuf")]
+    #[error("FlatGeobuf error {0}")]
+    FlatGeobuf(String),
+
+    /// Error while reprojecting coördinates.
+    #[cfg(feature = "proj")]
+    #[error("Projection error {0}")]
+    Proj(#[from] proj4rs::errors::Error),
+
+    /// Error while serializing tags.
+    #[cfg(feature = "serde")]
+    #[error("Serialize error: {0}")]
+    Serialize(String),
+
     /// Error while encoding protobuf data.
     #[error("Protobuf error {0}")]
     Protobuf(#[from] ProtobufError),
+
+    /// Error with context of where it occurred.
+    #[error("{source} ({context})")]
+    Context {
+        /// Where the error occurred
+        context: ErrorContext,
+
+        /// Underlying error
+        source: Box<Error>,
+    },
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut sep = "";
+        if let Some(layer) = &self.layer {
+            write!(f, "layer {layer:?}")?;
+            sep = ", ";
+        }
+        if let Some(feature) = self.feature {
+            write!(f, "{sep}feature {feature}")?;
+            sep = ", ";
+        }
+        if let Some(part) = self.part {
+            write!(f, "{sep}part {part}")?;
+            sep = ", ";
+        }
+        if let Some(vertex) = self.vertex {
+            write!(f, "{sep}vertex {vertex}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error {
+    /// Get the context of where the error occurred, if known.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Get the underlying error, without context.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    /// Convert into the underlying error, discarding context.
+    ///
+    /// This is useful for matching error variants, e.g.
+    /// `res.map_err(Error::into_root)`.
+    pub fn into_root(self) -> Error {
+        match self {
+            Error::Context { source, .. } => source.into_root(),
+            err => err,
+        }
+    }
+
+    /// Add context, keeping any fields which are already set.
+    fn with_context<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut ErrorContext),
+    {
+        let (mut context, source) = match self {
+            Error::Context { context, source } => (context, source),
+            err => (ErrorContext::default(), Box::new(err)),
+        };
+        f(&mut context);
+        Error::Context { context, source }
+    }
+
+    /// Add layer name context.
+    pub(crate) fn with_layer(self, name: &str) -> Self {
+        self.with_context(|c| {
+            c.layer.get_or_insert_with(|| name.to_string());
+        })
+    }
+
+    /// Add feature index context.
+    pub(crate) fn with_feature(self, feature: usize) -> Self {
+        self.with_context(|c| {
+            c.feature.get_or_insert(feature);
+        })
+    }
+
+    /// Add part index context.
+    pub(crate) fn with_part(self, part: usize) -> Self {
+        self.with_context(|c| {
+            c.part.get_or_insert(part);
+        })
+    }
+
+    /// Add vertex index context.
+    pub(crate) fn with_vertex(self, vertex: usize) -> Self {
+        self.with_context(|c| {
+            c.vertex.get_or_insert(vertex);
+        })
+    }
 }
 
 /// MVT Result
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn context() {
+        let err = Error::OutOfBounds().with_vertex(5).with_part(1);
+        let err = err.with_feature(3).with_layer("roads").with_feature(9);
+        assert_eq!(
+            err.to_string(),
+            "Coordinate out of bounds (layer \"roads\", feature 3, part 1, vertex 5)"
+        );
+        assert_eq!(err.context().unwrap().feature, Some(3));
+        assert!(matches!(err.root(), Error::OutOfBounds()));
+        assert!(Error::InvalidTid().context().is_none());
+    }
+}
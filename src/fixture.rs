@@ -0,0 +1,197 @@
+// fixture.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Test fixtures: shared tile factories, and binary fixtures for encoder
+//! golden tests.
+//!
+//! Binary fixture format (all values little-endian):
+//!
+//! * magic `MVTF` and format version (`u8`, 1)
+//! * geometry type (`u8`: 0 point, 1 linestring, 2 polygon)
+//! * part count (`u32`), then for each part: point count (`u32`) followed by
+//!   `x`, `y` (`f64`) for each point
+//! * expected data length (`u32`), then each encoded value (`u32`)
+use crate::encoder::{GeomEncoder, GeomType};
+use crate::mapgrid::TileId;
+use crate::tile::{Feature, Tile};
+use std::path::Path;
+
+/// Fixture magic bytes
+const MAGIC: &[u8] = b"MVTF";
+
+/// Fixture format version
+const VERSION: u8 = 1;
+
+/// Source geometry parts
+type Parts = Vec<Vec<(f64, f64)>>;
+
+/// Fixture reader
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    /// Take a number of bytes
+    fn take(&mut self, n: usize) -> &[u8] {
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        head
+    }
+
+    /// Read a `u8`
+    fn u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    /// Read a `u32`
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+
+    /// Read an `f64`
+    fn f64(&mut self) -> f64 {
+        f64::from_le_bytes(self.take(8).try_into().unwrap())
+    }
+}
+
+/// Load a fixture.
+///
+/// Returns the geometry type, source parts and expected encoded data.
+///
+/// # Panics
+///
+/// Panics if the fixture is malformed.
+pub fn load_fixture(bytes: &[u8]) -> (GeomType, Parts, Vec<u32>) {
+    let mut rdr = Reader { bytes };
+    assert_eq!(rdr.take(MAGIC.len()), MAGIC, "fixture magic");
+    assert_eq!(rdr.u8(), VERSION, "fixture version");
+    let geom_tp = match rdr.u8() {
+        0 => GeomType::Point,
+        1 => GeomType::Linestring,
+        2 => GeomType::Polygon,
+        tp => panic!("invalid geometry type: {tp}"),
+    };
+    let n_parts = rdr.u32();
+    let parts = (0..n_parts)
+        .map(|_| {
+            let n_pts = rdr.u32();
+            (0..n_pts).map(|_| (rdr.f64(), rdr.f64())).collect()
+        })
+        .collect();
+    let n_data = rdr.u32();
+    let data = (0..n_data).map(|_| rdr.u32()).collect();
+    assert!(rdr.bytes.is_empty(), "fixture trailing bytes");
+    (geom_tp, parts, data)
+}
+
+/// Write a fixture.
+pub fn write_fixture(
+    geom_tp: GeomType,
+    parts: &[Vec<(f64, f64)>],
+    data: &[u32],
+) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.push(match geom_tp {
+        GeomType::Point => 0,
+        GeomType::Linestring => 1,
+        GeomType::Polygon => 2,
+    });
+    bytes.extend((parts.len() as u32).to_le_bytes());
+    for part in parts {
+        bytes.extend((part.len() as u32).to_le_bytes());
+        for (x, y) in part {
+            bytes.extend(x.to_le_bytes());
+            bytes.extend(y.to_le_bytes());
+        }
+    }
+    bytes.extend((data.len() as u32).to_le_bytes());
+    for v in data {
+        bytes.extend(v.to_le_bytes());
+    }
+    bytes
+}
+
+/// Make a tile ID, panicking if it is invalid
+pub(crate) fn tid(x: u32, y: u32, z: u32) -> TileId {
+    TileId::new(x, y, z).unwrap()
+}
+
+/// Add a layer containing one feature to a tile.
+///
+/// * `tile` Tile to add the layer to.
+/// * `layer` Layer name.
+/// * `geom_tp` Geometry type of the feature.
+/// * `pts` Points of the feature geometry.
+/// * `tags` Function to add tags (or an ID) to the feature.
+pub(crate) fn add_layer(
+    tile: &mut Tile,
+    layer: &str,
+    geom_tp: GeomType,
+    pts: &[(f64, f64)],
+    tags: impl FnOnce(&mut Feature),
+) {
+    let layer = tile.create_layer(layer);
+    let geom = GeomEncoder::new(geom_tp)
+        .points(pts.iter().copied())
+        .unwrap()
+        .encode()
+        .unwrap();
+    let mut feature = layer.into_feature(geom);
+    tags(&mut feature);
+    tile.add_layer(feature.into_layer()).unwrap();
+}
+
+/// Make a 4096 extent tile with one layer containing one feature.
+///
+/// See [add_layer] for the arguments.
+pub(crate) fn make_tile(
+    layer: &str,
+    geom_tp: GeomType,
+    pts: &[(f64, f64)],
+    tags: impl FnOnce(&mut Feature),
+) -> Tile {
+    let mut tile = Tile::new(4096);
+    add_layer(&mut tile, layer, geom_tp, pts, tags);
+    tile
+}
+
+/// Encode fixture parts
+fn encode(geom_tp: GeomType, parts: &[Vec<(f64, f64)>]) -> Vec<u32> {
+    let mut enc = GeomEncoder::new(geom_tp);
+    for part in parts {
+        for (x, y) in part {
+            enc.add_point(*x, *y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+    }
+    enc.encode().unwrap().into_vec()
+}
+
+#[test]
+fn fixture_roundtrip() {
+    let parts = vec![vec![(2.0, 2.0), (2.0, 10.0)], vec![(1.0, 1.5)]];
+    let data = encode(GeomType::Linestring, &parts);
+    let bytes = write_fixture(GeomType::Linestring, &parts, &data);
+    let (geom_tp, p, d) = load_fixture(&bytes);
+    assert_eq!(geom_tp, GeomType::Linestring);
+    assert_eq!(p, parts);
+    assert_eq!(d, data);
+}
+
+#[test]
+fn fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut count = 0;
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "mvtf") {
+            let (geom_tp, parts, data) =
+                load_fixture(&std::fs::read(&path).unwrap());
+            assert_eq!(encode(geom_tp, &parts), data, "{path:?}");
+            count += 1;
+        }
+    }
+    assert!(count > 0);
+}
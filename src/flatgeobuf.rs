@@ -0,0 +1,185 @@
+// flatgeobuf.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! FlatGeobuf source integration.
+//!
+use crate::error::{Error, Result};
+use crate::mapgrid::{MapGrid, TileId};
+use crate::tile::Layer;
+use flatgeobuf::FgbReader;
+use std::io::{Read, Seek};
+
+/// Convert a FlatGeobuf (or `geozero`) error
+fn fgb_err<E: ToString>(e: E) -> Error {
+    Error::FlatGeobuf(e.to_string())
+}
+
+impl Layer {
+    /// Add features from FlatGeobuf data within a tile.
+    ///
+    /// * `reader` FlatGeobuf data, in the coördinate reference system of
+    ///   the grid.
+    /// * `grid` Map grid.
+    /// * `tid` Tile ID.
+    ///
+    /// When the data has a spatial index, only features intersecting the
+    /// tile bounds (expanded by the [clip buffer](Layer::clip_buffer)) are
+    /// read; otherwise, all features are scanned.  Features are processed
+    /// by a [LayerProcessor](crate::LayerProcessor), clipped to the tile.
+    ///
+    /// If a feature cannot be read or encoded, an error is returned; features
+    /// processed before it remain in the layer.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use mvt::Error;
+    /// # fn main() -> Result<(), Error> {
+    /// use mvt::{MapGrid, Tile, TileId};
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    ///
+    /// let grid = MapGrid::default();
+    /// let tid = TileId::new(246, 368, 10)?;
+    /// let mut tile = Tile::new(4096);
+    /// let file = BufReader::new(File::open("roads.fgb")?);
+    /// let mut layer = tile.create_layer("roads");
+    /// layer.add_fgb_features(file, &grid, tid)?;
+    /// tile.add_layer(layer)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_fgb_features<R: Read + Seek>(
+        &mut self,
+        reader: R,
+        grid: &MapGrid,
+        tid: TileId,
+    ) -> Result<()> {
+        let bbox = grid.tile_bbox(tid);
+        let scale = f64::from(self.extent());
+        let transform = grid.tile_transform(tid).scale(scale, scale);
+        let buf = f64::from(self.buffer()) / scale;
+        let (bx, by) = (bbox.x_span() * buf, bbox.y_span() * buf);
+        let fgb = FgbReader::open(reader).map_err(fgb_err)?;
+        let header = fgb.header();
+        let mut features =
+            if header.index_node_size() > 0 && header.features_count() > 0 {
+                fgb.select_bbox(
+                    bbox.x_min() - bx,
+                    bbox.y_min() - by,
+                    bbox.x_max() + bx,
+                    bbox.y_max() + by,
+                )
+            } else {
+                log::debug!("scanning FlatGeobuf without spatial index");
+                fgb.select_all()
+            }
+            .map_err(fgb_err)?;
+        let layer = std::mem::take(self);
+        let mut proc = layer.into_processor(transform).bbox(bbox);
+        let res = features.process_features(&mut proc);
+        *self = proc.into_layer();
+        res.map_err(fgb_err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tile::Tile;
+    use crate::value::TagValue;
+    use flatgeobuf::{ColumnType, FgbWriter, FgbWriterOptions, GeometryType};
+    use geozero::geojson::GeoJson;
+    use std::io::Cursor;
+
+    /// Create FlatGeobuf data from GeoJSON features
+    fn fgb_data(features: &[&str], write_index: bool) -> Vec<u8> {
+        let options = FgbWriterOptions {
+            write_index,
+            ..Default::default()
+        };
+        let mut fgb = FgbWriter::create_with_options(
+            "fgb",
+            GeometryType::Unknown,
+            options,
+        )
+        .unwrap();
+        fgb.add_column("name", ColumnType::String, |_, _| {});
+        for feature in features {
+            fgb.add_feature(GeoJson(feature)).unwrap();
+        }
+        let mut data = Vec::new();
+        fgb.write(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn tile_features() {
+        let features = [
+            r#"{"type": "Feature", "properties": {"name": "inside"},
+                "geometry": {"type": "LineString",
+                "coordinates": [[-10000000, 5000000], [-9000000, 6000000]]}}"#,
+            r#"{"type": "Feature", "properties": {"name": "outside"},
+                "geometry": {"type": "LineString",
+                "coordinates": [[10000000, -5000000], [9000000, -6000000]]}}"#,
+            r#"{"type": "Feature", "properties": {"name": "crossing"},
+                "geometry": {"type": "LineString",
+                "coordinates": [[-10000000, 100000], [-10000000, -100000]]}}"#,
+        ];
+        let grid = MapGrid::default();
+        let tid = TileId::new(0, 0, 1).unwrap();
+        for write_index in [true, false] {
+            let data = fgb_data(&features, write_index);
+            let mut tile = Tile::new(4096);
+            let mut layer = tile.create_layer("fgb");
+            layer
+                .add_fgb_features(Cursor::new(data), &grid, tid)
+                .unwrap();
+            tile.add_layer(layer).unwrap();
+            let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+            let layer = tile.layer("fgb").unwrap();
+            let names: Vec<_> = layer
+                .features()
+                .map(|f| f.tags().find(|(k, _)| *k == "name").unwrap().1)
+                .collect();
+            assert_eq!(
+                names,
+                [
+                    TagValue::String("inside".into()),
+                    TagValue::String("crossing".into())
+                ]
+            );
+            // linestring clipped to the tile edge
+            let geom = layer.features().nth(1).unwrap().geom_data().unwrap();
+            assert_eq!(geom.bounds().unwrap().3, 4096);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let grid = MapGrid::default();
+        let tid = TileId::new(0, 0, 1).unwrap();
+        let mut layer = Tile::new(4096).create_layer("fgb");
+        let res = layer.add_fgb_features(
+            Cursor::new(b"not fgb".to_vec()),
+            &grid,
+            tid,
+        );
+        assert!(matches!(res, Err(Error::FlatGeobuf(_))));
+        // truncated last feature
+        let features = [
+            r#"{"type": "Feature", "properties": {"name": "first"},
+                "geometry": {"type": "LineString",
+                "coordinates": [[-10000000, 5000000], [-9000000, 6000000]]}}"#,
+            r#"{"type": "Feature", "properties": {"name": "last"},
+                "geometry": {"type": "LineString",
+                "coordinates": [[-10000000, 5000000], [-9000000, 6000000]]}}"#,
+        ];
+        let mut data = fgb_data(&features, false);
+        data.truncate(data.len() - 8);
+        let res = layer.add_fgb_features(Cursor::new(data), &grid, tid);
+        assert!(matches!(res, Err(Error::FlatGeobuf(_))));
+        assert_eq!(layer.num_features(), 1);
+        assert_eq!(layer.name(), Some("fgb"));
+    }
+}
@@ -118,6 +118,7 @@ impl From<WebMercatorPos> for Pt<f64> {
 }
 
 #[cfg(test)]
+#[allow(clippy::excessive_precision)]
 mod test {
     use super::*;
 
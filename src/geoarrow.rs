@@ -0,0 +1,531 @@
+// geoarrow.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! GeoArrow / GeoParquet input.
+//!
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::{Error, Result};
+use crate::tile::Layer;
+use crate::value::TagValue;
+use crate::wkb::wkb_geom_type;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int8Type, Int16Type, Int32Type, Int64Type,
+    UInt8Type, UInt16Type, UInt32Type, UInt64Type,
+};
+use arrow_array::{Array, Float64Array, ListArray, RecordBatch};
+use arrow_schema::{DataType, Field};
+use pointy::Transform;
+use std::ops::Range;
+
+/// Field metadata key for the extension type name
+const EXTENSION_NAME: &str = "ARROW:extension:name";
+
+/// Coördinates of a native GeoArrow geometry column
+enum Coords<'a> {
+    /// Separate X and Y arrays (struct)
+    Separated(&'a Float64Array, &'a Float64Array),
+
+    /// Interleaved array (fixed size list), with dimensions
+    Interleaved(&'a Float64Array, usize),
+}
+
+/// Geometry column of a record batch
+enum GeomColumn<'a> {
+    /// Well-known binary
+    Wkb(Vec<Option<&'a [u8]>>),
+
+    /// Native GeoArrow encoding
+    Native {
+        /// Geometry array
+        array: &'a dyn Array,
+        /// Geometry type
+        geom_tp: GeomType,
+        /// Nested lists, from outer to inner
+        lists: Vec<&'a ListArray>,
+        /// Coördinates
+        coords: Coords<'a>,
+    },
+}
+
+/// Create an Arrow error
+fn arrow_err(msg: String) -> Error {
+    Error::Arrow(msg)
+}
+
+impl<'a> Coords<'a> {
+    /// Create coördinates from an array
+    fn new(array: &'a dyn Array) -> Result<Self> {
+        if let Some(st) = array.as_struct_opt() {
+            let x = st.column_by_name("x").and_then(|a| a.as_primitive_opt());
+            let y = st.column_by_name("y").and_then(|a| a.as_primitive_opt());
+            if let (Some(x), Some(y)) = (x, y) {
+                return Ok(Coords::Separated(x, y));
+            }
+        }
+        if let Some(fsl) = array.as_fixed_size_list_opt() {
+            let dims = usize::try_from(fsl.value_length()).unwrap_or(0);
+            if let Some(values) = fsl.values().as_primitive_opt()
+                && dims >= 2
+            {
+                return Ok(Coords::Interleaved(values, dims));
+            }
+        }
+        Err(arrow_err(format!(
+            "unsupported coördinates: {}",
+            array.data_type()
+        )))
+    }
+
+    /// Get one coördinate
+    fn get(&self, i: usize) -> (f64, f64) {
+        match self {
+            Coords::Separated(x, y) => (x.value(i), y.value(i)),
+            Coords::Interleaved(v, dims) => {
+                (v.value(i * dims), v.value(i * dims + 1))
+            }
+        }
+    }
+}
+
+impl<'a> GeomColumn<'a> {
+    /// Create a geometry column
+    fn new(field: &Field, array: &'a dyn Array) -> Result<Self> {
+        let ext = field.metadata().get(EXTENSION_NAME).map(String::as_str);
+        match array.data_type() {
+            DataType::Binary => {
+                return Ok(GeomColumn::Wkb(
+                    array.as_binary::<i32>().iter().collect(),
+                ));
+            }
+            DataType::LargeBinary => {
+                return Ok(GeomColumn::Wkb(
+                    array.as_binary::<i64>().iter().collect(),
+                ));
+            }
+            DataType::BinaryView => {
+                return Ok(GeomColumn::Wkb(
+                    array.as_binary_view().iter().collect(),
+                ));
+            }
+            _ => (),
+        }
+        let mut lists = Vec::new();
+        let mut values = array;
+        while let Some(list) = values.as_list_opt::<i32>() {
+            lists.push(list);
+            values = list.values().as_ref();
+        }
+        let coords = Coords::new(values)?;
+        let (geom_tp, depth) = match ext {
+            Some("geoarrow.point") => (GeomType::Point, 0),
+            Some("geoarrow.linestring") => (GeomType::Linestring, 1),
+            Some("geoarrow.polygon") => (GeomType::Polygon, 2),
+            Some("geoarrow.multipoint") => (GeomType::Point, 1),
+            Some("geoarrow.multilinestring") => (GeomType::Linestring, 2),
+            Some("geoarrow.multipolygon") => (GeomType::Polygon, 3),
+            Some(ext) => {
+                return Err(arrow_err(format!("unsupported extension: {ext}")));
+            }
+            // no extension type; infer from nesting
+            None => match lists.len() {
+                0 => (GeomType::Point, 0),
+                1 => (GeomType::Linestring, 1),
+                2 => (GeomType::Polygon, 2),
+                _ => (GeomType::Polygon, 3),
+            },
+        };
+        if lists.len() != depth {
+            return Err(arrow_err(format!(
+                "unexpected nesting for {geom_tp:?}: {}",
+                array.data_type()
+            )));
+        }
+        Ok(GeomColumn::Native {
+            array,
+            geom_tp,
+            lists,
+            coords,
+        })
+    }
+
+    /// Encode the geometry of one row
+    fn encode(
+        &self,
+        layer: &Layer,
+        transform: Transform<f64>,
+        row: usize,
+    ) -> Result<Option<GeomData>> {
+        match self {
+            GeomColumn::Wkb(wkb) => {
+                let Some(wkb) = wkb[row] else {
+                    return Ok(None);
+                };
+                let geom = layer
+                    .geom_encoder(wkb_geom_type(wkb)?)
+                    .transform(transform)
+                    .add_wkb(wkb)?
+                    .encode()?;
+                Ok(Some(geom))
+            }
+            GeomColumn::Native {
+                array,
+                geom_tp,
+                lists,
+                coords,
+            } => {
+                if array.is_null(row) {
+                    return Ok(None);
+                }
+                let mut enc = layer.geom_encoder(*geom_tp).transform(transform);
+                for part in parts(lists, row) {
+                    add_part(&mut enc, coords, part)?;
+                }
+                Ok(Some(enc.encode()?))
+            }
+        }
+    }
+}
+
+/// Get coördinate ranges of all parts of one row.
+///
+/// Each list level maps a range of rows to the ranges of its values; the
+/// innermost level gives parts (or all points, for multipoints).
+fn parts(lists: &[&ListArray], row: usize) -> Vec<Range<usize>> {
+    let mut ranges = vec![Range {
+        start: row,
+        end: row + 1,
+    }];
+    for list in lists {
+        let offsets = list.value_offsets();
+        ranges = ranges
+            .into_iter()
+            .flatten()
+            .map(|i| offsets[i] as usize..offsets[i + 1] as usize)
+            .collect();
+    }
+    ranges
+}
+
+/// Add one part (points, linestring or polygon ring)
+fn add_part(
+    enc: &mut GeomEncoder<f64>,
+    coords: &Coords,
+    mut part: Range<usize>,
+) -> Result<()> {
+    if enc.geom_type() == GeomType::Polygon
+        && part.len() > 1
+        && coords.get(part.start) == coords.get(part.end - 1)
+    {
+        // skip closing vertex
+        part.end -= 1;
+    }
+    for i in part {
+        let (x, y) = coords.get(i);
+        enc.add_point(x, y)?;
+    }
+    if enc.geom_type() == GeomType::Point {
+        Ok(())
+    } else {
+        enc.complete_geom()
+    }
+}
+
+/// Convert an Arrow array into tag values.
+///
+/// Returns `None` for unsupported data types.
+fn tag_values(array: &dyn Array) -> Option<Vec<Option<TagValue>>> {
+    macro_rules! primitive {
+        ($tp:ty, $variant:ident) => {
+            array
+                .as_primitive::<$tp>()
+                .iter()
+                .map(|v| v.map(|v| TagValue::$variant(v.into())))
+                .collect()
+        };
+    }
+    let string = |v: Option<&str>| v.map(|v| TagValue::String(v.into()));
+    Some(match array.data_type() {
+        DataType::Boolean => array
+            .as_boolean()
+            .iter()
+            .map(|v| v.map(TagValue::Bool))
+            .collect(),
+        DataType::Int8 => primitive!(Int8Type, I64),
+        DataType::Int16 => primitive!(Int16Type, I64),
+        DataType::Int32 => primitive!(Int32Type, I64),
+        DataType::Int64 => primitive!(Int64Type, I64),
+        DataType::UInt8 => primitive!(UInt8Type, U64),
+        DataType::UInt16 => primitive!(UInt16Type, U64),
+        DataType::UInt32 => primitive!(UInt32Type, U64),
+        DataType::UInt64 => primitive!(UInt64Type, U64),
+        DataType::Float32 => primitive!(Float32Type, F32),
+        DataType::Float64 => primitive!(Float64Type, F64),
+        DataType::Utf8 => array.as_string::<i32>().iter().map(string).collect(),
+        DataType::LargeUtf8 => {
+            array.as_string::<i64>().iter().map(string).collect()
+        }
+        DataType::Utf8View => {
+            array.as_string_view().iter().map(string).collect()
+        }
+        DataType::Dictionary(_, _) => {
+            // convert dictionary values only once
+            let dict = array.as_any_dictionary_opt()?;
+            let values = tag_values(dict.values().as_ref())?;
+            dict.normalized_keys()
+                .into_iter()
+                .enumerate()
+                .map(|(i, k)| {
+                    if array.is_valid(i) {
+                        values.get(k).cloned().flatten()
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        }
+        _ => return None,
+    })
+}
+
+impl Layer {
+    /// Add features from an Arrow record batch.
+    ///
+    /// * `batch` Record batch, such as a GeoParquet row group.
+    /// * `geom_column` Name of geometry column.
+    /// * `transform` Transform from source to tile coördinates.
+    ///
+    /// The geometry column can be WKB (binary), or a native GeoArrow
+    /// encoding, with separated (struct) or interleaved (fixed size list)
+    /// coördinates.  The geometry type of native columns is determined from
+    /// the GeoArrow extension name, or inferred from the nesting depth.
+    /// Geometry is encoded using the [layer options](Layer::geom_encoder),
+    /// and rows with null or empty geometry are skipped.
+    ///
+    /// All other columns are added as tags, converted one column at a time.
+    /// Boolean, integer, float and string (including dictionary-encoded)
+    /// columns are supported; other columns are skipped, as are null values.
+    ///
+    /// Returns `Error::Arrow` if the geometry column is missing or has an
+    /// unsupported data type.  Errors encoding geometry include the row
+    /// index as [context](Error::context); rows before the invalid one
+    /// remain in the layer.
+    pub fn add_record_batch(
+        &mut self,
+        batch: &RecordBatch,
+        geom_column: &str,
+        transform: Transform<f64>,
+    ) -> Result<()> {
+        let schema = batch.schema();
+        let (idx, field) = schema
+            .column_with_name(geom_column)
+            .ok_or_else(|| arrow_err(format!("no column: {geom_column}")))?;
+        let geom_col = GeomColumn::new(field, batch.column(idx).as_ref())?;
+        let mut columns = Vec::new();
+        for (i, field) in schema.fields().iter().enumerate() {
+            if i == idx {
+                continue;
+            }
+            match tag_values(batch.column(i).as_ref()) {
+                Some(values) => columns.push((field.name().as_str(), values)),
+                None => log::debug!(
+                    "skipping column {}: {}",
+                    field.name(),
+                    field.data_type()
+                ),
+            }
+        }
+        for row in 0..batch.num_rows() {
+            let geom = geom_col
+                .encode(self, transform, row)
+                .map_err(|e| e.with_feature(row))?;
+            let Some(geom) = geom.filter(|g| !g.is_empty()) else {
+                continue;
+            };
+            let mut feature = std::mem::take(self).into_feature(geom);
+            for (key, values) in &mut columns {
+                if let Some(value) = values[row].take() {
+                    feature.add_tag(key, value);
+                }
+            }
+            *self = feature.into_layer();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tile::Tile;
+    use arrow_array::builder::{
+        FixedSizeListBuilder, Float64Builder, ListBuilder,
+    };
+    use arrow_array::{
+        ArrayRef, BinaryArray, Date32Array, DictionaryArray, Int32Array,
+        StringArray, StructArray,
+    };
+    use arrow_schema::Schema;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// Build a record batch from named columns
+    fn batch(columns: Vec<(Field, ArrayRef)>) -> RecordBatch {
+        let (fields, arrays): (Vec<_>, Vec<_>) = columns.into_iter().unzip();
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays).unwrap()
+    }
+
+    /// Create a field for an array
+    fn field(name: &str, array: &ArrayRef) -> Field {
+        Field::new(name, array.data_type().clone(), true)
+    }
+
+    /// Decoded feature geometry and tags
+    type Decoded = (Vec<u32>, Vec<(String, TagValue)>);
+
+    /// Decode geometry and tags of all features in a layer
+    fn decode(layer: Layer) -> Vec<Decoded> {
+        let mut tile = Tile::new(4096);
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layers().next().unwrap();
+        layer
+            .features()
+            .map(|f| {
+                let tags = f.tags().map(|(k, v)| (k.to_string(), v)).collect();
+                (f.geom_data().unwrap().into_vec(), tags)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn native_polygons() {
+        let mut builder = ListBuilder::new(ListBuilder::new(
+            FixedSizeListBuilder::new(Float64Builder::new(), 2),
+        ));
+        for row in [Some(0.0), None, Some(100.0)] {
+            if let Some(dx) = row {
+                let ring = builder.values().values();
+                for (x, y) in
+                    [(3.0, 6.0), (8.0, 12.0), (20.0, 34.0), (3.0, 6.0)]
+                {
+                    ring.values().append_value(x + dx);
+                    ring.values().append_value(y);
+                    ring.append(true);
+                }
+                builder.values().append(true);
+            }
+            builder.append(row.is_some());
+        }
+        let geom: ArrayRef = Arc::new(builder.finish());
+        let ext = (EXTENSION_NAME.into(), "geoarrow.polygon".into());
+        let geom_field =
+            field("geometry", &geom).with_metadata(HashMap::from([ext]));
+        let name: ArrayRef =
+            Arc::new(StringArray::from(vec![Some("a"), Some("b"), None]));
+        let kind: ArrayRef = Arc::new(
+            [Some("park"), None, Some("park")]
+                .into_iter()
+                .collect::<DictionaryArray<Int32Type>>(),
+        );
+        let count: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+        let date: ArrayRef = Arc::new(Date32Array::from(vec![1, 2, 3]));
+        let batch = batch(vec![
+            (field("name", &name), name),
+            (geom_field, geom),
+            (field("kind", &kind), kind),
+            (field("count", &count), count),
+            (field("date", &date), date),
+        ]);
+        let mut layer = Tile::new(4096).create_layer("arrow");
+        layer
+            .add_record_batch(&batch, "geometry", Transform::default())
+            .unwrap();
+        let features = decode(layer);
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0].0, vec![9, 6, 12, 18, 10, 12, 24, 44, 15]);
+        assert_eq!(
+            features[0].1,
+            [
+                ("name".into(), TagValue::String("a".into())),
+                ("kind".into(), TagValue::String("park".into())),
+                ("count".into(), TagValue::I64(1)),
+            ]
+        );
+        assert_eq!(
+            features[1].1,
+            [
+                ("kind".into(), TagValue::String("park".into())),
+                ("count".into(), TagValue::I64(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn wkb() {
+        let mut point = vec![1, 1, 0, 0, 0];
+        point.extend(25.0f64.to_le_bytes());
+        point.extend(17.0f64.to_le_bytes());
+        let geom: ArrayRef = Arc::new(BinaryArray::from(vec![
+            Some(&point[..]),
+            None,
+            Some(&point[..]),
+        ]));
+        let batch = batch(vec![(field("geom", &geom), geom)]);
+        let mut layer = Tile::new(4096).create_layer("arrow");
+        layer
+            .add_record_batch(&batch, "geom", Transform::with_scale(2.0, 2.0))
+            .unwrap();
+        let features = decode(layer);
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0].0, vec![9, 100, 68]);
+    }
+
+    #[test]
+    fn separated_points() {
+        let x: ArrayRef = Arc::new(Float64Array::from(vec![25.0, 1.0]));
+        let y: ArrayRef = Arc::new(Float64Array::from(vec![17.0, 2.0]));
+        let geom: ArrayRef = Arc::new(StructArray::from(vec![
+            (Arc::new(field("x", &x)), x),
+            (Arc::new(field("y", &y)), y),
+        ]));
+        let batch = batch(vec![(field("geom", &geom), geom)]);
+        let mut layer = Tile::new(4096).create_layer("arrow");
+        layer
+            .add_record_batch(&batch, "geom", Transform::default())
+            .unwrap();
+        let features = decode(layer);
+        assert_eq!(features[0].0, vec![9, 50, 34]);
+        assert_eq!(features[1].0, vec![9, 2, 4]);
+    }
+
+    #[test]
+    fn invalid() {
+        let count: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+        let counts = batch(vec![(field("count", &count), count)]);
+        let mut layer = Tile::new(4096).create_layer("arrow");
+        assert!(matches!(
+            layer.add_record_batch(&counts, "geom", Transform::default()),
+            Err(Error::Arrow(_))
+        ));
+        assert!(matches!(
+            layer.add_record_batch(&counts, "count", Transform::default()),
+            Err(Error::Arrow(_))
+        ));
+        // rows before an invalid row are kept
+        let x: ArrayRef = Arc::new(Float64Array::from(vec![1.0, f64::NAN]));
+        let y: ArrayRef = Arc::new(Float64Array::from(vec![2.0, 3.0]));
+        let geom: ArrayRef = Arc::new(StructArray::from(vec![
+            (Arc::new(field("x", &x)), x),
+            (Arc::new(field("y", &y)), y),
+        ]));
+        let batch = batch(vec![(field("geom", &geom), geom)]);
+        let err = layer
+            .add_record_batch(&batch, "geom", Transform::default())
+            .unwrap_err();
+        assert_eq!(err.context().unwrap().feature, Some(1));
+        assert_eq!(layer.num_features(), 1);
+        assert_eq!(layer.name(), Some("arrow"));
+    }
+}
@@ -0,0 +1,493 @@
+// geojson.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! GeoJSON import / export.
+//!
+use crate::decoder::FeatureRef;
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::{Error, Result};
+use crate::geo::{WebMercatorPos, Wgs84Pos};
+use crate::mapgrid::{MapGrid, TileId};
+use crate::tile::{Feature, Layer, Tile};
+use crate::validate::group_polygons;
+use crate::value::TagValue;
+use geojson::{
+    FeatureCollection, Geometry, JsonObject, JsonValue, Position, Value,
+    feature::Id,
+};
+use pointy::{Pt, Transform};
+
+/// Get the geometry type for a GeoJSON geometry value.
+///
+/// Returns `None` for geometry collections.
+fn geom_type(value: &Value) -> Option<GeomType> {
+    match value {
+        Value::Point(_) | Value::MultiPoint(_) => Some(GeomType::Point),
+        Value::LineString(_) | Value::MultiLineString(_) => {
+            Some(GeomType::Linestring)
+        }
+        Value::Polygon(_) | Value::MultiPolygon(_) => Some(GeomType::Polygon),
+        Value::GeometryCollection(_) => None,
+    }
+}
+
+/// Project a WGS-84 position to Web Mercator
+fn project(pos: &Position) -> Result<Pt<f64>> {
+    match pos[..] {
+        [lon, lat, ..] => {
+            Ok(Pt::from(WebMercatorPos::from(Wgs84Pos::new(lat, lon))))
+        }
+        _ => Err(Error::InvalidGeometry()),
+    }
+}
+
+/// GeoJSON geometry encoder
+struct GeoJsonEncoder {
+    /// Geometry encoder
+    enc: GeomEncoder<f64>,
+    /// Transform to tile coördinates
+    transform: Transform<f64>,
+}
+
+impl GeoJsonEncoder {
+    /// Add a WGS-84 position
+    fn add_position(&mut self, pos: &Position) -> Result<()> {
+        let pt = project(pos)?;
+        self.enc.add_point(pt.x, pt.y)
+    }
+
+    /// Add a linestring and complete it
+    fn add_linestring(&mut self, line: &[Position]) -> Result<()> {
+        for pos in line {
+            self.add_position(pos)?;
+        }
+        self.enc.complete_geom()
+    }
+
+    /// Add the rings of a polygon, skipping closing positions.
+    ///
+    /// GeoJSON rings are identified by position (exterior first), so each
+    /// ring is reversed if its winding order does not match.
+    fn add_polygon(&mut self, rings: &[Vec<Position>]) -> Result<()> {
+        for (i, ring) in rings.iter().enumerate() {
+            let ring = match ring.split_last() {
+                Some((last, rest)) if Some(last) == ring.first() => rest,
+                _ => &ring[..],
+            };
+            let mut pts =
+                ring.iter().map(project).collect::<Result<Vec<_>>>()?;
+            let n = pts.len();
+            let area: f64 = (0..n)
+                .map(|j| {
+                    let p0 = self.transform * pts[j];
+                    let p1 = self.transform * pts[(j + 1) % n];
+                    p0.x * p1.y - p1.x * p0.y
+                })
+                .sum();
+            // exterior rings have positive area in tile coördinates
+            if (i == 0) != (area > 0.0) {
+                pts.reverse();
+            }
+            for pt in pts {
+                self.enc.add_point(pt.x, pt.y)?;
+            }
+            self.enc.complete_geom()?;
+        }
+        Ok(())
+    }
+
+    /// Add a geometry value
+    fn add_value(&mut self, value: &Value) -> Result<()> {
+        match value {
+            Value::Point(pos) => self.add_position(pos),
+            Value::MultiPoint(pts) => {
+                pts.iter().try_for_each(|pos| self.add_position(pos))
+            }
+            Value::LineString(line) => self.add_linestring(line),
+            Value::MultiLineString(lines) => {
+                lines.iter().try_for_each(|line| self.add_linestring(line))
+            }
+            Value::Polygon(rings) => self.add_polygon(rings),
+            Value::MultiPolygon(polys) => {
+                polys.iter().try_for_each(|rings| self.add_polygon(rings))
+            }
+            Value::GeometryCollection(_) => Err(Error::InvalidGeometry()),
+        }
+    }
+}
+
+/// Encode a GeoJSON geometry
+fn encode_geometry(
+    geometry: &Geometry,
+    transform: Transform<f64>,
+) -> Result<GeomData> {
+    let geom_tp = geom_type(&geometry.value).ok_or(Error::InvalidGeometry())?;
+    let enc = GeomEncoder::new(geom_tp).transform(transform);
+    let mut enc = GeoJsonEncoder { enc, transform };
+    enc.add_value(&geometry.value)?;
+    enc.enc.encode()
+}
+
+/// Add a property as a feature tag
+fn add_property(feature: &mut Feature, key: &str, value: &JsonValue) {
+    match value {
+        JsonValue::Null => (),
+        JsonValue::Bool(v) => feature.add_tag_bool(key, *v),
+        JsonValue::Number(num) => {
+            if let Some(v) = num.as_u64() {
+                feature.add_tag_uint(key, v);
+            } else if let Some(v) = num.as_i64() {
+                feature.add_tag_sint(key, v);
+            } else if let Some(v) = num.as_f64() {
+                feature.add_tag_double(key, v);
+            }
+        }
+        JsonValue::String(v) => feature.add_tag_string(key, v),
+        // arrays and objects are encoded as JSON strings
+        _ => feature.add_tag_string(key, &value.to_string()),
+    }
+}
+
+impl Layer {
+    /// Add features from a GeoJSON feature collection.
+    ///
+    /// * `fc` Feature collection, with WGS-84 coördinates.
+    /// * `transform` Transform from Web Mercator to tile coördinates,
+    ///   normally from [MapGrid::tile_transform], scaled to the tile extent.
+    ///
+    /// Geometry is encoded with a [GeomEncoder], properties are added as
+    /// tags and numeric IDs are used as feature IDs.  Null properties are
+    /// skipped, and arrays / objects are added as JSON strings.  Features
+    /// without geometry are skipped.
+    ///
+    /// Returns `Error::InvalidGeometry` for geometry collections or
+    /// positions with fewer than two values, with the feature index as
+    /// [context](Error::context).  Features before the invalid one remain in
+    /// the layer.
+    ///
+    /// [MapGrid::tile_transform]: crate::MapGrid::tile_transform
+    pub fn add_geojson(
+        &mut self,
+        fc: &FeatureCollection,
+        transform: Transform<f64>,
+    ) -> Result<()> {
+        for (i, gj_feature) in fc.features.iter().enumerate() {
+            let Some(geometry) = &gj_feature.geometry else {
+                log::debug!("skipping feature without geometry");
+                continue;
+            };
+            let geom_data = encode_geometry(geometry, transform)
+                .map_err(|e| e.with_feature(i))?;
+            let mut feature = std::mem::take(self).into_feature(geom_data);
+            if let Some(Id::Number(id)) = &gj_feature.id {
+                match id.as_u64() {
+                    Some(id) => feature.set_id(id),
+                    None => log::warn!("invalid feature ID: {id}"),
+                }
+            }
+            if let Some(props) = &gj_feature.properties {
+                for (key, value) in props {
+                    add_property(&mut feature, key, value);
+                }
+            }
+            *self = feature.into_layer();
+        }
+        Ok(())
+    }
+}
+
+impl From<TagValue> for JsonValue {
+    fn from(value: TagValue) -> Self {
+        match value {
+            TagValue::String(v) => JsonValue::from(v),
+            TagValue::F64(v) => JsonValue::from(v),
+            TagValue::F32(v) => JsonValue::from(v),
+            TagValue::I64(v) | TagValue::Sint(v) => JsonValue::from(v),
+            TagValue::U64(v) => JsonValue::from(v),
+            TagValue::Bool(v) => JsonValue::from(v),
+        }
+    }
+}
+
+/// Decode feature geometry to a GeoJSON geometry value
+fn decode_geometry(
+    feature: &FeatureRef,
+    transform: Transform<f64>,
+) -> Result<Value> {
+    let geom = feature.geom_data().ok_or(Error::InvalidGeometry())?;
+    let parts = geom.decode()?;
+    let pos = |&(x, y): &(i32, i32)| -> Position {
+        let pt = transform * (f64::from(x), f64::from(y));
+        let pos = Wgs84Pos::from(WebMercatorPos::new(pt.x, pt.y));
+        vec![pos.lon_deg(), pos.lat_deg()]
+    };
+    let line = |part: &Vec<(i32, i32)>| -> Vec<Position> {
+        part.iter().map(pos).collect()
+    };
+    let ring = |part: &Vec<(i32, i32)>| -> Vec<Position> {
+        let mut ring = line(part);
+        if let Some(first) = ring.first() {
+            ring.push(first.clone());
+        }
+        ring
+    };
+    Ok(match geom.geom_type() {
+        GeomType::Point => {
+            let mut pts = line(&parts.concat());
+            if pts.len() == 1 {
+                Value::Point(pts.remove(0))
+            } else {
+                Value::MultiPoint(pts)
+            }
+        }
+        GeomType::Linestring => {
+            let mut lines: Vec<_> = parts.iter().map(line).collect();
+            if lines.len() == 1 {
+                Value::LineString(lines.remove(0))
+            } else {
+                Value::MultiLineString(lines)
+            }
+        }
+        GeomType::Polygon => {
+            let mut polys: Vec<Vec<_>> = group_polygons(parts)?
+                .iter()
+                .map(|rings| rings.iter().map(ring).collect())
+                .collect();
+            if polys.len() == 1 {
+                Value::Polygon(polys.remove(0))
+            } else {
+                Value::MultiPolygon(polys)
+            }
+        }
+    })
+}
+
+impl Tile {
+    /// Convert all layers and features to a GeoJSON feature collection.
+    ///
+    /// * `tid` Tile ID, on the Web Mercator grid ([MapGrid::default]).
+    ///
+    /// Coördinates are converted to WGS-84.  Tags are added as properties,
+    /// and the layer name is added as a `layer` foreign member of each
+    /// feature.
+    ///
+    /// Returns `Error::InvalidGeometry` for features with unknown geometry,
+    /// or a [decode](crate::GeomData::decode) error for malformed geometry.
+    pub fn to_geojson(&self, tid: TileId) -> Result<FeatureCollection> {
+        let grid = MapGrid::default();
+        let e = 1.0 / f64::from(self.extent());
+        let transform =
+            Transform::with_scale(e, e) * grid.tile_inverse_transform(tid);
+        let mut features = Vec::new();
+        for layer in self.layers() {
+            for (i, feature) in layer.features().enumerate() {
+                let value = decode_geometry(&feature, transform)
+                    .map_err(|e| e.with_layer(layer.name()).with_feature(i))?;
+                let properties: JsonObject = feature
+                    .tags()
+                    .map(|(k, v)| (k.to_string(), JsonValue::from(v)))
+                    .collect();
+                let mut foreign_members = JsonObject::new();
+                foreign_members.insert("layer".into(), layer.name().into());
+                features.push(geojson::Feature {
+                    bbox: None,
+                    geometry: Some(Geometry::new(value)),
+                    id: feature.id().map(|id| Id::Number(id.into())),
+                    properties: Some(properties),
+                    foreign_members: Some(foreign_members),
+                });
+            }
+        }
+        Ok(FeatureCollection {
+            bbox: None,
+            features,
+            foreign_members: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RingType;
+
+    const GEOJSON: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [{
+            "type": "Feature",
+            "id": 12,
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [[
+                    [-93.0, 44.0], [-92.0, 44.0], [-92.0, 45.0],
+                    [-93.0, 45.0], [-93.0, 44.0]
+                ]]
+            },
+            "properties": {
+                "name": "square",
+                "lanes": 2,
+                "offset": -3,
+                "width": 1.5,
+                "open": true,
+                "note": null,
+                "tags": ["a", "b"]
+            }
+        }, {
+            "type": "Feature",
+            "geometry": null,
+            "properties": {}
+        }, {
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": [-92.5, 44.5]
+            },
+            "properties": null
+        }]
+    }"#;
+
+    #[test]
+    fn import() {
+        let fc: FeatureCollection = GEOJSON.parse().unwrap();
+        let grid = MapGrid::default();
+        let tid = TileId::new(0, 0, 0).unwrap();
+        let t = grid.tile_transform(tid).scale(4096.0, 4096.0);
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("geojson");
+        layer.add_geojson(&fc, t).unwrap();
+        assert_eq!(layer.num_features(), 2);
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layer("geojson").unwrap();
+        let mut features = layer.features();
+        let feature = features.next().unwrap();
+        assert_eq!(feature.id(), Some(12));
+        assert_eq!(feature.geom_type(), Some(GeomType::Polygon));
+        let parts = feature.geom_data().unwrap().decode().unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].len(), 4);
+        let mut tags: Vec<_> = feature.tags().collect();
+        tags.sort_by(|a, b| a.0.cmp(b.0));
+        assert_eq!(
+            tags,
+            vec![
+                ("lanes", TagValue::U64(2)),
+                ("name", TagValue::String("square".to_string())),
+                ("offset", TagValue::Sint(-3)),
+                ("open", TagValue::Bool(true)),
+                ("tags", TagValue::String(r#"["a","b"]"#.to_string())),
+                ("width", TagValue::F64(1.5)),
+            ]
+        );
+        let feature = features.next().unwrap();
+        assert_eq!(feature.id(), None);
+        assert_eq!(feature.geom_type(), Some(GeomType::Point));
+        assert_eq!(feature.tags().count(), 0);
+    }
+
+    #[test]
+    fn import_winding() {
+        // RFC 7946 winding: exterior counter-clockwise and interior
+        // clockwise (lon / lat), which is reversed in tile coördinates
+        let fc: FeatureCollection = r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [[
+                        [-100.0, 40.0], [-80.0, 40.0], [-80.0, 50.0],
+                        [-100.0, 50.0], [-100.0, 40.0]
+                    ], [
+                        [-95.0, 42.0], [-95.0, 48.0], [-85.0, 48.0],
+                        [-85.0, 42.0], [-95.0, 42.0]
+                    ]]
+                },
+                "properties": {}
+            }]
+        }"#
+        .parse()
+        .unwrap();
+        let grid = MapGrid::default();
+        let tid = TileId::new(0, 0, 0).unwrap();
+        let t = grid.tile_transform(tid).scale(4096.0, 4096.0);
+        let mut layer = Tile::new(4096).create_layer("geojson");
+        layer.add_geojson(&fc, t).unwrap();
+        let mut tile = Tile::new(4096);
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layer("geojson").unwrap();
+        let feature = layer.features().next().unwrap();
+        let rings = feature.geom_data().unwrap().rings().unwrap();
+        let types: Vec<_> = rings.iter().map(|r| r.ring_type).collect();
+        assert_eq!(types, [RingType::Exterior, RingType::Interior]);
+    }
+
+    #[test]
+    fn import_error() {
+        let fc: FeatureCollection = r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [1, 2] },
+                "properties": {}
+            }, {
+                "type": "Feature",
+                "geometry": { "type": "GeometryCollection", "geometries": [] },
+                "properties": {}
+            }]
+        }"#
+        .parse()
+        .unwrap();
+        let tile = Tile::new(4096);
+        let mut layer = tile.create_layer("geojson");
+        layer.add_geojson(&fc, Transform::default()).unwrap_err();
+        // features before the error are kept
+        assert_eq!(layer.num_features(), 1);
+        assert_eq!(layer.name(), Some("geojson"));
+    }
+
+    #[test]
+    fn export() {
+        let fc: FeatureCollection = GEOJSON.parse().unwrap();
+        let grid = MapGrid::default();
+        let tid = TileId::new(0, 0, 0).unwrap();
+        let t = grid.tile_transform(tid).scale(4096.0, 4096.0);
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("geojson");
+        layer.add_geojson(&fc, t).unwrap();
+        tile.add_layer(layer).unwrap();
+        let fc = tile.to_geojson(tid).unwrap();
+        assert_eq!(fc.features.len(), 2);
+        let feature = &fc.features[0];
+        assert_eq!(feature.id, Some(Id::Number(12.into())));
+        assert_eq!(feature.property("name"), Some(&"square".into()));
+        assert_eq!(feature.property("lanes"), Some(&2.into()));
+        assert_eq!(
+            feature.foreign_members.as_ref().unwrap()["layer"],
+            JsonValue::from("geojson")
+        );
+        let Some(Value::Polygon(rings)) =
+            feature.geometry.as_ref().map(|g| &g.value)
+        else {
+            panic!("not a polygon");
+        };
+        assert_eq!(rings.len(), 1);
+        assert_eq!(rings[0].len(), 5);
+        assert_eq!(rings[0][0], rings[0][4]);
+        // one tile unit at zoom 0 is about 0.09 degrees
+        let near = |pos: &Position, lon: f64, lat: f64| {
+            (pos[0] - lon).abs() < 0.1 && (pos[1] - lat).abs() < 0.1
+        };
+        assert!(rings[0].iter().any(|pos| near(pos, -93.0, 44.0)));
+        assert!(rings[0].iter().any(|pos| near(pos, -92.0, 45.0)));
+        let feature = &fc.features[1];
+        let Some(Value::Point(pos)) =
+            feature.geometry.as_ref().map(|g| &g.value)
+        else {
+            panic!("not a point");
+        };
+        assert!(near(pos, -92.5, 44.5));
+    }
+}
@@ -0,0 +1,472 @@
+// geotypes.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Conversions to / from `geo-types` geometry.
+//!
+use crate::decoder::FeatureRef;
+use crate::encoder::{GeomEncoder, GeomType};
+use crate::error::{Error, Result};
+use crate::tile::Layer;
+use crate::validate::group_polygons;
+use crate::value::TagValue;
+use geo_types::{
+    Coord, CoordFloat, Geometry, LineString, MultiLineString, MultiPoint,
+    MultiPolygon, Point, Polygon,
+};
+use pointy::{Float, Transform};
+
+/// Get the geometry type for a `geo-types` geometry.
+///
+/// Returns `None` for geometry collections.
+fn geom_type<F: CoordFloat>(geom: &Geometry<F>) -> Option<GeomType> {
+    match geom {
+        Geometry::Point(_) | Geometry::MultiPoint(_) => Some(GeomType::Point),
+        Geometry::Line(_)
+        | Geometry::LineString(_)
+        | Geometry::MultiLineString(_) => Some(GeomType::Linestring),
+        Geometry::Polygon(_)
+        | Geometry::MultiPolygon(_)
+        | Geometry::Rect(_)
+        | Geometry::Triangle(_) => Some(GeomType::Polygon),
+        Geometry::GeometryCollection(_) => None,
+    }
+}
+
+/// Flatten a geometry, expanding (nested) geometry collections.
+fn flatten<'a, F: CoordFloat>(
+    geom: &'a Geometry<F>,
+    geoms: &mut Vec<&'a Geometry<F>>,
+) {
+    match geom {
+        Geometry::GeometryCollection(gc) => {
+            for g in gc {
+                flatten(g, geoms);
+            }
+        }
+        _ => geoms.push(geom),
+    }
+}
+
+impl<F> GeomEncoder<F>
+where
+    F: Float + CoordFloat,
+{
+    /// Create an encoder from a `geo-types` geometry.
+    ///
+    /// The geometry type is determined from the geometry.  Since no
+    /// transform is set, coördinates must already be in tile units; use
+    /// [GeomEncoder::add_geometry] to set up the encoder first.
+    ///
+    /// Returns `Error::InvalidGeometry` for geometry collections.
+    pub fn from_geometry(geom: &Geometry<F>) -> Result<Self> {
+        let geom_tp = geom_type(geom).ok_or(Error::InvalidGeometry())?;
+        GeomEncoder::new(geom_tp).add_geometry(geom)
+    }
+
+    /// Add a `geo-types` geometry, taking ownership (for method chaining).
+    ///
+    /// Each part (linestring or polygon ring) is completed after it is
+    /// added.  Closing vertices of polygon rings are skipped.
+    ///
+    /// Returns `Error::WrongGeomType` if the geometry does not match the
+    /// encoder's geometry type, or `Error::InvalidGeometry` for geometry
+    /// collections.
+    pub fn add_geometry(mut self, geom: &Geometry<F>) -> Result<Self> {
+        match geom_type(geom) {
+            Some(tp) if tp == self.geom_type() => (),
+            Some(_) => return Err(Error::WrongGeomType(self.geom_type())),
+            None => return Err(Error::InvalidGeometry()),
+        }
+        match geom {
+            Geometry::Point(p) => self.add_point(p.x(), p.y())?,
+            Geometry::MultiPoint(MultiPoint(pts)) => {
+                for p in pts {
+                    self.add_point(p.x(), p.y())?;
+                }
+            }
+            Geometry::Line(line) => {
+                self.add_point(line.start.x, line.start.y)?;
+                self.add_point(line.end.x, line.end.y)?;
+                self.complete_geom()?;
+            }
+            Geometry::LineString(ls) => self.add_linestring(ls)?,
+            Geometry::MultiLineString(MultiLineString(lss)) => {
+                for ls in lss {
+                    self.add_linestring(ls)?;
+                }
+            }
+            Geometry::Polygon(poly) => self.add_polygon(poly)?,
+            Geometry::MultiPolygon(MultiPolygon(polys)) => {
+                for poly in polys {
+                    self.add_polygon(poly)?;
+                }
+            }
+            Geometry::Rect(rect) => self.add_polygon(&rect.to_polygon())?,
+            Geometry::Triangle(tri) => self.add_polygon(&tri.to_polygon())?,
+            Geometry::GeometryCollection(_) => unreachable!(),
+        }
+        Ok(self)
+    }
+
+    /// Add one linestring and complete it
+    fn add_linestring(&mut self, ls: &LineString<F>) -> Result<()> {
+        for c in ls.coords() {
+            self.add_point(c.x, c.y)?;
+        }
+        self.complete_geom()
+    }
+
+    /// Add one ring, skipping the closing vertex, and complete it
+    fn add_ring(&mut self, ring: &LineString<F>) -> Result<()> {
+        let mut coords = &ring.0[..];
+        if ring.is_closed() && coords.len() > 1 {
+            coords = &coords[..coords.len() - 1];
+        }
+        for c in coords {
+            self.add_point(c.x, c.y)?;
+        }
+        self.complete_geom()
+    }
+
+    /// Add the rings of a polygon
+    fn add_polygon(&mut self, poly: &Polygon<F>) -> Result<()> {
+        self.add_ring(poly.exterior())?;
+        for ring in poly.interiors() {
+            self.add_ring(ring)?;
+        }
+        Ok(())
+    }
+}
+
+impl Layer {
+    /// Add a `geo-types` geometry as one or more features.
+    ///
+    /// * `geom` Geometry, in tile coördinates.
+    /// * `tags` Tags to add to each feature.
+    /// * `id` Feature ID, or `None`.
+    ///
+    /// Since MVT has no collection type, geometry collections (including
+    /// nested ones) are fanned out into one feature per geometry type, in
+    /// order of first appearance; e.g. a collection of two points and a
+    /// polygon becomes a multipoint feature and a polygon feature.  Each
+    /// feature gets the same tags, but only the first gets the ID, so IDs
+    /// stay unique with [IdMode::Unique](crate::IdMode::Unique).  Geometry
+    /// is encoded using the [layer options](Layer::geom_encoder), and
+    /// features which are empty after clipping are skipped.
+    ///
+    /// Returns the number of features added.  If any geometry fails to
+    /// encode, no features are added; errors include the feature index as
+    /// [context](Error::context).
+    pub fn add_geo_feature(
+        &mut self,
+        geom: &Geometry<f64>,
+        tags: &[(&str, TagValue)],
+        id: Option<u64>,
+    ) -> Result<usize> {
+        let mut geoms = Vec::new();
+        flatten(geom, &mut geoms);
+        let mut types: Vec<GeomType> = Vec::new();
+        for tp in geoms.iter().filter_map(|g| geom_type(g)) {
+            if !types.contains(&tp) {
+                types.push(tp);
+            }
+        }
+        let mut geom_data = Vec::with_capacity(types.len());
+        for (i, tp) in types.into_iter().enumerate() {
+            let feature = self.num_features() + i;
+            let mut enc = self.geom_encoder(tp);
+            for g in geoms.iter().filter(|g| geom_type(g) == Some(tp)) {
+                enc =
+                    enc.add_geometry(g).map_err(|e| e.with_feature(feature))?;
+            }
+            let data = enc.encode().map_err(|e| e.with_feature(feature))?;
+            if !data.is_empty() {
+                geom_data.push(data);
+            }
+        }
+        let count = geom_data.len();
+        let mut layer = std::mem::take(self);
+        for (i, data) in geom_data.into_iter().enumerate() {
+            let mut feature = layer.into_feature(data);
+            if let (0, Some(id)) = (i, id) {
+                feature.set_id(id);
+            }
+            for (key, value) in tags {
+                feature.add_tag(key, value.clone());
+            }
+            layer = feature.into_layer();
+        }
+        *self = layer;
+        Ok(count)
+    }
+}
+
+impl FeatureRef<'_> {
+    /// Convert the feature geometry to `geo-types` geometry.
+    ///
+    /// * `transform` Transform from tile coördinates to world coördinates.
+    ///
+    /// The transform is the inverse of the one used to encode the feature.
+    /// For features encoded using [MapGrid::tile_transform], scaled to the
+    /// tile extent, use [MapGrid::tile_inverse_transform] (see example).
+    ///
+    /// Single points, linestrings and polygons are returned as their simple
+    /// types; otherwise the multi type is used.  Polygon rings are grouped
+    /// by winding order: each exterior ring starts a new polygon.
+    ///
+    /// Returns `Error::InvalidGeometry` for unknown geometry types, a
+    /// [decode](crate::GeomData::decode) error for malformed geometry data,
+    /// or `Error::InteriorBeforeExterior` if an interior ring appears before
+    /// any exterior ring.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::Error;
+    /// # fn main() -> Result<(), Error> {
+    /// use geo_types::{Geometry, Point};
+    /// use mvt::{GeomEncoder, GeomType, MapGrid, Tile, TileId};
+    /// use pointy::Transform;
+    ///
+    /// let grid = MapGrid::default();
+    /// let tid = TileId::new(0, 0, 0)?;
+    /// let mut tile = Tile::new(4096);
+    /// let e = f64::from(tile.extent());
+    /// let geom = GeomEncoder::new(GeomType::Point)
+    ///     .transform(grid.tile_transform(tid).scale(e, e))
+    ///     .point(10_000_000.0, 5_000_000.0)?
+    ///     .encode()?;
+    /// let layer = tile.create_layer("points").into_feature(geom).into_layer();
+    /// tile.add_layer(layer)?;
+    ///
+    /// let tile = Tile::from_bytes(&tile.to_bytes()?)?;
+    /// let layer = tile.layer("points").unwrap();
+    /// let e = 1.0 / f64::from(layer.extent());
+    /// let t = Transform::with_scale(e, e) * grid.tile_inverse_transform(tid);
+    /// for feature in layer.features() {
+    ///     let Geometry::Point(pt) = feature.to_geo(t)? else {
+    ///         panic!("not a point");
+    ///     };
+    ///     // rounded to the nearest tile unit
+    ///     assert!((pt.x() - 10_000_000.0).abs() < 10_000.0);
+    ///     assert!((pt.y() - 5_000_000.0).abs() < 10_000.0);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [MapGrid::tile_inverse_transform]: crate::MapGrid::tile_inverse_transform
+    /// [MapGrid::tile_transform]: crate::MapGrid::tile_transform
+    pub fn to_geo(&self, transform: Transform<f64>) -> Result<Geometry<f64>> {
+        let geom = self.geom_data().ok_or(Error::InvalidGeometry())?;
+        let parts = geom.decode()?;
+        let coord = |&(x, y): &(i32, i32)| {
+            let p = transform * (f64::from(x), f64::from(y));
+            Coord { x: p.x, y: p.y }
+        };
+        let line = |part: &Vec<(i32, i32)>| {
+            LineString(part.iter().map(coord).collect())
+        };
+        Ok(match geom.geom_type() {
+            GeomType::Point => {
+                let mut pts: Vec<Point> =
+                    parts.concat().iter().map(|p| coord(p).into()).collect();
+                if pts.len() == 1 {
+                    Geometry::Point(pts.remove(0))
+                } else {
+                    Geometry::MultiPoint(MultiPoint(pts))
+                }
+            }
+            GeomType::Linestring => {
+                let mut lines: Vec<LineString> =
+                    parts.iter().map(line).collect();
+                if lines.len() == 1 {
+                    Geometry::LineString(lines.remove(0))
+                } else {
+                    Geometry::MultiLineString(MultiLineString(lines))
+                }
+            }
+            GeomType::Polygon => {
+                let mut polys: Vec<Polygon> = group_polygons(parts)?
+                    .iter()
+                    .map(|rings| {
+                        let mut rings = rings.iter().map(|r| {
+                            let mut ring = line(r);
+                            ring.close();
+                            ring
+                        });
+                        let exterior =
+                            rings.next().unwrap_or(LineString(vec![]));
+                        Polygon::new(exterior, rings.collect())
+                    })
+                    .collect();
+                if polys.len() == 1 {
+                    Geometry::Polygon(polys.remove(0))
+                } else {
+                    Geometry::MultiPolygon(MultiPolygon(polys))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{IdMode, MapGrid, Tile, TileId};
+    use geo_types::{GeometryCollection, line_string, polygon};
+
+    #[test]
+    fn point() {
+        let geom = Geometry::Point(Point::new(25.0, 17.0));
+        let data = GeomEncoder::from_geometry(&geom).unwrap().encode().unwrap();
+        assert_eq!(data.into_vec(), vec![9, 50, 34]);
+    }
+
+    #[test]
+    fn multi_linestring() {
+        let geom = Geometry::MultiLineString(MultiLineString(vec![
+            line_string![(x: 2.0, y: 2.0), (x: 2.0, y: 10.0), (x: 10.0, y: 10.0)],
+            line_string![(x: 1.0, y: 1.0), (x: 3.0, y: 5.0)],
+        ]));
+        let data = GeomEncoder::from_geometry(&geom).unwrap().encode().unwrap();
+        assert_eq!(
+            data.into_vec(),
+            vec![9, 4, 4, 18, 0, 16, 16, 0, 9, 17, 17, 10, 4, 8]
+        );
+    }
+
+    #[test]
+    fn polygon() {
+        let geom = Geometry::Polygon(polygon![
+            (x: 3.0, y: 6.0),
+            (x: 8.0, y: 12.0),
+            (x: 20.0, y: 34.0),
+            (x: 3.0, y: 6.0),
+        ]);
+        let data = GeomEncoder::from_geometry(&geom).unwrap().encode().unwrap();
+        assert_eq!(data.into_vec(), vec![9, 6, 12, 18, 10, 12, 24, 44, 15]);
+    }
+
+    #[test]
+    fn mismatch() {
+        let geom = Geometry::Point(Point::new(25.0, 17.0));
+        assert!(matches!(
+            GeomEncoder::new(GeomType::Polygon).add_geometry(&geom),
+            Err(Error::WrongGeomType(GeomType::Polygon))
+        ));
+        let geom = Geometry::GeometryCollection(Default::default());
+        assert!(GeomEncoder::<f64>::from_geometry(&geom).is_err());
+    }
+
+    #[test]
+    fn to_geo() {
+        let grid = MapGrid::default();
+        let tid = TileId::new(1, 1, 1).unwrap();
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("polygons");
+        let t = grid.tile_transform(tid).scale(4096.0, 4096.0);
+        let geom = Geometry::MultiPolygon(MultiPolygon(vec![
+            polygon!(
+                exterior: [
+                    (x: 0.0, y: 0.0),
+                    (x: 8_000_000.0, y: 0.0),
+                    (x: 8_000_000.0, y: -8_000_000.0),
+                    (x: 0.0, y: -8_000_000.0),
+                ],
+                interiors: [[
+                    (x: 2_000_000.0, y: -2_000_000.0),
+                    (x: 2_000_000.0, y: -6_000_000.0),
+                    (x: 6_000_000.0, y: -6_000_000.0),
+                    (x: 6_000_000.0, y: -2_000_000.0),
+                ]],
+            ),
+            polygon![
+                (x: 10_000_000.0, y: -10_000_000.0),
+                (x: 12_000_000.0, y: -12_000_000.0),
+                (x: 10_000_000.0, y: -12_000_000.0),
+            ],
+        ]));
+        let geom = GeomEncoder::new(GeomType::Polygon)
+            .transform(t)
+            .add_geometry(&geom)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let layer = layer.into_feature(geom).into_layer();
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layer("polygons").unwrap();
+        let e = 1.0 / f64::from(layer.extent());
+        let t = Transform::with_scale(e, e) * grid.tile_inverse_transform(tid);
+        let feature = layer.features().next().unwrap();
+        let Geometry::MultiPolygon(polys) = feature.to_geo(t).unwrap() else {
+            panic!("not a multipolygon");
+        };
+        assert_eq!(polys.0.len(), 2);
+        assert_eq!(polys.0[0].interiors().len(), 1);
+        assert_eq!(polys.0[1].interiors().len(), 0);
+        let c = polys.0[0].exterior().0[1];
+        assert!((c.x - 8_000_000.0).abs() < 10_000.0);
+        assert!(c.y.abs() < 10_000.0);
+        assert!(polys.0[0].exterior().is_closed());
+    }
+
+    #[test]
+    fn add_geo_feature() {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("mixed").skip_outside(true);
+        let geom = Geometry::GeometryCollection(GeometryCollection(vec![
+            Geometry::Point(Point::new(25.0, 17.0)),
+            Geometry::Polygon(polygon![
+                (x: 3.0, y: 6.0),
+                (x: 8.0, y: 12.0),
+                (x: 20.0, y: 34.0),
+            ]),
+            Geometry::GeometryCollection(GeometryCollection(vec![
+                Geometry::Point(Point::new(1.0, 2.0)),
+                // outside of tile
+                Geometry::LineString(line_string![
+                    (x: -9.0, y: -9.0),
+                    (x: -5.0, y: -5.0),
+                ]),
+            ])),
+        ]));
+        let tags = [("name", TagValue::String("mixed".into()))];
+        assert_eq!(layer.add_geo_feature(&geom, &tags, Some(7)).unwrap(), 2);
+        // no features added on error
+        let geom = Geometry::Point(Point::new(f64::NAN, 1.0));
+        let res = layer.add_geo_feature(&geom, &tags, None);
+        assert_eq!(res.unwrap_err().context().unwrap().feature, Some(2));
+        assert_eq!(layer.num_features(), 2);
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layer("mixed").unwrap();
+        let features: Vec<_> = layer.features().collect();
+        let geom = features[0].geom_data().unwrap();
+        assert_eq!(geom.geom_type(), GeomType::Point);
+        assert_eq!(geom.into_vec(), vec![17, 50, 34, 47, 29]);
+        let geom = features[1].geom_data().unwrap();
+        assert_eq!(geom.geom_type(), GeomType::Polygon);
+        assert_eq!(features[0].id(), Some(7));
+        assert_eq!(features[1].id(), None);
+        for feature in features {
+            assert_eq!(feature.get_str("name"), Some("mixed"));
+        }
+    }
+
+    #[test]
+    fn add_geo_feature_unique() {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("mixed").id_mode(IdMode::Unique);
+        let geom = Geometry::GeometryCollection(GeometryCollection(vec![
+            Geometry::Point(Point::new(25.0, 17.0)),
+            Geometry::LineString(line_string![
+                (x: 3.0, y: 6.0),
+                (x: 8.0, y: 12.0),
+            ]),
+        ]));
+        assert_eq!(layer.add_geo_feature(&geom, &[], Some(7)).unwrap(), 2);
+        tile.add_layer(layer).unwrap();
+    }
+}
@@ -0,0 +1,422 @@
+// geozero.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! `geozero` processor integration.
+//!
+use crate::encoder::{GeomEncoder, GeomType};
+use crate::error::Error;
+use crate::tile::Layer;
+use crate::value::TagValue;
+use geozero::error::{GeozeroError, Result};
+use geozero::{
+    ColumnValue, FeatureProcessor, GeomProcessor, PropertyProcessor,
+};
+use pointy::{BBox, Float, Transform};
+
+/// Convert an encoder error to a `geozero` error
+fn geom_err(e: Error) -> GeozeroError {
+    GeozeroError::Geometry(e.to_string())
+}
+
+impl<F> GeomEncoder<F>
+where
+    F: Float,
+{
+    /// Check that the geometry type matches
+    fn check_type(&self, geom_tp: GeomType) -> Result<()> {
+        if self.geom_type() == geom_tp {
+            Ok(())
+        } else {
+            Err(geom_err(Error::WrongGeomType(self.geom_type())))
+        }
+    }
+}
+
+impl<F> GeomProcessor for GeomEncoder<F>
+where
+    F: Float,
+{
+    fn xy(&mut self, x: f64, y: f64, _idx: usize) -> Result<()> {
+        let (Some(x), Some(y)) = (F::from(x), F::from(y)) else {
+            return Err(GeozeroError::Coord);
+        };
+        self.add_point(x, y).map_err(geom_err)
+    }
+
+    fn point_begin(&mut self, _idx: usize) -> Result<()> {
+        self.check_type(GeomType::Point)
+    }
+
+    fn multipoint_begin(&mut self, _size: usize, _idx: usize) -> Result<()> {
+        self.check_type(GeomType::Point)
+    }
+
+    fn linestring_begin(
+        &mut self,
+        tagged: bool,
+        _size: usize,
+        _idx: usize,
+    ) -> Result<()> {
+        if tagged {
+            self.check_type(GeomType::Linestring)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn linestring_end(&mut self, _tagged: bool, _idx: usize) -> Result<()> {
+        self.complete_ring().map_err(geom_err)
+    }
+
+    fn multilinestring_begin(
+        &mut self,
+        _size: usize,
+        _idx: usize,
+    ) -> Result<()> {
+        self.check_type(GeomType::Linestring)
+    }
+
+    fn polygon_begin(
+        &mut self,
+        tagged: bool,
+        _size: usize,
+        _idx: usize,
+    ) -> Result<()> {
+        if tagged {
+            self.check_type(GeomType::Polygon)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn multipolygon_begin(&mut self, _size: usize, _idx: usize) -> Result<()> {
+        self.check_type(GeomType::Polygon)
+    }
+
+    fn geometrycollection_begin(
+        &mut self,
+        _size: usize,
+        _idx: usize,
+    ) -> Result<()> {
+        Err(geom_err(Error::InvalidGeometry()))
+    }
+}
+
+/// `geozero` feature processor which adds features to a [Layer].
+///
+/// Since a layer has no transform, this wraps one along with the layer.
+/// Create with [Layer::into_processor].
+///
+/// Geometry type is determined by the first geometry event of each
+/// feature, and encoded using the [layer options](Layer::geom_encoder).
+/// Properties are added as tags; binary values are skipped.  Features
+/// without geometry (or empty after clipping) are skipped.
+pub struct LayerProcessor {
+    /// Layer being built
+    layer: Option<Layer>,
+    /// Transform to tile coördinates
+    transform: Transform<f64>,
+    /// Bounding box for clipping (source coördinates)
+    bbox: Option<BBox<f64>>,
+    /// Geometry encoder for current feature
+    encoder: Option<GeomEncoder<f64>>,
+    /// Tags for current feature
+    tags: Vec<(String, TagValue)>,
+}
+
+impl Layer {
+    /// Convert into a `geozero` feature processor.
+    ///
+    /// * `transform` Transform from source to tile coördinates.
+    pub fn into_processor(self, transform: Transform<f64>) -> LayerProcessor {
+        LayerProcessor {
+            layer: Some(self),
+            transform,
+            bbox: None,
+            encoder: None,
+            tags: Vec::new(),
+        }
+    }
+}
+
+impl LayerProcessor {
+    /// Set a bounding box for clipping.
+    ///
+    /// * `bbox` Bounding box, in source coördinates (e.g. from
+    ///   [MapGrid::tile_bbox](crate::MapGrid::tile_bbox)).
+    ///
+    /// Geometry is clipped to the bounding box, expanded by the layer
+    /// [clip buffer](Layer::clip_buffer).
+    pub fn bbox(mut self, bbox: BBox<f64>) -> Self {
+        self.bbox = Some(bbox);
+        self
+    }
+
+    /// Get the layer, with all processed features.
+    pub fn into_layer(self) -> Layer {
+        self.layer.unwrap_or_default()
+    }
+
+    /// Begin a geometry of the specified type
+    fn begin(&mut self, geom_tp: GeomType) -> &mut GeomEncoder<f64> {
+        let layer = self.layer.get_or_insert_with(Layer::default);
+        let enc = layer.geom_encoder(geom_tp).transform(self.transform);
+        let bbox = self.bbox;
+        self.encoder.get_or_insert_with(|| match bbox {
+            Some(bbox) => enc.bbox(bbox),
+            None => enc,
+        })
+    }
+
+    /// Check if a point is within the bounding box and clip buffer
+    fn contains(&self, x: f64, y: f64) -> bool {
+        let (Some(layer), Some(bbox)) = (&self.layer, self.bbox) else {
+            return true;
+        };
+        let buf = f64::from(layer.buffer()) / f64::from(layer.extent());
+        let (bx, by) = (bbox.x_span() * buf, bbox.y_span() * buf);
+        (bbox.x_min() - bx..=bbox.x_max() + bx).contains(&x)
+            && (bbox.y_min() - by..=bbox.y_max() + by).contains(&y)
+    }
+
+    /// Get the current encoder
+    fn encoder(&mut self) -> Result<&mut GeomEncoder<f64>> {
+        self.encoder
+            .as_mut()
+            .ok_or_else(|| GeozeroError::Geometry("no geometry".into()))
+    }
+}
+
+impl GeomProcessor for LayerProcessor {
+    fn xy(&mut self, x: f64, y: f64, idx: usize) -> Result<()> {
+        let enc = self.encoder()?;
+        // points are not clipped by the encoder
+        if enc.geom_type() == GeomType::Point && !self.contains(x, y) {
+            return Ok(());
+        }
+        self.encoder()?.xy(x, y, idx)
+    }
+
+    fn point_begin(&mut self, idx: usize) -> Result<()> {
+        self.begin(GeomType::Point).point_begin(idx)
+    }
+
+    fn multipoint_begin(&mut self, size: usize, idx: usize) -> Result<()> {
+        self.begin(GeomType::Point).multipoint_begin(size, idx)
+    }
+
+    fn linestring_begin(
+        &mut self,
+        tagged: bool,
+        size: usize,
+        idx: usize,
+    ) -> Result<()> {
+        self.begin(GeomType::Linestring)
+            .linestring_begin(tagged, size, idx)
+    }
+
+    fn linestring_end(&mut self, tagged: bool, idx: usize) -> Result<()> {
+        self.encoder()?.linestring_end(tagged, idx)
+    }
+
+    fn multilinestring_begin(&mut self, size: usize, idx: usize) -> Result<()> {
+        self.begin(GeomType::Linestring)
+            .multilinestring_begin(size, idx)
+    }
+
+    fn polygon_begin(
+        &mut self,
+        tagged: bool,
+        size: usize,
+        idx: usize,
+    ) -> Result<()> {
+        self.begin(GeomType::Polygon)
+            .polygon_begin(tagged, size, idx)
+    }
+
+    fn multipolygon_begin(&mut self, size: usize, idx: usize) -> Result<()> {
+        self.begin(GeomType::Polygon).multipolygon_begin(size, idx)
+    }
+
+    fn geometrycollection_begin(
+        &mut self,
+        _size: usize,
+        _idx: usize,
+    ) -> Result<()> {
+        Err(geom_err(Error::InvalidGeometry()))
+    }
+}
+
+impl PropertyProcessor for LayerProcessor {
+    fn property(
+        &mut self,
+        _idx: usize,
+        name: &str,
+        value: &ColumnValue,
+    ) -> Result<bool> {
+        let value = match *value {
+            ColumnValue::Byte(v) => TagValue::I64(v.into()),
+            ColumnValue::UByte(v) => TagValue::U64(v.into()),
+            ColumnValue::Bool(v) => TagValue::Bool(v),
+            ColumnValue::Short(v) => TagValue::I64(v.into()),
+            ColumnValue::UShort(v) => TagValue::U64(v.into()),
+            ColumnValue::Int(v) => TagValue::I64(v.into()),
+            ColumnValue::UInt(v) => TagValue::U64(v.into()),
+            ColumnValue::Long(v) => TagValue::I64(v),
+            ColumnValue::ULong(v) => TagValue::U64(v),
+            ColumnValue::Float(v) => TagValue::F32(v),
+            ColumnValue::Double(v) => TagValue::F64(v),
+            ColumnValue::String(v)
+            | ColumnValue::Json(v)
+            | ColumnValue::DateTime(v) => TagValue::String(v.to_string()),
+            ColumnValue::Binary(_) => {
+                log::debug!("skipping binary property: {name}");
+                return Ok(false);
+            }
+        };
+        self.tags.push((name.to_string(), value));
+        Ok(false)
+    }
+}
+
+impl FeatureProcessor for LayerProcessor {
+    fn feature_begin(&mut self, _idx: u64) -> Result<()> {
+        self.encoder = None;
+        self.tags.clear();
+        Ok(())
+    }
+
+    fn feature_end(&mut self, idx: u64) -> Result<()> {
+        let Some(enc) = self.encoder.take() else {
+            log::debug!("skipping feature without geometry");
+            return Ok(());
+        };
+        let geom = enc
+            .encode()
+            .map_err(|e| geom_err(e.with_feature(idx as usize)))?;
+        if geom.is_empty() {
+            log::debug!("skipping empty feature");
+            return Ok(());
+        }
+        let layer = self.layer.take().unwrap_or_default();
+        let mut feature = layer.into_feature(geom);
+        for (key, value) in self.tags.drain(..) {
+            feature.add_tag(&key, value);
+        }
+        self.layer = Some(feature.into_layer());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tile::Tile;
+    use geozero::GeozeroDatasource;
+    use geozero::geojson::GeoJson;
+
+    #[test]
+    fn geojson_source() {
+        let json = r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [[[3, 6], [8, 12], [20, 34], [3, 6]]]
+                },
+                "properties": { "name": "triangle", "sides": 3 }
+            }, {
+                "type": "Feature",
+                "geometry": {
+                    "type": "MultiLineString",
+                    "coordinates": [
+                        [[2, 2], [2, 10], [10, 10]],
+                        [[1, 1], [3, 5]]
+                    ]
+                },
+                "properties": {}
+            }]
+        }"#;
+        let tile = Tile::new(4096);
+        let mut proc = tile
+            .create_layer("geozero")
+            .into_processor(Transform::default());
+        GeoJson(json).process(&mut proc).unwrap();
+        let layer = proc.into_layer();
+        assert_eq!(layer.num_features(), 2);
+        let mut tile = Tile::new(4096);
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layer("geozero").unwrap();
+        let mut features = layer.features();
+        let feature = features.next().unwrap();
+        assert_eq!(
+            feature.geom_data().unwrap().into_vec(),
+            vec![9, 6, 12, 18, 10, 12, 24, 44, 15]
+        );
+        let tags: Vec<_> = feature.tags().collect();
+        assert!(tags.contains(&("name", TagValue::String("triangle".into()))));
+        assert_eq!(tags.len(), 2);
+        let feature = features.next().unwrap();
+        assert_eq!(
+            feature.geom_data().unwrap().into_vec(),
+            vec![9, 4, 4, 18, 0, 16, 16, 0, 9, 17, 17, 10, 4, 8]
+        );
+    }
+
+    #[test]
+    fn bbox() {
+        let json = r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "geometry": {
+                    "type": "MultiPoint",
+                    "coordinates": [[1, 1], [3, 3], [9, 9]]
+                },
+                "properties": {}
+            }, {
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": [[6, 6], [8, 8]]
+                },
+                "properties": {}
+            }]
+        }"#;
+        let tile = Tile::new(4096);
+        let mut proc = tile
+            .create_layer("geozero")
+            .into_processor(Transform::with_scale(1024.0, 1024.0))
+            .bbox(BBox::new([(0.0, 0.0), (4.0, 4.0)]));
+        GeoJson(json).process(&mut proc).unwrap();
+        let layer = proc.into_layer();
+        assert_eq!(layer.num_features(), 1);
+        let mut tile = Tile::new(4096);
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layer("geozero").unwrap();
+        let feature = layer.features().next().unwrap();
+        assert_eq!(
+            feature.geom_data().unwrap().into_vec(),
+            vec![17, 2048, 2048, 4096, 4096]
+        );
+    }
+
+    #[test]
+    fn encoder() {
+        let mut enc = GeomEncoder::<f64>::new(GeomType::Linestring);
+        GeoJson(r#"{"type": "LineString", "coordinates": [[2, 2], [2, 10]]}"#)
+            .process_geom(&mut enc)
+            .unwrap();
+        assert_eq!(enc.encode().unwrap().into_vec(), vec![9, 4, 4, 10, 0, 16]);
+        let mut enc = GeomEncoder::<f64>::new(GeomType::Polygon);
+        assert!(
+            GeoJson(r#"{"type": "Point", "coordinates": [2, 2]}"#)
+                .process_geom(&mut enc)
+                .is_err()
+        );
+    }
+}
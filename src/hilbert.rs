@@ -0,0 +1,86 @@
+// hilbert.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Hilbert curve indexing.
+//!
+
+/// Get the distance along a Hilbert curve
+///
+/// * `order` Curve order; grid is 2^order cells on each side.
+/// * `x` Grid column.
+/// * `y` Grid row.
+pub(crate) fn hilbert_index(order: u32, x: u64, y: u64) -> u64 {
+    let n = 1u64 << order;
+    let (mut x, mut y) = (x, y);
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = u64::from(x & s > 0);
+        let ry = u64::from(y & s > 0);
+        d += s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+/// Get the grid cell at a distance along a Hilbert curve
+///
+/// * `order` Curve order; grid is 2^order cells on each side.
+/// * `d` Distance along the curve.
+pub(crate) fn hilbert_point(order: u32, d: u64) -> (u64, u64) {
+    let n = 1u64 << order;
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn order_1() {
+        let d: Vec<_> = [(0, 0), (0, 1), (1, 1), (1, 0)]
+            .into_iter()
+            .map(|(x, y)| hilbert_index(1, x, y))
+            .collect();
+        assert_eq!(d, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn round_trip() {
+        for order in [0, 1, 3, 8] {
+            let n = 1u64 << order;
+            for d in 0..n * n {
+                let (x, y) = hilbert_point(order, d);
+                assert_eq!(hilbert_index(order, x, y), d);
+            }
+        }
+        let d = hilbert_index(31, 123_456_789, 987_654_321);
+        assert_eq!(hilbert_point(31, d), (123_456_789, 987_654_321));
+    }
+}
@@ -0,0 +1,367 @@
+// label.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Label placement for tile geometry.
+//!
+use crate::simplify::{path_length, signed_area};
+use crate::validate::{Rings, point_in_ring};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Grid cell for pole of inaccessibility search
+struct Cell {
+    /// Center X
+    x: f64,
+    /// Center Y
+    y: f64,
+    /// Half of cell size
+    half: f64,
+    /// Signed distance from center to polygon (positive inside)
+    dist: f64,
+    /// Maximum possible distance within the cell
+    max: f64,
+}
+
+impl Cell {
+    /// Create a new cell
+    fn new(x: f64, y: f64, half: f64, poly: &[Vec<(i32, i32)>]) -> Self {
+        let dist = polygon_distance(x, y, poly);
+        let max = dist + half * std::f64::consts::SQRT_2;
+        Cell {
+            x,
+            y,
+            half,
+            dist,
+            max,
+        }
+    }
+}
+
+impl PartialEq for Cell {
+    fn eq(&self, other: &Self) -> bool {
+        self.max.total_cmp(&other.max) == Ordering::Equal
+    }
+}
+
+impl Eq for Cell {}
+
+impl PartialOrd for Cell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.max.total_cmp(&other.max)
+    }
+}
+
+/// Calculate the squared distance from a point to a segment.
+fn segment_dist_sq(px: f64, py: f64, a: (i32, i32), b: (i32, i32)) -> f64 {
+    let (ax, ay) = (f64::from(a.0), f64::from(a.1));
+    let (bx, by) = (f64::from(b.0), f64::from(b.1));
+    let (dx, dy) = (bx - ax, by - ay);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 {
+        (((px - ax) * dx + (py - ay) * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (ex, ey) = (px - (ax + t * dx), py - (ay + t * dy));
+    ex * ex + ey * ey
+}
+
+/// Calculate the signed distance from a point to a polygon's rings.
+///
+/// The distance is positive inside the polygon (accounting for holes).
+fn polygon_distance(x: f64, y: f64, poly: &[Vec<(i32, i32)>]) -> f64 {
+    let mut inside = false;
+    let mut min_sq = f64::INFINITY;
+    for ring in poly {
+        let n = ring.len();
+        for i in 0..n {
+            let a = ring[i];
+            let b = ring[(i + 1) % n];
+            let (ay, by) = (f64::from(a.1), f64::from(b.1));
+            if (ay > y) != (by > y) {
+                let (ax, bx) = (f64::from(a.0), f64::from(b.0));
+                if x < (bx - ax) * (y - ay) / (by - ay) + ax {
+                    inside = !inside;
+                }
+            }
+            min_sq = min_sq.min(segment_dist_sq(x, y, a, b));
+        }
+    }
+    let dist = min_sq.sqrt();
+    if inside { dist } else { -dist }
+}
+
+/// Find the pole of inaccessibility of a polygon (polylabel).
+///
+/// * `poly` Polygon rings, exterior first.
+/// * `precision` Precision of the result (tile units).
+///
+/// Returns the position and its distance to the nearest ring edge.
+pub(crate) fn polylabel(
+    poly: &[Vec<(i32, i32)>],
+    precision: f64,
+) -> Option<((f64, f64), f64)> {
+    let exterior = poly.first().filter(|r| !r.is_empty())?;
+    let x_min = exterior.iter().map(|p| p.0).min()?;
+    let y_min = exterior.iter().map(|p| p.1).min()?;
+    let x_max = exterior.iter().map(|p| p.0).max()?;
+    let y_max = exterior.iter().map(|p| p.1).max()?;
+    let (x_min, y_min) = (f64::from(x_min), f64::from(y_min));
+    let (width, height) = (f64::from(x_max) - x_min, f64::from(y_max) - y_min);
+    let size = width.min(height);
+    let center = (x_min + width / 2.0, y_min + height / 2.0);
+    if size <= 0.0 {
+        return Some((center, 0.0));
+    }
+    let precision = precision.max(f64::EPSILON * size);
+    let half = size / 2.0;
+    let mut queue = BinaryHeap::new();
+    let mut x = x_min;
+    while x < x_min + width {
+        let mut y = y_min;
+        while y < y_min + height {
+            queue.push(Cell::new(x + half, y + half, half, poly));
+            y += size;
+        }
+        x += size;
+    }
+    // initial guesses: area centroid and bounding box center
+    let mut best = Cell::new(center.0, center.1, 0.0, poly);
+    if let Some((cx, cy)) = ring_centroid(exterior) {
+        let cell = Cell::new(cx, cy, 0.0, poly);
+        if cell.dist > best.dist {
+            best = cell;
+        }
+    }
+    while let Some(cell) = queue.pop() {
+        if cell.dist > best.dist {
+            best = Cell::new(cell.x, cell.y, 0.0, poly);
+        }
+        if cell.max - best.dist <= precision {
+            continue;
+        }
+        let h = cell.half / 2.0;
+        for (dx, dy) in [(-h, -h), (h, -h), (-h, h), (h, h)] {
+            queue.push(Cell::new(cell.x + dx, cell.y + dy, h, poly));
+        }
+    }
+    Some(((best.x, best.y), best.dist))
+}
+
+/// Calculate the area centroid of a ring.
+pub(crate) fn ring_centroid(ring: &[(i32, i32)]) -> Option<(f64, f64)> {
+    let n = ring.len();
+    let (mut cx, mut cy, mut area2) = (0.0, 0.0, 0.0);
+    for i in 0..n {
+        let (x0, y0) = (f64::from(ring[i].0), f64::from(ring[i].1));
+        let j = (i + 1) % n;
+        let (x1, y1) = (f64::from(ring[j].0), f64::from(ring[j].1));
+        let cross = x0 * y1 - x1 * y0;
+        cx += (x0 + x1) * cross;
+        cy += (y0 + y1) * cross;
+        area2 += cross;
+    }
+    (area2 != 0.0).then(|| (cx / (3.0 * area2), cy / (3.0 * area2)))
+}
+
+/// Find the label position of a multipolygon.
+///
+/// The pole of inaccessibility of the polygon with the largest area is
+/// used.
+pub(crate) fn label_position(
+    polys: &[Rings],
+    precision: f64,
+) -> Option<(f64, f64)> {
+    let area = |poly: &Rings| poly.iter().map(|r| signed_area(r)).sum::<f64>();
+    let poly = polys.iter().max_by(|a, b| area(a).total_cmp(&area(b)))?;
+    polylabel(poly, precision).map(|(pos, _)| pos)
+}
+
+/// Calculate the centroid of points.
+pub(crate) fn points_centroid(pts: &[(i32, i32)]) -> Option<(f64, f64)> {
+    if pts.is_empty() {
+        return None;
+    }
+    let n = pts.len() as f64;
+    let x = pts.iter().map(|p| f64::from(p.0)).sum::<f64>() / n;
+    let y = pts.iter().map(|p| f64::from(p.1)).sum::<f64>() / n;
+    Some((x, y))
+}
+
+/// Calculate the length-weighted centroid of paths.
+///
+/// Falls back to the centroid of all vertices if every path has zero length.
+pub(crate) fn paths_centroid(paths: &[Vec<(i32, i32)>]) -> Option<(f64, f64)> {
+    let (mut cx, mut cy, mut len) = (0.0, 0.0, 0.0);
+    for path in paths {
+        for w in path.windows(2) {
+            let (x0, y0) = (f64::from(w[0].0), f64::from(w[0].1));
+            let (x1, y1) = (f64::from(w[1].0), f64::from(w[1].1));
+            let l = (x1 - x0).hypot(y1 - y0);
+            cx += (x0 + x1) / 2.0 * l;
+            cy += (y0 + y1) / 2.0 * l;
+            len += l;
+        }
+    }
+    if len > 0.0 {
+        Some((cx / len, cy / len))
+    } else {
+        points_centroid(&paths.concat())
+    }
+}
+
+/// Calculate the area-weighted centroid of polygon rings.
+///
+/// Interior rings (negative area) are subtracted.
+pub(crate) fn rings_centroid(rings: &[Vec<(i32, i32)>]) -> Option<(f64, f64)> {
+    let (mut cx, mut cy, mut area) = (0.0, 0.0, 0.0);
+    for ring in rings {
+        let a = signed_area(ring);
+        if let Some((x, y)) = ring_centroid(ring) {
+            cx += x * a;
+            cy += y * a;
+            area += a;
+        }
+    }
+    if area != 0.0 {
+        Some((cx / area, cy / area))
+    } else {
+        paths_centroid(rings)
+    }
+}
+
+/// Find the point at half the length of the longest path.
+pub(crate) fn path_midpoint(paths: &[Vec<(i32, i32)>]) -> Option<(f64, f64)> {
+    let path = paths
+        .iter()
+        .filter(|p| !p.is_empty())
+        .max_by(|a, b| path_length(a).total_cmp(&path_length(b)))?;
+    let mut remaining = path_length(path) / 2.0;
+    for w in path.windows(2) {
+        let (x0, y0) = (f64::from(w[0].0), f64::from(w[0].1));
+        let (x1, y1) = (f64::from(w[1].0), f64::from(w[1].1));
+        let l = (x1 - x0).hypot(y1 - y0);
+        if l > 0.0 && remaining <= l {
+            let t = remaining / l;
+            return Some((x0 + (x1 - x0) * t, y0 + (y1 - y0) * t));
+        }
+        remaining -= l;
+    }
+    Some((f64::from(path[0].0), f64::from(path[0].1)))
+}
+
+/// Check if a point is inside a polygon (exterior first, then holes).
+pub(crate) fn point_in_polygon(
+    pt: (i32, i32),
+    poly: &[Vec<(i32, i32)>],
+) -> bool {
+    match poly.split_first() {
+        Some((exterior, holes)) => {
+            point_in_ring(pt, exterior)
+                && !holes.iter().any(|h| point_in_ring(pt, h))
+        }
+        None => false,
+    }
+}
+
+/// Find a representative point within a multipolygon.
+///
+/// The centroid is used if it falls inside, otherwise the pole of
+/// inaccessibility of the largest polygon.
+pub(crate) fn polygons_representative(polys: &[Rings]) -> Option<(i32, i32)> {
+    let rings = polys.concat();
+    if let Some((x, y)) = rings_centroid(&rings) {
+        let pt = (x.round() as i32, y.round() as i32);
+        if polys.iter().any(|poly| point_in_polygon(pt, poly)) {
+            return Some(pt);
+        }
+    }
+    label_position(polys, 1.0)
+        .map(|(x, y)| (x.round() as i32, y.round() as i32))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn square() {
+        let poly = vec![vec![(0, 0), (100, 0), (100, 100), (0, 100)]];
+        let ((x, y), dist) = polylabel(&poly, 1.0).unwrap();
+        assert!((x - 50.0).abs() <= 1.0 && (y - 50.0).abs() <= 1.0);
+        assert!((dist - 50.0).abs() <= 1.0);
+    }
+
+    #[test]
+    fn concave() {
+        // U shape: centroid falls outside of the polygon
+        let poly = vec![vec![
+            (0, 0),
+            (30, 0),
+            (30, 90),
+            (70, 90),
+            (70, 0),
+            (100, 0),
+            (100, 100),
+            (0, 100),
+        ]];
+        let ((x, y), dist) = polylabel(&poly, 0.5).unwrap();
+        assert!(dist > 0.0);
+        assert!(polygon_distance(x, y, &poly) > 0.0);
+    }
+
+    #[test]
+    fn hole() {
+        let poly = vec![
+            vec![(0, 0), (100, 0), (100, 100), (0, 100)],
+            vec![(20, 20), (20, 80), (80, 80), (80, 20)],
+        ];
+        let ((x, y), _) = polylabel(&poly, 0.5).unwrap();
+        assert!(!(20.0..=80.0).contains(&x) || !(20.0..=80.0).contains(&y));
+        assert!(polygon_distance(x, y, &poly) > 0.0);
+    }
+
+    #[test]
+    fn centroids() {
+        assert_eq!(
+            points_centroid(&[(0, 0), (10, 0), (20, 30)]),
+            Some((10.0, 10.0))
+        );
+        assert_eq!(points_centroid(&[]), None);
+        let paths = vec![vec![(0, 0), (10, 0)], vec![(0, 10), (30, 10)]];
+        assert_eq!(paths_centroid(&paths), Some((12.5, 7.5)));
+        assert_eq!(path_midpoint(&paths), Some((15.0, 10.0)));
+        let rings = vec![
+            vec![(0, 0), (100, 0), (100, 100), (0, 100)],
+            vec![(0, 0), (0, 50), (50, 50), (50, 0)],
+        ];
+        let (x, y) = rings_centroid(&rings).unwrap();
+        assert!((x - 58.333).abs() < 0.001 && (y - 58.333).abs() < 0.001);
+    }
+
+    #[test]
+    fn representative() {
+        // C shape: centroid falls in the opening
+        let poly = vec![vec![
+            (0, 0),
+            (100, 0),
+            (100, 20),
+            (20, 20),
+            (20, 80),
+            (100, 80),
+            (100, 100),
+            (0, 100),
+        ]];
+        let pt = polygons_representative(std::slice::from_ref(&poly)).unwrap();
+        assert!(point_in_polygon(pt, &poly));
+        let square = vec![vec![(0, 0), (10, 0), (10, 10), (0, 10)]];
+        assert_eq!(polygons_representative(&[square]), Some((5, 5)));
+    }
+}
@@ -2,7 +2,7 @@
 //
 // Copyright (c) 2019-2024  Minnesota Department of Transportation
 //
-//! A library for encoding [mapbox vector tiles].
+//! A library for encoding and decoding [mapbox vector tiles].
 //!
 //! A [tile] is composed of one or more [layer]s.  Each layer can have any number
 //! of [feature]s, which contain the geometry to be rendered.  They can also have
@@ -42,15 +42,92 @@
 //! [tile]: struct.Tile.html
 #![forbid(unsafe_code)]
 
+mod clip;
+#[cfg(any(feature = "brotli", feature = "flate2", feature = "zstd"))]
+mod compress;
+mod decoder;
+mod describe;
+mod diff;
 mod encoder;
 mod error;
+#[cfg(test)]
+mod fixture;
+#[cfg(feature = "flatgeobuf")]
+mod flatgeobuf;
 mod geo;
+#[cfg(feature = "geoarrow")]
+mod geoarrow;
+#[cfg(feature = "geojson")]
+mod geojson;
+#[cfg(feature = "geo-types")]
+mod geotypes;
+#[cfg(feature = "geozero")]
+mod geozero;
+mod hilbert;
+mod label;
 mod mapgrid;
+#[cfg(feature = "mbtiles")]
+mod mbtiles;
+mod overzoom;
+#[cfg(feature = "pmtiles")]
+mod pmtiles;
+#[cfg(feature = "proj")]
+mod proj;
+#[cfg(feature = "rayon")]
+mod rayon;
+#[cfg(test)]
+mod roundtrip;
+mod schema;
+#[cfg(feature = "sdf")]
+mod sdf;
+#[cfg(feature = "serde")]
+mod serde;
+mod simplify;
+mod stats;
 mod tile;
+mod tiler;
+#[cfg(any(feature = "mbtiles", feature = "pmtiles"))]
+mod tileset;
+#[cfg(feature = "tokio")]
+mod tokio;
+#[cfg(feature = "v3")]
+mod v3;
+mod validate;
+mod value;
 mod vector_tile;
+mod view;
+#[cfg(feature = "wkb")]
+mod wkb;
+#[cfg(feature = "wkt")]
+mod wkt;
 
-pub use crate::encoder::{GeomData, GeomEncoder, GeomType};
-pub use crate::error::Error;
+#[cfg(any(feature = "brotli", feature = "flate2", feature = "zstd"))]
+pub use crate::compress::Compression;
+pub use crate::decoder::{FeatureRef, LayerRef};
+pub use crate::diff::{Change, FeatureDiff, FeatureKey, TileDiff};
+pub use crate::encoder::{
+    EncodeReport, GeomCommand, GeomData, GeomEncoder, GeomStats, GeomType,
+    NegMode, SimplifyMode, Strictness,
+};
+pub use crate::error::{Error, ErrorContext};
 pub use crate::geo::{WebMercatorPos, Wgs84Pos};
-pub use crate::mapgrid::{MapGrid, TileId};
-pub use crate::tile::{Feature, Layer, Tile};
+#[cfg(feature = "geozero")]
+pub use crate::geozero::LayerProcessor;
+pub use crate::mapgrid::{GridOrigin, LonLatTransform, MapGrid, TileId};
+#[cfg(feature = "mbtiles")]
+pub use crate::mbtiles::{MbTilesReader, MbTilesWriter};
+#[cfg(feature = "pmtiles")]
+pub use crate::pmtiles::{PmTilesReader, PmTilesWriter};
+#[cfg(feature = "proj")]
+pub use crate::proj::Reprojection;
+#[cfg(feature = "rayon")]
+pub use crate::rayon::TileBatch;
+pub use crate::schema::{FieldType, TilesetSchema};
+pub use crate::stats::{LayerStats, TileStats};
+pub use crate::tile::{Feature, IdMode, Layer, Tile};
+pub use crate::tiler::{SourceGeom, Tiler, TilerIter};
+#[cfg(feature = "v3")]
+pub use crate::v3::{AttrValue, Scaling};
+pub use crate::validate::{RingInfo, RingType};
+pub use crate::value::TagValue;
+pub use crate::view::{FeatureView, LayerView, TileView, ValueView};
@@ -4,7 +4,7 @@
 fn main() {
     protobuf_codegen::Codegen::new()
         .out_dir("src/")
-        .inputs(&["protos/vector_tile.proto"])
+        .inputs(["protos/vector_tile.proto"])
         .include("protos")
         .run()
         .expect("Codegen failed");
@@ -4,16 +4,26 @@
 //
 //! TileId and MapGrid structs.
 //!
+use crate::clip::{clip_line, clip_points};
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
 use crate::error::{Error, Result};
-use crate::geo::WebMercatorPos;
+use crate::geo::{WebMercatorPos, Wgs84Pos};
+use crate::hilbert::{hilbert_index, hilbert_point};
 use pointy::{BBox, Pt, Transform};
 use std::fmt;
+use std::ops::{Mul, Range};
 
 /// A tile ID identifies a tile on a map grid at a specific zoom level.
 ///
 /// It uses XYZ addressing, with X increasing from west to east and Y increasing
 /// from north to south.  The X and Y values can range from 0 to
 /// 2<sup>Z</sup>-1.
+///
+/// For [TMS] addressing, with Y increasing from south to north, use
+/// [from_tms](#method.from_tms) and [tms_y](#method.tms_y) to convert, or
+/// a [MapGrid] with a [GridOrigin::BottomLeft] origin.
+///
+/// [TMS]: https://wiki.osgeo.org/wiki/Tile_Map_Service_Specification
 #[derive(Clone, Copy, Debug)]
 pub struct TileId {
     x: u32, // not public to prevent invalid values being created
@@ -21,10 +31,35 @@ pub struct TileId {
     z: u32,
 }
 
+/// Corner of a [MapGrid] where tile X and Y are 0.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GridOrigin {
+    /// North-west corner, with Y increasing to the south
+    #[default]
+    TopLeft,
+
+    /// South-west corner, with Y increasing to the north (TMS)
+    BottomLeft,
+}
+
 /// A map grid is used to address [tile]s on a map.
 ///
 /// The grid should be in projected coördinates.  Use `default()` for
-/// [Web Mercator].
+/// [Web Mercator].  Other grids, such as national grids in a local
+/// projection, can be created with [new](#method.new); for square tiles,
+/// the bounds should be square.
+///
+/// # Example
+/// ```
+/// use mvt::{GridOrigin, MapGrid, TileId};
+/// use pointy::BBox;
+///
+/// // Swiss LV95 (EPSG:2056)
+/// let bbox = BBox::new([(2_420_000.0, 870_000.0), (2_900_000.0, 1_350_000.0)]);
+/// let grid = MapGrid::new(2056, bbox).with_origin(GridOrigin::TopLeft);
+/// let tid = TileId::new(1, 0, 1).unwrap();
+/// assert_eq!(grid.tile_bbox(tid).x_min(), 2_660_000.0);
+/// ```
 ///
 /// [tile]: struct.Tile.html
 /// [Web Mercator]: https://en.wikipedia.org/wiki/Web_Mercator_projection
@@ -35,6 +70,33 @@ pub struct MapGrid {
 
     /// Bounding box
     bbox: BBox<f64>,
+
+    /// Origin corner
+    origin: GridOrigin,
+}
+
+/// Transform from WGS84 longitude / latitude to tile coördinates.
+///
+/// This projects to [Web Mercator], then applies an affine [Transform] to
+/// the tile's 0 to `extent` range.  Since the projection is not affine, it
+/// cannot be represented by a `Transform` alone.
+///
+/// # Example
+/// ```
+/// use mvt::{LonLatTransform, TileId};
+///
+/// let tid = TileId::from_lon_lat(-93.2650, 44.9778, 10).unwrap();
+/// let t = LonLatTransform::new(tid, 4096);
+/// let p = t * (-93.2650, 44.9778);
+/// assert!(p.x >= 0.0 && p.x < 4096.0);
+/// assert!(p.y >= 0.0 && p.y < 4096.0);
+/// ```
+///
+/// [Web Mercator]: https://en.wikipedia.org/wiki/Web_Mercator_projection
+#[derive(Clone, Copy, Debug)]
+pub struct LonLatTransform {
+    /// Transform from Web Mercator to tile coördinates
+    transform: Transform<f64>,
 }
 
 impl TileId {
@@ -69,6 +131,193 @@ impl TileId {
         Ok(TileId { x, y, z })
     }
 
+    /// Create a new TileId from TMS addressing.
+    ///
+    /// * `x` Tile X.
+    /// * `y` Tile row, increasing from south to north.
+    /// * `z` Zoom level.
+    ///
+    /// If invalid, returns [Error::InvalidTid](enum.Error.html).
+    pub fn from_tms(x: u32, y: u32, z: u32) -> Result<Self> {
+        TileId::check_valid(x, y, z)?;
+        Ok(TileId { x, y, z }.flip_y())
+    }
+
+    /// Get the TMS Y value (row), increasing from south to north.
+    pub fn tms_y(&self) -> u32 {
+        self.flip_y().y
+    }
+
+    /// Flip the Y axis, converting between XYZ and TMS addressing.
+    pub fn flip_y(&self) -> Self {
+        let max = ((1u64 << self.z) - 1) as u32;
+        TileId {
+            x: self.x,
+            y: max - self.y,
+            z: self.z,
+        }
+    }
+
+    /// Find the Web Mercator tile containing a WGS84 position.
+    ///
+    /// * `lon` Longitude (degrees), from -180 to 180.
+    /// * `lat` Latitude (degrees); clamped to the Web Mercator limits.
+    /// * `z` Zoom level.
+    ///
+    /// If invalid, returns [Error::InvalidTid](enum.Error.html).
+    pub fn from_lon_lat(lon: f64, lat: f64, z: u32) -> Result<Self> {
+        if z > 31 || !(-180.0..=180.0).contains(&lon) || lat.is_nan() {
+            return Err(Error::InvalidTid());
+        }
+        let pos = WebMercatorPos::from(Wgs84Pos::new(lat, lon));
+        let bbox = WebMercatorPos::bbox();
+        let s = 1u32 << z;
+        let max = f64::from(s - 1);
+        let fx = (pos.x - bbox.x_min()) / bbox.x_span() * f64::from(s);
+        let fy = (bbox.y_max() - pos.y) / bbox.y_span() * f64::from(s);
+        let x = fx.floor().clamp(0.0, max) as u32;
+        let y = fy.floor().clamp(0.0, max) as u32;
+        TileId::new(x, y, z)
+    }
+
+    /// Get the WGS84 bounding box of a Web Mercator tile.
+    ///
+    /// X values are longitude and Y values are latitude (degrees).
+    pub fn lon_lat_bbox(&self) -> BBox<f64> {
+        let bbox = MapGrid::default().tile_bbox(*self);
+        let p0 =
+            Wgs84Pos::from(WebMercatorPos::new(bbox.x_min(), bbox.y_min()));
+        let p1 =
+            Wgs84Pos::from(WebMercatorPos::new(bbox.x_max(), bbox.y_max()));
+        BBox::from((
+            Pt::new(p0.lon_deg(), p0.lat_deg()),
+            Pt::new(p1.lon_deg(), p1.lat_deg()),
+        ))
+    }
+
+    /// Get the parent tile ID, at the next lower zoom level.
+    ///
+    /// Returns `None` at zoom level 0.
+    pub fn parent(&self) -> Option<Self> {
+        match self.z {
+            0 => None,
+            z => Some(TileId {
+                x: self.x >> 1,
+                y: self.y >> 1,
+                z: z - 1,
+            }),
+        }
+    }
+
+    /// Get the four child tile IDs, at the next higher zoom level.
+    ///
+    /// Children are ordered north-west, north-east, south-west, south-east.
+    /// Returns `None` at the maximum zoom level (31).
+    pub fn children(&self) -> Option<[Self; 4]> {
+        if self.z >= 31 {
+            return None;
+        }
+        let (x, y, z) = (self.x << 1, self.y << 1, self.z + 1);
+        Some([
+            TileId { x, y, z },
+            TileId { x: x + 1, y, z },
+            TileId { x, y: y + 1, z },
+            TileId {
+                x: x + 1,
+                y: y + 1,
+                z,
+            },
+        ])
+    }
+
+    /// Get the other tile IDs with the same parent.
+    ///
+    /// Returns an empty `Vec` at zoom level 0.
+    pub fn siblings(&self) -> Vec<Self> {
+        self.parent()
+            .and_then(|p| p.children())
+            .into_iter()
+            .flatten()
+            .filter(|t| t.x != self.x || t.y != self.y)
+            .collect()
+    }
+
+    /// Get the [quadkey] of a tile ID.
+    ///
+    /// The quadkey has one digit per zoom level; it is empty at zoom 0.
+    ///
+    /// [quadkey]: https://learn.microsoft.com/en-us/bingmaps/articles/bing-maps-tile-system
+    pub fn to_quadkey(&self) -> String {
+        (1..=self.z)
+            .rev()
+            .map(|i| {
+                let mask = 1 << (i - 1);
+                let d = u8::from(self.x & mask != 0)
+                    + 2 * u8::from(self.y & mask != 0);
+                char::from(b'0' + d)
+            })
+            .collect()
+    }
+
+    /// Create a tile ID from a [quadkey].
+    ///
+    /// If invalid, returns [Error::InvalidTid](enum.Error.html).
+    ///
+    /// [quadkey]: https://learn.microsoft.com/en-us/bingmaps/articles/bing-maps-tile-system
+    pub fn from_quadkey(quadkey: &str) -> Result<Self> {
+        if quadkey.len() > 31 {
+            return Err(Error::InvalidTid());
+        }
+        let (mut x, mut y) = (0, 0);
+        for c in quadkey.bytes() {
+            let d = match c {
+                b'0'..=b'3' => u32::from(c - b'0'),
+                _ => return Err(Error::InvalidTid()),
+            };
+            x = (x << 1) | (d & 1);
+            y = (y << 1) | (d >> 1);
+        }
+        TileId::new(x, y, quadkey.len() as u32)
+    }
+
+    /// Get a key ordered by zoom level, then [Morton] (Z-order) index.
+    ///
+    /// Keys are unique across all zoom levels.
+    ///
+    /// [Morton]: https://en.wikipedia.org/wiki/Z-order_curve
+    pub fn morton_key(&self) -> u64 {
+        zoom_base(self.z) + (spread_bits(self.x) | (spread_bits(self.y) << 1))
+    }
+
+    /// Create a tile ID from a [Morton key](#method.morton_key).
+    ///
+    /// If invalid, returns [Error::InvalidTid](enum.Error.html).
+    pub fn from_morton_key(key: u64) -> Result<Self> {
+        let z = key_zoom(key)?;
+        let i = key - zoom_base(z);
+        TileId::new(compact_bits(i), compact_bits(i >> 1), z)
+    }
+
+    /// Get a key ordered by zoom level, then [Hilbert] curve index.
+    ///
+    /// This is the tile ID used by [PMTiles].
+    ///
+    /// [Hilbert]: https://en.wikipedia.org/wiki/Hilbert_curve
+    /// [PMTiles]: https://github.com/protomaps/PMTiles
+    pub fn hilbert_key(&self) -> u64 {
+        let (x, y) = (u64::from(self.x), u64::from(self.y));
+        zoom_base(self.z) + hilbert_index(self.z, x, y)
+    }
+
+    /// Create a tile ID from a [Hilbert key](#method.hilbert_key).
+    ///
+    /// If invalid, returns [Error::InvalidTid](enum.Error.html).
+    pub fn from_hilbert_key(key: u64) -> Result<Self> {
+        let z = key_zoom(key)?;
+        let (x, y) = hilbert_point(z, key - zoom_base(z));
+        TileId::new(x as u32, y as u32, z)
+    }
+
     /// Check whether a tile ID is valid.
     fn check_valid(x: u32, y: u32, z: u32) -> Result<()> {
         if z > 31 {
@@ -83,12 +332,44 @@ impl TileId {
     }
 }
 
+impl LonLatTransform {
+    /// Create a new transform for one tile.
+    ///
+    /// * `tid` Tile ID, on the default (Web Mercator) [MapGrid].
+    /// * `extent` Height / width of tile bounds.
+    pub fn new(tid: TileId, extent: u32) -> Self {
+        let extent = f64::from(extent);
+        let transform =
+            MapGrid::default().tile_transform(tid).scale(extent, extent);
+        LonLatTransform { transform }
+    }
+
+    /// Get the transform from Web Mercator to tile coördinates.
+    ///
+    /// This can be used with [GeomEncoder::transform] for Web Mercator
+    /// source geometry.
+    pub fn transform(&self) -> Transform<f64> {
+        self.transform
+    }
+}
+
+impl Mul<(f64, f64)> for LonLatTransform {
+    type Output = Pt<f64>;
+
+    /// Transform a (longitude, latitude) position, in degrees
+    fn mul(self, (lon, lat): (f64, f64)) -> Pt<f64> {
+        let pos = WebMercatorPos::from(Wgs84Pos::new(lat, lon));
+        self.transform * Pt::from(pos)
+    }
+}
+
 impl Default for MapGrid {
     fn default() -> Self {
         const WEB_MERCATOR_SRID: i32 = 3857;
         let srid = WEB_MERCATOR_SRID;
         let bbox = WebMercatorPos::bbox();
-        Self { srid, bbox }
+        let origin = GridOrigin::default();
+        Self { srid, bbox, origin }
     }
 }
 
@@ -97,8 +378,24 @@ impl MapGrid {
     ///
     /// * `srid` Spatial reference ID.
     /// * `bbox` Bounding box.
+    ///
+    /// The origin is the top-left corner.
     pub fn new(srid: i32, bbox: BBox<f64>) -> Self {
-        MapGrid { srid, bbox }
+        let origin = GridOrigin::default();
+        MapGrid { srid, bbox, origin }
+    }
+
+    /// Set the origin corner.
+    ///
+    /// * `origin` Corner where tile X and Y are 0.
+    pub fn with_origin(mut self, origin: GridOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Get the origin corner.
+    pub fn origin(&self) -> GridOrigin {
+        self.origin
     }
 
     /// Get the spatial reference ID.
@@ -114,11 +411,14 @@ impl MapGrid {
     /// Get the bounding box of a tile ID.
     pub fn tile_bbox(&self, tid: TileId) -> BBox<f64> {
         let tx = self.bbox.x_min(); // west edge
-        let ty = self.bbox.y_max(); // north edge
         let tz = zoom_scale(tid.z);
         let sx = self.bbox.x_span() * tz;
         let sy = self.bbox.y_span() * tz;
-        let t = Transform::with_scale(sx, -sy).translate(tx, ty);
+        let (ty, sy) = match self.origin {
+            GridOrigin::TopLeft => (self.bbox.y_max(), -sy),
+            GridOrigin::BottomLeft => (self.bbox.y_min(), sy),
+        };
+        let t = Transform::with_scale(sx, sy).translate(tx, ty);
         let tidx = f64::from(tid.x);
         let tidy = f64::from(tid.y);
         let p0 = t * Pt::new(tidx, tidy);
@@ -127,18 +427,142 @@ impl MapGrid {
     }
 
     /// Get the transform to coördinates in 0 to 1 range.
+    ///
+    /// Y increases from the north edge of the tile, for any grid origin.
     pub fn tile_transform(&self, tid: TileId) -> Transform<f64> {
         let tx = self.bbox.x_min(); // west edge
-        let ty = self.bbox.y_max(); // north edge
         let tz = f64::from(1 << tid.z);
         let sx = tz / self.bbox.x_span();
         let sy = tz / self.bbox.y_span();
         let tidx = f64::from(tid.x);
         let tidy = f64::from(tid.y);
-        Transform::with_translate(-tx, -ty)
-            .scale(sx, -sy)
-            .translate(-tidx, -tidy)
+        match self.origin {
+            GridOrigin::TopLeft => {
+                let ty = self.bbox.y_max(); // north edge
+                Transform::with_translate(-tx, -ty)
+                    .scale(sx, -sy)
+                    .translate(-tidx, -tidy)
+            }
+            GridOrigin::BottomLeft => {
+                let ty = self.bbox.y_min(); // south edge
+                Transform::with_translate(-tx, -ty)
+                    .scale(sx, -sy)
+                    .translate(-tidx, tidy + 1.0)
+            }
+        }
     }
+
+    /// Get the transform from coördinates in 0 to 1 range.
+    ///
+    /// This is the inverse of [tile_transform](Self::tile_transform).
+    pub fn tile_inverse_transform(&self, tid: TileId) -> Transform<f64> {
+        let tx = self.bbox.x_min(); // west edge
+        let tz = f64::from(1 << tid.z);
+        let sx = self.bbox.x_span() / tz;
+        let sy = self.bbox.y_span() / tz;
+        let tidx = f64::from(tid.x);
+        let tidy = f64::from(tid.y);
+        match self.origin {
+            GridOrigin::TopLeft => {
+                let ty = self.bbox.y_max(); // north edge
+                Transform::with_translate(tidx, tidy)
+                    .scale(sx, -sy)
+                    .translate(tx, ty)
+            }
+            GridOrigin::BottomLeft => {
+                let ty = self.bbox.y_min(); // south edge
+                Transform::with_translate(tidx, -(tidy + 1.0))
+                    .scale(sx, -sy)
+                    .translate(tx, ty)
+            }
+        }
+    }
+
+    /// Get the fractional tile X and Y of a point at a zoom level.
+    pub(crate) fn tile_position(&self, z: u32, p: Pt<f64>) -> Pt<f64> {
+        let tz = f64::from(1 << z);
+        let x = (p.x - self.bbox.x_min()) / self.bbox.x_span() * tz;
+        let y = match self.origin {
+            GridOrigin::TopLeft => self.bbox.y_max() - p.y,
+            GridOrigin::BottomLeft => p.y - self.bbox.y_min(),
+        };
+        Pt::new(x, y / self.bbox.y_span() * tz)
+    }
+
+    /// Encode one source geometry into a range of tiles.
+    ///
+    /// * `source_parts` Parts of geometry, in grid coördinates.
+    /// * `geom_tp` Geometry type.
+    /// * `extent` Height / width of tile bounds.
+    /// * `z` Zoom level.
+    /// * `x_range` Range of tile X values.
+    /// * `y_range` Range of tile Y values.
+    ///
+    /// The geometry is clipped to each tile.  Only tiles with non-empty
+    /// geometry are returned, keyed by tile X and Y values.
+    pub fn encode_tile_range(
+        &self,
+        source_parts: &[Vec<(f64, f64)>],
+        geom_tp: GeomType,
+        extent: u32,
+        z: u32,
+        x_range: Range<u32>,
+        y_range: Range<u32>,
+    ) -> Result<Vec<((u32, u32), GeomData)>> {
+        let parts: Vec<Vec<Pt<f64>>> = source_parts
+            .iter()
+            .map(|part| part.iter().map(|p| Pt::from(*p)).collect())
+            .collect();
+        let scale = f64::from(extent);
+        let mut tiles = Vec::new();
+        for y in y_range {
+            for x in x_range.clone() {
+                let tid = TileId::new(x, y, z)?;
+                let bbox = self.tile_bbox(tid);
+                let transform = self.tile_transform(tid).scale(scale, scale);
+                let mut enc =
+                    GeomEncoder::new(geom_tp).bbox(bbox).transform(transform);
+                for part in &parts {
+                    let clipped = match geom_tp {
+                        GeomType::Point => vec![clip_points(part, bbox)],
+                        GeomType::Linestring => clip_line(part, bbox),
+                        // polygons are clipped by the encoder
+                        GeomType::Polygon => vec![part.clone()],
+                    };
+                    enc.add_geometries(clipped)?;
+                }
+                let geom = enc.encode()?;
+                if !geom.is_empty() {
+                    tiles.push(((x, y), geom));
+                }
+            }
+        }
+        Ok(tiles)
+    }
+}
+
+/// Get the number of tiles at all lower zoom levels.
+fn zoom_base(z: u32) -> u64 {
+    ((1u64 << (2 * z)) - 1) / 3
+}
+
+/// Get the zoom level of a tile key.
+fn key_zoom(key: u64) -> Result<u32> {
+    (0..=31)
+        .rev()
+        .find(|&z| zoom_base(z) <= key)
+        .filter(|&z| key - zoom_base(z) < 1 << (2 * z))
+        .ok_or(Error::InvalidTid())
+}
+
+/// Spread the bits of a value to even bit positions.
+fn spread_bits(v: u32) -> u64 {
+    (0..32).fold(0, |k, i| k | (u64::from(v >> i & 1) << (2 * i)))
+}
+
+/// Compact the even bit positions of a value.
+fn compact_bits(k: u64) -> u32 {
+    (0..32).fold(0, |v, i| v | (((k >> (2 * i)) & 1) as u32) << i)
 }
 
 /// Calculate scales at one zoom level.
@@ -147,6 +571,7 @@ fn zoom_scale(zoom: u32) -> f64 {
 }
 
 #[cfg(test)]
+#[allow(clippy::excessive_precision)]
 mod test {
     use super::*;
 
@@ -182,6 +607,27 @@ mod test {
         assert_eq!(b.y_max(), 5635549.221409475);
     }
 
+    #[test]
+    fn test_encode_tile_range() {
+        let bbox = BBox::new([(0.0, 0.0), (1024.0, 1024.0)]);
+        let g = MapGrid::new(0, bbox);
+        let line = vec![vec![(100.0, 700.0), (900.0, 700.0)]];
+        let tiles = g
+            .encode_tile_range(&line, GeomType::Linestring, 4096, 1, 0..2, 0..2)
+            .unwrap();
+        let tiles: Vec<_> = tiles
+            .into_iter()
+            .map(|(xy, geom)| (xy, geom.decode().unwrap()))
+            .collect();
+        assert_eq!(
+            tiles,
+            vec![
+                ((0, 0), vec![vec![(800, 2592), (4096, 2592)]]),
+                ((1, 0), vec![vec![(0, 2592), (3104, 2592)]]),
+            ]
+        );
+    }
+
     #[test]
     fn test_tile_transform() {
         let g = MapGrid::default();
@@ -223,4 +669,165 @@ mod test {
             t * Pt::new(-10370975.997732716, 5596413.462927466)
         );
     }
+
+    #[test]
+    fn test_tile_inverse_transform() {
+        let g = MapGrid::default();
+        let tid = TileId::new(1, 1, 1).unwrap();
+        let t = g.tile_inverse_transform(tid);
+        assert_eq!(Pt::new(0.0, 0.0), t * Pt::new(0.0, 0.0));
+        assert_eq!(
+            Pt::new(20037508.342789248, -20037508.342789248),
+            t * Pt::new(1.0, 1.0)
+        );
+        let tid = TileId::new(246, 368, 10).unwrap();
+        let p = g.tile_inverse_transform(tid) * Pt::new(0.5, 0.5);
+        let p = g.tile_transform(tid) * p;
+        assert!((p.x - 0.5).abs() < 1e-9 && (p.y - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_custom_grid() {
+        let bbox =
+            BBox::new([(2_420_000.0, 870_000.0), (2_900_000.0, 1_350_000.0)]);
+        let top = MapGrid::new(2056, bbox);
+        let bottom =
+            MapGrid::new(2056, bbox).with_origin(GridOrigin::BottomLeft);
+        assert_eq!(top.origin(), GridOrigin::TopLeft);
+        let tid = TileId::new(1, 0, 1).unwrap();
+        let b = top.tile_bbox(tid);
+        assert_eq!((b.x_min(), b.y_min()), (2_660_000.0, 1_110_000.0));
+        assert_eq!((b.x_max(), b.y_max()), (2_900_000.0, 1_350_000.0));
+        let b = bottom.tile_bbox(tid);
+        assert_eq!((b.x_min(), b.y_min()), (2_660_000.0, 870_000.0));
+        assert_eq!((b.x_max(), b.y_max()), (2_900_000.0, 1_110_000.0));
+        for grid in [top, bottom] {
+            let tid = TileId::new(2, 1, 2).unwrap();
+            let b = grid.tile_bbox(tid);
+            let t = grid.tile_transform(tid);
+            // tile Y increases from the north edge
+            assert_eq!(t * Pt::new(b.x_min(), b.y_max()), Pt::new(0.0, 0.0));
+            assert_eq!(t * Pt::new(b.x_max(), b.y_min()), Pt::new(1.0, 1.0));
+            let p = grid.tile_inverse_transform(tid) * Pt::new(0.25, 0.75);
+            let p = t * p;
+            assert!((p.x - 0.25).abs() < 1e-9 && (p.y - 0.75).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_tile_navigation() {
+        let tid = TileId::new(5, 2, 3).unwrap();
+        let p = tid.parent().unwrap();
+        assert_eq!((p.x(), p.y(), p.z()), (2, 1, 2));
+        let xy: Vec<_> = p.children().unwrap().map(|t| (t.x(), t.y())).into();
+        assert_eq!(xy, [(4, 2), (5, 2), (4, 3), (5, 3)]);
+        assert!(p.children().unwrap().iter().all(|t| t.z() == 3));
+        let xy: Vec<_> =
+            tid.siblings().iter().map(|t| (t.x(), t.y())).collect();
+        assert_eq!(xy, [(4, 2), (4, 3), (5, 3)]);
+        let root = TileId::new(0, 0, 0).unwrap();
+        assert!(root.parent().is_none());
+        assert!(root.siblings().is_empty());
+        assert_eq!(root.children().unwrap().len(), 4);
+        let max = TileId::new(0, 0, 31).unwrap();
+        assert!(max.children().is_none());
+        assert_eq!(max.siblings().len(), 3);
+    }
+
+    #[test]
+    fn test_lon_lat() {
+        let tid = TileId::from_lon_lat(-93.2650, 44.9778, 10).unwrap();
+        assert_eq!((tid.x(), tid.y(), tid.z()), (246, 368, 10));
+        let tid = TileId::from_lon_lat(0.0, 0.0, 0).unwrap();
+        assert_eq!((tid.x(), tid.y()), (0, 0));
+        let tid = TileId::from_lon_lat(180.0, -90.0, 2).unwrap();
+        assert_eq!((tid.x(), tid.y()), (3, 3));
+        let tid = TileId::from_lon_lat(-180.0, 90.0, 2).unwrap();
+        assert_eq!((tid.x(), tid.y()), (0, 0));
+        assert!(TileId::from_lon_lat(181.0, 0.0, 2).is_err());
+        assert!(TileId::from_lon_lat(0.0, f64::NAN, 2).is_err());
+        assert!(TileId::from_lon_lat(0.0, 0.0, 32).is_err());
+        let b = TileId::new(1, 0, 1).unwrap().lon_lat_bbox();
+        assert!(b.x_min().abs() < 1e-9 && (b.x_max() - 180.0).abs() < 1e-9);
+        assert!(b.y_min().abs() < 1e-9);
+        assert!((b.y_max() - 85.051_128_78).abs() < 1e-6);
+        let tid = TileId::new(246, 368, 10).unwrap();
+        let b = tid.lon_lat_bbox();
+        assert!(b.x_min() <= -93.2650 && b.x_max() >= -93.2650);
+        assert!(b.y_min() <= 44.9778 && b.y_max() >= 44.9778);
+    }
+
+    #[test]
+    fn test_quadkey() {
+        let tid = TileId::new(3, 5, 3).unwrap();
+        assert_eq!(tid.to_quadkey(), "213");
+        let tid = TileId::from_quadkey("213").unwrap();
+        assert_eq!((tid.x(), tid.y(), tid.z()), (3, 5, 3));
+        assert_eq!(TileId::new(0, 0, 0).unwrap().to_quadkey(), "");
+        assert_eq!(TileId::from_quadkey("").unwrap().z(), 0);
+        assert!(TileId::from_quadkey("124").is_err());
+        assert!(TileId::from_quadkey(&"3".repeat(32)).is_err());
+        let tid = TileId::from_quadkey(&"3".repeat(31)).unwrap();
+        assert_eq!((tid.x(), tid.y()), (u32::MAX >> 1, u32::MAX >> 1));
+    }
+
+    #[test]
+    fn test_tile_keys() {
+        let keys: Vec<_> = TileId::new(0, 0, 0)
+            .unwrap()
+            .children()
+            .unwrap()
+            .iter()
+            .map(|t| t.morton_key())
+            .collect();
+        assert_eq!(keys, [1, 2, 3, 4]);
+        assert_eq!(TileId::new(0, 0, 2).unwrap().morton_key(), 5);
+        for (x, y, z) in [(0, 0, 0), (3, 5, 3), (12_345, 67_890, 17)] {
+            let tid = TileId::new(x, y, z).unwrap();
+            let t = TileId::from_morton_key(tid.morton_key()).unwrap();
+            assert_eq!((t.x(), t.y(), t.z()), (x, y, z));
+            let t = TileId::from_hilbert_key(tid.hilbert_key()).unwrap();
+            assert_eq!((t.x(), t.y(), t.z()), (x, y, z));
+        }
+        let max = u32::MAX >> 1;
+        let tid = TileId::new(max, max, 31).unwrap();
+        let key = tid.morton_key();
+        assert_eq!(TileId::from_morton_key(key).unwrap().x(), max);
+        assert!(TileId::from_morton_key(key + 1).is_err());
+        assert!(TileId::from_hilbert_key(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_tms() {
+        let tid = TileId::new(3, 1, 3).unwrap();
+        assert_eq!(tid.tms_y(), 6);
+        let tms = TileId::from_tms(3, 6, 3).unwrap();
+        assert_eq!((tms.x(), tms.y(), tms.z()), (3, 1, 3));
+        assert_eq!(tid.flip_y().flip_y().y(), 1);
+        assert!(TileId::from_tms(0, 8, 3).is_err());
+        assert_eq!(TileId::new(0, 0, 0).unwrap().tms_y(), 0);
+        assert_eq!(TileId::new(0, 0, 31).unwrap().tms_y(), u32::MAX >> 1);
+        let xyz = MapGrid::default();
+        let tms = MapGrid::default().with_origin(GridOrigin::BottomLeft);
+        let b0 = xyz.tile_bbox(tid);
+        let b1 = tms.tile_bbox(tid.flip_y());
+        assert!((b0.y_min() - b1.y_min()).abs() < 1e-6);
+        assert!((b0.y_max() - b1.y_max()).abs() < 1e-6);
+        assert_eq!((b0.x_min(), b0.x_max()), (b1.x_min(), b1.x_max()));
+    }
+
+    #[test]
+    fn test_lon_lat_transform() {
+        let tid = TileId::new(1, 0, 1).unwrap();
+        let t = LonLatTransform::new(tid, 4096);
+        let p = t * (0.0, 0.0);
+        assert!(p.x.abs() < 1e-6 && (p.y - 4096.0).abs() < 1e-6);
+        let p = t * (90.0, 66.513_260_443);
+        assert!((p.x - 2048.0).abs() < 1e-6 && (p.y - 2048.0).abs() < 1e-3);
+        let p = t * (180.0, 90.0);
+        assert!((p.x - 4096.0).abs() < 1e-6 && p.y.abs() < 1e-6);
+        let m = WebMercatorPos::from(Wgs84Pos::new(0.0, 90.0));
+        let p = t.transform() * Pt::from(m);
+        assert!((p.x - 2048.0).abs() < 1e-6);
+    }
 }
@@ -0,0 +1,372 @@
+// mbtiles.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! MBTiles reader and writer.
+//!
+use crate::error::Result;
+use crate::mapgrid::TileId;
+use crate::tile::Tile;
+use crate::tileset::TilesetInfo;
+use flate2::read::GzDecoder;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Gzip compression level for tile data
+const GZIP_LEVEL: u32 = 6;
+
+/// SQL to create the MBTiles schema
+const SCHEMA_SQL: &str = "\
+    CREATE TABLE metadata (name TEXT, value TEXT);\
+    CREATE UNIQUE INDEX metadata_name ON metadata (name);\
+    CREATE TABLE tiles (\
+        zoom_level INTEGER, \
+        tile_column INTEGER, \
+        tile_row INTEGER, \
+        tile_data BLOB\
+    );\
+    CREATE UNIQUE INDEX tile_index \
+        ON tiles (zoom_level, tile_column, tile_row);";
+
+/// Writer for [MBTiles] files.
+///
+/// Tiles are gzip-compressed and stored with TMS row numbering.  All writes
+/// happen within one transaction, which is committed by [finish]; dropping
+/// the writer without calling `finish` discards all tiles.
+///
+/// # Example
+/// ```no_run
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{MbTilesWriter, Tile, TileId};
+///
+/// let mut writer = MbTilesWriter::create("roads.mbtiles", "roads")?;
+/// let tile = Tile::new(4096);
+/// // ...
+/// // add layers to the tile
+/// // ...
+/// writer.write_tile(TileId::new(0, 0, 0)?, &tile)?;
+/// writer.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [finish]: #method.finish
+/// [MBTiles]: https://github.com/mapbox/mbtiles-spec
+pub struct MbTilesWriter {
+    conn: Connection,
+    name: String,
+    bounds: Option<[f64; 4]>,
+    info: TilesetInfo,
+}
+
+/// Reader for [MBTiles] files.
+///
+/// Tile data is returned uncompressed, ready for [Tile::from_bytes].
+///
+/// # Example
+/// ```no_run
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{MbTilesReader, Tile};
+///
+/// let reader = MbTilesReader::open("roads.mbtiles")?;
+/// for res in reader.tiles()? {
+///     let (tid, data) = res?;
+///     let tile = Tile::from_bytes(&data)?;
+///     println!("{tid}: {} layers", tile.num_layers());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [MBTiles]: https://github.com/mapbox/mbtiles-spec
+pub struct MbTilesReader {
+    conn: Connection,
+}
+
+/// Decompress tile data, if gzip-compressed
+fn decompress(data: Vec<u8>) -> Result<Vec<u8>> {
+    if data.starts_with(&[0x1F, 0x8B]) {
+        let mut bytes = Vec::with_capacity(data.len() * 2);
+        GzDecoder::new(&data[..]).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        Ok(data)
+    }
+}
+
+impl MbTilesWriter {
+    /// Create a new MBTiles file.
+    ///
+    /// * `path` Path to the file, which must not already exist.
+    /// * `name` Name of the tileset.
+    pub fn create<P: AsRef<Path>>(path: P, name: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA_SQL)?;
+        conn.execute_batch("BEGIN")?;
+        Ok(MbTilesWriter {
+            conn,
+            name: name.to_string(),
+            bounds: None,
+            info: TilesetInfo::default(),
+        })
+    }
+
+    /// Set the bounds (WGS-84 degrees).
+    ///
+    /// By default, bounds are the union of all written tiles.
+    pub fn bounds(
+        mut self,
+        west: f64,
+        south: f64,
+        east: f64,
+        north: f64,
+    ) -> Self {
+        self.bounds = Some([west, south, east, north]);
+        self
+    }
+
+    /// Write one tile.
+    ///
+    /// * `tid` Tile ID, using XYZ addressing.
+    /// * `tile` Tile to write.
+    ///
+    /// Any existing tile with the same ID is replaced.
+    pub fn write_tile(&mut self, tid: TileId, tile: &Tile) -> Result<()> {
+        let data = tile.to_bytes_gzip(GZIP_LEVEL)?;
+        // MBTiles uses TMS addressing, with Y increasing from south to north
+        let row = tid.tms_y();
+        self.conn
+            .prepare_cached(
+                "INSERT OR REPLACE INTO tiles \
+                (zoom_level, tile_column, tile_row, tile_data) \
+                VALUES (?1, ?2, ?3, ?4)",
+            )?
+            .execute(params![tid.z(), tid.x(), row, data])?;
+        self.info.add_tile(tid, tile);
+        Ok(())
+    }
+
+    /// Write metadata and commit all tiles to the file.
+    pub fn finish(self) -> Result<()> {
+        let info = &self.info;
+        let mut metadata = vec![
+            ("name", self.name.clone()),
+            ("format", "pbf".to_string()),
+            ("json", info.schema().to_json()),
+        ];
+        if let Some([w, s, e, n]) = self.bounds.or_else(|| info.bounds()) {
+            metadata.push(("bounds", format!("{w},{s},{e},{n}")));
+        }
+        if let Some(minzoom) = info.minzoom() {
+            metadata.push(("minzoom", minzoom.to_string()));
+        }
+        if let Some(maxzoom) = info.maxzoom() {
+            metadata.push(("maxzoom", maxzoom.to_string()));
+        }
+        {
+            let mut stmt = self.conn.prepare(
+                "INSERT OR REPLACE INTO metadata (name, value) VALUES (?1, ?2)",
+            )?;
+            for (name, value) in &metadata {
+                stmt.execute(params![name, value])?;
+            }
+        }
+        self.conn.execute_batch("COMMIT")?;
+        Ok(())
+    }
+}
+
+impl MbTilesReader {
+    /// Open an existing MBTiles file (read-only).
+    ///
+    /// * `path` Path to the file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        Ok(MbTilesReader { conn })
+    }
+
+    /// Get one metadata value.
+    ///
+    /// * `name` Metadata name, such as `format` or `bounds`.
+    pub fn metadata_value(&self, name: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .prepare_cached("SELECT value FROM metadata WHERE name = ?1")?
+            .query_row([name], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// Get all metadata as name/value pairs.
+    pub fn metadata(&self) -> Result<BTreeMap<String, String>> {
+        let mut stmt = self.conn.prepare("SELECT name, value FROM metadata")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Read one tile.
+    ///
+    /// * `tid` Tile ID, using XYZ addressing.
+    ///
+    /// Returns `None` if the tile does not exist.
+    pub fn tile(&self, tid: TileId) -> Result<Option<Vec<u8>>> {
+        let row = tid.tms_y();
+        let data: Option<Vec<u8>> = self
+            .conn
+            .prepare_cached(
+                "SELECT tile_data FROM tiles \
+                WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+            )?
+            .query_row(params![tid.z(), tid.x(), row], |row| row.get(0))
+            .optional()?;
+        data.map(decompress).transpose()
+    }
+
+    /// Get IDs of all tiles, ordered by zoom level, X and Y.
+    pub fn tile_ids(&self) -> Result<Vec<TileId>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT zoom_level, tile_column, tile_row FROM tiles \
+            ORDER BY zoom_level, tile_column, tile_row DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?, row.get(2)?))
+        })?;
+        let mut tids = Vec::new();
+        for row in rows {
+            let (z, x, row) = row?;
+            tids.push(TileId::from_tms(x, row, z)?);
+        }
+        Ok(tids)
+    }
+
+    /// Get an iterator of all tiles, ordered by zoom level, X and Y.
+    ///
+    /// Each tile is read when the iterator advances.
+    pub fn tiles(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(TileId, Vec<u8>)>> + '_> {
+        let tids = self.tile_ids()?;
+        Ok(tids.into_iter().filter_map(move |tid| {
+            self.tile(tid).transpose().map(|data| Ok((tid, data?)))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomType;
+    use crate::fixture::make_tile;
+
+    fn road_tile() -> Tile {
+        let pts = [(0.0, 0.0), (4096.0, 4096.0)];
+        make_tile("roads", GeomType::Linestring, &pts, |f| {
+            f.add_tag_string("name", "Main \"St\"");
+            f.add_tag_uint("lanes", 2);
+        })
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let file = format!("mvt-{name}-{}.mbtiles", std::process::id());
+        let path = std::env::temp_dir().join(file);
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn write_tiles() {
+        let path = temp_path("write");
+        let tile = road_tile();
+        let mut writer = MbTilesWriter::create(&path, "test").unwrap();
+        writer
+            .write_tile(TileId::new(1, 0, 2).unwrap(), &tile)
+            .unwrap();
+        writer
+            .write_tile(TileId::new(2, 3, 3).unwrap(), &tile)
+            .unwrap();
+        writer.finish().unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let data: Vec<u8> = conn
+            .query_row(
+                "SELECT tile_data FROM tiles \
+                WHERE zoom_level = 2 AND tile_column = 1 AND tile_row = 3",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let bytes = decompress(data).unwrap();
+        assert_eq!(bytes, tile.to_bytes().unwrap());
+        let meta = |name: &str| -> String {
+            conn.query_row(
+                "SELECT value FROM metadata WHERE name = ?1",
+                [name],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(meta("name"), "test");
+        assert_eq!(meta("format"), "pbf");
+        assert_eq!(meta("minzoom"), "2");
+        assert_eq!(meta("maxzoom"), "3");
+        assert_eq!(
+            meta("json"),
+            "{\"vector_layers\":[{\"id\":\"roads\",\"fields\":\
+            {\"lanes\":\"Number\",\"name\":\"String\"},\
+            \"minzoom\":2,\"maxzoom\":3}]}"
+        );
+        let bounds: Vec<f64> = meta("bounds")
+            .split(',')
+            .map(|v| v.parse().unwrap())
+            .collect();
+        assert_eq!(bounds.len(), 4);
+        assert!((bounds[0] - -90.0).abs() < 1e-6);
+        assert!((bounds[2] - 0.0).abs() < 1e-6);
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_tiles() {
+        let path = temp_path("read");
+        let tile = road_tile();
+        let mut writer = MbTilesWriter::create(&path, "test").unwrap();
+        for (x, y, z) in [(2, 3, 3), (1, 0, 2), (1, 2, 2)] {
+            writer
+                .write_tile(TileId::new(x, y, z).unwrap(), &tile)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = MbTilesReader::open(&path).unwrap();
+        let bytes = tile.to_bytes().unwrap();
+        let data = reader.tile(TileId::new(1, 2, 2).unwrap()).unwrap();
+        assert_eq!(data, Some(bytes.clone()));
+        let data = reader.tile(TileId::new(1, 1, 2).unwrap()).unwrap();
+        assert_eq!(data, None);
+        let tids: Vec<String> = reader
+            .tile_ids()
+            .unwrap()
+            .iter()
+            .map(|tid| tid.to_string())
+            .collect();
+        assert_eq!(tids, ["2/1/0", "2/1/2", "3/2/3"]);
+        for res in reader.tiles().unwrap() {
+            let (_tid, data) = res.unwrap();
+            assert_eq!(data, bytes);
+        }
+        assert_eq!(reader.metadata_value("format").unwrap().unwrap(), "pbf");
+        assert_eq!(reader.metadata_value("missing").unwrap(), None);
+        let meta = reader.metadata().unwrap();
+        assert_eq!(meta.get("name").unwrap(), "test");
+        assert_eq!(meta.len(), 6);
+        drop(reader);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
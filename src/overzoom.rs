@@ -0,0 +1,172 @@
+// overzoom.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Overzooming tiles.
+//!
+use crate::clip::clip_points;
+use crate::encoder::{GeomEncoder, GeomType, Strictness};
+use crate::error::{Error, Result};
+use crate::mapgrid::TileId;
+use crate::tile::Tile;
+use pointy::{BBox, Pt, Transform};
+
+impl Tile {
+    /// Derive a child tile from a parent tile by overzooming.
+    ///
+    /// * `parent` ID of this tile.
+    /// * `child` ID of the child tile, at a higher zoom level.
+    ///
+    /// Geometry is scaled up and clipped to the child tile bounds.  Features
+    /// with no geometry remaining are dropped; IDs and tags are preserved.
+    ///
+    /// Returns `Error::InvalidTid` if `child` is not within `parent`.
+    pub fn overzoom(&self, parent: TileId, child: TileId) -> Result<Tile> {
+        let dz = child
+            .z()
+            .checked_sub(parent.z())
+            .ok_or(Error::InvalidTid())?;
+        if child.x() >> dz != parent.x() || child.y() >> dz != parent.y() {
+            return Err(Error::InvalidTid());
+        }
+        let scale = (1u64 << dz) as f64;
+        // child tile position within the parent, in parent tile units
+        let size = f64::from(self.extent()) / scale;
+        let x0 = f64::from(child.x() - (parent.x() << dz)) * size;
+        let y0 = f64::from(child.y() - (parent.y() << dz)) * size;
+        let bbox = BBox::new([(x0, y0), (x0 + size, y0 + size)]);
+        let transform = Transform::with_translate(-x0, -y0).scale(scale, scale);
+        let mut tile = Tile::new(self.extent());
+        for layer in self.layers() {
+            let mut out = tile.create_layer(layer.name());
+            for feature in layer.features() {
+                let (Some(geom_tp), Some(geom_data)) =
+                    (feature.geom_type(), feature.geom_data())
+                else {
+                    continue;
+                };
+                let parts = geom_data.decode()?.into_iter().map(|part| {
+                    part.into_iter()
+                        .map(|(x, y)| Pt::new(f64::from(x), f64::from(y)))
+                        .collect::<Vec<_>>()
+                });
+                let mut enc = GeomEncoder::new(geom_tp)
+                    .bbox(bbox)
+                    .transform(transform)
+                    .strictness(Strictness::Lenient);
+                match geom_tp {
+                    GeomType::Point => {
+                        for part in parts {
+                            enc.add_points(clip_points(&part, bbox))?;
+                        }
+                    }
+                    _ => enc.add_geometries(parts)?,
+                }
+                let geom = enc.encode()?;
+                if geom.is_empty() {
+                    continue;
+                }
+                let mut feat = out.into_feature(geom);
+                if let Some(id) = feature.id() {
+                    feat.set_id(id);
+                }
+                for (key, value) in feature.tags() {
+                    feat.add_tag(key, value);
+                }
+                out = feat.into_layer();
+            }
+            if out.num_features() > 0 {
+                tile.add_layer(out)?;
+            }
+        }
+        Ok(tile)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::{add_layer, make_tile, tid};
+    use crate::value::TagValue;
+
+    fn sample_tile() -> Tile {
+        let pts = [(100.0, 100.0), (3000.0, 3000.0)];
+        let mut tile = make_tile("points", GeomType::Point, &pts, |f| {
+            f.set_id(7);
+            f.add_tag_string("name", "a");
+        });
+        let pts = [(0.0, 1024.0), (4096.0, 1024.0)];
+        add_layer(&mut tile, "lines", GeomType::Linestring, &pts, |_f| ());
+        let pts = [
+            (1024.0, 1024.0),
+            (3072.0, 1024.0),
+            (3072.0, 3072.0),
+            (1024.0, 3072.0),
+        ];
+        add_layer(&mut tile, "polygons", GeomType::Polygon, &pts, |f| {
+            f.add_tag_uint("lanes", 2)
+        });
+        tile
+    }
+
+    fn parts(tile: &Tile, layer: &str) -> Vec<Vec<(i32, i32)>> {
+        let layer = tile.layer(layer).unwrap();
+        let feature = layer.features().next().unwrap();
+        feature.geom_data().unwrap().decode().unwrap()
+    }
+
+    #[test]
+    fn overzoom() {
+        let tile = sample_tile();
+        let child = tile.overzoom(tid(2, 1, 3), tid(4, 2, 4)).unwrap();
+        assert_eq!(child.num_layers(), 3);
+        assert_eq!(parts(&child, "points"), [[(200, 200)]]);
+        assert_eq!(parts(&child, "lines"), [[(0, 2048), (4096, 2048)]]);
+        assert_eq!(
+            parts(&child, "polygons"),
+            [[(2048, 4096), (2048, 2048), (4096, 2048), (4096, 4096)]]
+        );
+        let layer = child.layer("points").unwrap();
+        let feature = layer.features().next().unwrap();
+        assert_eq!(feature.id(), Some(7));
+        assert_eq!(
+            feature.tags().collect::<Vec<_>>(),
+            [("name", TagValue::String("a".to_string()))]
+        );
+        let layer = child.layer("polygons").unwrap();
+        let feature = layer.features().next().unwrap();
+        assert_eq!(
+            feature.tags().collect::<Vec<_>>(),
+            [("lanes", TagValue::U64(2))]
+        );
+    }
+
+    #[test]
+    fn overzoom_empty() {
+        let tile = sample_tile();
+        // bottom-right quarter at two zoom levels deeper
+        let child = tile.overzoom(tid(0, 0, 0), tid(3, 3, 2)).unwrap();
+        assert_eq!(child.num_layers(), 0);
+        let child = tile.overzoom(tid(0, 0, 0), tid(2, 2, 2)).unwrap();
+        assert_eq!(child.num_layers(), 2);
+        assert_eq!(parts(&child, "points"), [[(3808, 3808)]]);
+    }
+
+    #[test]
+    fn overzoom_same() {
+        let tile = sample_tile();
+        let same = tile.overzoom(tid(1, 1, 1), tid(1, 1, 1)).unwrap();
+        assert_eq!(same.to_bytes().unwrap(), tile.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn overzoom_invalid() {
+        let tile = sample_tile();
+        let res = tile.overzoom(tid(1, 1, 1), tid(0, 0, 1));
+        assert!(matches!(res, Err(Error::InvalidTid())));
+        let res = tile.overzoom(tid(1, 1, 1), tid(0, 0, 0));
+        assert!(matches!(res, Err(Error::InvalidTid())));
+        let res = tile.overzoom(tid(1, 1, 1), tid(1, 2, 2));
+        assert!(matches!(res, Err(Error::InvalidTid())));
+    }
+}
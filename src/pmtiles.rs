@@ -0,0 +1,886 @@
+// pmtiles.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! PMTiles (version 3) archives.
+//!
+use crate::error::{Error, Result};
+use crate::mapgrid::TileId;
+use crate::schema::json_escape;
+use crate::tile::Tile;
+use crate::tileset::TilesetInfo;
+use flate2::Compression as GzLevel;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Gzip compression level for tiles and directories
+const GZIP_LEVEL: u32 = 6;
+
+/// Length of header (bytes)
+const HEADER_LEN: usize = 127;
+
+/// Maximum length of header plus root directory (bytes)
+const ROOT_MAX_LEN: usize = 16_384;
+
+/// Minimum number of entries in a leaf directory
+const LEAF_MIN_ENTRIES: usize = 4096;
+
+/// Maximum depth of nested leaf directories
+const MAX_LEAF_DEPTH: usize = 3;
+
+/// Compression type: unknown
+const COMPRESSION_UNKNOWN: u8 = 0;
+
+/// Compression type: none
+const COMPRESSION_NONE: u8 = 1;
+
+/// Compression type: gzip
+const COMPRESSION_GZIP: u8 = 2;
+
+/// Compression type: brotli
+#[cfg(feature = "brotli")]
+const COMPRESSION_BROTLI: u8 = 3;
+
+/// Compression type: zstd
+#[cfg(feature = "zstd")]
+const COMPRESSION_ZSTD: u8 = 4;
+
+/// Tile type: Mapbox Vector Tile
+const TILE_TYPE_MVT: u8 = 1;
+
+/// PMTiles header
+#[derive(Debug, Default, PartialEq)]
+struct Header {
+    root_offset: u64,
+    root_length: u64,
+    metadata_offset: u64,
+    metadata_length: u64,
+    leaf_offset: u64,
+    leaf_length: u64,
+    data_offset: u64,
+    data_length: u64,
+    n_addressed_tiles: u64,
+    n_tile_entries: u64,
+    n_tile_contents: u64,
+    clustered: bool,
+    internal_compression: u8,
+    tile_compression: u8,
+    tile_type: u8,
+    min_zoom: u8,
+    max_zoom: u8,
+    bounds_e7: [i32; 4],
+    center_zoom: u8,
+    center_e7: [i32; 2],
+}
+
+/// Directory entry
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Entry {
+    /// Hilbert tile ID
+    tile_id: u64,
+
+    /// Offset of tile data (or leaf directory)
+    offset: u64,
+
+    /// Length of tile data (or leaf directory)
+    length: u32,
+
+    /// Number of tiles with the same data (0 for leaf directory)
+    run_length: u32,
+}
+
+/// Writer for [PMTiles] archives.
+///
+/// Tiles can be written in any order; they are gzip-compressed and held in
+/// memory until [finish], which sorts them by Hilbert tile ID, deduplicates
+/// identical tile data and writes a clustered archive.
+///
+/// # Example
+/// ```no_run
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{PmTilesWriter, Tile, TileId};
+///
+/// let mut writer = PmTilesWriter::create("roads.pmtiles", "roads")?;
+/// let tile = Tile::new(4096);
+/// // ...
+/// // add layers to the tile
+/// // ...
+/// writer.write_tile(TileId::new(0, 0, 0)?, &tile)?;
+/// writer.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [finish]: #method.finish
+/// [PMTiles]: https://github.com/protomaps/PMTiles/blob/main/spec/v3/spec.md
+pub struct PmTilesWriter<W: Write> {
+    out: W,
+    name: String,
+    bounds: Option<[f64; 4]>,
+    tiles: BTreeMap<u64, Vec<u8>>,
+    info: TilesetInfo,
+}
+
+/// Reader for [PMTiles] archives.
+///
+/// Tiles are looked up by ID, reading only the directories and tile data
+/// needed, so any `Read + Seek` source can be used.  Tile data is returned
+/// uncompressed, ready for [Tile::from_bytes].
+///
+/// Gzip-compressed archives are always supported; brotli and zstd require
+/// the corresponding crate features.
+///
+/// # Example
+/// ```no_run
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{PmTilesReader, Tile, TileId};
+///
+/// let mut reader = PmTilesReader::open("roads.pmtiles")?;
+/// if let Some(data) = reader.tile(TileId::new(0, 0, 0)?)? {
+///     let tile = Tile::from_bytes(&data)?;
+///     println!("{} layers", tile.num_layers());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [PMTiles]: https://github.com/protomaps/PMTiles/blob/main/spec/v3/spec.md
+pub struct PmTilesReader<R: Read + Seek> {
+    reader: R,
+    header: Header,
+    root: Vec<Entry>,
+    leaf: Option<(u64, Vec<Entry>)>,
+}
+
+/// Push a value to a buffer as a varint
+fn push_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+/// Read a varint from a buffer
+fn read_varint(buf: &mut &[u8]) -> Result<u64> {
+    let mut v = 0;
+    for shift in (0..64).step_by(7) {
+        let (&b, rest) = buf.split_first().ok_or(Error::InvalidArchive())?;
+        *buf = rest;
+        v |= u64::from(b & 0x7F) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(Error::InvalidArchive())
+}
+
+/// Decompress data
+fn decompress(compression: u8, data: Vec<u8>) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(data.len() * 2);
+    match compression {
+        COMPRESSION_UNKNOWN | COMPRESSION_NONE => return Ok(data),
+        COMPRESSION_GZIP => {
+            GzDecoder::new(&data[..]).read_to_end(&mut bytes)?;
+        }
+        #[cfg(feature = "brotli")]
+        COMPRESSION_BROTLI => {
+            brotli::Decompressor::new(&data[..], 4096)
+                .read_to_end(&mut bytes)?;
+        }
+        #[cfg(feature = "zstd")]
+        COMPRESSION_ZSTD => {
+            zstd::Decoder::new(&data[..])?.read_to_end(&mut bytes)?;
+        }
+        _ => return Err(Error::UnsupportedCompression(compression)),
+    }
+    Ok(bytes)
+}
+
+/// Compress data with gzip
+fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut enc = GzEncoder::new(Vec::new(), GzLevel::new(GZIP_LEVEL));
+    enc.write_all(data)?;
+    Ok(enc.finish()?)
+}
+
+/// Serialize and compress a directory
+fn serialize_directory(entries: &[Entry]) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(entries.len() * 8);
+    push_varint(&mut buf, entries.len() as u64);
+    let mut last_id = 0;
+    for entry in entries {
+        push_varint(&mut buf, entry.tile_id - last_id);
+        last_id = entry.tile_id;
+    }
+    for entry in entries {
+        push_varint(&mut buf, entry.run_length.into());
+    }
+    for entry in entries {
+        push_varint(&mut buf, entry.length.into());
+    }
+    let mut next = None;
+    for entry in entries {
+        // offset 0 means "contiguous with previous entry"
+        if next == Some(entry.offset) {
+            push_varint(&mut buf, 0);
+        } else {
+            push_varint(&mut buf, entry.offset + 1);
+        }
+        next = Some(entry.offset + u64::from(entry.length));
+    }
+    gzip(&buf)
+}
+
+/// Parse a decompressed directory
+fn parse_directory(mut buf: &[u8]) -> Result<Vec<Entry>> {
+    let buf = &mut buf;
+    let n_entries = usize::try_from(read_varint(buf)?)
+        .map_err(|_| Error::InvalidArchive())?;
+    // each entry needs at least 4 bytes
+    if n_entries > buf.len() / 4 {
+        return Err(Error::InvalidArchive());
+    }
+    let mut entries = Vec::with_capacity(n_entries);
+    let mut tile_id = 0u64;
+    for _ in 0..n_entries {
+        tile_id = tile_id
+            .checked_add(read_varint(buf)?)
+            .ok_or(Error::InvalidArchive())?;
+        entries.push(Entry {
+            tile_id,
+            offset: 0,
+            length: 0,
+            run_length: 0,
+        });
+    }
+    for entry in entries.iter_mut() {
+        entry.run_length = u32::try_from(read_varint(buf)?)
+            .map_err(|_| Error::InvalidArchive())?;
+    }
+    for entry in entries.iter_mut() {
+        entry.length = u32::try_from(read_varint(buf)?)
+            .map_err(|_| Error::InvalidArchive())?;
+    }
+    let mut next = None;
+    for entry in entries.iter_mut() {
+        entry.offset = match (read_varint(buf)?, next) {
+            (0, Some(next)) => next,
+            (0, None) => return Err(Error::InvalidArchive()),
+            (offset, _) => offset - 1,
+        };
+        next = entry.offset.checked_add(u64::from(entry.length));
+    }
+    Ok(entries)
+}
+
+/// Find the entry for a tile ID (or the leaf directory containing it)
+fn find_entry(entries: &[Entry], tile_id: u64) -> Option<Entry> {
+    let i = entries.partition_point(|e| e.tile_id <= tile_id);
+    let entry = entries.get(i.checked_sub(1)?)?;
+    if entry.run_length == 0
+        || tile_id - entry.tile_id < u64::from(entry.run_length)
+    {
+        Some(*entry)
+    } else {
+        None
+    }
+}
+
+/// Build root and leaf directories
+fn build_directories(entries: &[Entry]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let root_max = ROOT_MAX_LEN - HEADER_LEN;
+    let root = serialize_directory(entries)?;
+    if root.len() <= root_max {
+        return Ok((root, Vec::new()));
+    }
+    let mut leaf_entries = LEAF_MIN_ENTRIES.max(entries.len() / 3500);
+    loop {
+        let mut leaves = Vec::new();
+        let mut root_entries = Vec::new();
+        for chunk in entries.chunks(leaf_entries) {
+            let leaf = serialize_directory(chunk)?;
+            root_entries.push(Entry {
+                tile_id: chunk[0].tile_id,
+                offset: leaves.len() as u64,
+                length: leaf.len() as u32,
+                run_length: 0,
+            });
+            leaves.extend(leaf);
+        }
+        let root = serialize_directory(&root_entries)?;
+        if root.len() <= root_max {
+            return Ok((root, leaves));
+        }
+        leaf_entries += leaf_entries / 5;
+    }
+}
+
+/// Convert degrees to fixed-point (10<sup>-7</sup> degrees)
+fn deg_e7(deg: f64) -> i32 {
+    (deg * 10_000_000.0).round() as i32
+}
+
+impl Header {
+    /// Serialize the header
+    fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut buf = Vec::with_capacity(HEADER_LEN);
+        buf.extend(b"PMTiles");
+        buf.push(3);
+        for v in [
+            self.root_offset,
+            self.root_length,
+            self.metadata_offset,
+            self.metadata_length,
+            self.leaf_offset,
+            self.leaf_length,
+            self.data_offset,
+            self.data_length,
+            self.n_addressed_tiles,
+            self.n_tile_entries,
+            self.n_tile_contents,
+        ] {
+            buf.extend(v.to_le_bytes());
+        }
+        buf.push(u8::from(self.clustered));
+        buf.push(self.internal_compression);
+        buf.push(self.tile_compression);
+        buf.push(self.tile_type);
+        buf.push(self.min_zoom);
+        buf.push(self.max_zoom);
+        for v in self.bounds_e7 {
+            buf.extend(v.to_le_bytes());
+        }
+        buf.push(self.center_zoom);
+        for v in self.center_e7 {
+            buf.extend(v.to_le_bytes());
+        }
+        let mut bytes = [0; HEADER_LEN];
+        bytes.copy_from_slice(&buf);
+        bytes
+    }
+
+    /// Parse a serialized header
+    fn from_bytes(buf: &[u8; HEADER_LEN]) -> Result<Self> {
+        if &buf[..7] != b"PMTiles" || buf[7] != 3 {
+            return Err(Error::InvalidArchive());
+        }
+        let u64_at = |pos: usize| {
+            let mut b = [0; 8];
+            b.copy_from_slice(&buf[pos..pos + 8]);
+            u64::from_le_bytes(b)
+        };
+        let i32_at = |pos: usize| {
+            let mut b = [0; 4];
+            b.copy_from_slice(&buf[pos..pos + 4]);
+            i32::from_le_bytes(b)
+        };
+        Ok(Header {
+            root_offset: u64_at(8),
+            root_length: u64_at(16),
+            metadata_offset: u64_at(24),
+            metadata_length: u64_at(32),
+            leaf_offset: u64_at(40),
+            leaf_length: u64_at(48),
+            data_offset: u64_at(56),
+            data_length: u64_at(64),
+            n_addressed_tiles: u64_at(72),
+            n_tile_entries: u64_at(80),
+            n_tile_contents: u64_at(88),
+            clustered: buf[96] == 1,
+            internal_compression: buf[97],
+            tile_compression: buf[98],
+            tile_type: buf[99],
+            min_zoom: buf[100],
+            max_zoom: buf[101],
+            bounds_e7: [i32_at(102), i32_at(106), i32_at(110), i32_at(114)],
+            center_zoom: buf[118],
+            center_e7: [i32_at(119), i32_at(123)],
+        })
+    }
+}
+
+impl PmTilesWriter<BufWriter<File>> {
+    /// Create a new PMTiles file.
+    ///
+    /// * `path` Path to the file, which is truncated if it exists.
+    /// * `name` Name of the tileset.
+    pub fn create<P: AsRef<Path>>(path: P, name: &str) -> Result<Self> {
+        let file = File::create(path)?;
+        Ok(PmTilesWriter::new(BufWriter::new(file), name))
+    }
+}
+
+impl<W: Write> PmTilesWriter<W> {
+    /// Create a new PMTiles writer.
+    ///
+    /// * `out` Writer to output the archive.
+    /// * `name` Name of the tileset.
+    pub fn new(out: W, name: &str) -> Self {
+        PmTilesWriter {
+            out,
+            name: name.to_string(),
+            bounds: None,
+            tiles: BTreeMap::new(),
+            info: TilesetInfo::default(),
+        }
+    }
+
+    /// Set the bounds (WGS-84 degrees).
+    ///
+    /// By default, bounds are the union of all written tiles.
+    pub fn bounds(
+        mut self,
+        west: f64,
+        south: f64,
+        east: f64,
+        north: f64,
+    ) -> Self {
+        self.bounds = Some([west, south, east, north]);
+        self
+    }
+
+    /// Write one tile.
+    ///
+    /// * `tid` Tile ID, using XYZ addressing.
+    /// * `tile` Tile to write.
+    ///
+    /// Any existing tile with the same ID is replaced.
+    pub fn write_tile(&mut self, tid: TileId, tile: &Tile) -> Result<()> {
+        let data = tile.to_bytes_gzip(GZIP_LEVEL)?;
+        self.tiles.insert(tid.hilbert_key(), data);
+        self.info.add_tile(tid, tile);
+        Ok(())
+    }
+
+    /// Build the JSON metadata
+    fn metadata(&self) -> String {
+        let mut json = String::from("{\"name\":");
+        json_escape(&mut json, &self.name);
+        json.push_str(",\"format\":\"pbf\",\"vector_layers\":");
+        json.push_str(&self.info.schema().vector_layers());
+        json.push('}');
+        json
+    }
+
+    /// Write the archive.
+    ///
+    /// Returns the output writer.
+    pub fn finish(mut self) -> Result<W> {
+        let mut data = Vec::new();
+        let mut entries: Vec<Entry> = Vec::with_capacity(self.tiles.len());
+        let mut contents: HashMap<&[u8], (u64, u32)> = HashMap::new();
+        for (tile_id, tile) in &self.tiles {
+            let (offset, length) =
+                *contents.entry(&tile[..]).or_insert_with(|| {
+                    let offset = data.len() as u64;
+                    data.extend_from_slice(tile);
+                    (offset, tile.len() as u32)
+                });
+            if let Some(last) = entries.last_mut()
+                && last.tile_id + u64::from(last.run_length) == *tile_id
+                && last.offset == offset
+            {
+                last.run_length += 1;
+                continue;
+            }
+            entries.push(Entry {
+                tile_id: *tile_id,
+                offset,
+                length,
+                run_length: 1,
+            });
+        }
+        let n_tile_contents = contents.len() as u64;
+        let (root, leaves) = build_directories(&entries)?;
+        let metadata = gzip(self.metadata().as_bytes())?;
+        let min_zoom = self.info.minzoom().unwrap_or(0) as u8;
+        let max_zoom = self.info.maxzoom().unwrap_or(0) as u8;
+        let [west, south, east, north] = self
+            .bounds
+            .or_else(|| self.info.bounds())
+            .unwrap_or([-180.0, -85.051129, 180.0, 85.051129]);
+        let root_offset = HEADER_LEN as u64;
+        let metadata_offset = root_offset + root.len() as u64;
+        let leaf_offset = metadata_offset + metadata.len() as u64;
+        let data_offset = leaf_offset + leaves.len() as u64;
+        let header = Header {
+            root_offset,
+            root_length: root.len() as u64,
+            metadata_offset,
+            metadata_length: metadata.len() as u64,
+            leaf_offset,
+            leaf_length: leaves.len() as u64,
+            data_offset,
+            data_length: data.len() as u64,
+            n_addressed_tiles: self.tiles.len() as u64,
+            n_tile_entries: entries.len() as u64,
+            n_tile_contents,
+            clustered: true,
+            internal_compression: COMPRESSION_GZIP,
+            tile_compression: COMPRESSION_GZIP,
+            tile_type: TILE_TYPE_MVT,
+            min_zoom,
+            max_zoom,
+            bounds_e7: [
+                deg_e7(west),
+                deg_e7(south),
+                deg_e7(east),
+                deg_e7(north),
+            ],
+            center_zoom: min_zoom,
+            center_e7: [
+                deg_e7((west + east) / 2.0),
+                deg_e7((south + north) / 2.0),
+            ],
+        };
+        self.out.write_all(&header.to_bytes())?;
+        self.out.write_all(&root)?;
+        self.out.write_all(&metadata)?;
+        self.out.write_all(&leaves)?;
+        self.out.write_all(&data)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl PmTilesReader<BufReader<File>> {
+    /// Open a PMTiles file.
+    ///
+    /// * `path` Path to the file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        PmTilesReader::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read + Seek> PmTilesReader<R> {
+    /// Create a new PMTiles reader.
+    ///
+    /// * `reader` Source of the archive.
+    ///
+    /// The header and root directory are read immediately.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut buf = [0; HEADER_LEN];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut buf)?;
+        let header = Header::from_bytes(&buf)?;
+        let mut pmtiles = PmTilesReader {
+            reader,
+            header,
+            root: Vec::new(),
+            leaf: None,
+        };
+        let root = pmtiles.read_directory(
+            pmtiles.header.root_offset,
+            pmtiles.header.root_length,
+        )?;
+        pmtiles.root = root;
+        Ok(pmtiles)
+    }
+
+    /// Get the minimum zoom level
+    pub fn min_zoom(&self) -> u32 {
+        self.header.min_zoom.into()
+    }
+
+    /// Get the maximum zoom level
+    pub fn max_zoom(&self) -> u32 {
+        self.header.max_zoom.into()
+    }
+
+    /// Get the bounds (WGS-84 west, south, east, north)
+    pub fn bounds(&self) -> [f64; 4] {
+        self.header.bounds_e7.map(|v| f64::from(v) / 10_000_000.0)
+    }
+
+    /// Read a section of the archive
+    fn read_section(&mut self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut buf = Vec::new();
+        (&mut self.reader).take(length).read_to_end(&mut buf)?;
+        if buf.len() as u64 != length {
+            return Err(Error::InvalidArchive());
+        }
+        Ok(buf)
+    }
+
+    /// Read and parse a directory
+    fn read_directory(
+        &mut self,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<Entry>> {
+        let buf = self.read_section(offset, length)?;
+        let buf = decompress(self.header.internal_compression, buf)?;
+        parse_directory(&buf)
+    }
+
+    /// Read the JSON metadata.
+    pub fn metadata(&mut self) -> Result<String> {
+        let buf = self.read_section(
+            self.header.metadata_offset,
+            self.header.metadata_length,
+        )?;
+        let buf = decompress(self.header.internal_compression, buf)?;
+        String::from_utf8(buf).map_err(|_| Error::InvalidArchive())
+    }
+
+    /// Find the entry for a tile ID
+    fn find_tile(&mut self, tile_id: u64) -> Result<Option<Entry>> {
+        let mut entry = find_entry(&self.root, tile_id);
+        for _depth in 0..=MAX_LEAF_DEPTH {
+            let Some(leaf) = entry.filter(|e| e.run_length == 0) else {
+                return Ok(entry);
+            };
+            let offset = self
+                .header
+                .leaf_offset
+                .checked_add(leaf.offset)
+                .ok_or(Error::InvalidArchive())?;
+            let cached = matches!(&self.leaf, Some((o, _)) if *o == offset);
+            if !cached {
+                let entries =
+                    self.read_directory(offset, leaf.length.into())?;
+                self.leaf = Some((offset, entries));
+            }
+            entry = self
+                .leaf
+                .as_ref()
+                .and_then(|(_, entries)| find_entry(entries, tile_id));
+        }
+        // leaf directories nested too deeply
+        Err(Error::InvalidArchive())
+    }
+
+    /// Read one tile.
+    ///
+    /// * `tid` Tile ID, using XYZ addressing.
+    ///
+    /// Returns `None` if the tile does not exist.
+    pub fn tile(&mut self, tid: TileId) -> Result<Option<Vec<u8>>> {
+        let Some(entry) = self.find_tile(tid.hilbert_key())? else {
+            return Ok(None);
+        };
+        let offset = self
+            .header
+            .data_offset
+            .checked_add(entry.offset)
+            .ok_or(Error::InvalidArchive())?;
+        let buf = self.read_section(offset, entry.length.into())?;
+        decompress(self.header.tile_compression, buf).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomType;
+    use crate::fixture::{make_tile, tid};
+
+    fn point_tile(name: &str) -> Tile {
+        make_tile("roads", GeomType::Point, &[(16.0, 16.0)], |f| {
+            f.add_tag_string("name", name)
+        })
+    }
+
+    fn read_u64(buf: &[u8], pos: usize) -> u64 {
+        u64::from_le_bytes(buf[pos..pos + 8].try_into().unwrap())
+    }
+
+    fn parse_gzip(buf: Vec<u8>) -> Vec<Entry> {
+        parse_directory(&decompress(COMPRESSION_GZIP, buf).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn hilbert() {
+        assert_eq!(tid(0, 0, 0).hilbert_key(), 0);
+        assert_eq!(tid(0, 0, 1).hilbert_key(), 1);
+        assert_eq!(tid(0, 1, 1).hilbert_key(), 2);
+        assert_eq!(tid(1, 1, 1).hilbert_key(), 3);
+        assert_eq!(tid(1, 0, 1).hilbert_key(), 4);
+        assert_eq!(tid(0, 0, 2).hilbert_key(), 5);
+        assert_eq!(tid(0, 0, 3).hilbert_key(), 21);
+        assert_eq!(tid(7, 0, 3).hilbert_key(), 84);
+        assert_eq!(tid(0, 0, 31).hilbert_key(), 1_537_228_672_809_129_301);
+    }
+
+    #[test]
+    fn varint() {
+        let mut buf = Vec::new();
+        push_varint(&mut buf, 1);
+        push_varint(&mut buf, 300);
+        assert_eq!(buf, [0x01, 0xAC, 0x02]);
+    }
+
+    #[test]
+    fn write_archive() {
+        let a = point_tile("a");
+        let b = point_tile("b");
+        let mut writer = PmTilesWriter::new(Vec::new(), "test");
+        // written out of order, with duplicate data
+        writer.write_tile(tid(1, 0, 1), &a).unwrap();
+        writer.write_tile(tid(0, 0, 1), &a).unwrap();
+        writer.write_tile(tid(0, 1, 1), &a).unwrap();
+        writer.write_tile(tid(0, 0, 0), &b).unwrap();
+        let buf = writer.finish().unwrap();
+        assert_eq!(&buf[..8], b"PMTiles\x03");
+        let root_offset = read_u64(&buf, 8);
+        assert_eq!(root_offset, 127);
+        let data_offset = read_u64(&buf, 56);
+        let data_length = read_u64(&buf, 64);
+        assert_eq!(data_offset + data_length, buf.len() as u64);
+        // addressed tiles, tile entries, tile contents
+        assert_eq!(read_u64(&buf, 72), 4);
+        assert_eq!(read_u64(&buf, 80), 3);
+        assert_eq!(read_u64(&buf, 88), 2);
+        // clustered, compression, tile type, zoom range
+        assert_eq!(&buf[96..102], &[1, 2, 2, 1, 0, 1]);
+    }
+
+    #[test]
+    fn leaf_directories() {
+        let entries: Vec<Entry> = (0..100_000)
+            .map(|i| Entry {
+                tile_id: i * 3,
+                offset: i * 1000,
+                length: 1000 - (i % 7) as u32,
+                run_length: 1,
+            })
+            .collect();
+        let (root, leaves) = build_directories(&entries).unwrap();
+        assert!(root.len() <= ROOT_MAX_LEN - HEADER_LEN);
+        assert!(!leaves.is_empty());
+    }
+
+    #[test]
+    fn directory_round_trip() {
+        let entries = [
+            Entry {
+                tile_id: 0,
+                offset: 0,
+                length: 10,
+                run_length: 1,
+            },
+            Entry {
+                tile_id: 5,
+                offset: 10,
+                length: 20,
+                run_length: 3,
+            },
+            Entry {
+                tile_id: 9,
+                offset: 0,
+                length: 10,
+                run_length: 1,
+            },
+        ];
+        let buf = serialize_directory(&entries).unwrap();
+        assert_eq!(parse_gzip(buf), entries);
+        assert_eq!(find_entry(&entries, 0), Some(entries[0]));
+        assert_eq!(find_entry(&entries, 3), None);
+        assert_eq!(find_entry(&entries, 7), Some(entries[1]));
+        assert_eq!(find_entry(&entries, 8), None);
+        assert_eq!(find_entry(&entries, 100), None);
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = Header {
+            root_offset: 127,
+            root_length: 50,
+            n_addressed_tiles: 7,
+            clustered: true,
+            tile_compression: COMPRESSION_GZIP,
+            max_zoom: 14,
+            bounds_e7: [-1_800_000_000, -850_511_290, 1_800_000_000, 5],
+            center_e7: [-3, 4],
+            ..Default::default()
+        };
+        let buf = header.to_bytes();
+        assert_eq!(Header::from_bytes(&buf).unwrap(), header);
+        let mut bad = buf;
+        bad[7] = 2;
+        assert!(matches!(
+            Header::from_bytes(&bad),
+            Err(Error::InvalidArchive())
+        ));
+    }
+
+    #[test]
+    fn read_archive() {
+        let a = point_tile("a");
+        let b = point_tile("b");
+        let mut writer = PmTilesWriter::new(Vec::new(), "test");
+        writer.write_tile(tid(3, 1, 2), &a).unwrap();
+        writer.write_tile(tid(0, 0, 1), &a).unwrap();
+        writer.write_tile(tid(0, 1, 1), &a).unwrap();
+        writer.write_tile(tid(0, 0, 0), &b).unwrap();
+        let buf = writer.finish().unwrap();
+        let mut reader = PmTilesReader::new(std::io::Cursor::new(buf)).unwrap();
+        assert_eq!(reader.min_zoom(), 0);
+        assert_eq!(reader.max_zoom(), 2);
+        let a = a.to_bytes().unwrap();
+        let b = b.to_bytes().unwrap();
+        assert_eq!(reader.tile(tid(0, 0, 0)).unwrap(), Some(b));
+        assert_eq!(reader.tile(tid(0, 0, 1)).unwrap(), Some(a.clone()));
+        assert_eq!(reader.tile(tid(0, 1, 1)).unwrap(), Some(a.clone()));
+        assert_eq!(reader.tile(tid(3, 1, 2)).unwrap(), Some(a));
+        assert_eq!(reader.tile(tid(1, 1, 1)).unwrap(), None);
+        assert_eq!(reader.tile(tid(3, 1, 5)).unwrap(), None);
+        let tile =
+            Tile::from_bytes(&reader.tile(tid(0, 0, 0)).unwrap().unwrap())
+                .unwrap();
+        assert_eq!(tile.layer("roads").unwrap().num_features(), 1);
+        let meta = reader.metadata().unwrap();
+        assert!(meta.starts_with("{\"name\":\"test\",\"format\":\"pbf\""));
+    }
+
+    #[test]
+    fn read_leaf_directories() {
+        // one entry per tile at zoom 8, with scattered offsets
+        let base = tid(0, 0, 8).hilbert_key();
+        let data: Vec<u8> = (0..65_521).map(|i| (i % 251) as u8).collect();
+        let offset = |i: u64| (i * 2_654_435_761) % 65_521;
+        let entries: Vec<Entry> = (0..65_536)
+            .map(|i| Entry {
+                tile_id: base + i,
+                offset: offset(i),
+                length: 1,
+                run_length: 1,
+            })
+            .collect();
+        let (root, leaves) = build_directories(&entries).unwrap();
+        assert!(!leaves.is_empty());
+        let header = Header {
+            root_offset: HEADER_LEN as u64,
+            root_length: root.len() as u64,
+            leaf_offset: (HEADER_LEN + root.len()) as u64,
+            leaf_length: leaves.len() as u64,
+            data_offset: (HEADER_LEN + root.len() + leaves.len()) as u64,
+            data_length: data.len() as u64,
+            internal_compression: COMPRESSION_GZIP,
+            tile_compression: COMPRESSION_NONE,
+            tile_type: TILE_TYPE_MVT,
+            ..Default::default()
+        };
+        let mut buf = header.to_bytes().to_vec();
+        buf.extend(root);
+        buf.extend(leaves);
+        buf.extend(&data);
+        let mut reader = PmTilesReader::new(std::io::Cursor::new(buf)).unwrap();
+        for (x, y) in [(0, 0), (255, 255), (17, 200), (128, 3), (99, 99)] {
+            let i = tid(x, y, 8).hilbert_key() - base;
+            let tile = reader.tile(tid(x, y, 8)).unwrap();
+            assert_eq!(tile, Some(vec![(offset(i) % 251) as u8]));
+        }
+        assert_eq!(reader.tile(tid(0, 0, 7)).unwrap(), None);
+        assert_eq!(reader.tile(tid(0, 0, 9)).unwrap(), None);
+    }
+}
@@ -0,0 +1,132 @@
+// proj.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Reprojection with `proj4rs`.
+//!
+use crate::error::Result;
+use proj4rs::Proj;
+use proj4rs::transform::transform;
+
+/// PROJ string for Web Mercator (EPSG:3857)
+const WEB_MERCATOR: &str = "+proj=merc +a=6378137 +b=6378137 +lat_ts=0 \
+    +lon_0=0 +x_0=0 +y_0=0 +k=1 +units=m +nadgrids=@null +no_defs";
+
+/// Reprojection between two coördinate reference systems.
+///
+/// Use with [GeomEncoder::reproject] to encode source data in a CRS other
+/// than the [MapGrid]'s, such as UTM or a national grid.
+///
+/// Systems are specified with [PROJ strings], which can be found for an
+/// EPSG code at [epsg.io].  Geographic coördinates are in degrees.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{GeomEncoder, GeomType, MapGrid, Reprojection, TileId};
+///
+/// // UTM zone 15N (EPSG:32615) to Web Mercator
+/// let utm = "+proj=utm +zone=15 +datum=WGS84 +units=m +no_defs";
+/// let reprojection = Reprojection::to_web_mercator(utm)?;
+/// let tid = TileId::new(246, 368, 10)?;
+/// let transform = MapGrid::default().tile_transform(tid).scale(4096.0, 4096.0);
+/// let geom = GeomEncoder::new(GeomType::Point)
+///     .reproject(reprojection)
+///     .transform(transform)
+///     .point(479_200.0, 4_980_400.0)?
+///     .encode()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [GeomEncoder::reproject]: struct.GeomEncoder.html#method.reproject
+/// [MapGrid]: struct.MapGrid.html
+/// [PROJ strings]: https://proj.org/en/latest/usage/quickstart.html
+/// [epsg.io]: https://epsg.io/
+#[derive(Clone, Debug)]
+pub struct Reprojection {
+    /// Source CRS
+    src: Proj,
+
+    /// Destination CRS
+    dst: Proj,
+}
+
+impl Reprojection {
+    /// Create a new reprojection.
+    ///
+    /// * `src` PROJ string of source CRS.
+    /// * `dst` PROJ string of destination CRS.
+    pub fn new(src: &str, dst: &str) -> Result<Self> {
+        let src = Proj::from_proj_string(src)?;
+        let dst = Proj::from_proj_string(dst)?;
+        Ok(Reprojection { src, dst })
+    }
+
+    /// Create a new reprojection to Web Mercator (EPSG:3857).
+    ///
+    /// * `src` PROJ string of source CRS.
+    pub fn to_web_mercator(src: &str) -> Result<Self> {
+        Reprojection::new(src, WEB_MERCATOR)
+    }
+
+    /// Reproject one point.
+    ///
+    /// * `x` X (or longitude in degrees) in source CRS.
+    /// * `y` Y (or latitude in degrees) in source CRS.
+    pub fn project(&self, x: f64, y: f64) -> Result<(f64, f64)> {
+        let mut pt = if self.src.is_latlong() {
+            (x.to_radians(), y.to_radians())
+        } else {
+            (x, y)
+        };
+        transform(&self.src, &self.dst, &mut pt)?;
+        if self.dst.is_latlong() {
+            pt = (pt.0.to_degrees(), pt.1.to_degrees());
+        }
+        Ok(pt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::geo::{WebMercatorPos, Wgs84Pos};
+    use pointy::Transform;
+
+    const WGS84: &str = "+proj=longlat +datum=WGS84 +no_defs";
+    const UTM15: &str = "+proj=utm +zone=15 +datum=WGS84 +units=m +no_defs";
+
+    #[test]
+    fn web_mercator() {
+        let r = Reprojection::to_web_mercator(WGS84).unwrap();
+        let (x, y) = r.project(-93.265, 44.9778).unwrap();
+        let m = WebMercatorPos::from(Wgs84Pos::new(44.9778, -93.265));
+        let p = pointy::Pt::from(m);
+        assert!((x - p.x).abs() < 1e-3 && (y - p.y).abs() < 1e-3);
+    }
+
+    #[test]
+    fn utm() {
+        let r = Reprojection::new(UTM15, WGS84).unwrap();
+        // central meridian of zone 15 is 93 W
+        let (lon, lat) = r.project(500_000.0, 0.0).unwrap();
+        assert!((lon + 93.0).abs() < 1e-9 && lat.abs() < 1e-9);
+        assert!(Reprojection::new("+proj=bogus", WGS84).is_err());
+    }
+
+    #[test]
+    fn encoder() {
+        let r = Reprojection::new(UTM15, UTM15).unwrap();
+        let geom = GeomEncoder::new(GeomType::Point)
+            .reproject(r)
+            .transform(Transform::with_translate(-500_000.0, 0.0))
+            .point(500_010.0, 20.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.into_vec(), [9, 20, 40]);
+    }
+}
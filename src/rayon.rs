@@ -0,0 +1,172 @@
+// rayon.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Parallel encoding with `rayon`.
+//!
+use crate::error::Result;
+use crate::mapgrid::TileId;
+use crate::tile::{Layer, Tile};
+use rayon::prelude::*;
+
+/// Batch of independent tiles, encoded in parallel.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{GeomEncoder, GeomType, Tile, TileBatch, TileId};
+///
+/// let tids = (0..4).map(|x| TileId::new(x, 0, 2)).collect::<Result<Vec<_>, _>>()?;
+/// let batch = TileBatch::new(tids);
+/// let results = batch.encode(|tid| {
+///     let mut tile = Tile::new(4096);
+///     let layer = tile.create_layer("points");
+///     let geom = GeomEncoder::new(GeomType::Point)
+///         .point(f64::from(tid.x()), 0.0)?
+///         .encode()?;
+///     tile.add_layer(layer.into_feature(geom).into_layer())?;
+///     Ok(tile)
+/// });
+/// for (tid, res) in results {
+///     let bytes = res?;
+///     println!("{tid}: {} bytes", bytes.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TileBatch {
+    tids: Vec<TileId>,
+}
+
+impl TileBatch {
+    /// Create a new tile batch.
+    ///
+    /// * `tids` IDs of tiles in the batch.
+    pub fn new<I: IntoIterator<Item = TileId>>(tids: I) -> Self {
+        TileBatch {
+            tids: tids.into_iter().collect(),
+        }
+    }
+
+    /// Get the number of tiles in the batch.
+    pub fn len(&self) -> usize {
+        self.tids.len()
+    }
+
+    /// Check if the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.tids.is_empty()
+    }
+
+    /// Build and encode all tiles in parallel.
+    ///
+    /// * `build` Function to build a tile from its ID.
+    ///
+    /// Returns the encoded bytes (or error) of each tile, in batch order.
+    /// An error for one tile does not stop the others.
+    pub fn encode<F>(&self, build: F) -> Vec<(TileId, Result<Vec<u8>>)>
+    where
+        F: Fn(TileId) -> Result<Tile> + Send + Sync,
+    {
+        self.tids
+            .par_iter()
+            .map(|&tid| (tid, build(tid).and_then(|tile| tile.to_bytes())))
+            .collect()
+    }
+}
+
+impl Tile {
+    /// Build and add layers in parallel.
+    ///
+    /// * `names` Names of layers to create.
+    /// * `build` Function to add features to one new layer.
+    ///
+    /// If any layer fails to build, the first error is returned and no
+    /// layers are added.  Otherwise, layers are added in the order of
+    /// `names`, stopping at the first error (see [add_layer]).
+    ///
+    /// [add_layer]: #method.add_layer
+    pub fn add_layers_par<F>(&mut self, names: &[&str], build: F) -> Result<()>
+    where
+        F: Fn(Layer) -> Result<Layer> + Send + Sync,
+    {
+        let layers: Vec<_> =
+            names.iter().map(|name| self.create_layer(name)).collect();
+        let layers = layers
+            .into_par_iter()
+            .map(build)
+            .collect::<Result<Vec<_>>>()?;
+        for layer in layers {
+            self.add_layer(layer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::error::Error;
+
+    fn point(layer: Layer, x: f64) -> Result<Layer> {
+        let geom = GeomEncoder::new(GeomType::Point).point(x, 1.0)?.encode()?;
+        Ok(layer.into_feature(geom).into_layer())
+    }
+
+    #[test]
+    fn batch() {
+        let tids: Vec<_> =
+            (0..8).map(|x| TileId::new(x, 1, 3).unwrap()).collect();
+        let batch = TileBatch::new(tids.clone());
+        assert_eq!(batch.len(), 8);
+        let results = batch.encode(|tid| {
+            if tid.x() == 5 {
+                return Err(Error::InvalidTid());
+            }
+            let mut tile = Tile::new(4096);
+            let layer = point(tile.create_layer("x"), tid.x().into())?;
+            tile.add_layer(layer)?;
+            Ok(tile)
+        });
+        assert_eq!(results.len(), 8);
+        for ((tid, res), expected) in results.into_iter().zip(tids) {
+            assert_eq!(tid.x(), expected.x());
+            match res {
+                Ok(bytes) => {
+                    let tile = Tile::from_bytes(&bytes).unwrap();
+                    assert_eq!(tile.num_layers(), 1);
+                }
+                Err(Error::InvalidTid()) => assert_eq!(tid.x(), 5),
+                Err(e) => panic!("unexpected error {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn layers() {
+        let mut tile = Tile::new(4096);
+        tile.add_layer(tile.create_layer("a")).unwrap();
+        tile.add_layers_par(&["b", "c", "d"], |layer| point(layer, 2.0))
+            .unwrap();
+        let names: Vec<_> = tile.layers().map(|l| l.name()).collect();
+        assert_eq!(names, ["a", "b", "c", "d"]);
+        let res = tile.add_layers_par(&["e", "b"], |layer| point(layer, 2.0));
+        assert!(matches!(
+            res.map_err(Error::into_root),
+            Err(Error::DuplicateName(_))
+        ));
+        assert_eq!(tile.num_layers(), 5);
+        let res = tile.add_layers_par(&["f", "g"], |layer| {
+            if layer.name() == Some("g") {
+                Err(Error::InvalidGeometry())
+            } else {
+                point(layer, 2.0)
+            }
+        });
+        assert!(matches!(res, Err(Error::InvalidGeometry())));
+        assert_eq!(tile.num_layers(), 5);
+    }
+}
@@ -0,0 +1,95 @@
+// roundtrip.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Round-trip testing of geometry encoding.
+//!
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use proptest::prelude::*;
+
+/// Check that integer parts survive an encode / decode round trip.
+///
+/// The parts are encoded in verbatim mode.  Point parts are combined into
+/// a single multipoint.
+pub fn roundtrip_ok(geom_tp: GeomType, parts: &[Vec<(i32, i32)>]) -> bool {
+    let mut enc = GeomEncoder::<f64>::new(geom_tp).verbatim(true);
+    for part in parts {
+        for (x, y) in part {
+            if enc.add_point(f64::from(*x), f64::from(*y)).is_err() {
+                return false;
+            }
+        }
+        if enc.complete_geom().is_err() {
+            return false;
+        }
+    }
+    let Ok(geom) = enc.encode() else {
+        return false;
+    };
+    let decoded = geom.decode().unwrap();
+    match geom_tp {
+        GeomType::Point => {
+            let pts: Vec<_> = parts.iter().flatten().copied().collect();
+            decoded == [pts]
+        }
+        _ => decoded == parts,
+    }
+}
+
+/// Strategy for geometry types
+fn geom_type() -> impl Strategy<Value = GeomType> {
+    prop_oneof![
+        Just(GeomType::Point),
+        Just(GeomType::Linestring),
+        Just(GeomType::Polygon),
+    ]
+}
+
+/// Strategy for geometry parts
+fn parts() -> impl Strategy<Value = Vec<Vec<(i32, i32)>>> {
+    let pt = (-4096..8192, -4096..8192);
+    prop::collection::vec(prop::collection::vec(pt, 1..20), 1..5)
+}
+
+/// Decode arbitrary (untrusted) geometry data, which must not panic.
+fn decode_arbitrary(geom_tp: GeomType, data: Vec<u32>) {
+    let geom = GeomData::from_vec(geom_tp, data);
+    let _ = geom.decode();
+    let _ = geom.commands().count();
+    let _ = geom.stats();
+    let _ = geom.bounds();
+    let _ = geom.rings();
+    let _ = geom.validate_topology();
+    let _ = geom.centroid();
+    let _ = geom.validate(geom_tp);
+}
+
+/// Strategy for geometry data, biased toward valid commands
+fn data() -> impl Strategy<Value = Vec<u32>> {
+    let code = prop_oneof![
+        (0u32..8, 0u32..6).prop_map(|(id, count)| id | (count << 3)),
+        any::<u32>(),
+    ];
+    prop::collection::vec(code, 0..40)
+}
+
+proptest! {
+    #[test]
+    fn roundtrip(geom_tp in geom_type(), parts in parts()) {
+        prop_assert!(roundtrip_ok(geom_tp, &parts));
+    }
+
+    #[test]
+    fn decode_untrusted(geom_tp in geom_type(), data in data()) {
+        decode_arbitrary(geom_tp, data);
+    }
+}
+
+#[test]
+fn roundtrip_examples() {
+    let parts = vec![vec![(2, 2), (2, 10), (10, 10)], vec![(10, 10), (1, 1)]];
+    assert!(roundtrip_ok(GeomType::Linestring, &parts));
+    let parts = vec![vec![(3, 6), (8, 12), (8, 12), (20, 34)]];
+    assert!(roundtrip_ok(GeomType::Polygon, &parts));
+    assert!(roundtrip_ok(GeomType::Point, &[vec![(5, 7)], vec![(3, 2)]]));
+}
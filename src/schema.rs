@@ -0,0 +1,274 @@
+// schema.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Tileset schema inference.
+//!
+use crate::decoder::LayerRef;
+use crate::tile::{Layer, Tile};
+use crate::value::TagValue;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Value type of a layer field, as used in `vector_layers` metadata.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FieldType {
+    /// String values
+    String,
+
+    /// Numeric values (integer or float)
+    Number,
+
+    /// Boolean values
+    Boolean,
+
+    /// Values of more than one type
+    Mixed,
+}
+
+/// Schema of one layer
+#[derive(Clone, Debug)]
+struct LayerSchema {
+    /// Minimum zoom level
+    minzoom: u32,
+
+    /// Maximum zoom level
+    maxzoom: u32,
+
+    /// Field types, by key
+    fields: BTreeMap<String, FieldType>,
+}
+
+/// Schema of a tileset, inferred from its tiles.
+///
+/// Tracks layer names, zoom ranges, field keys and value types, for
+/// producing `vector_layers` metadata (as used by MBTiles and TileJSON).
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{GeomEncoder, GeomType, Tile, TilesetSchema};
+///
+/// let mut schema = TilesetSchema::default();
+/// let mut tile = Tile::new(4096);
+/// let layer = tile.create_layer("roads");
+/// let geom = GeomEncoder::new(GeomType::Point).point(1.0, 2.0)?.encode()?;
+/// let mut feature = layer.into_feature(geom);
+/// feature.add_tag_uint("lanes", 2);
+/// tile.add_layer(feature.into_layer())?;
+/// schema.add_tile(12, &tile);
+/// assert_eq!(
+///     schema.to_json(),
+///     r#"{"vector_layers":[{"id":"roads","fields":{"lanes":"Number"},"minzoom":12,"maxzoom":12}]}"#
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TilesetSchema {
+    layers: BTreeMap<String, LayerSchema>,
+}
+
+impl FieldType {
+    /// Get the field type of a tag value
+    fn from_value(value: &TagValue) -> Self {
+        match value {
+            TagValue::String(_) => FieldType::String,
+            TagValue::Bool(_) => FieldType::Boolean,
+            _ => FieldType::Number,
+        }
+    }
+
+    /// Get the name used in `vector_layers` metadata
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FieldType::String => "String",
+            FieldType::Number => "Number",
+            FieldType::Boolean => "Boolean",
+            FieldType::Mixed => "Mixed",
+        }
+    }
+}
+
+/// Push a string to JSON output as an escaped string literal
+pub(crate) fn json_escape(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl TilesetSchema {
+    /// Add all layers of a tile.
+    ///
+    /// * `zoom` Zoom level of the tile.
+    /// * `tile` Tile to add.
+    pub fn add_tile(&mut self, zoom: u32, tile: &Tile) {
+        for layer in tile.layers() {
+            self.add_layer_ref(zoom, layer);
+        }
+    }
+
+    /// Add one layer.
+    ///
+    /// * `zoom` Zoom level of the tile containing the layer.
+    /// * `layer` Layer to add.
+    pub fn add_layer(&mut self, zoom: u32, layer: &Layer) {
+        self.add_layer_ref(zoom, layer.layer_ref());
+    }
+
+    /// Add a layer view
+    fn add_layer_ref(&mut self, zoom: u32, layer: LayerRef) {
+        let schema = self
+            .layers
+            .entry(layer.name().to_string())
+            .or_insert_with(|| LayerSchema {
+                minzoom: zoom,
+                maxzoom: zoom,
+                fields: BTreeMap::new(),
+            });
+        schema.minzoom = schema.minzoom.min(zoom);
+        schema.maxzoom = schema.maxzoom.max(zoom);
+        for feature in layer.features() {
+            for (key, value) in feature.tags() {
+                let tp = FieldType::from_value(&value);
+                schema
+                    .fields
+                    .entry(key.to_string())
+                    .and_modify(|t| {
+                        if *t != tp {
+                            *t = FieldType::Mixed;
+                        }
+                    })
+                    .or_insert(tp);
+            }
+        }
+    }
+
+    /// Get an iterator of layer names, in sorted order.
+    pub fn layer_names(&self) -> impl Iterator<Item = &str> {
+        self.layers.keys().map(String::as_str)
+    }
+
+    /// Get the zoom range of a layer.
+    pub fn zoom_range(&self, layer: &str) -> Option<(u32, u32)> {
+        self.layers.get(layer).map(|l| (l.minzoom, l.maxzoom))
+    }
+
+    /// Get the type of a layer field.
+    pub fn field_type(&self, layer: &str, key: &str) -> Option<FieldType> {
+        self.layers.get(layer)?.fields.get(key).copied()
+    }
+
+    /// Build the `vector_layers` JSON array.
+    pub fn vector_layers(&self) -> String {
+        let mut json = String::from("[");
+        for (i, (name, layer)) in self.layers.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str("{\"id\":");
+            json_escape(&mut json, name);
+            json.push_str(",\"fields\":{");
+            for (j, (key, tp)) in layer.fields.iter().enumerate() {
+                if j > 0 {
+                    json.push(',');
+                }
+                json_escape(&mut json, key);
+                json.push(':');
+                json_escape(&mut json, tp.as_str());
+            }
+            let _ = write!(
+                json,
+                "}},\"minzoom\":{},\"maxzoom\":{}}}",
+                layer.minzoom, layer.maxzoom
+            );
+        }
+        json.push(']');
+        json
+    }
+
+    /// Build the `json` metadata object, containing `vector_layers`.
+    pub fn to_json(&self) -> String {
+        format!("{{\"vector_layers\":{}}}", self.vector_layers())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+
+    fn make_layer(tile: &Tile, name: &str, mixed: bool) -> Layer {
+        let layer = tile.create_layer(name);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 1.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.add_tag_string("name", "A \"quoted\"\nname");
+        if mixed {
+            feature.add_tag_string("ref", "I-94");
+        } else {
+            feature.add_tag_sint("ref", 94);
+        }
+        feature.add_tag_bool("oneway", true);
+        feature.into_layer()
+    }
+
+    #[test]
+    fn infer_schema() {
+        let mut schema = TilesetSchema::default();
+        let mut tile = Tile::new(4096);
+        tile.add_layer(make_layer(&tile, "roads", false)).unwrap();
+        schema.add_tile(10, &tile);
+        let layer = make_layer(&tile, "roads", true);
+        schema.add_layer(12, &layer);
+        schema.add_layer(8, &make_layer(&tile, "water\\", false));
+        assert_eq!(
+            schema.layer_names().collect::<Vec<_>>(),
+            ["roads", "water\\"]
+        );
+        assert_eq!(schema.zoom_range("roads"), Some((10, 12)));
+        assert_eq!(schema.zoom_range("water\\"), Some((8, 8)));
+        assert_eq!(schema.zoom_range("rail"), None);
+        assert_eq!(schema.field_type("roads", "name"), Some(FieldType::String));
+        assert_eq!(schema.field_type("roads", "ref"), Some(FieldType::Mixed));
+        assert_eq!(
+            schema.field_type("water\\", "ref"),
+            Some(FieldType::Number)
+        );
+        assert_eq!(
+            schema.field_type("roads", "oneway"),
+            Some(FieldType::Boolean)
+        );
+        assert_eq!(schema.field_type("roads", "lanes"), None);
+        assert_eq!(
+            schema.to_json(),
+            "{\"vector_layers\":[\
+            {\"id\":\"roads\",\"fields\":{\"name\":\"String\",\
+            \"oneway\":\"Boolean\",\"ref\":\"Mixed\"},\
+            \"minzoom\":10,\"maxzoom\":12},\
+            {\"id\":\"water\\\\\",\"fields\":{\"name\":\"String\",\
+            \"oneway\":\"Boolean\",\"ref\":\"Number\"},\
+            \"minzoom\":8,\"maxzoom\":8}]}"
+        );
+    }
+
+    #[test]
+    fn escape() {
+        let mut out = String::new();
+        json_escape(&mut out, "a\"b\\c\nd");
+        assert_eq!(out, "\"a\\\"b\\\\c\\u000ad\"");
+    }
+}
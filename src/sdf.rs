@@ -0,0 +1,261 @@
+// sdf.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Isolines from signed distance fields.
+//!
+use crate::encoder::{GeomEncoder, GeomType};
+use crate::error::{Error, Result};
+use ahash::HashMap;
+use pointy::{BBox, Bounded, Float, Pt, Transform};
+
+/// Grid edge key: column, row and direction (0 horizontal, 1 vertical)
+type EdgeKey = (usize, usize, u8);
+
+/// Contour segment crossing one grid cell
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    /// Start edge
+    e0: EdgeKey,
+    /// End edge
+    e1: EdgeKey,
+}
+
+/// Marching squares contour extraction
+struct Contours<'a> {
+    /// Sample values
+    sdf: &'a [f32],
+    /// Grid width
+    width: usize,
+    /// Iso value
+    iso: f32,
+}
+
+impl Contours<'_> {
+    /// Get sample value
+    fn value(&self, x: usize, y: usize) -> f32 {
+        self.sdf[y * self.width + x]
+    }
+
+    /// Check whether a sample is inside the contour
+    fn inside(&self, x: usize, y: usize) -> bool {
+        self.value(x, y) < self.iso
+    }
+
+    /// Get the corners of an edge
+    fn edge_corners(key: EdgeKey) -> ((usize, usize), (usize, usize)) {
+        let (x, y, dir) = key;
+        if dir == 0 {
+            ((x, y), (x + 1, y))
+        } else {
+            ((x, y), (x, y + 1))
+        }
+    }
+
+    /// Get the interpolated iso point on an edge (grid coördinates)
+    fn edge_point(&self, key: EdgeKey) -> (f64, f64) {
+        let ((x0, y0), (x1, y1)) = Self::edge_corners(key);
+        let v0 = f64::from(self.value(x0, y0));
+        let v1 = f64::from(self.value(x1, y1));
+        let den = v1 - v0;
+        let t = if den != 0.0 {
+            ((f64::from(self.iso) - v0) / den).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+        let (x0, y0) = (x0 as f64, y0 as f64);
+        let (x1, y1) = (x1 as f64, y1 as f64);
+        (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t)
+    }
+
+    /// Get the midpoint of an edge (grid coördinates)
+    fn edge_midpoint(key: EdgeKey) -> (f64, f64) {
+        let ((x0, y0), (x1, y1)) = Self::edge_corners(key);
+        ((x0 + x1) as f64 / 2.0, (y0 + y1) as f64 / 2.0)
+    }
+
+    /// Make a segment oriented with the inside on the right (Y down)
+    fn segment(&self, e0: EdgeKey, e1: EdgeKey) -> Segment {
+        let (c0, c1) = Self::edge_corners(e0);
+        let c = if self.inside(c0.0, c0.1) { c0 } else { c1 };
+        // edge midpoints are used, since interpolated points can coincide
+        let a = Self::edge_midpoint(e0);
+        let b = Self::edge_midpoint(e1);
+        let cross =
+            (b.0 - a.0) * (c.1 as f64 - a.1) - (b.1 - a.1) * (c.0 as f64 - a.0);
+        if cross >= 0.0 {
+            Segment { e0, e1 }
+        } else {
+            Segment { e0: e1, e1: e0 }
+        }
+    }
+
+    /// Get contour segments for one cell
+    fn cell_segments(&self, x: usize, y: usize, segs: &mut Vec<Segment>) {
+        let tl = self.inside(x, y);
+        let tr = self.inside(x + 1, y);
+        let br = self.inside(x + 1, y + 1);
+        let bl = self.inside(x, y + 1);
+        let top = (x, y, 0);
+        let bottom = (x, y + 1, 0);
+        let left = (x, y, 1);
+        let right = (x + 1, y, 1);
+        let case =
+            (tl as u8) << 3 | (tr as u8) << 2 | (br as u8) << 1 | bl as u8;
+        match case {
+            0 | 15 => (),
+            1 | 14 => segs.push(self.segment(left, bottom)),
+            2 | 13 => segs.push(self.segment(bottom, right)),
+            3 | 12 => segs.push(self.segment(left, right)),
+            4 | 11 => segs.push(self.segment(top, right)),
+            6 | 9 => segs.push(self.segment(top, bottom)),
+            7 | 8 => segs.push(self.segment(left, top)),
+            5 | 10 => {
+                // saddle: resolve using the cell center
+                let center = (self.value(x, y)
+                    + self.value(x + 1, y)
+                    + self.value(x + 1, y + 1)
+                    + self.value(x, y + 1))
+                    / 4.0;
+                let center_in = center < self.iso;
+                if center_in == tl {
+                    segs.push(self.segment(left, bottom));
+                    segs.push(self.segment(top, right));
+                } else {
+                    segs.push(self.segment(left, top));
+                    segs.push(self.segment(bottom, right));
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Extract contours as chains of grid points.
+    fn chains(&self, height: usize) -> Vec<Vec<(f64, f64)>> {
+        let mut segs = Vec::new();
+        for y in 0..height - 1 {
+            for x in 0..self.width - 1 {
+                self.cell_segments(x, y, &mut segs);
+            }
+        }
+        let mut starts = HashMap::default();
+        for (i, seg) in segs.iter().enumerate() {
+            starts.insert(seg.e0, i);
+        }
+        let ends: ahash::HashSet<EdgeKey> = segs.iter().map(|s| s.e1).collect();
+        let mut used = vec![false; segs.len()];
+        // open chains first, starting where no segment ends
+        let mut order: Vec<usize> = (0..segs.len())
+            .filter(|i| !ends.contains(&segs[*i].e0))
+            .collect();
+        order.extend(0..segs.len());
+        let mut chains = Vec::new();
+        for first in order {
+            if used[first] {
+                continue;
+            }
+            let mut chain = vec![self.edge_point(segs[first].e0)];
+            let mut i = first;
+            loop {
+                used[i] = true;
+                let end = segs[i].e1;
+                if end == segs[first].e0 {
+                    break;
+                }
+                chain.push(self.edge_point(end));
+                match starts.get(&end) {
+                    Some(next) if !used[*next] => i = *next,
+                    _ => break,
+                }
+            }
+            chains.push(chain);
+        }
+        chains
+    }
+}
+
+impl<F> GeomEncoder<F>
+where
+    F: Float,
+{
+    /// Add an isoline from a signed distance field (marching squares).
+    ///
+    /// * `sdf` Sample values (row-major).
+    /// * `width` Number of columns.
+    /// * `height` Number of rows.
+    /// * `iso` Contour value; samples less than this are inside.
+    /// * `transform` Transform from grid (column, row) to source coördinates.
+    /// * `bbox` Source bounds; contours entirely outside are skipped.
+    ///
+    /// Contour vertices are interpolated along cell edges.  For linestrings,
+    /// each contour is added as a part.  For polygons, each contour is added
+    /// as a ring (contours reaching the grid edge are closed directly), with
+    /// the inside on the right in grid coördinates.
+    pub fn add_sdf_isoline(
+        &mut self,
+        sdf: &[f32],
+        width: usize,
+        height: usize,
+        iso: f32,
+        transform: Transform<F>,
+        bbox: BBox<F>,
+    ) -> Result<()> {
+        if self.geom_type() == GeomType::Point
+            || sdf.len() != width * height
+            || width < 2
+            || height < 2
+        {
+            return Err(Error::InvalidGeometry());
+        }
+        let contours = Contours { sdf, width, iso };
+        for chain in contours.chains(height) {
+            let pts: Vec<Pt<F>> = chain
+                .into_iter()
+                .filter_map(|(x, y)| {
+                    Some(transform * (F::from(x)?, F::from(y)?))
+                })
+                .collect();
+            if !BBox::new(pts.iter().copied()).bounded_by(bbox) {
+                continue;
+            }
+            self.add_points(pts)?;
+            self.complete_geom()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn radial_isoline() {
+        let (w, h) = (64, 64);
+        let sdf: Vec<f32> = (0..w * h)
+            .map(|i| {
+                let dx = (i % w) as f32 - 32.0;
+                let dy = (i / w) as f32 - 32.0;
+                dx.hypot(dy) - 20.0
+            })
+            .collect();
+        let mut enc = GeomEncoder::new(GeomType::Polygon);
+        let t = Transform::with_scale(10.0, 10.0);
+        let bbox = BBox::new([(0.0, 0.0), (640.0, 640.0)]);
+        enc.add_sdf_isoline(&sdf, w, h, 0.0, t, bbox).unwrap();
+        let parts = enc.encode().unwrap().decode().unwrap();
+        assert_eq!(parts.len(), 1);
+        let ring = &parts[0];
+        assert!(ring.len() > 100);
+        for (x, y) in ring {
+            let r = f64::from(x - 320).hypot(f64::from(y - 320));
+            assert!((r - 200.0).abs() < 2.0, "r: {r}");
+        }
+        assert!(crate::simplify::signed_area(ring) > 0.0);
+        // isoline outside of bbox is skipped
+        let mut enc = GeomEncoder::new(GeomType::Linestring);
+        let bbox = BBox::new([(1000.0, 1000.0), (2000.0, 2000.0)]);
+        enc.add_sdf_isoline(&sdf, w, h, 0.0, t, bbox).unwrap();
+        assert!(enc.encode().unwrap().is_empty());
+    }
+}
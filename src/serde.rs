@@ -0,0 +1,582 @@
+// serde.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! `serde` tag serialization.
+//!
+use crate::error::{Error, Result};
+use crate::tile::Feature;
+use crate::value::TagValue;
+use serde::ser::{
+    Impossible, Serialize, SerializeMap, SerializeStruct, Serializer,
+};
+use std::fmt::Display;
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Serialize(msg.to_string())
+    }
+}
+
+/// Error for an unsupported value
+fn unsupported<T>(what: &str) -> Result<T> {
+    Err(Error::Serialize(format!("unsupported {what}")))
+}
+
+/// Serializer for a map or struct of tags
+struct TagsSerializer<'a> {
+    /// Feature to add tags to
+    feature: &'a mut Feature,
+
+    /// Key of the next map value
+    key: Option<String>,
+}
+
+/// Serializer for one tag value
+///
+/// `None` means the tag should be skipped.
+struct ValueSerializer;
+
+impl TagsSerializer<'_> {
+    /// Add one tag, if the value is not empty
+    fn add_tag<T: Serialize + ?Sized>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<()> {
+        match value.serialize(ValueSerializer) {
+            Ok(Some(value)) => self.feature.add_tag(key, value),
+            Ok(None) => (),
+            Err(Error::Serialize(msg)) => {
+                return Err(Error::Serialize(format!("{key}: {msg}")));
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Serializer for TagsSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<()> {
+        unsupported("tags (bool)")
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<()> {
+        unsupported("tags (number)")
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<()> {
+        unsupported("tags (number)")
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        unsupported("tags (number)")
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<()> {
+        unsupported("tags (string)")
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<()> {
+        unsupported("tags (string)")
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        unsupported("tags (bytes)")
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        unsupported("tags (enum)")
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        unsupported("tags (enum)")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        unsupported("tags (sequence)")
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        unsupported("tags (tuple)")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        unsupported("tags (tuple)")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        unsupported("tags (enum)")
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        unsupported("tags (enum)")
+    }
+}
+
+impl SerializeMap for TagsSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(ValueSerializer)? {
+            Some(TagValue::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => unsupported("key (not a string)"),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or(Error::Serialize("value without key".to_string()))?;
+        self.add_tag(&key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SerializeStruct for TagsSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.add_tag(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = Option<TagValue>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Option<TagValue>, Error>;
+    type SerializeTuple = Impossible<Option<TagValue>, Error>;
+    type SerializeTupleStruct = Impossible<Option<TagValue>, Error>;
+    type SerializeTupleVariant = Impossible<Option<TagValue>, Error>;
+    type SerializeMap = Impossible<Option<TagValue>, Error>;
+    type SerializeStruct = Impossible<Option<TagValue>, Error>;
+    type SerializeStructVariant = Impossible<Option<TagValue>, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok(Some(TagValue::Bool(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        Ok(Some(TagValue::I64(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        Ok(Some(TagValue::U64(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        Ok(Some(TagValue::F32(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        Ok(Some(TagValue::F64(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        Ok(Some(TagValue::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok(Some(TagValue::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
+        unsupported("value (bytes)")
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok> {
+        unsupported("value (nested enum)")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        unsupported("value (nested sequence)")
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        unsupported("value (nested tuple)")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        unsupported("value (nested tuple)")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        unsupported("value (nested enum)")
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        unsupported("value (nested object)")
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        unsupported("value (nested object)")
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        unsupported("value (nested enum)")
+    }
+}
+
+impl Feature {
+    /// Add tags from the fields of a serializable struct or map.
+    ///
+    /// * `tags` Struct or map of tags, such as a `serde_json::Value` object.
+    ///
+    /// Numbers are added as int, uint, float or double tags; strings, chars
+    /// and unit enum variants as string tags.  Fields which are `None` or
+    /// unit are skipped.  Returns `Error::Serialize` for nested values
+    /// (objects, sequences, etc.), or if `tags` is not a struct or map.
+    /// Tags added before an error are kept.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::Error;
+    /// # fn main() -> Result<(), Error> {
+    /// use mvt::{GeomEncoder, GeomType, Tile};
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Road {
+    ///     name: String,
+    ///     lanes: u32,
+    ///     oneway: bool,
+    /// }
+    ///
+    /// let tile = Tile::new(4096);
+    /// let layer = tile.create_layer("roads");
+    /// let geom = GeomEncoder::new(GeomType::Point).point(1.0, 2.0)?.encode()?;
+    /// let mut feature = layer.into_feature(geom);
+    /// let road = Road {
+    ///     name: "Main St".to_string(),
+    ///     lanes: 2,
+    ///     oneway: false,
+    /// };
+    /// feature.set_tags_from(&road)?;
+    /// let layer = feature.into_layer();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_tags_from<T: Serialize + ?Sized>(
+        &mut self,
+        tags: &T,
+    ) -> Result<()> {
+        tags.serialize(TagsSerializer {
+            feature: self,
+            key: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Tile;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    enum Surface {
+        Paved,
+    }
+
+    #[derive(Serialize)]
+    struct Road {
+        name: &'static str,
+        lanes: u8,
+        offset: i32,
+        speed: f32,
+        length: f64,
+        oneway: bool,
+        surface: Surface,
+        bridge: Option<bool>,
+    }
+
+    #[derive(Serialize)]
+    struct Nested {
+        name: &'static str,
+        road: Road,
+    }
+
+    fn tags<T: Serialize>(value: &T) -> Result<Vec<(String, TagValue)>> {
+        let mut tile = Tile::new(4096);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 1.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("x").into_feature(geom);
+        feature.set_tags_from(value)?;
+        tile.add_layer(feature.into_layer()).unwrap();
+        let layer = tile.layer("x").unwrap();
+        let feature = layer.features().next().unwrap();
+        Ok(feature.tags().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    fn road() -> Road {
+        Road {
+            name: "Main",
+            lanes: 2,
+            offset: -3,
+            speed: 4.5,
+            length: 6.25,
+            oneway: true,
+            surface: Surface::Paved,
+            bridge: None,
+        }
+    }
+
+    #[test]
+    fn struct_tags() {
+        assert_eq!(
+            tags(&road()).unwrap(),
+            [
+                ("name".to_string(), TagValue::String("Main".to_string())),
+                ("lanes".to_string(), TagValue::U64(2)),
+                ("offset".to_string(), TagValue::I64(-3)),
+                ("speed".to_string(), TagValue::F32(4.5)),
+                ("length".to_string(), TagValue::F64(6.25)),
+                ("oneway".to_string(), TagValue::Bool(true)),
+                ("surface".to_string(), TagValue::String("Paved".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_tags() {
+        let json = serde_json::json!({
+            "name": "Main",
+            "lanes": 2,
+            "offset": -3,
+            "length": 6.25,
+            "bridge": null,
+        });
+        assert_eq!(
+            tags(&json).unwrap(),
+            [
+                ("lanes".to_string(), TagValue::U64(2)),
+                ("length".to_string(), TagValue::F64(6.25)),
+                ("name".to_string(), TagValue::String("Main".to_string())),
+                ("offset".to_string(), TagValue::I64(-3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_tags() {
+        let nested = Nested {
+            name: "a",
+            road: road(),
+        };
+        assert!(matches!(tags(&nested), Err(Error::Serialize(_))));
+        let json = serde_json::json!({ "refs": [1, 2] });
+        assert!(matches!(tags(&json), Err(Error::Serialize(_))));
+        assert!(matches!(tags(&5), Err(Error::Serialize(_))));
+    }
+}
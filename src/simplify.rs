@@ -0,0 +1,306 @@
+// simplify.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Simplification of tile geometry.
+//!
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Calculate the length of a path (tile units).
+pub(crate) fn path_length(pts: &[(i32, i32)]) -> f64 {
+    pts.windows(2)
+        .map(|w| {
+            let dx = f64::from(w[1].0) - f64::from(w[0].0);
+            let dy = f64::from(w[1].1) - f64::from(w[0].1);
+            dx.hypot(dy)
+        })
+        .sum()
+}
+
+/// Calculate the signed area of a ring (tile units squared).
+///
+/// With tile coördinates (Y increasing downward), exterior rings have
+/// positive area.
+pub(crate) fn signed_area(pts: &[(i32, i32)]) -> f64 {
+    let n = pts.len();
+    let mut area = 0.0;
+    for i in 0..n {
+        let (x0, y0) = pts[i];
+        let (x1, y1) = pts[(i + 1) % n];
+        area += f64::from(x0) * f64::from(y1) - f64::from(x1) * f64::from(y0);
+    }
+    area / 2.0
+}
+
+/// Calculate the area of the triangle formed by three points.
+fn triangle_area(a: (i32, i32), b: (i32, i32), c: (i32, i32)) -> f64 {
+    let (ax, ay) = (f64::from(a.0), f64::from(a.1));
+    let (bx, by) = (f64::from(b.0), f64::from(b.1));
+    let (cx, cy) = (f64::from(c.0), f64::from(c.1));
+    ((bx - ax) * (cy - ay) - (cx - ax) * (by - ay)).abs() / 2.0
+}
+
+/// Simplify a path to a target number of vertices (Visvalingam–Whyatt).
+///
+/// * `pts` Path vertices.
+/// * `closed` Whether the path is a closed ring.
+/// * `target` Number of vertices to keep.
+///
+/// The vertex forming the smallest triangle with its neighbors is removed
+/// repeatedly until the target is reached.  Returns indices of the vertices
+/// to keep.  Endpoints of open paths are always kept.
+pub(crate) fn visvalingam_count(
+    pts: &[(i32, i32)],
+    closed: bool,
+    target: usize,
+) -> Vec<usize> {
+    visvalingam(pts, closed, target, f64::INFINITY)
+}
+
+/// Simplify a path within an area threshold (Visvalingam–Whyatt).
+///
+/// * `pts` Path vertices.
+/// * `closed` Whether the path is a closed ring.
+/// * `min_area` Minimum triangle area of kept vertices.
+///
+/// Returns indices of the vertices to keep.  Endpoints of open paths are
+/// always kept, and closed rings keep at least 3 vertices.
+pub(crate) fn visvalingam_area(
+    pts: &[(i32, i32)],
+    closed: bool,
+    min_area: f64,
+) -> Vec<usize> {
+    visvalingam(pts, closed, 0, min_area)
+}
+
+/// Vertex triangle area, for Visvalingam–Whyatt simplification
+struct Triangle {
+    /// Triangle area with neighbors
+    area: f64,
+    /// Vertex index
+    i: usize,
+}
+
+impl PartialEq for Triangle {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Triangle {}
+
+impl PartialOrd for Triangle {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Triangle {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed for a min-heap; ties favor the lowest index
+        other
+            .area
+            .total_cmp(&self.area)
+            .then_with(|| other.i.cmp(&self.i))
+    }
+}
+
+/// Remove vertices forming the smallest triangles (Visvalingam–Whyatt).
+///
+/// Vertices are removed until the target count is reached, or no triangle
+/// is smaller than `min_area`.  Returns indices of the vertices to keep.
+///
+/// Remaining vertices are linked to their neighbors, and triangles are
+/// kept in a min-heap.  When a vertex is removed, the triangles of its
+/// neighbors are pushed again; outdated entries are skipped when popped.
+fn visvalingam(
+    pts: &[(i32, i32)],
+    closed: bool,
+    target: usize,
+    min_area: f64,
+) -> Vec<usize> {
+    let n = pts.len();
+    let min = if closed { 3 } else { 2 };
+    let target = target.max(min);
+    if n <= target {
+        return (0..n).collect();
+    }
+    let mut prev: Vec<usize> = (0..n).map(|i| (i + n - 1) % n).collect();
+    let mut next: Vec<usize> = (0..n).map(|i| (i + 1) % n).collect();
+    let mut areas = vec![f64::INFINITY; n];
+    let mut removed = vec![false; n];
+    let removable = |i: usize| closed || (i > 0 && i < n - 1);
+    let mut heap = BinaryHeap::new();
+    for i in (0..n).filter(|i| removable(*i)) {
+        let area = triangle_area(pts[prev[i]], pts[i], pts[next[i]]);
+        areas[i] = area;
+        heap.push(Triangle { area, i });
+    }
+    let mut count = n;
+    while count > target {
+        let Some(Triangle { area, i }) = heap.pop() else {
+            break;
+        };
+        if removed[i] || area != areas[i] {
+            continue;
+        }
+        if area >= min_area {
+            break;
+        }
+        removed[i] = true;
+        count -= 1;
+        let (p, q) = (prev[i], next[i]);
+        next[p] = q;
+        prev[q] = p;
+        for j in [p, q].into_iter().filter(|j| removable(*j)) {
+            let area = triangle_area(pts[prev[j]], pts[j], pts[next[j]]);
+            areas[j] = area;
+            heap.push(Triangle { area, i: j });
+        }
+    }
+    (0..n).filter(|i| !removed[*i]).collect()
+}
+
+/// Calculate the distance from a point to a line segment.
+pub(crate) fn segment_distance(
+    p: (i32, i32),
+    a: (i32, i32),
+    b: (i32, i32),
+) -> f64 {
+    let (px, py) = (f64::from(p.0), f64::from(p.1));
+    let (ax, ay) = (f64::from(a.0), f64::from(a.1));
+    let (bx, by) = (f64::from(b.0), f64::from(b.1));
+    let (dx, dy) = (bx - ax, by - ay);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 {
+        (((px - ax) * dx + (py - ay) * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (px - (ax + t * dx)).hypot(py - (ay + t * dy))
+}
+
+/// Find the vertex farthest from a segment, between two indices.
+fn farthest(
+    pts: &[(i32, i32)],
+    i0: usize,
+    i1: usize,
+    a: (i32, i32),
+    b: (i32, i32),
+) -> Option<(usize, f64)> {
+    (i0 + 1..i1)
+        .map(|i| (i, segment_distance(pts[i], a, b)))
+        .max_by(|x, y| x.1.total_cmp(&y.1))
+}
+
+/// Mark vertices to keep between two indices (Douglas–Peucker).
+fn douglas_peucker_keep(
+    pts: &[(i32, i32)],
+    i0: usize,
+    i1: usize,
+    tolerance: f64,
+    keep: &mut [bool],
+) {
+    let n = pts.len();
+    // explicit stack of spans, to avoid deep recursion on long paths
+    let mut spans = vec![(i0, i1)];
+    while let Some((i0, i1)) = spans.pop() {
+        let (a, b) = (pts[i0], pts[i1 % n]);
+        if let Some((i, dist)) = farthest(pts, i0, i1, a, b)
+            && dist > tolerance
+        {
+            keep[i] = true;
+            spans.push((i, i1));
+            spans.push((i0, i));
+        }
+    }
+}
+
+/// Simplify a path within a distance tolerance (Douglas–Peucker).
+///
+/// * `pts` Path vertices.
+/// * `closed` Whether the path is a closed ring.
+/// * `tolerance` Maximum distance of removed vertices from the result.
+///
+/// Returns indices of the vertices to keep.  Endpoints of open paths are
+/// always kept, and closed rings keep at least 3 vertices.
+pub(crate) fn douglas_peucker(
+    pts: &[(i32, i32)],
+    closed: bool,
+    tolerance: f64,
+) -> Vec<usize> {
+    let n = pts.len();
+    let min = if closed { 3 } else { 2 };
+    if n <= min {
+        return (0..n).collect();
+    }
+    let mut keep = vec![false; n];
+    keep[0] = true;
+    if closed {
+        // split ring at the vertex farthest from the first
+        let first = pts[0];
+        let (k, _) = farthest(pts, 0, n, first, first).unwrap_or((1, 0.0));
+        keep[k] = true;
+        douglas_peucker_keep(pts, 0, k, tolerance, &mut keep);
+        douglas_peucker_keep(pts, k, n, tolerance, &mut keep);
+        if keep.iter().filter(|k| **k).count() < min {
+            let (a, b) = (pts[0], pts[k]);
+            let far = (1..n)
+                .filter(|i| *i != k)
+                .map(|i| (i, segment_distance(pts[i], a, b)))
+                .max_by(|x, y| x.1.total_cmp(&y.1));
+            if let Some((i, _)) = far {
+                keep[i] = true;
+            }
+        }
+    } else {
+        keep[n - 1] = true;
+        douglas_peucker_keep(pts, 0, n - 1, tolerance, &mut keep);
+    }
+    (0..n).filter(|i| keep[*i]).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn area() {
+        let ring = [(0, 0), (10, 0), (10, 10), (0, 10)];
+        assert_eq!(signed_area(&ring), 100.0);
+        let ring = [(0, 0), (0, 10), (10, 10), (10, 0)];
+        assert_eq!(signed_area(&ring), -100.0);
+    }
+
+    #[test]
+    fn visvalingam() {
+        let line = [(0, 0), (5, 1), (10, 0), (15, 8), (20, 0)];
+        assert_eq!(visvalingam_count(&line, false, 3), vec![0, 3, 4]);
+        let ring = [(0, 0), (5, 0), (10, 0), (10, 10), (0, 10)];
+        let v = visvalingam_count(&ring, true, 1);
+        assert_eq!(v.len(), 3);
+    }
+
+    #[test]
+    fn visvalingam_threshold() {
+        let line = [(0, 0), (5, 1), (10, 0), (15, 8), (20, 0)];
+        assert_eq!(visvalingam_area(&line, false, 10.0), vec![0, 2, 3, 4]);
+        assert_eq!(visvalingam_area(&line, false, 50.0), vec![0, 3, 4]);
+        assert_eq!(visvalingam_area(&line, false, 0.0), vec![0, 1, 2, 3, 4]);
+        let ring = [(0, 0), (5, 0), (10, 0), (10, 10), (0, 10)];
+        assert_eq!(visvalingam_area(&ring, true, 1000.0).len(), 3);
+    }
+
+    #[test]
+    fn douglas_peucker_path() {
+        let line = [(0, 0), (5, 1), (10, 0), (15, 8), (20, 0)];
+        assert_eq!(douglas_peucker(&line, false, 2.0), vec![0, 2, 3, 4]);
+        assert_eq!(douglas_peucker(&line, false, 5.0), vec![0, 3, 4]);
+        assert_eq!(douglas_peucker(&line, false, 0.5), vec![0, 1, 2, 3, 4]);
+        assert_eq!(douglas_peucker(&line, false, 10.0), vec![0, 4]);
+        let ring = [(0, 0), (5, 1), (10, 0), (10, 10), (0, 10)];
+        assert_eq!(douglas_peucker(&ring, true, 2.0), vec![0, 2, 3, 4]);
+        assert_eq!(douglas_peucker(&ring, true, 100.0).len(), 3);
+    }
+}
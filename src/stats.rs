@@ -0,0 +1,165 @@
+// stats.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Tile statistics.
+//!
+use crate::decoder::LayerRef;
+use crate::tile::Tile;
+
+/// Statistics of one layer in a tile.
+///
+/// See [Tile::stats].
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LayerStats {
+    /// Layer name
+    pub name: String,
+
+    /// Number of features
+    pub num_features: usize,
+
+    /// Number of vertices (not including closing vertices of rings)
+    pub num_vertices: usize,
+
+    /// Number of keys in the key table
+    pub num_keys: usize,
+
+    /// Number of values in the value table
+    pub num_values: usize,
+
+    /// Encoded size of geometry fields (bytes)
+    pub geometry_size: usize,
+
+    /// Encoded size of the layer (bytes)
+    pub encoded_size: usize,
+}
+
+/// Statistics of a tile, with totals across all layers.
+///
+/// See [Tile::stats].
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TileStats {
+    /// Statistics of each layer
+    pub layers: Vec<LayerStats>,
+
+    /// Total number of features
+    pub num_features: usize,
+
+    /// Total number of vertices
+    pub num_vertices: usize,
+
+    /// Total number of keys in key tables
+    pub num_keys: usize,
+
+    /// Total number of values in value tables
+    pub num_values: usize,
+
+    /// Total encoded size of geometry fields (bytes)
+    pub geometry_size: usize,
+
+    /// Encoded size of the tile (bytes)
+    pub encoded_size: usize,
+}
+
+impl From<LayerRef<'_>> for LayerStats {
+    fn from(layer: LayerRef<'_>) -> Self {
+        let mut stats = LayerStats {
+            name: layer.name().to_string(),
+            num_features: layer.num_features(),
+            num_keys: layer.keys_len(),
+            num_values: layer.values_len(),
+            encoded_size: layer.encoded_size(),
+            ..Default::default()
+        };
+        for feature in layer.features() {
+            if let Some(geom) = feature.geom_data() {
+                let gs = geom.stats();
+                stats.num_vertices += gs.num_vertices;
+                stats.geometry_size += gs.encoded_size;
+            }
+        }
+        stats
+    }
+}
+
+impl TileStats {
+    /// Get statistics of the layer with the largest encoded size.
+    pub fn largest_layer(&self) -> Option<&LayerStats> {
+        self.layers.iter().max_by_key(|l| l.encoded_size)
+    }
+}
+
+impl Tile {
+    /// Get statistics of the tile.
+    ///
+    /// Counts of features, vertices and key/value table entries, along with
+    /// encoded sizes, are calculated for each layer, plus totals.  This can
+    /// be logged to find which layers contribute most to tile size, without
+    /// serializing the tile.
+    pub fn stats(&self) -> TileStats {
+        let layers: Vec<LayerStats> =
+            self.layers().map(LayerStats::from).collect();
+        TileStats {
+            num_features: layers.iter().map(|l| l.num_features).sum(),
+            num_vertices: layers.iter().map(|l| l.num_vertices).sum(),
+            num_keys: layers.iter().map(|l| l.num_keys).sum(),
+            num_values: layers.iter().map(|l| l.num_values).sum(),
+            geometry_size: layers.iter().map(|l| l.geometry_size).sum(),
+            encoded_size: self.compute_size(),
+            layers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+
+    #[test]
+    fn stats() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("points");
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 2.0)
+            .unwrap()
+            .point(3.0, 4.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.add_tag_string("name", "a");
+        feature.add_tag_uint("rank", 1);
+        tile.add_layer(feature.into_layer()).unwrap();
+        let layer = tile.create_layer("lines");
+        let geom = GeomEncoder::new(GeomType::Linestring)
+            .points([(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)])
+            .unwrap()
+            .encode()
+            .unwrap();
+        let layer = layer.into_feature(geom).into_layer();
+        tile.add_layer(layer).unwrap();
+        let stats = tile.stats();
+        assert_eq!(stats.layers.len(), 2);
+        let points = &stats.layers[0];
+        assert_eq!(points.name, "points");
+        assert_eq!(points.num_features, 1);
+        assert_eq!(points.num_vertices, 2);
+        assert_eq!(points.num_keys, 2);
+        assert_eq!(points.num_values, 2);
+        let lines = &stats.layers[1];
+        assert_eq!(lines.num_vertices, 3);
+        assert_eq!(lines.num_keys, 0);
+        assert_eq!(stats.num_features, 2);
+        assert_eq!(stats.num_vertices, 5);
+        assert_eq!(stats.num_keys, 2);
+        assert_eq!(stats.encoded_size, tile.to_bytes().unwrap().len());
+        assert_eq!(
+            stats.encoded_size,
+            points.encoded_size + lines.encoded_size
+        );
+        assert_eq!(stats.largest_layer().unwrap().name, "points");
+    }
+}
@@ -4,16 +4,37 @@
 //
 //! Tile, Layer and Feature structs.
 //!
-use crate::encoder::{GeomData, GeomType};
+use crate::decoder::LayerRef;
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
 use crate::error::{Error, Result};
+use crate::hilbert::hilbert_index;
+#[cfg(feature = "v3")]
+use crate::v3::{AttrMark, AttrTables, AttrValue, Scaling};
+use crate::value::TagValue;
 use crate::vector_tile::Tile as VecTile;
 use crate::vector_tile::tile::{
     Feature as VtFeature, GeomType as VtGeomType, Layer as VtLayer, Value,
 };
-use ahash::HashMap;
+use ahash::{HashMap, HashSet};
+use pointy::Float;
 use protobuf::{CodedOutputStream, EnumOrUnknown, Message};
 use std::io::Write;
 
+/// Hilbert curve order for sorting features
+const HILBERT_ORDER: u32 = 16;
+
+/// Hashable key of a tag value, for deduplicating and sorting value tables
+#[derive(Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum ValueKey {
+    String(String),
+    F64(u64),
+    F32(u32),
+    I64(i64),
+    U64(u64),
+    Sint(i64),
+    Bool(bool),
+}
+
 /// A tile represents a rectangular region of a map.
 ///
 /// Each tile can contain any number of [layers].  When all layers have been
@@ -45,6 +66,7 @@ use std::io::Write;
 pub struct Tile {
     vec_tile: VecTile,
     extent: u32,
+    sort_tables: bool,
 }
 
 /// A layer is a set of related features in a tile.
@@ -61,8 +83,36 @@ pub struct Tile {
 /// ```
 pub struct Layer {
     layer: VtLayer,
-    value_pos_map: HashMap<String, usize>,
     key_pos_map: HashMap<String, usize>,
+    value_pos_map: HashMap<ValueKey, usize>,
+    clip_buffer: i32,
+    skip_outside: bool,
+    id_mode: IdMode,
+    next_id: u64,
+    max_features: Option<usize>,
+    max_bytes: Option<usize>,
+    sort_features: bool,
+    #[cfg(feature = "v3")]
+    attr_tables: AttrTables,
+}
+
+/// Feature ID handling mode for a layer.
+///
+/// Some renderers (e.g. Mapbox GL feature-state) silently misbehave when
+/// feature IDs within a layer are not unique.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum IdMode {
+    /// IDs are not checked (a warning is logged for duplicates)
+    #[default]
+    Unchecked,
+
+    /// IDs must be unique within the layer, checked when the layer is
+    /// added to a tile
+    Unique,
+
+    /// Features without an ID are assigned sequential IDs, starting after
+    /// the highest ID already in the layer
+    Auto,
 }
 
 /// A Feature contains map geometry with related metadata.
@@ -100,6 +150,8 @@ pub struct Feature {
     layer: Layer,
     num_keys: usize,
     num_values: usize,
+    #[cfg(feature = "v3")]
+    attr_mark: AttrMark,
 }
 
 impl Tile {
@@ -108,7 +160,23 @@ impl Tile {
     /// * `extent` Height / width of tile bounds.
     pub fn new(extent: u32) -> Self {
         let vec_tile = VecTile::new();
-        Tile { vec_tile, extent }
+        Tile {
+            vec_tile,
+            extent,
+            sort_tables: false,
+        }
+    }
+
+    /// Sort key and value tables of layers added to the tile.
+    ///
+    /// * `sort` If true, tables of each layer are sorted (with tag indices
+    ///   rewritten) when it is added.
+    ///
+    /// Sorted tables compress better, especially for attribute-heavy tiles.
+    /// Keys are sorted by name, and values by type and then value.
+    pub fn sort_tables(mut self, sort: bool) -> Self {
+        self.sort_tables = sort;
+        self
     }
 
     /// Get extent, or height / width of tile bounds.
@@ -116,11 +184,45 @@ impl Tile {
         self.extent
     }
 
+    /// Create a tile from decoded protobuf data.
+    ///
+    /// All layers must have the same extent (4096 if not specified).
+    pub(crate) fn from_vec_tile(vec_tile: VecTile) -> Result<Self> {
+        let extent = vec_tile.layers.first().map_or(4096, |l| l.extent());
+        if vec_tile.layers.iter().any(|l| l.extent() != extent) {
+            return Err(Error::WrongExtent());
+        }
+        Ok(Tile {
+            vec_tile,
+            extent,
+            sort_tables: false,
+        })
+    }
+
     /// Get the number of layers.
     pub fn num_layers(&self) -> usize {
         self.vec_tile.layers.len()
     }
 
+    /// Get an iterator of layers.
+    pub fn layers(&self) -> impl Iterator<Item = LayerRef<'_>> {
+        self.vec_tile.layers.iter().map(LayerRef::new)
+    }
+
+    /// Get a layer by name.
+    pub fn layer(&self, name: &str) -> Option<LayerRef<'_>> {
+        self.vec_tile
+            .layers
+            .iter()
+            .find(|l| l.name() == name)
+            .map(LayerRef::new)
+    }
+
+    /// Convert the tile into layers, which can be modified.
+    pub fn into_layers(self) -> Vec<Layer> {
+        self.vec_tile.layers.into_iter().map(Layer::from).collect()
+    }
+
     /// Create a new layer.
     ///
     /// * `name` Layer name.
@@ -133,33 +235,110 @@ impl Tile {
     /// * `layer` The layer.
     ///
     /// Returns an error if:
-    /// * a layer with the same name already exists
+    /// * the layer name is empty (`Error::EmptyName`)
+    /// * a layer with the same name already exists (`Error::DuplicateName`)
     /// * the layer extent does not match the tile extent
+    /// * the layer has duplicate feature IDs, with [IdMode::Unique]
+    /// * the layer exceeds its [limits](struct.Layer.html#method.with_limits)
+    ///
+    /// Errors include the layer name as [context](Error::context).
     pub fn add_layer(&mut self, layer: Layer) -> Result<()> {
+        let name = layer.layer.name().to_string();
+        self.add_layer_checked(layer)
+            .map_err(|e| e.with_layer(&name))
+    }
+
+    /// Check and add a layer.
+    fn add_layer_checked(&mut self, mut layer: Layer) -> Result<()> {
+        let name = layer.layer.name();
+        if name.is_empty() {
+            return Err(Error::EmptyName());
+        }
+        if self.vec_tile.layers.iter().any(|l| l.name() == name) {
+            log::debug!("duplicate layer name: {name}");
+            return Err(Error::DuplicateName(name.to_string()));
+        }
         if layer.layer.extent != Some(self.extent) {
             return Err(Error::WrongExtent());
         }
-        if self
-            .vec_tile
-            .layers
-            .iter()
-            .any(|n| n.name == layer.layer.name)
-        {
-            Err(Error::DuplicateName())
-        } else {
-            self.vec_tile.layers.push(layer.layer);
-            Ok(())
+        layer.check_ids()?;
+        layer.check_limits()?;
+        if layer.sort_features {
+            layer.hilbert_sort();
+        }
+        // attribute keys of version 3 layers are not re-indexed
+        if self.sort_tables && layer.layer.version() == 2 {
+            layer.sort_tables();
+        }
+        self.vec_tile.layers.push(layer.layer);
+        Ok(())
+    }
+
+    /// Retain only layers matching a predicate.
+    ///
+    /// * `f` Predicate called with each layer name; layers are removed if it
+    ///   returns `false`.
+    pub fn retain_layers<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str) -> bool,
+    {
+        self.vec_tile.layers.retain(|l| f(l.name()));
+    }
+
+    /// Remove a layer by name.
+    ///
+    /// * `name` Layer name.
+    ///
+    /// Returns the removed layer, or `None` if not found.
+    pub fn remove_layer(&mut self, name: &str) -> Option<Layer> {
+        let i = self.vec_tile.layers.iter().position(|l| l.name() == name)?;
+        Some(Layer::from(self.vec_tile.layers.remove(i)))
+    }
+
+    /// Merge with another tile.
+    ///
+    /// * `other` Tile to merge; its layers are added after existing layers.
+    ///
+    /// Layers with the same name are combined into one, appending features
+    /// and rebuilding the key/value tables.  Returns an error if the tiles
+    /// have different extents.
+    pub fn merge(mut self, other: Tile) -> Result<Tile> {
+        if other.extent != self.extent && other.num_layers() > 0 {
+            return Err(Error::WrongExtent());
+        }
+        for layer in other.vec_tile.layers {
+            match self
+                .vec_tile
+                .layers
+                .iter_mut()
+                .find(|l| l.name == layer.name)
+            {
+                Some(existing) => {
+                    let merged = Layer::from(std::mem::take(existing))
+                        .merge(Layer::from(layer))?;
+                    *existing = merged.layer;
+                }
+                None => self.vec_tile.layers.push(layer),
+            }
         }
+        Ok(self)
     }
 
     /// Write the tile.
     ///
-    /// * `out` Writer to output the tile.
-    pub fn write_to(&self, mut out: &mut dyn Write) -> Result<()> {
+    /// * `out` Writer to output the tile, such as a file, socket or
+    ///   compressing encoder.
+    ///
+    /// The tile is streamed to the writer, without building a buffer of the
+    /// full tile first.  Returns the number of bytes written.
+    pub fn write_to<W: Write + ?Sized>(
+        &self,
+        mut out: &mut W,
+    ) -> Result<usize> {
         let mut os = CodedOutputStream::new(&mut out);
-        let _ = self.vec_tile.write_to(&mut os);
+        self.vec_tile.write_to(&mut os)?;
         os.flush()?;
-        Ok(())
+        Ok(os.total_bytes_written() as usize)
     }
 
     /// Encode the tile and return the bytes.
@@ -179,9 +358,55 @@ impl Default for Layer {
     fn default() -> Self {
         let layer = VtLayer::new();
         Layer {
+            #[cfg(feature = "v3")]
+            attr_tables: AttrTables::new(&layer),
             layer,
-            value_pos_map: HashMap::default(),
             key_pos_map: HashMap::default(),
+            value_pos_map: HashMap::default(),
+            clip_buffer: 0,
+            skip_outside: false,
+            id_mode: IdMode::default(),
+            next_id: 0,
+            max_features: None,
+            max_bytes: None,
+            sort_features: false,
+        }
+    }
+}
+
+impl From<VtLayer> for Layer {
+    fn from(layer: VtLayer) -> Self {
+        let key_pos_map = layer
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| (k.clone(), i))
+            .collect();
+        let mut value_pos_map = HashMap::default();
+        for (i, v) in layer.values.iter().enumerate() {
+            if let Some(key) = ValueKey::new(v) {
+                value_pos_map.entry(key).or_insert(i);
+            }
+        }
+        let next_id = layer
+            .features
+            .iter()
+            .filter_map(|f| f.id)
+            .max()
+            .map_or(0, |id| id.saturating_add(1));
+        Layer {
+            #[cfg(feature = "v3")]
+            attr_tables: AttrTables::new(&layer),
+            layer,
+            key_pos_map,
+            value_pos_map,
+            clip_buffer: 0,
+            skip_outside: false,
+            id_mode: IdMode::default(),
+            next_id,
+            max_features: None,
+            max_bytes: None,
+            sort_features: false,
         }
     }
 }
@@ -197,29 +422,243 @@ impl Layer {
         layer.set_name(name.to_string());
         layer.set_extent(extent);
         Layer {
+            #[cfg(feature = "v3")]
+            attr_tables: AttrTables::new(&layer),
             layer,
-            value_pos_map: HashMap::default(),
             key_pos_map: HashMap::default(),
+            value_pos_map: HashMap::default(),
+            clip_buffer: 0,
+            skip_outside: false,
+            id_mode: IdMode::default(),
+            next_id: 0,
+            max_features: None,
+            max_bytes: None,
+            sort_features: false,
+        }
+    }
+
+    /// Set the clip buffer for geometry encoders created by the layer.
+    ///
+    /// * `buffer` Buffer distance outside the tile extent, in tile units.
+    ///   A negative buffer is treated as zero.
+    pub fn clip_buffer(mut self, buffer: i32) -> Self {
+        self.clip_buffer = buffer.max(0);
+        self
+    }
+
+    /// Reserve capacity for a number of features.
+    ///
+    /// * `n` Number of additional features expected.
+    pub fn with_feature_capacity(mut self, n: usize) -> Self {
+        self.layer.features.reserve(n);
+        self
+    }
+
+    /// Skip geometry outside of the tile for encoders created by the layer.
+    ///
+    /// See [GeomEncoder::skip_outside]; the clip buffer is used as the
+    /// buffer around the layer extent.
+    pub fn skip_outside(mut self, skip: bool) -> Self {
+        self.skip_outside = skip;
+        self
+    }
+
+    /// Set the elevation scaling (`v3` feature).
+    ///
+    /// * `scaling` Scaling of vertex elevations.
+    ///
+    /// This must be set before adding features with elevations (see
+    /// [GeomEncoder::add_point_3d]).  The default has a precision of 1.
+    #[cfg(feature = "v3")]
+    pub fn elevation_scaling(mut self, scaling: Scaling) -> Self {
+        self.attr_tables.set_elevation_scaling(scaling);
+        self
+    }
+
+    /// Set the feature ID mode.
+    ///
+    /// * `mode` ID handling mode.
+    pub fn id_mode(mut self, mode: IdMode) -> Self {
+        self.id_mode = mode;
+        self
+    }
+
+    /// Sort features along a Hilbert curve when added to a tile.
+    ///
+    /// * `sort` If true, features are sorted by the position of their first
+    ///   vertex.
+    ///
+    /// Spatially ordered features encode and compress better.  Features
+    /// with no geometry are placed last.
+    pub fn sort_features(mut self, sort: bool) -> Self {
+        self.sort_features = sort;
+        self
+    }
+
+    /// Sort features by Hilbert index of their first vertex
+    fn hilbert_sort(&mut self) {
+        let extent = u64::from(self.layer.extent().max(1));
+        self.layer.features.sort_by_cached_key(|feature| {
+            let geom = &feature.geometry;
+            // first command must be MoveTo with at least one point
+            if geom.len() < 3 || geom[0] & 0x7 != 1 || geom[0] >> 3 == 0 {
+                return u64::MAX;
+            }
+            let zigzag =
+                |v: u32| i64::from((v >> 1) as i32 ^ -((v & 1) as i32));
+            let scale = |v: i64| {
+                let v = v.clamp(0, extent as i64 - 1) as u64;
+                (v << HILBERT_ORDER) / extent
+            };
+            hilbert_index(
+                HILBERT_ORDER,
+                scale(zigzag(geom[1])),
+                scale(zigzag(geom[2])),
+            )
+        });
+    }
+
+    /// Set limits on the number of features and encoded size.
+    ///
+    /// * `max_features` Maximum number of features.
+    /// * `max_bytes` Maximum encoded size in bytes (see
+    ///   [compute_size](#method.compute_size)).
+    ///
+    /// Limits are checked when the layer is added to a tile, or by calling
+    /// [check_limits](#method.check_limits).
+    pub fn with_limits(
+        mut self,
+        max_features: usize,
+        max_bytes: usize,
+    ) -> Self {
+        self.max_features = Some(max_features);
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Check that the layer is within its limits.
+    ///
+    /// Returns `Error::TooManyFeatures` or `Error::LayerTooLarge` if a limit
+    /// set by [with_limits](#method.with_limits) is exceeded.
+    pub fn check_limits(&self) -> Result<()> {
+        if let Some(max) = self.max_features
+            && self.num_features() > max
+        {
+            return Err(Error::TooManyFeatures(max));
+        }
+        if let Some(max) = self.max_bytes
+            && self.compute_size() > max
+        {
+            return Err(Error::LayerTooLarge(max));
+        }
+        Ok(())
+    }
+
+    /// Check that feature IDs are unique, if required by the ID mode
+    fn check_ids(&self) -> Result<()> {
+        if self.id_mode == IdMode::Unique {
+            let mut ids = HashSet::default();
+            for id in self.layer.features.iter().filter_map(|f| f.id) {
+                if !ids.insert(id) {
+                    return Err(Error::DuplicateId(id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the clip buffer (tile units).
+    #[cfg(feature = "geozero")]
+    pub(crate) fn buffer(&self) -> i32 {
+        self.clip_buffer
+    }
+
+    /// Create a geometry encoder using the layer options.
+    ///
+    /// * `geom_tp` Geometry type.
+    pub fn geom_encoder<F: Float>(&self, geom_tp: GeomType) -> GeomEncoder<F> {
+        let enc = GeomEncoder::new(geom_tp).clip_buffer(self.clip_buffer);
+        if self.skip_outside {
+            let buffer = u32::try_from(self.clip_buffer).unwrap_or_default();
+            enc.skip_outside(self.layer.extent(), buffer)
+        } else {
+            enc
         }
     }
 
+    /// Compute the encoded size in bytes.
+    ///
+    /// This is the size the layer adds to a tile, including its field
+    /// header.  It can be checked against size limits (e.g. 500 KB) before
+    /// the layer is added to a tile.
+    pub fn compute_size(&self) -> usize {
+        let len = self.layer.compute_size();
+        let header = 1 + protobuf::rt::compute_raw_varint64_size(len);
+        (header + len) as usize
+    }
+
     /// Get the layer name.
     pub fn name(&self) -> Option<&str> {
         self.layer.name.as_deref()
     }
 
+    /// Get the layer version.
+    pub fn version(&self) -> u32 {
+        self.layer.version()
+    }
+
+    /// Get the layer extent, or height / width of tile bounds.
+    pub fn extent(&self) -> u32 {
+        self.layer.extent()
+    }
+
+    /// Get a read-only view of the layer.
+    pub(crate) fn layer_ref(&self) -> LayerRef<'_> {
+        LayerRef::new(&self.layer)
+    }
+
     /// Get number of features (count).
     pub fn num_features(&self) -> usize {
         self.layer.features.len()
     }
 
+    /// Get number of keys in the key table.
+    ///
+    /// Each distinct key is stored once per layer.
+    pub fn keys_len(&self) -> usize {
+        self.layer.keys.len()
+    }
+
+    /// Get number of values in the value table.
+    ///
+    /// Each distinct value is stored once per layer (except values added by
+    /// [Feature::add_tag_string_at_end]).  A value table growing with the
+    /// number of features (e.g. from timestamps) can bloat tiles.
+    pub fn values_len(&self) -> usize {
+        self.layer.values.len()
+    }
+
     /// Create a new feature, giving it ownership of the layer.
     ///
     /// * `geom_data` Geometry data (consumed by this method).
-    pub fn into_feature(self, geom_data: GeomData) -> Feature {
+    pub fn into_feature(
+        #[cfg_attr(not(feature = "v3"), allow(unused_mut))] mut self,
+        geom_data: GeomData,
+    ) -> Feature {
         let num_keys = self.layer.keys.len();
         let num_values = self.layer.values.len();
+        #[cfg(feature = "v3")]
+        let attr_mark = self.attr_tables.mark(&self.layer);
         let mut feature = VtFeature::new();
+        #[cfg(feature = "v3")]
+        if !geom_data.elevations().is_empty() {
+            let elevations = self.attr_tables.encode_elevations(
+                geom_data.elevations(),
+                self.layer.special_fields.mut_unknown_fields(),
+            );
+            crate::v3::add_elevations(&mut feature, elevations);
+            self.layer.set_version(3);
+        }
         feature.type_ = Some(EnumOrUnknown::new(match geom_data.geom_type() {
             GeomType::Point => VtGeomType::POINT,
             GeomType::Linestring => VtGeomType::LINESTRING,
@@ -231,7 +670,125 @@ impl Layer {
             layer: self,
             num_keys,
             num_values,
+            #[cfg(feature = "v3")]
+            attr_mark,
+        }
+    }
+
+    /// Merge with another layer.
+    ///
+    /// * `other` Layer to merge; its features are added after existing
+    ///   features.
+    ///
+    /// Tags of all features are re-indexed into one key table and one value
+    /// table, with duplicates removed.  Feature IDs are preserved.  Returns
+    /// an error if the layers have different extents, or if either is not
+    /// version 2.
+    pub fn merge(self, other: Layer) -> Result<Self> {
+        if self.layer.extent() != other.layer.extent() {
+            return Err(Error::WrongExtent());
         }
+        for version in [self.layer.version(), other.layer.version()] {
+            if version != 2 {
+                return Err(Error::UnsupportedVersion(version));
+            }
+        }
+        let mut vt_layer = VtLayer::new();
+        vt_layer.version = self.layer.version;
+        vt_layer.name = self.layer.name.clone();
+        vt_layer.extent = self.layer.extent;
+        let mut merged = Layer {
+            #[cfg(feature = "v3")]
+            attr_tables: AttrTables::new(&vt_layer),
+            layer: vt_layer,
+            key_pos_map: HashMap::default(),
+            value_pos_map: HashMap::default(),
+            clip_buffer: self.clip_buffer,
+            skip_outside: self.skip_outside,
+            id_mode: self.id_mode,
+            next_id: self.next_id.max(other.next_id),
+            max_features: self.max_features,
+            max_bytes: self.max_bytes,
+            sort_features: self.sort_features,
+        };
+        merged.append(self.layer);
+        merged.append(other.layer);
+        Ok(merged)
+    }
+
+    /// Append all features of another layer, re-indexing their tags.
+    ///
+    /// * `other` Layer to append.
+    ///
+    /// Tags referring to keys or values outside of the other layer's tables
+    /// are dropped.
+    fn append(&mut self, other: VtLayer) {
+        let keys: Vec<usize> =
+            other.keys.iter().map(|k| self.key_pos(k)).collect();
+        let values: Vec<usize> =
+            other.values.into_iter().map(|v| self.val_pos(v)).collect();
+        for mut feature in other.features {
+            let tags = std::mem::take(&mut feature.tags);
+            for pair in tags.chunks_exact(2) {
+                let key = keys.get(pair[0] as usize);
+                let value = values.get(pair[1] as usize);
+                if let (Some(&key), Some(&value)) = (key, value) {
+                    feature.tags.push(key as u32);
+                    feature.tags.push(value as u32);
+                }
+            }
+            self.layer.features.push(feature);
+        }
+    }
+
+    /// Sort the key and value tables, rewriting tag indices
+    fn sort_tables(&mut self) {
+        let layer = &mut self.layer;
+        let mut keys: Vec<_> = std::mem::take(&mut layer.keys)
+            .into_iter()
+            .enumerate()
+            .collect();
+        keys.sort_by(|a, b| a.1.cmp(&b.1));
+        let mut key_idx = vec![0; keys.len()];
+        for (i, (j, _k)) in keys.iter().enumerate() {
+            key_idx[*j] = i as u32;
+        }
+        layer.keys = keys.into_iter().map(|(_j, k)| k).collect();
+        let mut values: Vec<_> = std::mem::take(&mut layer.values)
+            .into_iter()
+            .enumerate()
+            .map(|(j, v)| (ValueKey::new(&v), j, v))
+            .collect();
+        // values with no type set are last
+        values
+            .sort_by(|a, b| (a.0.is_none(), &a.0).cmp(&(b.0.is_none(), &b.0)));
+        let mut val_idx = vec![0; values.len()];
+        self.value_pos_map.clear();
+        for (i, (key, j, _v)) in values.iter_mut().enumerate() {
+            val_idx[*j] = i as u32;
+            if let Some(key) = key.take() {
+                self.value_pos_map.entry(key).or_insert(i);
+            }
+        }
+        layer.values = values.into_iter().map(|(_key, _j, v)| v).collect();
+        for feature in &mut layer.features {
+            for pair in feature.tags.chunks_exact_mut(2) {
+                // invalid indices are left as-is
+                if let (Some(&k), Some(&v)) = (
+                    key_idx.get(pair[0] as usize),
+                    val_idx.get(pair[1] as usize),
+                ) {
+                    pair[0] = k;
+                    pair[1] = v;
+                }
+            }
+        }
+        self.key_pos_map = layer
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| (k.clone(), i))
+            .collect();
     }
 
     /// Get position of a key in the layer keys.  If the key is not found, it
@@ -255,30 +812,67 @@ impl Layer {
 
     /// Get position of a value in the layer values.  If the value is not found,
     /// it is added as the last value.
+    ///
+    /// Values with no type set are always added.
     fn val_pos(&mut self, value: Value) -> usize {
-        self.layer
-            .values
-            .iter()
-            .position(|v| *v == value)
-            .unwrap_or_else(|| {
-                self.layer.values.push(value);
-                self.layer.values.len() - 1
-            })
+        let values = &mut self.layer.values;
+        match ValueKey::new(&value) {
+            Some(key) => *self.value_pos_map.entry(key).or_insert_with(|| {
+                values.push(value);
+                values.len() - 1
+            }),
+            None => {
+                values.push(value);
+                values.len() - 1
+            }
+        }
+    }
+}
+
+impl ValueKey {
+    /// Create a value key
+    fn new(value: &Value) -> Option<Self> {
+        Some(match TagValue::from_value(value)? {
+            TagValue::String(v) => ValueKey::String(v),
+            TagValue::F64(v) => ValueKey::F64(v.to_bits()),
+            TagValue::F32(v) => ValueKey::F32(v.to_bits()),
+            TagValue::I64(v) => ValueKey::I64(v),
+            TagValue::U64(v) => ValueKey::U64(v),
+            TagValue::Sint(v) => ValueKey::Sint(v),
+            TagValue::Bool(v) => ValueKey::Bool(v),
+        })
     }
 }
 
 impl Feature {
     /// Complete the feature, returning ownership of the layer.
     pub fn into_layer(mut self) -> Layer {
-        self.layer.layer.features.push(self.feature);
+        let layer = &mut self.layer;
+        match self.feature.id {
+            Some(id) => layer.next_id = layer.next_id.max(id.saturating_add(1)),
+            None if layer.id_mode == IdMode::Auto => {
+                self.feature.id = Some(layer.next_id);
+                layer.next_id = layer.next_id.saturating_add(1);
+            }
+            None => (),
+        }
+        layer.layer.features.push(self.feature);
         self.layer
     }
 
     /// Get the layer, abandoning the feature.
     pub fn layer(mut self) -> Layer {
-        // Reset key/value lengths
-        self.layer.layer.keys.truncate(self.num_keys);
-        self.layer.layer.values.truncate(self.num_values);
+        // Reset key/value lengths (and attribute tables)
+        let (num_keys, num_values) = (self.num_keys, self.num_values);
+        let layer = &mut self.layer;
+        layer.layer.keys.truncate(num_keys);
+        layer.layer.values.truncate(num_values);
+        layer.key_pos_map.retain(|_k, i| *i < num_keys);
+        layer.value_pos_map.retain(|_v, i| *i < num_values);
+        #[cfg(feature = "v3")]
+        layer
+            .attr_tables
+            .rollback(&self.attr_mark, &mut layer.layer);
         self.layer
     }
 
@@ -303,7 +897,7 @@ impl Feature {
     pub fn add_tag_string(&mut self, key: &str, val: &str) {
         let mut value = Value::new();
         value.set_string_value(val.to_string());
-        self.add_tag(key, value);
+        self.add_value(key, value);
     }
     pub fn add_tag_string_at_end(&mut self, key: &str, val: &str) {
         let mut value = Value::new();
@@ -314,46 +908,99 @@ impl Feature {
     pub fn add_tag_double(&mut self, key: &str, val: f64) {
         let mut value = Value::new();
         value.set_double_value(val);
-        self.add_tag(key, value);
+        self.add_value(key, value);
     }
 
     /// Add a tag of float type.
     pub fn add_tag_float(&mut self, key: &str, val: f32) {
         let mut value = Value::new();
         value.set_float_value(val);
-        self.add_tag(key, value);
+        self.add_value(key, value);
     }
 
     /// Add a tag of int type.
     pub fn add_tag_int(&mut self, key: &str, val: i64) {
         let mut value = Value::new();
         value.set_int_value(val);
-        self.add_tag(key, value);
+        self.add_value(key, value);
     }
 
     /// Add a tag of uint type.
     pub fn add_tag_uint(&mut self, key: &str, val: u64) {
         let mut value = Value::new();
         value.set_uint_value(val);
-        self.add_tag(key, value);
+        self.add_value(key, value);
     }
 
     /// Add a tag of sint type.
     pub fn add_tag_sint(&mut self, key: &str, val: i64) {
         let mut value = Value::new();
         value.set_sint_value(val);
-        self.add_tag(key, value);
+        self.add_value(key, value);
     }
 
     /// Add a tag of bool type.
     pub fn add_tag_bool(&mut self, key: &str, val: bool) {
         let mut value = Value::new();
         value.set_bool_value(val);
-        self.add_tag(key, value);
+        self.add_value(key, value);
     }
 
     /// Add a tag.
-    fn add_tag(&mut self, key: &str, value: Value) {
+    ///
+    /// * `key` Tag key.
+    /// * `value` Tag value, of any type.
+    pub fn add_tag(&mut self, key: &str, value: TagValue) {
+        self.add_value(key, value.into_value());
+    }
+
+    /// Add tags from a map of properties.
+    ///
+    /// * `properties` Tag keys and values.
+    ///
+    /// Tags are added in key order, so the encoded layer does not depend on
+    /// the map's iteration order.
+    pub fn add_properties<S>(
+        &mut self,
+        properties: &std::collections::HashMap<String, TagValue, S>,
+    ) {
+        let mut props: Vec<_> = properties.iter().collect();
+        props.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in props {
+            self.add_tag(key, value.clone());
+        }
+    }
+
+    /// Add an attribute (`v3` feature).
+    ///
+    /// * `key` Attribute key.
+    /// * `value` Attribute value.
+    ///
+    /// Attributes use the experimental MVT 3.0 draft encoding, and the layer
+    /// version is set to 3.  Most decoders ignore attributes; use
+    /// [FeatureRef::attributes](crate::FeatureRef::attributes) to decode
+    /// them.  Tables of version 3 layers are not sorted, and the layers
+    /// cannot be merged.
+    #[cfg(feature = "v3")]
+    pub fn add_attribute(&mut self, key: &str, value: &AttrValue) {
+        let layer = &mut self.layer;
+        let key = layer.key_pos(key) as u64;
+        let keys: HashMap<&str, u64> = crate::v3::map_keys(value)
+            .into_iter()
+            .map(|k| (k, layer.key_pos(k) as u64))
+            .collect();
+        let attrs = layer.attr_tables.encode(
+            key,
+            value,
+            &|k| keys[k],
+            layer.layer.special_fields.mut_unknown_fields(),
+        );
+        layer.layer.set_version(3);
+        crate::v3::add_attributes(&mut self.feature, attrs);
+    }
+
+    /// Add a tag with a protobuf value
+    fn add_value(&mut self, key: &str, value: Value) {
         let kidx = self.layer.key_pos(key);
         self.feature.tags.push(kidx as u32);
         let vidx = self.layer.val_pos(value);
@@ -368,3 +1015,413 @@ impl Feature {
         self.feature.tags.push(vidx as u32);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomEncoder;
+    use crate::fixture::make_tile;
+
+    #[test]
+    fn compute_size() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("First Layer");
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.add_tag_string("key", "value");
+        let layer = feature.into_layer();
+        let layer_size = layer.compute_size();
+        tile.add_layer(layer).unwrap();
+        assert_eq!(tile.compute_size(), layer_size);
+        assert_eq!(tile.compute_size(), tile.to_bytes().unwrap().len());
+    }
+
+    #[test]
+    fn write_to() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("First Layer");
+        tile.add_layer(layer).unwrap();
+        let mut out = Vec::new();
+        let len = tile.write_to(&mut out).unwrap();
+        assert_eq!(len, out.len());
+        assert_eq!(out, tile.to_bytes().unwrap());
+        let out: &mut dyn Write = &mut Vec::new();
+        assert_eq!(tile.write_to(out).unwrap(), len);
+    }
+
+    #[test]
+    fn layer_names() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("roads");
+        assert_eq!(layer.version(), 2);
+        assert_eq!(layer.extent(), 4096);
+        tile.add_layer(layer).unwrap();
+        let err = tile.add_layer(tile.create_layer("roads")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Duplicate layer name \"roads\" (layer \"roads\")"
+        );
+        assert!(matches!(err.root(), Error::DuplicateName(n) if n == "roads"));
+        assert!(matches!(
+            tile.add_layer(tile.create_layer(""))
+                .map_err(Error::into_root),
+            Err(Error::EmptyName())
+        ));
+        assert_eq!(tile.num_layers(), 1);
+    }
+
+    fn tagged_tile(layer: &str, key: &str, value: &str, id: u64) -> Tile {
+        make_tile(layer, GeomType::Point, &[(1.0, 2.0)], |f| {
+            f.set_id(id);
+            f.add_tag_string("name", "shared");
+            f.add_tag_string(key, value);
+        })
+    }
+
+    #[test]
+    fn merge() {
+        let base = tagged_tile("roads", "kind", "highway", 1);
+        let overlay = tagged_tile("roads", "color", "highway", 2);
+        let tile = base.merge(overlay).unwrap();
+        let tile = tile
+            .merge(tagged_tile("labels", "kind", "city", 3))
+            .unwrap();
+        assert_eq!(tile.num_layers(), 2);
+        let roads = tile.layer("roads").unwrap();
+        assert_eq!(roads.num_features(), 2);
+        let tags: Vec<Vec<_>> =
+            roads.features().map(|f| f.tags().collect()).collect();
+        assert_eq!(
+            tags[0],
+            [
+                ("name", TagValue::String("shared".into())),
+                ("kind", TagValue::String("highway".into())),
+            ]
+        );
+        assert_eq!(
+            tags[1],
+            [
+                ("name", TagValue::String("shared".into())),
+                ("color", TagValue::String("highway".into())),
+            ]
+        );
+        let ids: Vec<_> = roads.features().map(|f| f.id()).collect();
+        assert_eq!(ids, [Some(1), Some(2)]);
+        // key and value tables are deduplicated
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = &tile.into_layers()[0].layer;
+        assert_eq!(layer.keys, ["name", "kind", "color"]);
+        assert_eq!(layer.values.len(), 2);
+        let wrong = Tile::new(512).merge(tagged_tile("a", "b", "c", 4));
+        assert!(matches!(wrong, Err(Error::WrongExtent())));
+    }
+
+    #[test]
+    fn merge_layers() {
+        let tile = Tile::new(4096);
+        let geom = || {
+            GeomEncoder::new(GeomType::Point)
+                .point(1.0, 2.0)
+                .unwrap()
+                .encode()
+                .unwrap()
+        };
+        let mut feature = tile.create_layer("chunk").into_feature(geom());
+        feature.set_id(10);
+        feature.add_tag_string("name", "a");
+        feature.add_tag_string_at_end("name", "a");
+        feature.add_tag_double("width", 1.5);
+        let first = feature.into_layer();
+        let mut feature = tile.create_layer("chunk").into_feature(geom());
+        feature.set_id(20);
+        feature.add_tag_double("width", 1.5);
+        feature.add_tag_string("name", "b");
+        feature.add_tag_bool("bridge", true);
+        let second = feature.into_layer();
+        let layer = first.merge(second).unwrap();
+        assert_eq!(layer.num_features(), 2);
+        assert_eq!(layer.layer.keys, ["name", "width", "bridge"]);
+        assert_eq!(layer.layer.values.len(), 4);
+        let ids: Vec<_> = layer.layer.features.iter().map(|f| f.id).collect();
+        assert_eq!(ids, [Some(10), Some(20)]);
+        assert_eq!(layer.layer.features[0].tags, [0, 0, 0, 0, 1, 1]);
+        assert_eq!(layer.layer.features[1].tags, [1, 1, 0, 2, 2, 3]);
+        let other = Tile::new(512).create_layer("chunk");
+        assert!(matches!(layer.merge(other), Err(Error::WrongExtent())));
+    }
+
+    #[test]
+    fn remove_layers() {
+        let mut tile = Tile::new(4096);
+        for name in ["roads", "debug", "water", "labels"] {
+            tile.add_layer(tile.create_layer(name)).unwrap();
+        }
+        let layer = tile.remove_layer("debug").unwrap();
+        assert_eq!(layer.name(), Some("debug"));
+        assert!(tile.remove_layer("debug").is_none());
+        tile.retain_layers(|name| name != "water");
+        let names: Vec<_> = tile.layers().map(|l| l.name()).collect();
+        assert_eq!(names, ["roads", "labels"]);
+        // removed layers can be added again
+        tile.add_layer(layer).unwrap();
+        assert_eq!(tile.num_layers(), 3);
+    }
+
+    fn add_point(layer: Layer, id: Option<u64>) -> Layer {
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        if let Some(id) = id {
+            feature.set_id(id);
+        }
+        feature.into_layer()
+    }
+
+    #[test]
+    fn unique_ids() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("x").id_mode(IdMode::Unique);
+        let layer = add_point(layer, Some(1));
+        let layer = add_point(layer, None);
+        let layer = add_point(layer, Some(2));
+        tile.add_layer(layer).unwrap();
+        let layer = tile.create_layer("y").id_mode(IdMode::Unique);
+        let layer = add_point(layer, Some(3));
+        let layer = add_point(layer, Some(3));
+        let err = tile.add_layer(layer).unwrap_err();
+        assert_eq!(err.context().unwrap().layer.as_deref(), Some("y"));
+        assert!(matches!(err.into_root(), Error::DuplicateId(3)));
+        // unchecked by default
+        let layer = add_point(tile.create_layer("y"), Some(3));
+        let layer = add_point(layer, Some(3));
+        tile.add_layer(layer).unwrap();
+    }
+
+    #[test]
+    fn auto_ids() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("x").id_mode(IdMode::Auto);
+        let layer = add_point(layer, None);
+        let layer = add_point(layer, Some(5));
+        let layer = add_point(layer, None);
+        let layer = add_point(layer, None);
+        tile.add_layer(layer).unwrap();
+        let layer = tile.layer("x").unwrap();
+        let ids: Vec<_> = layer.features().map(|f| f.id()).collect();
+        assert_eq!(ids, [Some(0), Some(5), Some(6), Some(7)]);
+    }
+
+    #[test]
+    fn typed_tags() {
+        let mut tile = Tile::new(4096);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("x").into_feature(geom);
+        let tags = [
+            ("s", TagValue::String("a".to_string())),
+            ("d", TagValue::F64(1.5)),
+            ("f", TagValue::F32(2.5)),
+            ("i", TagValue::I64(-3)),
+            ("u", TagValue::U64(4)),
+            ("z", TagValue::Sint(-5)),
+            ("b", TagValue::Bool(true)),
+        ];
+        for (key, value) in tags.iter().cloned() {
+            feature.add_tag(key, value);
+        }
+        tile.add_layer(feature.into_layer()).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layer("x").unwrap();
+        let feature = layer.features().next().unwrap();
+        assert_eq!(feature.tags().collect::<Vec<_>>(), tags);
+    }
+
+    #[test]
+    fn properties() {
+        let mut tile = Tile::new(4096);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("x").into_feature(geom);
+        let mut props = std::collections::HashMap::new();
+        props.insert("name".to_string(), TagValue::String("a".to_string()));
+        props.insert("lanes".to_string(), TagValue::U64(2));
+        props.insert("oneway".to_string(), TagValue::Bool(false));
+        feature.add_properties(&props);
+        tile.add_layer(feature.into_layer()).unwrap();
+        let layer = tile.layer("x").unwrap();
+        let feature = layer.features().next().unwrap();
+        let keys: Vec<_> = feature.tags().map(|(k, _v)| k).collect();
+        assert_eq!(keys, ["lanes", "name", "oneway"]);
+        let decoded = feature.properties();
+        assert_eq!(decoded.len(), 3);
+        for (key, value) in &props {
+            assert_eq!(decoded.get(key.as_str()), Some(value));
+        }
+    }
+
+    fn point() -> GeomData {
+        GeomEncoder::new(GeomType::Point)
+            .point(1.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+    }
+
+    fn dedup_layer(tile: &Tile) -> Layer {
+        let mut layer = tile.create_layer("x");
+        for i in 0..10 {
+            let mut feature = layer.into_feature(point());
+            feature.add_tag_string("name", "a");
+            feature.add_tag_double("nan", f64::NAN);
+            feature.add_tag_uint("class", i % 2);
+            layer = feature.into_layer();
+        }
+        layer
+    }
+
+    #[test]
+    fn dedup_tables() {
+        let tile = Tile::new(4096);
+        let layer = dedup_layer(&tile);
+        assert_eq!(layer.keys_len(), 3);
+        assert_eq!(layer.values_len(), 4);
+        // abandoned feature does not leave stale table entries
+        let mut feature = layer.into_feature(point());
+        feature.add_tag_string("ref", "b");
+        let mut feature = feature.layer().into_feature(point());
+        feature.add_tag_string("ref", "b");
+        let layer = feature.into_layer();
+        assert_eq!(layer.keys_len(), 4);
+        assert_eq!(layer.values_len(), 5);
+        let merged = layer.merge(dedup_layer(&tile)).unwrap();
+        assert_eq!(merged.num_features(), 21);
+        assert_eq!(merged.keys_len(), 4);
+        assert_eq!(merged.values_len(), 5);
+        // decoded layers keep deduplicating
+        let mut tile = Tile::new(4096);
+        tile.add_layer(merged).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.into_layers().pop().unwrap();
+        let mut feature = layer.into_feature(point());
+        feature.add_tag_double("nan", f64::NAN);
+        feature.add_tag_string("ref", "b");
+        let layer = feature.into_layer();
+        assert_eq!(layer.keys_len(), 4);
+        assert_eq!(layer.values_len(), 5);
+    }
+
+    fn unsorted_tile(tile: Tile) -> Tile {
+        let mut tile = tile;
+        let mut layer = tile.create_layer("x");
+        for (name, class) in [("c", 3), ("a", 1), ("b", 2), ("a", 3)] {
+            let mut feature = layer.into_feature(point());
+            feature.add_tag_uint("z", class);
+            feature.add_tag_string("name", name);
+            feature.add_tag_bool("m", class > 1);
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer).unwrap();
+        tile
+    }
+
+    #[test]
+    fn sort_tables() {
+        let tile = unsorted_tile(Tile::new(4096));
+        let sorted = unsorted_tile(Tile::new(4096).sort_tables(true));
+        let layer = &sorted.vec_tile.layers[0];
+        assert_eq!(layer.keys, ["m", "name", "z"]);
+        let values: Vec<_> = layer
+            .values
+            .iter()
+            .filter_map(TagValue::from_value)
+            .collect();
+        assert_eq!(
+            values,
+            [
+                TagValue::String("a".to_string()),
+                TagValue::String("b".to_string()),
+                TagValue::String("c".to_string()),
+                TagValue::U64(1),
+                TagValue::U64(2),
+                TagValue::U64(3),
+                TagValue::Bool(false),
+                TagValue::Bool(true),
+            ]
+        );
+        assert!(tile.diff(&sorted).is_empty());
+        let tile = Tile::from_bytes(&sorted.to_bytes().unwrap()).unwrap();
+        assert!(tile.diff(&sorted).is_empty());
+    }
+
+    #[test]
+    fn limits() {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("x").with_limits(3, 100);
+        for _ in 0..3 {
+            layer = layer.into_feature(point()).into_layer();
+        }
+        assert!(layer.check_limits().is_ok());
+        let layer = layer.into_feature(point()).into_layer();
+        assert!(matches!(
+            layer.check_limits(),
+            Err(Error::TooManyFeatures(3))
+        ));
+        assert!(matches!(
+            tile.add_layer(layer).map_err(Error::into_root),
+            Err(Error::TooManyFeatures(3))
+        ));
+        let mut feature = tile
+            .create_layer("x")
+            .with_limits(3, 100)
+            .into_feature(point());
+        feature.add_tag_string("name", &"a".repeat(100));
+        assert!(matches!(
+            tile.add_layer(feature.into_layer())
+                .map_err(Error::into_root),
+            Err(Error::LayerTooLarge(100))
+        ));
+        assert_eq!(tile.num_layers(), 0);
+    }
+
+    #[test]
+    fn hilbert_sort() {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("x").sort_features(true);
+        let empty = GeomEncoder::<f64>::new(GeomType::Point).encode().unwrap();
+        let mut feature = layer.into_feature(empty);
+        feature.set_id(4);
+        layer = feature.into_layer();
+        for (id, x, y) in [
+            (3, 3000.0, 100.0),
+            (0, 100.0, 100.0),
+            (2, 3000.0, 3000.0),
+            (1, 100.0, 3000.0),
+        ] {
+            let geom = GeomEncoder::new(GeomType::Point)
+                .point(x, y)
+                .unwrap()
+                .encode()
+                .unwrap();
+            let mut feature = layer.into_feature(geom);
+            feature.set_id(id);
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer).unwrap();
+        let layer = tile.layer("x").unwrap();
+        let ids: Vec<_> = layer.features().filter_map(|f| f.id()).collect();
+        assert_eq!(ids, [0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,615 @@
+// tiler.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Tile pyramid builder.
+//!
+use crate::clip::clip_points;
+use crate::encoder::GeomType;
+use crate::error::Result;
+use crate::mapgrid::{MapGrid, TileId};
+use crate::tile::{Layer, Tile};
+use crate::value::TagValue;
+use pointy::{BBox, Pt, Transform};
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+use std::ops::RangeInclusive;
+
+/// Source geometry for a [Tiler], in grid coördinates.
+#[derive(Clone, Debug)]
+pub struct SourceGeom {
+    /// Geometry type
+    geom_tp: GeomType,
+
+    /// Points (Point), linestrings (Linestring) or rings (Polygon)
+    parts: Vec<Vec<Pt<f64>>>,
+
+    /// Bounding box of all parts
+    bbox: BBox<f64>,
+}
+
+/// Source feature, with geometry and properties
+#[derive(Clone, Debug)]
+struct SourceFeature {
+    /// Feature geometry
+    geom: SourceGeom,
+
+    /// Feature properties
+    props: Vec<(String, TagValue)>,
+}
+
+/// Builder for a pyramid of tiles from a set of source features.
+///
+/// Each feature is clipped (and optionally simplified) to every tile it
+/// touches, at each zoom level in the range.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{GeomType, SourceGeom, TagValue, Tiler};
+///
+/// let road = SourceGeom::new(
+///     GeomType::Linestring,
+///     [[(-1_000_000.0, 500_000.0), (2_000_000.0, -500_000.0)]],
+/// );
+/// let props = [("name", TagValue::String("Main St".into()))];
+/// let tiler = Tiler::new("roads", 0..=4).clip_buffer(64);
+/// for res in tiler.tile([(road, props)]) {
+///     let (tid, tile) = res?;
+///     println!("{tid}: {} bytes", tile.to_bytes()?.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Tiler {
+    /// Map grid
+    grid: MapGrid,
+
+    /// Tile extent
+    extent: u32,
+
+    /// Zoom levels
+    zooms: RangeInclusive<u32>,
+
+    /// Layer name
+    layer: String,
+
+    /// Clip buffer (tile units)
+    clip_buffer: i32,
+
+    /// Simplification tolerance (tile units)
+    simplify: Option<f64>,
+
+    /// Automatic simplification for display
+    auto_simplify: bool,
+
+    /// Minimum polygon area (tile units squared)
+    min_area: Option<f64>,
+
+    /// Minimum linestring length (tile units)
+    min_length: Option<f64>,
+}
+
+/// Iterator of tiles built by a [Tiler].
+///
+/// Tiles are produced in order of zoom level, then Y, then X.  Tiles
+/// with no features are skipped.
+pub struct TilerIter {
+    /// Tiler options
+    tiler: Tiler,
+
+    /// Source features
+    features: Vec<SourceFeature>,
+
+    /// Next zoom level
+    zoom: u32,
+
+    /// Feature indices of remaining tiles at current zoom level
+    pending: btree_map::IntoIter<(u32, u32), Vec<usize>>,
+
+    /// Count of dropped features at each zoom level
+    dropped: BTreeMap<u32, usize>,
+}
+
+impl SourceGeom {
+    /// Create a new source geometry.
+    ///
+    /// * `geom_tp` Geometry type.
+    /// * `parts` Points (Point), linestrings (Linestring) or rings
+    ///   (Polygon), each an iterator of points.
+    pub fn new<I>(geom_tp: GeomType, parts: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: IntoIterator,
+        <I::Item as IntoIterator>::Item: Into<Pt<f64>>,
+    {
+        let parts: Vec<Vec<Pt<f64>>> = parts
+            .into_iter()
+            .map(|part| part.into_iter().map(Into::into).collect())
+            .collect();
+        let bbox = BBox::new(parts.iter().flatten().copied());
+        SourceGeom {
+            geom_tp,
+            parts,
+            bbox,
+        }
+    }
+
+    /// Get the geometry type.
+    pub fn geom_type(&self) -> GeomType {
+        self.geom_tp
+    }
+
+    /// Check if the geometry has no points
+    fn is_empty(&self) -> bool {
+        self.parts.iter().all(Vec::is_empty)
+    }
+}
+
+impl Tiler {
+    /// Create a new tiler.
+    ///
+    /// * `layer` Name of layer to create in each tile.
+    /// * `zooms` Range of zoom levels.
+    ///
+    /// The default [MapGrid] (Web Mercator) and an extent of 4096 are used.
+    pub fn new(layer: &str, zooms: RangeInclusive<u32>) -> Self {
+        Tiler {
+            grid: MapGrid::default(),
+            extent: 4096,
+            zooms,
+            layer: layer.to_string(),
+            clip_buffer: 0,
+            simplify: None,
+            auto_simplify: false,
+            min_area: None,
+            min_length: None,
+        }
+    }
+
+    /// Set the map grid.
+    pub fn grid(mut self, grid: MapGrid) -> Self {
+        self.grid = grid;
+        self
+    }
+
+    /// Set the tile extent.
+    pub fn extent(mut self, extent: u32) -> Self {
+        self.extent = extent;
+        self
+    }
+
+    /// Set the clip buffer.
+    ///
+    /// * `buffer` Buffer distance outside the tile extent, in tile units.
+    ///   A negative buffer is treated as zero.
+    pub fn clip_buffer(mut self, buffer: i32) -> Self {
+        self.clip_buffer = buffer.max(0);
+        self
+    }
+
+    /// Set a tolerance for simplifying linestrings and polygon rings.
+    ///
+    /// * `tolerance` Tolerance, in tile units.
+    ///
+    /// See [GeomEncoder::simplify](struct.GeomEncoder.html#method.simplify).
+    pub fn simplify(mut self, tolerance: f64) -> Self {
+        self.simplify = Some(tolerance);
+        self
+    }
+
+    /// Simplify automatically for display.
+    ///
+    /// * `auto` If true, geometry is simplified with a tolerance of one
+    ///   tile unit, except at the highest zoom level of the range.
+    ///
+    /// A tile unit covers a different distance at each zoom level, so the
+    /// same detail is kept relative to the display resolution.  The highest
+    /// zoom level keeps full detail for overzooming.  An explicit
+    /// [simplify](#method.simplify) tolerance takes precedence.
+    pub fn auto_simplify(mut self, auto: bool) -> Self {
+        self.auto_simplify = auto;
+        self
+    }
+
+    /// Get the simplification tolerance (tile units) at a zoom level.
+    ///
+    /// * `z` Zoom level.
+    pub fn simplify_tolerance(&self, z: u32) -> Option<f64> {
+        match self.simplify {
+            Some(tolerance) => Some(tolerance),
+            None if self.auto_simplify && z < *self.zooms.end() => Some(1.0),
+            None => None,
+        }
+    }
+
+    /// Drop polygons smaller than a minimum area.
+    ///
+    /// * `area` Minimum area, in tile units squared.
+    ///
+    /// The area of the whole feature is checked at each zoom level, before
+    /// clipping.  Dropped features are counted by [TilerIter::dropped].
+    pub fn min_area(mut self, area: f64) -> Self {
+        self.min_area = Some(area);
+        self
+    }
+
+    /// Drop linestrings shorter than a minimum length.
+    ///
+    /// * `length` Minimum length, in tile units.
+    ///
+    /// The length of the whole feature is checked at each zoom level,
+    /// before clipping.  Dropped features are counted by
+    /// [TilerIter::dropped].
+    pub fn min_length(mut self, length: f64) -> Self {
+        self.min_length = Some(length);
+        self
+    }
+
+    /// Build tiles from source features.
+    ///
+    /// * `features` Source geometry and properties of each feature.
+    ///
+    /// Tiles are built as the returned iterator advances.
+    pub fn tile<I, P, K>(&self, features: I) -> TilerIter
+    where
+        I: IntoIterator<Item = (SourceGeom, P)>,
+        P: IntoIterator<Item = (K, TagValue)>,
+        K: Into<String>,
+    {
+        let features = features
+            .into_iter()
+            .filter(|(geom, _)| !geom.is_empty())
+            .map(|(geom, props)| SourceFeature {
+                geom,
+                props: props.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+            })
+            .collect();
+        TilerIter {
+            tiler: self.clone(),
+            features,
+            zoom: *self.zooms.start(),
+            pending: BTreeMap::new().into_iter(),
+            dropped: BTreeMap::new(),
+        }
+    }
+
+    /// Get the clip buffer as a fraction of tile size
+    fn buffer_fraction(&self) -> f64 {
+        f64::from(self.clip_buffer) / f64::from(self.extent.max(1))
+    }
+
+    /// Check if a geometry is too small to keep at a zoom level
+    fn is_too_small(&self, geom: &SourceGeom, z: u32) -> bool {
+        let scale = f64::from(self.extent);
+        let tile_parts = || {
+            geom.parts.iter().map(move |part| {
+                part.iter()
+                    .map(move |p| self.grid.tile_position(z, *p) * scale)
+            })
+        };
+        match (geom.geom_tp, self.min_length, self.min_area) {
+            (GeomType::Linestring, Some(min), _) => {
+                let length: f64 = tile_parts()
+                    .map(|part| {
+                        let pts: Vec<_> = part.collect();
+                        pts.windows(2).map(|w| w[0].distance(w[1])).sum::<f64>()
+                    })
+                    .sum();
+                length < min
+            }
+            (GeomType::Polygon, _, Some(min)) => {
+                let area: f64 = tile_parts()
+                    .map(|part| {
+                        // rings may be unclosed, so wrap to the first point
+                        let pts: Vec<_> = part.collect();
+                        let n = pts.len();
+                        (0..n)
+                            .map(|i| {
+                                let (p0, p1) = (pts[i], pts[(i + 1) % n]);
+                                p0.x * p1.y - p1.x * p0.y
+                            })
+                            .sum::<f64>()
+                    })
+                    .sum();
+                area.abs() / 2.0 < min
+            }
+            _ => false,
+        }
+    }
+
+    /// Index features by the tiles they touch at a zoom level.
+    ///
+    /// Returns the index and a count of dropped features.
+    fn index(
+        &self,
+        features: &[SourceFeature],
+        z: u32,
+    ) -> (BTreeMap<(u32, u32), Vec<usize>>, usize) {
+        let n = f64::from(1 << z);
+        let buf = self.buffer_fraction();
+        let mut index: BTreeMap<(u32, u32), Vec<usize>> = BTreeMap::new();
+        let mut dropped = 0;
+        for (i, feat) in features.iter().enumerate() {
+            if self.is_too_small(&feat.geom, z) {
+                dropped += 1;
+                continue;
+            }
+            let b = feat.geom.bbox;
+            let p0 = self.grid.tile_position(z, Pt::new(b.x_min(), b.y_min()));
+            let p1 = self.grid.tile_position(z, Pt::new(b.x_max(), b.y_max()));
+            let (x0, x1) = (p0.x.min(p1.x) - buf, p0.x.max(p1.x) + buf);
+            let (y0, y1) = (p0.y.min(p1.y) - buf, p0.y.max(p1.y) + buf);
+            if x1 < 0.0 || y1 < 0.0 || x0 >= n || y0 >= n {
+                continue;
+            }
+            let x0 = x0.floor().max(0.0) as u32;
+            let y0 = y0.floor().max(0.0) as u32;
+            let x1 = x1.floor().min(n - 1.0) as u32;
+            let y1 = y1.floor().min(n - 1.0) as u32;
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    index.entry((y, x)).or_default().push(i);
+                }
+            }
+        }
+        (index, dropped)
+    }
+
+    /// Build one tile from a set of features
+    fn build_tile(
+        &self,
+        tid: TileId,
+        features: &[SourceFeature],
+        indices: &[usize],
+    ) -> Result<Option<Tile>> {
+        let mut tile = Tile::new(self.extent);
+        let mut layer =
+            tile.create_layer(&self.layer).clip_buffer(self.clip_buffer);
+        let bbox = self.grid.tile_bbox(tid);
+        let scale = f64::from(self.extent);
+        let transform = self.grid.tile_transform(tid).scale(scale, scale);
+        let buf = self.buffer_fraction();
+        let (bx, by) = (bbox.x_span() * buf, bbox.y_span() * buf);
+        let clip_bbox = BBox::new([
+            (bbox.x_min() - bx, bbox.y_min() - by),
+            (bbox.x_max() + bx, bbox.y_max() + by),
+        ]);
+        let tolerance = self.simplify_tolerance(tid.z());
+        for &i in indices {
+            let feat = &features[i];
+            layer = self
+                .add_feature(layer, feat, tolerance, bbox, clip_bbox, transform)
+                .map_err(|e| e.with_layer(&self.layer).with_feature(i))?;
+        }
+        if layer.num_features() == 0 {
+            return Ok(None);
+        }
+        tile.add_layer(layer)?;
+        Ok(Some(tile))
+    }
+
+    /// Add one feature to a layer
+    fn add_feature(
+        &self,
+        layer: Layer,
+        feat: &SourceFeature,
+        tolerance: Option<f64>,
+        bbox: BBox<f64>,
+        clip_bbox: BBox<f64>,
+        transform: Transform<f64>,
+    ) -> Result<Layer> {
+        let geom_tp = feat.geom.geom_tp;
+        let mut enc =
+            layer.geom_encoder(geom_tp).bbox(bbox).transform(transform);
+        if let Some(tolerance) = tolerance {
+            enc = enc.simplify(tolerance);
+        }
+        match geom_tp {
+            // points are not clipped by the encoder
+            GeomType::Point => {
+                for part in &feat.geom.parts {
+                    enc.add_points(clip_points(part, clip_bbox))?;
+                }
+            }
+            _ => enc.add_geometries(feat.geom.parts.iter().cloned())?,
+        }
+        let geom = enc.encode()?;
+        if geom.is_empty() {
+            return Ok(layer);
+        }
+        let mut feature = layer.into_feature(geom);
+        for (key, value) in &feat.props {
+            feature.add_tag(key, value.clone());
+        }
+        Ok(feature.into_layer())
+    }
+}
+
+impl TilerIter {
+    /// Get the count of dropped features at each zoom level.
+    ///
+    /// Features are dropped by [Tiler::min_area] or [Tiler::min_length].
+    /// Counts are available for each zoom level once its first tile has
+    /// been built.
+    pub fn dropped(&self) -> &BTreeMap<u32, usize> {
+        &self.dropped
+    }
+}
+
+impl Iterator for TilerIter {
+    type Item = Result<(TileId, Tile)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(((y, x), indices)) = self.pending.next() {
+                let z = self.zoom - 1;
+                let tid = match TileId::new(x, y, z) {
+                    Ok(tid) => tid,
+                    Err(e) => return Some(Err(e)),
+                };
+                match self.tiler.build_tile(tid, &self.features, &indices) {
+                    Ok(Some(tile)) => return Some(Ok((tid, tile))),
+                    Ok(None) => continue,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            if self.zoom > *self.tiler.zooms.end() || self.zoom > 31 {
+                return None;
+            }
+            let (index, dropped) = self.tiler.index(&self.features, self.zoom);
+            self.pending = index.into_iter();
+            self.dropped.insert(self.zoom, dropped);
+            self.zoom += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::geo::WebMercatorPos;
+
+    fn square(x: f64, y: f64, size: f64) -> SourceGeom {
+        SourceGeom::new(
+            GeomType::Polygon,
+            [[
+                (x, y),
+                (x + size, y),
+                (x + size, y + size),
+                (x, y + size),
+                (x, y),
+            ]],
+        )
+    }
+
+    fn props(name: &str) -> Vec<(&'static str, TagValue)> {
+        vec![("name", TagValue::String(name.to_string()))]
+    }
+
+    #[test]
+    fn pyramid() {
+        let features = vec![
+            (square(-1_000.0, -1_000.0, 2_000.0), props("center")),
+            (square(10_000_000.0, 10_000_000.0, 1_000.0), props("ne")),
+        ];
+        let tiler = Tiler::new("squares", 0..=2);
+        let tiles: Vec<_> =
+            tiler.tile(features).collect::<Result<_>>().unwrap();
+        let tids: Vec<_> =
+            tiles.iter().map(|(t, _)| (t.z(), t.x(), t.y())).collect();
+        assert_eq!(
+            tids,
+            [
+                (0, 0, 0),
+                (1, 0, 0),
+                (1, 1, 0),
+                (1, 0, 1),
+                (1, 1, 1),
+                (2, 1, 1),
+                (2, 2, 1),
+                (2, 1, 2),
+                (2, 2, 2),
+            ]
+        );
+        let counts: Vec<_> = tiles
+            .iter()
+            .map(|(_, tile)| tile.layer("squares").unwrap().num_features())
+            .collect();
+        assert_eq!(counts, [2, 1, 2, 1, 1, 1, 2, 1, 1]);
+    }
+
+    #[test]
+    fn points() {
+        let size = WebMercatorPos::bbox().x_span() / 4.0;
+        // just west of the 1/0 tile
+        let pt = SourceGeom::new(GeomType::Point, [[(-1.0, size * 1.5)]]);
+        let tiler = Tiler::new("pts", 2..=2);
+        let tids: Vec<_> = tiler
+            .tile([(pt.clone(), props("a"))])
+            .map(|r| r.unwrap().0)
+            .map(|t| (t.x(), t.y()))
+            .collect();
+        assert_eq!(tids, [(1, 0)]);
+        let tiler = tiler.clip_buffer(16);
+        let tids: Vec<_> = tiler
+            .tile([(pt, props("a"))])
+            .map(|r| r.unwrap().0)
+            .map(|t| (t.x(), t.y()))
+            .collect();
+        assert_eq!(tids, [(1, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn auto_simplify() {
+        let tiler = Tiler::new("x", 0..=4);
+        assert_eq!(tiler.simplify_tolerance(0), None);
+        let tiler = tiler.auto_simplify(true);
+        assert_eq!(tiler.simplify_tolerance(0), Some(1.0));
+        assert_eq!(tiler.simplify_tolerance(3), Some(1.0));
+        assert_eq!(tiler.simplify_tolerance(4), None);
+        let tiler = tiler.simplify(2.5);
+        assert_eq!(tiler.simplify_tolerance(4), Some(2.5));
+        // nearly straight line, with a small wiggle
+        let line = SourceGeom::new(
+            GeomType::Linestring,
+            [[(-1_000_000.0, 0.0), (0.0, 1_000.0), (1_000_000.0, 0.0)]],
+        );
+        let size = |zooms: RangeInclusive<u32>| {
+            let tiler = Tiler::new("x", zooms).auto_simplify(true);
+            let (_, tile) = tiler
+                .tile([(line.clone(), props("a"))])
+                .next()
+                .unwrap()
+                .unwrap();
+            tile.to_bytes().unwrap().len()
+        };
+        // wiggle is smaller than one tile unit at zoom 0
+        assert!(size(0..=4) < size(0..=0));
+    }
+
+    #[test]
+    fn drop_small() {
+        // ~1000 m buildings and roads
+        let features = vec![
+            (square(100_000.0, 100_000.0, 1_000.0), props("a")),
+            (square(5_100_000.0, 100_000.0, 1_000.0), props("b")),
+            (
+                SourceGeom::new(
+                    GeomType::Linestring,
+                    [[(-5_100_000.0, 100_000.0), (-5_099_000.0, 100_000.0)]],
+                ),
+                props("c"),
+            ),
+        ]; // 1000 m is ~1.6 tile units at zoom 4, ~3.3 at zoom 5
+        let tiler = Tiler::new("x", 0..=10).min_area(2.0).min_length(3.0);
+        let mut tiles = tiler.tile(features);
+        let mut per_zoom = BTreeMap::new();
+        for res in tiles.by_ref() {
+            let (tid, tile) = res.unwrap();
+            let n = tile.layer("x").unwrap().num_features();
+            *per_zoom.entry(tid.z()).or_insert(0) += n;
+        }
+        assert_eq!(per_zoom.get(&3), None);
+        assert_eq!(per_zoom.get(&4), Some(&2));
+        assert_eq!(per_zoom.get(&5), Some(&3));
+        let dropped = tiles.dropped();
+        assert_eq!(dropped.len(), 11);
+        assert_eq!(dropped[&0], 3);
+        assert_eq!(dropped[&4], 1);
+        assert_eq!(dropped[&5], 0);
+    }
+
+    #[test]
+    fn drop_small_unclosed() {
+        let (x, y, size) = (100_000.0, 100_000.0, 1_000.0);
+        let ring = [(x, y), (x + size, y), (x + size, y + size), (x, y + size)];
+        let geom = SourceGeom::new(GeomType::Polygon, [ring]);
+        let tiler = Tiler::new("x", 5..=5).min_area(2.0);
+        assert!(!tiler.is_too_small(&geom, 5));
+        assert!(tiler.is_too_small(&geom, 3));
+        assert!(!tiler.is_too_small(&square(x, y, size), 5));
+    }
+}
@@ -0,0 +1,69 @@
+// tileset.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Tileset summary, shared by archive writers.
+//!
+use crate::geo::{WebMercatorPos, Wgs84Pos};
+use crate::mapgrid::{MapGrid, TileId};
+use crate::schema::TilesetSchema;
+use crate::tile::Tile;
+use pointy::BBox;
+
+/// Summary of all tiles written to an archive
+#[derive(Default)]
+pub(crate) struct TilesetInfo {
+    /// Union of tile bounds (Web Mercator)
+    tile_bbox: Option<BBox<f64>>,
+
+    /// Minimum zoom level
+    minzoom: Option<u32>,
+
+    /// Maximum zoom level
+    maxzoom: Option<u32>,
+
+    /// Layer schema
+    schema: TilesetSchema,
+}
+
+impl TilesetInfo {
+    /// Add a tile to the summary
+    pub fn add_tile(&mut self, tid: TileId, tile: &Tile) {
+        self.minzoom = Some(self.minzoom.map_or(tid.z(), |z| z.min(tid.z())));
+        self.maxzoom = Some(self.maxzoom.map_or(tid.z(), |z| z.max(tid.z())));
+        let bbox = MapGrid::default().tile_bbox(tid);
+        match &mut self.tile_bbox {
+            Some(b) => b.extend([
+                (bbox.x_min(), bbox.y_min()),
+                (bbox.x_max(), bbox.y_max()),
+            ]),
+            None => self.tile_bbox = Some(bbox),
+        }
+        self.schema.add_tile(tid.z(), tile);
+    }
+
+    /// Get the minimum zoom level
+    pub fn minzoom(&self) -> Option<u32> {
+        self.minzoom
+    }
+
+    /// Get the maximum zoom level
+    pub fn maxzoom(&self) -> Option<u32> {
+        self.maxzoom
+    }
+
+    /// Get bounds of all tiles (WGS-84 west, south, east, north)
+    pub fn bounds(&self) -> Option<[f64; 4]> {
+        let bbox = self.tile_bbox?;
+        let sw =
+            Wgs84Pos::from(WebMercatorPos::new(bbox.x_min(), bbox.y_min()));
+        let ne =
+            Wgs84Pos::from(WebMercatorPos::new(bbox.x_max(), bbox.y_max()));
+        Some([sw.lon_deg(), sw.lat_deg(), ne.lon_deg(), ne.lat_deg()])
+    }
+
+    /// Get the layer schema
+    pub fn schema(&self) -> &TilesetSchema {
+        &self.schema
+    }
+}
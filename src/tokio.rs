@@ -0,0 +1,60 @@
+// tokio.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Async tile I/O with tokio.
+//!
+use crate::error::Result;
+use crate::tile::Tile;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+impl Tile {
+    /// Write the tile asynchronously.
+    ///
+    /// * `out` Async writer to output the tile.
+    ///
+    /// The tile is serialized into a buffer, which is then written without
+    /// blocking.  Returns the number of bytes written.
+    pub async fn write_async<W>(&self, out: &mut W) -> Result<usize>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let buf = self.to_bytes()?;
+        out.write_all(&buf).await?;
+        out.flush().await?;
+        Ok(buf.len())
+    }
+
+    /// Read a tile asynchronously.
+    ///
+    /// * `input` Async reader of encoded tile data.
+    ///
+    /// All data is read until EOF, then decoded with
+    /// [from_bytes](#method.from_bytes).
+    pub async fn read_async<R>(input: &mut R) -> Result<Self>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+    {
+        let mut buf = Vec::new();
+        input.read_to_end(&mut buf).await?;
+        Tile::from_bytes(&buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn roundtrip() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("First Layer");
+        tile.add_layer(layer).unwrap();
+        let mut out = Vec::new();
+        let len = tile.write_async(&mut out).await.unwrap();
+        assert_eq!(len, out.len());
+        let tile = Tile::read_async(&mut &out[..]).await.unwrap();
+        assert_eq!(tile.num_layers(), 1);
+        assert_eq!(tile.layer("First Layer").unwrap().name(), "First Layer");
+    }
+}
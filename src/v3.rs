@@ -0,0 +1,1015 @@
+// v3.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Experimental MVT 3.0 draft attribute and elevation encoding.
+//!
+//! The 3.0 proposal adds typed attributes to features.  Each attribute is a
+//! key index followed by a "complex value": a 64-bit integer with the value
+//! type in the lowest 4 bits.  Values can be inline, or refer to new layer
+//! tables of strings, floats, doubles and integers.  Lists and maps are
+//! nested, and number lists are delta-encoded with a scaling.
+//!
+//! Features can also have an elevation for each vertex, delta-encoded with
+//! a layer elevation scaling.
+//!
+//! These fields are not in the 2.1 protobuf definition, so they are stored
+//! as unknown fields.  The proposal is a draft, and this encoding may change.
+use crate::error::{Error, Result};
+use crate::value::TagValue;
+use crate::vector_tile::tile::{Feature as VtFeature, Layer as VtLayer};
+use crate::view::{Reader, WIRE_FIXED64, WIRE_VARINT};
+use ahash::HashMap;
+use protobuf::{UnknownFields, UnknownValue, UnknownValueRef};
+use std::hash::Hash;
+
+/// Layer field: string values
+const STRING_VALUES: u32 = 6;
+
+/// Layer field: float values
+const FLOAT_VALUES: u32 = 7;
+
+/// Layer field: double values
+const DOUBLE_VALUES: u32 = 8;
+
+/// Layer field: integer values
+const INT_VALUES: u32 = 9;
+
+/// Layer field: elevation scaling
+const ELEVATION_SCALING: u32 = 10;
+
+/// Layer field: attribute scalings
+const ATTRIBUTE_SCALINGS: u32 = 11;
+
+/// Feature field: attributes
+const ATTRIBUTES: u32 = 5;
+
+/// Feature field: elevations
+const ELEVATIONS: u32 = 7;
+
+/// Largest parameter of an inline value (60 bits)
+const INLINE_MAX: u64 = (1 << 60) - 1;
+
+/// Complex value type: string (index)
+const TP_STRING: u64 = 0;
+
+/// Complex value type: float (index)
+const TP_FLOAT: u64 = 1;
+
+/// Complex value type: double (index)
+const TP_DOUBLE: u64 = 2;
+
+/// Complex value type: unsigned integer (index)
+const TP_UINT: u64 = 3;
+
+/// Complex value type: signed integer (index)
+const TP_SINT: u64 = 4;
+
+/// Complex value type: inline unsigned integer
+const TP_INLINE_UINT: u64 = 5;
+
+/// Complex value type: inline signed integer (zigzag)
+const TP_INLINE_SINT: u64 = 6;
+
+/// Complex value type: boolean / null
+const TP_BOOL_NULL: u64 = 7;
+
+/// Complex value type: list (count)
+const TP_LIST: u64 = 8;
+
+/// Complex value type: map (count)
+const TP_MAP: u64 = 9;
+
+/// Complex value type: delta-encoded number list (count)
+const TP_NUMBER_LIST: u64 = 10;
+
+/// Scaling of number list values and elevations.
+///
+/// Each value `v` is encoded as an integer `n`, where
+/// `v = base + multiplier * (offset + n)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scaling {
+    /// Offset added to encoded integers
+    pub offset: i64,
+
+    /// Multiplier (precision) of values
+    pub multiplier: f64,
+
+    /// Base value
+    pub base: f64,
+}
+
+/// Attribute value (experimental MVT 3.0 draft).
+///
+/// Unlike [TagValue], attributes can be null or nested.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttrValue {
+    /// Null value
+    Null,
+
+    /// Boolean value
+    Bool(bool),
+
+    /// String value
+    String(String),
+
+    /// Float (32-bit) value
+    Float(f32),
+
+    /// Double (64-bit float) value
+    Double(f64),
+
+    /// Unsigned integer value
+    Uint(u64),
+
+    /// Signed integer value
+    Sint(i64),
+
+    /// List of values
+    List(Vec<AttrValue>),
+
+    /// Map of keys to values
+    Map(Vec<(String, AttrValue)>),
+
+    /// List of numbers (or nulls), delta-encoded with a scaling
+    NumberList(Scaling, Vec<Option<f64>>),
+}
+
+/// Deduplicated table of layer values
+struct Table<K> {
+    /// Positions of values
+    pos: HashMap<K, u64>,
+
+    /// Number of values in table
+    len: u64,
+}
+
+/// Attribute value tables of a layer, for deduplicating values
+pub(crate) struct AttrTables {
+    strings: Table<String>,
+    floats: Table<u32>,
+    doubles: Table<u64>,
+    ints: Table<u64>,
+    scalings: Table<(i64, u64, u64)>,
+    elevation_scaling: Option<Scaling>,
+}
+
+/// Attribute table lengths of a layer, for rolling back an abandoned feature
+pub(crate) struct AttrMark {
+    version: Option<u32>,
+    strings: u64,
+    floats: u64,
+    doubles: u64,
+    ints: u64,
+    scalings: u64,
+    elevation_scaling: Option<Scaling>,
+    has_elevation_scaling: bool,
+}
+
+/// Attribute value tables decoded from a layer
+#[derive(Default)]
+struct Tables {
+    strings: Vec<String>,
+    floats: Vec<f32>,
+    doubles: Vec<f64>,
+    ints: Vec<u64>,
+    scalings: Vec<Scaling>,
+    elevation_scaling: Option<Scaling>,
+}
+
+/// Attribute value decoder
+struct Decoder<'a> {
+    keys: &'a [String],
+    tables: &'a Tables,
+    values: std::slice::Iter<'a, u64>,
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Scaling {
+            offset: 0,
+            multiplier: 1.0,
+            base: 0.0,
+        }
+    }
+}
+
+impl Scaling {
+    /// Encode a value to a scaled integer
+    fn encode(&self, v: f64) -> i64 {
+        ((v - self.base) / self.multiplier).round() as i64 - self.offset
+    }
+
+    /// Decode a scaled integer to a value
+    fn decode(&self, n: i64) -> f64 {
+        self.base + self.multiplier * self.offset.wrapping_add(n) as f64
+    }
+
+    /// Encode as a protobuf message
+    fn to_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        put_varint(&mut buf, 1 << 3);
+        put_varint(&mut buf, zigzag(self.offset));
+        buf.push((2 << 3) | 1);
+        buf.extend(self.multiplier.to_le_bytes());
+        buf.push((3 << 3) | 1);
+        buf.extend(self.base.to_le_bytes());
+        buf
+    }
+
+    /// Decode from a protobuf message
+    fn from_bytes(buf: &[u8]) -> Result<Self> {
+        let mut scaling = Scaling::default();
+        let mut reader = Reader::new(buf);
+        while let Some((field, wire)) = reader.field()? {
+            match (field, wire) {
+                (1, WIRE_VARINT) => scaling.offset = unzigzag(reader.varint()?),
+                (2, WIRE_FIXED64) => {
+                    scaling.multiplier = f64::from_le_bytes(reader.fixed64()?)
+                }
+                (3, WIRE_FIXED64) => {
+                    scaling.base = f64::from_le_bytes(reader.fixed64()?)
+                }
+                _ => reader.skip(wire)?,
+            }
+        }
+        Ok(scaling)
+    }
+}
+
+impl From<TagValue> for AttrValue {
+    fn from(value: TagValue) -> Self {
+        match value {
+            TagValue::String(v) => AttrValue::String(v),
+            TagValue::F64(v) => AttrValue::Double(v),
+            TagValue::F32(v) => AttrValue::Float(v),
+            TagValue::I64(v) | TagValue::Sint(v) => AttrValue::Sint(v),
+            TagValue::U64(v) => AttrValue::Uint(v),
+            TagValue::Bool(v) => AttrValue::Bool(v),
+        }
+    }
+}
+
+impl AttrValue {
+    /// Collect keys of nested maps
+    fn map_keys<'a>(&'a self, keys: &mut Vec<&'a str>) {
+        match self {
+            AttrValue::List(values) => {
+                values.iter().for_each(|v| v.map_keys(keys));
+            }
+            AttrValue::Map(entries) => {
+                for (key, value) in entries {
+                    keys.push(key);
+                    value.map_keys(keys);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl<K: Eq + Hash> Table<K> {
+    /// Create a table from existing values
+    fn new(values: impl IntoIterator<Item = K>) -> Self {
+        let mut table = Table {
+            pos: HashMap::default(),
+            len: 0,
+        };
+        for value in values {
+            table.pos.entry(value).or_insert(table.len);
+            table.len += 1;
+        }
+        table
+    }
+
+    /// Get position of a value, calling `add` if it is not in the table
+    fn pos(&mut self, value: K, add: impl FnOnce()) -> u64 {
+        if let Some(pos) = self.pos.get(&value) {
+            return *pos;
+        }
+        add();
+        let pos = self.len;
+        self.pos.insert(value, pos);
+        self.len += 1;
+        pos
+    }
+
+    /// Truncate the table, returning the number of values removed
+    fn truncate(&mut self, len: u64) -> u64 {
+        let removed = self.len.saturating_sub(len);
+        if removed > 0 {
+            self.pos.retain(|_v, pos| *pos < len);
+            self.len = len;
+        }
+        removed
+    }
+}
+
+impl AttrTables {
+    /// Create attribute tables from the unknown fields of a layer
+    pub(crate) fn new(layer: &VtLayer) -> Self {
+        let tables = Tables::decode(layer).unwrap_or_else(|e| {
+            log::warn!("Invalid attribute tables: {e}");
+            Tables::default()
+        });
+        AttrTables {
+            strings: Table::new(tables.strings),
+            floats: Table::new(tables.floats.iter().map(|v| v.to_bits())),
+            doubles: Table::new(tables.doubles.iter().map(|v| v.to_bits())),
+            ints: Table::new(tables.ints),
+            scalings: Table::new(
+                tables.scalings.iter().map(|s| scaling_key(*s)),
+            ),
+            elevation_scaling: tables.elevation_scaling,
+        }
+    }
+
+    /// Mark the current table lengths and version of a layer
+    pub(crate) fn mark(&self, layer: &VtLayer) -> AttrMark {
+        let fields = layer.special_fields.unknown_fields();
+        AttrMark {
+            version: layer.version,
+            strings: self.strings.len,
+            floats: self.floats.len,
+            doubles: self.doubles.len,
+            ints: self.ints.len,
+            scalings: self.scalings.len,
+            elevation_scaling: self.elevation_scaling,
+            has_elevation_scaling: fields.get(ELEVATION_SCALING).is_some(),
+        }
+    }
+
+    /// Roll back tables and version of a layer to a mark
+    pub(crate) fn rollback(&mut self, mark: &AttrMark, layer: &mut VtLayer) {
+        let fields = layer.special_fields.mut_unknown_fields();
+        let n = self.strings.truncate(mark.strings);
+        remove_last(fields, STRING_VALUES, n, |v| {
+            matches!(v, UnknownValue::LengthDelimited(_))
+        });
+        let n = self.floats.truncate(mark.floats);
+        remove_last(fields, FLOAT_VALUES, n, |v| {
+            matches!(v, UnknownValue::Fixed32(_))
+        });
+        let n = self.doubles.truncate(mark.doubles);
+        remove_last(fields, DOUBLE_VALUES, n, |v| {
+            matches!(v, UnknownValue::Fixed64(_))
+        });
+        let n = self.ints.truncate(mark.ints);
+        remove_last(fields, INT_VALUES, n, |v| {
+            matches!(v, UnknownValue::Fixed64(_))
+        });
+        let n = self.scalings.truncate(mark.scalings);
+        remove_last(fields, ATTRIBUTE_SCALINGS, n, |v| {
+            matches!(v, UnknownValue::LengthDelimited(_))
+        });
+        if !mark.has_elevation_scaling {
+            fields.remove(ELEVATION_SCALING);
+        }
+        self.elevation_scaling = mark.elevation_scaling;
+        layer.version = mark.version;
+    }
+
+    /// Set the elevation scaling
+    pub(crate) fn set_elevation_scaling(&mut self, scaling: Scaling) {
+        self.elevation_scaling = Some(scaling);
+    }
+
+    /// Encode elevations of a feature.
+    ///
+    /// * `elevations` Elevation of each vertex.
+    /// * `fields` Layer unknown fields.
+    ///
+    /// Returns packed elevation data for the feature.
+    pub(crate) fn encode_elevations(
+        &mut self,
+        elevations: &[f64],
+        fields: &mut UnknownFields,
+    ) -> Vec<u8> {
+        let scaling = *self.elevation_scaling.get_or_insert_default();
+        if fields.get(ELEVATION_SCALING).is_none() {
+            fields.add_length_delimited(ELEVATION_SCALING, scaling.to_bytes());
+        }
+        let mut buf = Vec::new();
+        let mut prev = 0i32;
+        for z in elevations {
+            let n = scaling
+                .encode(*z)
+                .clamp(i64::from(i32::MIN), i64::from(i32::MAX))
+                as i32;
+            put_varint(&mut buf, zigzag(n.wrapping_sub(prev).into()));
+            prev = n;
+        }
+        buf
+    }
+
+    /// Encode an attribute, adding values to layer tables.
+    ///
+    /// * `key` Key index.
+    /// * `value` Attribute value.
+    /// * `keys` Key indices of nested map keys.
+    /// * `fields` Layer unknown fields.
+    ///
+    /// Returns packed attribute data for the feature.
+    pub(crate) fn encode(
+        &mut self,
+        key: u64,
+        value: &AttrValue,
+        keys: &dyn Fn(&str) -> u64,
+        fields: &mut UnknownFields,
+    ) -> Vec<u8> {
+        let mut attrs = vec![key];
+        self.encode_value(value, keys, fields, &mut attrs);
+        let mut buf = Vec::new();
+        for v in attrs {
+            put_varint(&mut buf, v);
+        }
+        buf
+    }
+
+    /// Encode a complex value
+    fn encode_value(
+        &mut self,
+        value: &AttrValue,
+        keys: &dyn Fn(&str) -> u64,
+        fields: &mut UnknownFields,
+        out: &mut Vec<u64>,
+    ) {
+        let (tp, param) = match value {
+            AttrValue::Null => (TP_BOOL_NULL, 2),
+            AttrValue::Bool(v) => (TP_BOOL_NULL, u64::from(*v)),
+            AttrValue::String(v) => {
+                let pos = self.strings.pos(v.clone(), || {
+                    fields.add_length_delimited(
+                        STRING_VALUES,
+                        v.as_bytes().to_vec(),
+                    );
+                });
+                (TP_STRING, pos)
+            }
+            AttrValue::Float(v) => {
+                let bits = v.to_bits();
+                let pos = self.floats.pos(bits, || {
+                    fields.add_fixed32(FLOAT_VALUES, bits);
+                });
+                (TP_FLOAT, pos)
+            }
+            AttrValue::Double(v) => {
+                let bits = v.to_bits();
+                let pos = self.doubles.pos(bits, || {
+                    fields.add_fixed64(DOUBLE_VALUES, bits);
+                });
+                (TP_DOUBLE, pos)
+            }
+            AttrValue::Uint(v) if *v <= INLINE_MAX => (TP_INLINE_UINT, *v),
+            AttrValue::Uint(v) => (TP_UINT, self.int_pos(*v, fields)),
+            AttrValue::Sint(v) if zigzag(*v) <= INLINE_MAX => {
+                (TP_INLINE_SINT, zigzag(*v))
+            }
+            AttrValue::Sint(v) => (TP_SINT, self.int_pos(*v as u64, fields)),
+            AttrValue::List(values) => {
+                out.push(TP_LIST | ((values.len() as u64) << 4));
+                for value in values {
+                    self.encode_value(value, keys, fields, out);
+                }
+                return;
+            }
+            AttrValue::Map(entries) => {
+                out.push(TP_MAP | ((entries.len() as u64) << 4));
+                for (key, value) in entries {
+                    out.push(keys(key));
+                    self.encode_value(value, keys, fields, out);
+                }
+                return;
+            }
+            AttrValue::NumberList(scaling, values) => {
+                out.push(TP_NUMBER_LIST | ((values.len() as u64) << 4));
+                let pos = self.scalings.pos(scaling_key(*scaling), || {
+                    fields.add_length_delimited(
+                        ATTRIBUTE_SCALINGS,
+                        scaling.to_bytes(),
+                    );
+                });
+                out.push(pos);
+                let mut prev = 0i64;
+                for value in values {
+                    match value {
+                        Some(v) => {
+                            let n = scaling.encode(*v);
+                            out.push(zigzag(n.wrapping_sub(prev)) + 1);
+                            prev = n;
+                        }
+                        None => out.push(0),
+                    }
+                }
+                return;
+            }
+        };
+        out.push(tp | (param << 4));
+    }
+
+    /// Get position of an integer value, adding it if necessary
+    fn int_pos(&mut self, v: u64, fields: &mut UnknownFields) -> u64 {
+        self.ints.pos(v, || fields.add_fixed64(INT_VALUES, v))
+    }
+}
+
+impl Tables {
+    /// Decode tables from the unknown fields of a layer
+    fn decode(layer: &VtLayer) -> Result<Self> {
+        let mut tables = Tables::default();
+        for (field, value) in layer.special_fields.unknown_fields().iter() {
+            match (field, value) {
+                (STRING_VALUES, UnknownValueRef::LengthDelimited(v)) => {
+                    let v = std::str::from_utf8(v)
+                        .map_err(|_| Error::InvalidData())?;
+                    tables.strings.push(v.to_string());
+                }
+                (FLOAT_VALUES, UnknownValueRef::Fixed32(v)) => {
+                    tables.floats.push(f32::from_bits(v));
+                }
+                (FLOAT_VALUES, UnknownValueRef::LengthDelimited(v)) => {
+                    for b in packed_fixed::<4>(v)? {
+                        tables.floats.push(f32::from_le_bytes(b));
+                    }
+                }
+                (DOUBLE_VALUES, UnknownValueRef::Fixed64(v)) => {
+                    tables.doubles.push(f64::from_bits(v));
+                }
+                (DOUBLE_VALUES, UnknownValueRef::LengthDelimited(v)) => {
+                    for b in packed_fixed::<8>(v)? {
+                        tables.doubles.push(f64::from_le_bytes(b));
+                    }
+                }
+                (INT_VALUES, UnknownValueRef::Fixed64(v)) => {
+                    tables.ints.push(v);
+                }
+                (INT_VALUES, UnknownValueRef::LengthDelimited(v)) => {
+                    for b in packed_fixed::<8>(v)? {
+                        tables.ints.push(u64::from_le_bytes(b));
+                    }
+                }
+                (ATTRIBUTE_SCALINGS, UnknownValueRef::LengthDelimited(v)) => {
+                    tables.scalings.push(Scaling::from_bytes(v)?);
+                }
+                (ELEVATION_SCALING, UnknownValueRef::LengthDelimited(v)) => {
+                    tables.elevation_scaling = Some(Scaling::from_bytes(v)?);
+                }
+                _ => (),
+            }
+        }
+        Ok(tables)
+    }
+}
+
+impl Decoder<'_> {
+    /// Get the next integer
+    fn next(&mut self) -> Result<u64> {
+        self.values.next().copied().ok_or(Error::InvalidData())
+    }
+
+    /// Decode a key
+    fn key(&mut self) -> Result<String> {
+        let pos = usize::try_from(self.next()?).ok();
+        pos.and_then(|pos| self.keys.get(pos))
+            .cloned()
+            .ok_or(Error::InvalidData())
+    }
+
+    /// Decode a complex value
+    fn value(&mut self) -> Result<AttrValue> {
+        let v = self.next()?;
+        let (tp, param) = (v & 0xF, v >> 4);
+        let tables = self.tables;
+        Ok(match tp {
+            TP_STRING => {
+                AttrValue::String(get(&tables.strings, param)?.clone())
+            }
+            TP_FLOAT => AttrValue::Float(*get(&tables.floats, param)?),
+            TP_DOUBLE => AttrValue::Double(*get(&tables.doubles, param)?),
+            TP_UINT => AttrValue::Uint(*get(&tables.ints, param)?),
+            TP_SINT => AttrValue::Sint(*get(&tables.ints, param)? as i64),
+            TP_INLINE_UINT => AttrValue::Uint(param),
+            TP_INLINE_SINT => AttrValue::Sint(unzigzag(param)),
+            TP_BOOL_NULL => match param {
+                0 => AttrValue::Bool(false),
+                1 => AttrValue::Bool(true),
+                2 => AttrValue::Null,
+                _ => return Err(Error::InvalidData()),
+            },
+            TP_LIST => AttrValue::List(
+                (0..param).map(|_| self.value()).collect::<Result<_>>()?,
+            ),
+            TP_MAP => AttrValue::Map(
+                (0..param)
+                    .map(|_| Ok((self.key()?, self.value()?)))
+                    .collect::<Result<_>>()?,
+            ),
+            TP_NUMBER_LIST => {
+                let scaling = *get(&tables.scalings, self.next()?)?;
+                let mut prev = 0i64;
+                let mut values = Vec::new();
+                for _ in 0..param {
+                    match self.next()? {
+                        0 => values.push(None),
+                        n => {
+                            prev = prev.wrapping_add(unzigzag(n - 1));
+                            values.push(Some(scaling.decode(prev)));
+                        }
+                    }
+                }
+                AttrValue::NumberList(scaling, values)
+            }
+            _ => return Err(Error::InvalidData()),
+        })
+    }
+}
+
+/// Decode attributes of a feature.
+///
+/// Returns `Error::InvalidData` for malformed attributes.
+pub(crate) fn decode_attributes(
+    layer: &VtLayer,
+    feature: &VtFeature,
+) -> Result<Vec<(String, AttrValue)>> {
+    let mut attrs = Vec::new();
+    for (field, value) in feature.special_fields.unknown_fields().iter() {
+        match (field, value) {
+            (ATTRIBUTES, UnknownValueRef::Varint(v)) => attrs.push(v),
+            (ATTRIBUTES, UnknownValueRef::LengthDelimited(v)) => {
+                let mut reader = Reader::new(v);
+                while !reader.is_empty() {
+                    attrs.push(reader.varint()?);
+                }
+            }
+            _ => (),
+        }
+    }
+    if attrs.is_empty() {
+        return Ok(Vec::new());
+    }
+    let tables = Tables::decode(layer)?;
+    let mut decoder = Decoder {
+        keys: &layer.keys,
+        tables: &tables,
+        values: attrs.iter(),
+    };
+    let mut values = Vec::new();
+    while decoder.values.len() > 0 {
+        values.push((decoder.key()?, decoder.value()?));
+    }
+    Ok(values)
+}
+
+/// Decode elevations of a feature.
+///
+/// Returns `Error::InvalidData` for malformed elevations.
+pub(crate) fn decode_elevations(
+    layer: &VtLayer,
+    feature: &VtFeature,
+) -> Result<Vec<f64>> {
+    let mut deltas = Vec::new();
+    for (field, value) in feature.special_fields.unknown_fields().iter() {
+        match (field, value) {
+            (ELEVATIONS, UnknownValueRef::Varint(v)) => deltas.push(v),
+            (ELEVATIONS, UnknownValueRef::LengthDelimited(v)) => {
+                let mut reader = Reader::new(v);
+                while !reader.is_empty() {
+                    deltas.push(reader.varint()?);
+                }
+            }
+            _ => (),
+        }
+    }
+    if deltas.is_empty() {
+        return Ok(Vec::new());
+    }
+    let scaling = Tables::decode(layer)?.elevation_scaling.unwrap_or_default();
+    let mut prev = 0i32;
+    Ok(deltas
+        .into_iter()
+        .map(|d| {
+            prev = prev.wrapping_add(unzigzag(d) as i32);
+            scaling.decode(prev.into())
+        })
+        .collect())
+}
+
+/// Add packed elevation data to a feature
+pub(crate) fn add_elevations(feature: &mut VtFeature, elevations: Vec<u8>) {
+    feature
+        .special_fields
+        .mut_unknown_fields()
+        .add_length_delimited(ELEVATIONS, elevations);
+}
+
+/// Add packed attribute data to a feature
+pub(crate) fn add_attributes(feature: &mut VtFeature, attrs: Vec<u8>) {
+    feature
+        .special_fields
+        .mut_unknown_fields()
+        .add_length_delimited(ATTRIBUTES, attrs);
+}
+
+/// Get nested map keys of an attribute value
+pub(crate) fn map_keys(value: &AttrValue) -> Vec<&str> {
+    let mut keys = Vec::new();
+    value.map_keys(&mut keys);
+    keys
+}
+
+/// Get a table value by index
+fn get<T>(table: &[T], pos: u64) -> Result<&T> {
+    usize::try_from(pos)
+        .ok()
+        .and_then(|pos| table.get(pos))
+        .ok_or(Error::InvalidData())
+}
+
+/// Split packed fixed-size values
+fn packed_fixed<const N: usize>(
+    buf: &[u8],
+) -> Result<impl Iterator<Item = [u8; N]> + '_> {
+    if !buf.len().is_multiple_of(N) {
+        return Err(Error::InvalidData());
+    }
+    Ok(buf.chunks_exact(N).map(|b| b.try_into().unwrap_or([0; N])))
+}
+
+/// Remove the last `n` values of a field added by the encoder
+///
+/// Values which do not match `added` (e.g. packed values of a decoded
+/// layer) are kept.
+fn remove_last(
+    fields: &mut UnknownFields,
+    field: u32,
+    mut n: u64,
+    added: impl Fn(&UnknownValue) -> bool,
+) {
+    if n == 0 {
+        return;
+    }
+    let mut values: Vec<UnknownValue> = fields
+        .iter()
+        .filter(|(f, _v)| *f == field)
+        .map(|(_f, v)| match v {
+            UnknownValueRef::Fixed32(v) => UnknownValue::Fixed32(v),
+            UnknownValueRef::Fixed64(v) => UnknownValue::Fixed64(v),
+            UnknownValueRef::Varint(v) => UnknownValue::Varint(v),
+            UnknownValueRef::LengthDelimited(v) => {
+                UnknownValue::LengthDelimited(v.to_vec())
+            }
+        })
+        .collect();
+    for i in (0..values.len()).rev() {
+        if n == 0 {
+            break;
+        }
+        if added(&values[i]) {
+            values.remove(i);
+            n -= 1;
+        }
+    }
+    fields.remove(field);
+    for value in values {
+        fields.add_value(field, value);
+    }
+}
+
+/// Get hashable key of a scaling
+fn scaling_key(s: Scaling) -> (i64, u64, u64) {
+    (s.offset, s.multiplier.to_bits(), s.base.to_bits())
+}
+
+/// Append a varint to a buffer
+fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+/// Zigzag encode a signed integer
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+/// Zigzag decode an unsigned integer
+fn unzigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{GeomEncoder, GeomType, Tile};
+
+    fn attrs() -> Vec<(String, AttrValue)> {
+        let scaling = Scaling {
+            offset: 10,
+            multiplier: 0.5,
+            base: 100.0,
+        };
+        vec![
+            ("name".into(), AttrValue::String("Main St".into())),
+            ("none".into(), AttrValue::Null),
+            ("oneway".into(), AttrValue::Bool(true)),
+            ("width".into(), AttrValue::Float(7.5)),
+            ("length".into(), AttrValue::Double(1234.5)),
+            ("lanes".into(), AttrValue::Uint(2)),
+            ("big".into(), AttrValue::Uint(u64::MAX)),
+            ("grade".into(), AttrValue::Sint(-3)),
+            ("min".into(), AttrValue::Sint(i64::MIN)),
+            (
+                "names".into(),
+                AttrValue::List(vec![
+                    AttrValue::String("Main St".into()),
+                    AttrValue::String("US 52".into()),
+                ]),
+            ),
+            (
+                "speed".into(),
+                AttrValue::Map(vec![
+                    ("car".into(), AttrValue::Uint(55)),
+                    ("truck".into(), AttrValue::Double(45.5)),
+                ]),
+            ),
+            (
+                "elevations".into(),
+                AttrValue::NumberList(
+                    scaling,
+                    vec![Some(100.0), Some(102.5), None, Some(99.0)],
+                ),
+            ),
+        ]
+    }
+
+    fn tile(attrs: &[(String, AttrValue)]) -> Tile {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("roads");
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(1.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.add_tag_string("name", "Main St");
+        for (key, value) in attrs {
+            feature.add_attribute(key, value);
+        }
+        tile.add_layer(feature.into_layer()).unwrap();
+        tile
+    }
+
+    #[test]
+    fn roundtrip() {
+        let attrs = attrs();
+        let bytes = tile(&attrs).to_bytes().unwrap();
+        let tile = Tile::from_bytes(&bytes).unwrap();
+        let layer = tile.layer("roads").unwrap();
+        assert_eq!(layer.version(), 3);
+        let feature = layer.features().next().unwrap();
+        assert_eq!(feature.attributes().unwrap(), attrs);
+        // tags are still 2.1 encoded
+        assert_eq!(feature.get_str("name"), Some("Main St"));
+    }
+
+    #[test]
+    fn tables() {
+        let value = AttrValue::String("Main St".into());
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("roads");
+        for _ in 0..2 {
+            let geom = GeomEncoder::new(GeomType::Point)
+                .point(1.0, 2.0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            let mut feature = layer.into_feature(geom);
+            feature.add_attribute("name", &value);
+            layer = feature.into_layer();
+        }
+        assert_eq!(layer.keys_len(), 1);
+        tile.add_layer(layer).unwrap();
+        // decoded layers keep deduplicating attribute values
+        let mut layer = tile.into_layers().remove(0);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(3.0, 4.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.add_attribute("name", &value);
+        feature.add_attribute("ref", &AttrValue::String("US 52".into()));
+        layer = feature.into_layer();
+        let mut tile = Tile::new(4096);
+        tile.add_layer(layer).unwrap();
+        let bytes = tile.to_bytes().unwrap();
+        let count = bytes.windows(7).filter(|w| w == b"Main St").count();
+        assert_eq!(count, 1);
+        let tile = Tile::from_bytes(&bytes).unwrap();
+        let layer = tile.layer("roads").unwrap();
+        let attrs: Vec<_> = layer
+            .features()
+            .map(|f| f.attributes().unwrap().len())
+            .collect();
+        assert_eq!(attrs, [1, 1, 2]);
+    }
+
+    #[test]
+    fn abandon() {
+        let point = || {
+            GeomEncoder::new(GeomType::Point)
+                .point(1.0, 2.0)
+                .unwrap()
+                .encode()
+                .unwrap()
+        };
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("roads");
+        let mut feature = layer.into_feature(point());
+        for (key, value) in attrs() {
+            feature.add_attribute(&key, &value);
+        }
+        let layer = feature.layer();
+        assert_eq!(layer.version(), 2);
+        let mut feature = layer.into_feature(point());
+        feature.add_attribute("name", &AttrValue::String("US 52".into()));
+        let mut feature = feature.into_layer().into_feature(point());
+        for (key, value) in attrs() {
+            feature.add_attribute(&key, &value);
+        }
+        let mut layer = feature.layer();
+        assert_eq!(layer.version(), 3);
+        let mut feature = layer.into_feature(point());
+        feature.add_attribute("ref", &AttrValue::Double(1234.5));
+        feature.add_attribute("ref", &AttrValue::String("US 52".into()));
+        layer = feature.into_layer();
+        tile.add_layer(layer).unwrap();
+        let bytes = tile.to_bytes().unwrap();
+        assert_eq!(bytes.windows(7).filter(|w| w == b"Main St").count(), 0);
+        let tile = Tile::from_bytes(&bytes).unwrap();
+        let layer = tile.layer("roads").unwrap();
+        let attrs: Vec<_> =
+            layer.features().map(|f| f.attributes().unwrap()).collect();
+        assert_eq!(
+            attrs,
+            [
+                vec![("name".into(), AttrValue::String("US 52".into()))],
+                vec![
+                    ("ref".into(), AttrValue::Double(1234.5)),
+                    ("ref".into(), AttrValue::String("US 52".into())),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let mut layer = VtLayer::new();
+        layer.keys.push("k".into());
+        let mut feature = VtFeature::new();
+        // string index out of table
+        add_attributes(&mut feature, vec![0, 0x10]);
+        assert!(decode_attributes(&layer, &feature).is_err());
+        // invalid bool / null
+        let mut feature = VtFeature::new();
+        add_attributes(&mut feature, vec![0, 0x37]);
+        assert!(decode_attributes(&layer, &feature).is_err());
+        // list missing values
+        let mut feature = VtFeature::new();
+        add_attributes(&mut feature, vec![0, 0x28]);
+        assert!(decode_attributes(&layer, &feature).is_err());
+    }
+
+    #[test]
+    fn elevations() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("peaks").elevation_scaling(Scaling {
+            offset: 0,
+            multiplier: 0.25,
+            base: -100.0,
+        });
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point_3d(1.0, 2.0, 350.25)
+            .unwrap()
+            .point_3d(3.0, 4.0, -20.5)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let feature = layer.into_feature(geom);
+        let geom = GeomEncoder::new(GeomType::Point)
+            .point(5.0, 6.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let feature = feature.into_layer().into_feature(geom);
+        tile.add_layer(feature.into_layer()).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let layer = tile.layer("peaks").unwrap();
+        assert_eq!(layer.version(), 3);
+        let elevations: Vec<_> =
+            layer.features().map(|f| f.elevations().unwrap()).collect();
+        assert_eq!(elevations, [vec![350.25, -20.5], vec![]]);
+    }
+}
@@ -0,0 +1,343 @@
+// validate.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Geometry validation helpers.
+//!
+use crate::error::{Error, Result};
+use crate::simplify::{segment_distance, signed_area};
+
+/// Polygon ring type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RingType {
+    /// Exterior ring (positive area, clockwise in tile coördinates)
+    Exterior,
+
+    /// Interior ring (negative area, counter-clockwise in tile coördinates)
+    Interior,
+}
+
+/// Polygon ring information.
+///
+/// Use [GeomData::rings](struct.GeomData.html#method.rings) to classify.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct RingInfo {
+    /// Signed area (tile units squared)
+    pub area: f64,
+
+    /// Ring type
+    pub ring_type: RingType,
+}
+
+/// Rings of one polygon (exterior first)
+pub(crate) type Rings = Vec<Vec<(i32, i32)>>;
+
+/// Check if a point is inside (or on the boundary of) a ring.
+pub(crate) fn point_in_ring(pt: (i32, i32), ring: &[(i32, i32)]) -> bool {
+    let (px, py) = (i64::from(pt.0), i64::from(pt.1));
+    let n = ring.len();
+    let mut inside = false;
+    for i in 0..n {
+        let (x0, y0) = ring[i];
+        let (x1, y1) = ring[(i + 1) % n];
+        let (x0, y0) = (i64::from(x0), i64::from(y0));
+        let (x1, y1) = (i64::from(x1), i64::from(y1));
+        // check if point is on the edge
+        let cross = i128::from(x1 - x0) * i128::from(py - y0)
+            - i128::from(y1 - y0) * i128::from(px - x0);
+        if cross == 0
+            && px >= x0.min(x1)
+            && px <= x0.max(x1)
+            && py >= y0.min(y1)
+            && py <= y0.max(y1)
+        {
+            return true;
+        }
+        if (y0 > py) != (y1 > py) {
+            // X coördinate where edge crosses the point's Y
+            let xc = x0 as f64
+                + (py - y0) as f64 * (x1 - x0) as f64 / (y1 - y0) as f64;
+            if (px as f64) < xc {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Check if a ring is within another ring.
+pub(crate) fn ring_within(inner: &[(i32, i32)], outer: &[(i32, i32)]) -> bool {
+    inner.iter().all(|pt| point_in_ring(*pt, outer))
+}
+
+/// Calculate the orientation of three points (sign of cross product).
+fn orientation(a: (i32, i32), b: (i32, i32), c: (i32, i32)) -> i64 {
+    let (ax, ay) = (i128::from(a.0), i128::from(a.1));
+    let (bx, by) = (i128::from(b.0), i128::from(b.1));
+    let (cx, cy) = (i128::from(c.0), i128::from(c.1));
+    ((bx - ax) * (cy - ay) - (by - ay) * (cx - ax)).signum() as i64
+}
+
+/// Check if a point collinear with a segment lies on it.
+fn on_segment(p: (i32, i32), a: (i32, i32), b: (i32, i32)) -> bool {
+    p.0 >= a.0.min(b.0)
+        && p.0 <= a.0.max(b.0)
+        && p.1 >= a.1.min(b.1)
+        && p.1 <= a.1.max(b.1)
+}
+
+/// Check if two segments cross, touch or overlap.
+fn segments_intersect(
+    a0: (i32, i32),
+    a1: (i32, i32),
+    b0: (i32, i32),
+    b1: (i32, i32),
+) -> bool {
+    let o0 = orientation(a0, a1, b0);
+    let o1 = orientation(a0, a1, b1);
+    let o2 = orientation(b0, b1, a0);
+    let o3 = orientation(b0, b1, a1);
+    (o0 * o1 < 0 && o2 * o3 < 0)
+        || (o0 == 0 && on_segment(b0, a0, a1))
+        || (o1 == 0 && on_segment(b1, a0, a1))
+        || (o2 == 0 && on_segment(a0, b0, b1))
+        || (o3 == 0 && on_segment(a1, b0, b1))
+}
+
+/// Check if adjacent segments fold back on each other (zero-width spike).
+fn segments_fold(a0: (i32, i32), a1: (i32, i32), a2: (i32, i32)) -> bool {
+    let (x0, y0) = (i128::from(a0.0), i128::from(a0.1));
+    let (x1, y1) = (i128::from(a1.0), i128::from(a1.1));
+    let (x2, y2) = (i128::from(a2.0), i128::from(a2.1));
+    orientation(a0, a1, a2) == 0
+        && (x1 - x0) * (x2 - x1) + (y1 - y0) * (y2 - y1) < 0
+}
+
+/// Find a pair of crossing segments in a ring.
+///
+/// Segment `i` runs from vertex `i` to the next vertex.  Segments are swept
+/// in order of minimum X, checking each against the active segments which
+/// overlap it.  Returns a pair of segments which intersect (lower index
+/// first): non-adjacent segments which cross, touch or overlap, or adjacent
+/// segments which fold back on each other.  Repeated vertices are ignored.
+pub(crate) fn find_ring_crossing(
+    ring: &[(i32, i32)],
+) -> Option<(usize, usize)> {
+    // distinct vertices, with the index of the last of each repeated run
+    let mut pts: Vec<((i32, i32), usize)> = Vec::with_capacity(ring.len());
+    for (i, pt) in ring.iter().enumerate() {
+        match pts.last_mut() {
+            Some(last) if last.0 == *pt => last.1 = i,
+            _ => pts.push((*pt, i)),
+        }
+    }
+    while pts.len() > 1 && pts.first().map(|p| p.0) == pts.last().map(|p| p.0) {
+        pts.pop();
+    }
+    let n = pts.len();
+    if n < 3 {
+        return None;
+    }
+    let seg = |i: usize| (pts[i].0, pts[(i + 1) % n].0);
+    let x_range = |i: usize| {
+        let (p0, p1) = seg(i);
+        (p0.0.min(p1.0), p0.0.max(p1.0))
+    };
+    let found = |i: usize, j: usize| {
+        let (lo, hi) = (pts[i].1, pts[j].1);
+        Some((lo.min(hi), lo.max(hi)))
+    };
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|i| x_range(*i).0);
+    let mut active: Vec<usize> = Vec::new();
+    for i in order {
+        let (x_min, _) = x_range(i);
+        active.retain(|j| x_range(*j).1 >= x_min);
+        let (a0, a1) = seg(i);
+        for &j in &active {
+            let (b0, b1) = seg(j);
+            if (i + 1) % n == j {
+                if segments_fold(a0, a1, b1) {
+                    return found(i, j);
+                }
+            } else if (j + 1) % n == i {
+                if segments_fold(b0, b1, a1) {
+                    return found(i, j);
+                }
+            } else if segments_intersect(a0, a1, b0, b1) {
+                return found(i, j);
+            }
+        }
+        active.push(i);
+    }
+    None
+}
+
+/// Repair crossings in a ring, such as those caused by rounding.
+///
+/// For each crossing, the segment endpoint nearest to the other segment is
+/// removed.  Returns indices of the vertices to keep.
+pub(crate) fn repair_ring_crossings(ring: &[(i32, i32)]) -> Vec<usize> {
+    let mut keep: Vec<usize> = (0..ring.len()).collect();
+    let mut ring = ring.to_vec();
+    while ring.len() > 3 {
+        let Some((i, j)) = find_ring_crossing(&ring) else {
+            break;
+        };
+        let n = ring.len();
+        let (i1, j1) = ((i + 1) % n, (j + 1) % n);
+        let candidates = [(i, j, j1), (i1, j, j1), (j, i, i1), (j1, i, i1)];
+        let nearest = candidates
+            .iter()
+            .map(|(v, s0, s1)| {
+                (*v, segment_distance(ring[*v], ring[*s0], ring[*s1]))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(v, _)| v)
+            .unwrap_or(i);
+        log::debug!("removing crossing vertex: {:?}", ring[nearest]);
+        ring.remove(nearest);
+        keep.remove(nearest);
+    }
+    keep
+}
+
+/// Classify polygon rings by signed area.
+///
+/// Returns `Error::TooFewPoints` if a ring has fewer than 3 vertices,
+/// `Error::ZeroAreaRing` if a ring has zero area, or
+/// `Error::InteriorBeforeExterior` if an interior ring appears before any
+/// exterior ring.
+pub(crate) fn classify_rings(
+    rings: &[Vec<(i32, i32)>],
+) -> Result<Vec<RingInfo>> {
+    let mut infos = Vec::with_capacity(rings.len());
+    for ring in rings {
+        if ring.len() < 3 {
+            log::debug!("ring with too few vertices: {ring:?}");
+            return Err(Error::TooFewPoints {
+                needed: 4,
+                got: ring.len() + 1,
+            });
+        }
+        let area = signed_area(ring);
+        let ring_type = if area > 0.0 {
+            RingType::Exterior
+        } else if area < 0.0 {
+            if infos.is_empty() {
+                log::debug!("interior ring before exterior ring");
+                return Err(Error::InteriorBeforeExterior());
+            }
+            RingType::Interior
+        } else {
+            log::debug!("zero-area ring: {ring:?}");
+            return Err(Error::ZeroAreaRing());
+        };
+        infos.push(RingInfo { area, ring_type });
+    }
+    Ok(infos)
+}
+
+/// Group polygon rings into polygons by winding order.
+///
+/// Each exterior ring (positive area) starts a new polygon, followed by its
+/// interior rings.  Rings with zero area are skipped.
+///
+/// Returns `Error::InteriorBeforeExterior` if an interior ring appears
+/// before any exterior ring.
+pub(crate) fn group_polygons(rings: Rings) -> Result<Vec<Rings>> {
+    let mut polys: Vec<Rings> = Vec::new();
+    for ring in rings {
+        let area = signed_area(&ring);
+        if area > 0.0 {
+            polys.push(vec![ring]);
+        } else if area < 0.0 {
+            match polys.last_mut() {
+                Some(poly) => poly.push(ring),
+                None => {
+                    log::warn!("interior ring before exterior");
+                    return Err(Error::InteriorBeforeExterior());
+                }
+            }
+        } else {
+            log::debug!("skipping ring with zero area");
+        }
+    }
+    Ok(polys)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn in_ring() {
+        let ring = [(0, 0), (10, 0), (10, 10), (0, 10)];
+        assert!(point_in_ring((5, 5), &ring));
+        assert!(point_in_ring((0, 5), &ring));
+        assert!(point_in_ring((10, 10), &ring));
+        assert!(!point_in_ring((11, 5), &ring));
+        assert!(!point_in_ring((-1, -1), &ring));
+        assert!(ring_within(&[(2, 2), (2, 8), (8, 8)], &ring));
+        assert!(!ring_within(&[(2, 2), (2, 18), (8, 8)], &ring));
+    }
+
+    #[test]
+    fn crossings() {
+        let ring = [(0, 0), (10, 0), (10, 10), (0, 10)];
+        assert_eq!(find_ring_crossing(&ring), None);
+        let bowtie = [(0, 0), (10, 10), (10, 0), (0, 10)];
+        assert_eq!(find_ring_crossing(&bowtie), Some((0, 2)));
+        // segments 2 and 3 both cross segment 0; the sweep finds 3 first
+        let ring = [(0, 0), (20, 3), (20, 10), (10, 1), (0, 10)];
+        assert!(segments_intersect(ring[0], ring[1], ring[2], ring[3]));
+        assert_eq!(find_ring_crossing(&ring), Some((0, 3)));
+        assert_eq!(repair_ring_crossings(&ring), vec![0, 1, 2, 4]);
+        // repeated vertices
+        let ring = [(0, 0), (10, 0), (10, 0), (10, 10), (0, 10), (0, 0)];
+        assert_eq!(find_ring_crossing(&ring), None);
+    }
+
+    #[test]
+    fn touching() {
+        // vertex touching another segment
+        let ring = [(0, 0), (10, 0), (10, 10), (5, 0), (0, 10)];
+        assert_eq!(find_ring_crossing(&ring), Some((0, 3)));
+        // two vertices at the same position (figure eight)
+        let ring = [(0, 0), (10, 0), (5, 5), (10, 10), (0, 10), (5, 5)];
+        assert_eq!(find_ring_crossing(&ring), Some((1, 4)));
+    }
+
+    #[test]
+    fn collinear() {
+        // overlapping collinear segments
+        let ring = [(0, 0), (10, 0), (10, 5), (5, 5), (5, 0), (2, 0), (2, -5)];
+        assert_eq!(find_ring_crossing(&ring), Some((0, 4)));
+        // zero-width spike
+        let ring = [(0, 0), (10, 0), (20, 0), (10, 0), (10, 10)];
+        assert!(find_ring_crossing(&ring).is_some());
+        let keep = repair_ring_crossings(&ring);
+        assert!(keep.len() < ring.len());
+        let ring: Vec<_> = keep.iter().map(|i| ring[*i]).collect();
+        assert_eq!(find_ring_crossing(&ring), None);
+    }
+
+    #[test]
+    fn classify() {
+        let exterior = vec![(0, 0), (10, 0), (10, 10), (0, 10)];
+        let interior = vec![(2, 2), (2, 8), (8, 8), (8, 2)];
+        let infos =
+            classify_rings(&[exterior.clone(), interior.clone()]).unwrap();
+        assert_eq!(infos[0].area, 100.0);
+        assert_eq!(infos[0].ring_type, RingType::Exterior);
+        assert_eq!(infos[1].area, -36.0);
+        assert_eq!(infos[1].ring_type, RingType::Interior);
+        assert!(classify_rings(&[interior, exterior]).is_err());
+        assert!(classify_rings(&[vec![(0, 0), (5, 5), (10, 10)]]).is_err());
+        assert!(matches!(
+            classify_rings(&[vec![(0, 0), (5, 5)]]),
+            Err(Error::TooFewPoints { needed: 4, got: 3 })
+        ));
+    }
+}
@@ -0,0 +1,144 @@
+// value.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Tag values.
+//!
+use crate::vector_tile::tile::Value;
+
+/// 2^63, the first `f64` above the range of `i64`
+const I64_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+/// 2^64, the first `f64` above the range of `u64`
+const U64_LIMIT: f64 = 18_446_744_073_709_551_616.0;
+
+/// Convert an integral `f64` to `i64`
+fn f64_to_i64(v: f64) -> Option<i64> {
+    (v.fract() == 0.0 && (-I64_LIMIT..I64_LIMIT).contains(&v))
+        .then_some(v as i64)
+}
+
+/// Convert an integral `f64` to `u64`
+fn f64_to_u64(v: f64) -> Option<u64> {
+    (v.fract() == 0.0 && (0.0..U64_LIMIT).contains(&v)).then_some(v as u64)
+}
+
+/// Value of a feature tag.
+///
+/// Each variant corresponds to one of the value types in the MVT spec.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagValue {
+    /// String value
+    String(String),
+
+    /// Double (64-bit float) value
+    F64(f64),
+
+    /// Float (32-bit) value
+    F32(f32),
+
+    /// Int (64-bit signed) value
+    I64(i64),
+
+    /// Uint (64-bit unsigned) value
+    U64(u64),
+
+    /// Sint (64-bit signed, zigzag encoded) value
+    Sint(i64),
+
+    /// Boolean value
+    Bool(bool),
+}
+
+impl TagValue {
+    /// Convert from a protobuf value.
+    ///
+    /// Returns `None` if no value type is set.
+    pub(crate) fn from_value(value: &Value) -> Option<Self> {
+        if let Some(v) = &value.string_value {
+            Some(TagValue::String(v.clone()))
+        } else if let Some(v) = value.double_value {
+            Some(TagValue::F64(v))
+        } else if let Some(v) = value.float_value {
+            Some(TagValue::F32(v))
+        } else if let Some(v) = value.int_value {
+            Some(TagValue::I64(v))
+        } else if let Some(v) = value.uint_value {
+            Some(TagValue::U64(v))
+        } else if let Some(v) = value.sint_value {
+            Some(TagValue::Sint(v))
+        } else {
+            value.bool_value.map(TagValue::Bool)
+        }
+    }
+
+    /// Get a string value.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TagValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Get a boolean value.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TagValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Get a numeric value as `i64`, if it can be converted losslessly.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            TagValue::I64(v) | TagValue::Sint(v) => Some(v),
+            TagValue::U64(v) => i64::try_from(v).ok(),
+            TagValue::F64(v) => f64_to_i64(v),
+            TagValue::F32(v) => f64_to_i64(v.into()),
+            _ => None,
+        }
+    }
+
+    /// Get a numeric value as `u64`, if it can be converted losslessly.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            TagValue::U64(v) => Some(v),
+            TagValue::I64(v) | TagValue::Sint(v) => u64::try_from(v).ok(),
+            TagValue::F64(v) => f64_to_u64(v),
+            TagValue::F32(v) => f64_to_u64(v.into()),
+            _ => None,
+        }
+    }
+
+    /// Get a numeric value as `f64`, if it can be converted losslessly.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            TagValue::F64(v) => Some(v),
+            TagValue::F32(v) => Some(v.into()),
+            TagValue::I64(v) | TagValue::Sint(v) => {
+                let f = v as f64;
+                (f < I64_LIMIT && f as i64 == v).then_some(f)
+            }
+            TagValue::U64(v) => {
+                let f = v as f64;
+                (f < U64_LIMIT && f as u64 == v).then_some(f)
+            }
+            _ => None,
+        }
+    }
+
+    /// Convert into a protobuf value.
+    pub(crate) fn into_value(self) -> Value {
+        let mut value = Value::new();
+        match self {
+            TagValue::String(v) => value.set_string_value(v),
+            TagValue::F64(v) => value.set_double_value(v),
+            TagValue::F32(v) => value.set_float_value(v),
+            TagValue::I64(v) => value.set_int_value(v),
+            TagValue::U64(v) => value.set_uint_value(v),
+            TagValue::Sint(v) => value.set_sint_value(v),
+            TagValue::Bool(v) => value.set_bool_value(v),
+        }
+        value
+    }
+}
@@ -0,0 +1,602 @@
+// view.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Zero-copy decoding of MVT tiles.
+//!
+use crate::encoder::{GeomData, GeomType};
+use crate::error::{Error, Result};
+use crate::tile::Tile;
+use crate::value::TagValue;
+
+/// Wire type: varint
+pub(crate) const WIRE_VARINT: u64 = 0;
+
+/// Wire type: 64-bit fixed
+pub(crate) const WIRE_FIXED64: u64 = 1;
+
+/// Wire type: length-delimited
+const WIRE_LEN: u64 = 2;
+
+/// Wire type: 32-bit fixed
+const WIRE_FIXED32: u64 = 5;
+
+/// Borrowed view of an encoded tile.
+///
+/// Layer names, keys and string values are slices of the input buffer, so
+/// inspecting a tile does not allocate strings.  The structure of the whole
+/// tile is checked when parsing, so accessors do not fail.  Use [to_tile]
+/// for an owned [Tile].
+///
+/// The input can be any byte slice, such as a `bytes::Bytes` buffer.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{GeomEncoder, GeomType, Tile, TileView, ValueView};
+///
+/// let mut tile = Tile::new(4096);
+/// let layer = tile.create_layer("roads");
+/// let geom = GeomEncoder::new(GeomType::Point).point(1.0, 2.0)?.encode()?;
+/// let mut feature = layer.into_feature(geom);
+/// feature.add_tag_string("name", "Main St");
+/// tile.add_layer(feature.into_layer())?;
+/// let bytes = tile.to_bytes()?;
+///
+/// let view = TileView::parse(&bytes)?;
+/// let layer = view.layer("roads").unwrap();
+/// let feature = layer.features().next().unwrap();
+/// assert_eq!(feature.get("name"), Some(ValueView::String("Main St")));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [to_tile]: #method.to_tile
+#[derive(Clone, Debug)]
+pub struct TileView<'a> {
+    bytes: &'a [u8],
+    layers: Vec<LayerView<'a>>,
+}
+
+/// Borrowed view of a layer in a [TileView].
+#[derive(Clone, Debug)]
+pub struct LayerView<'a> {
+    name: &'a str,
+    version: u32,
+    extent: u32,
+    keys: Vec<&'a str>,
+    values: Vec<Option<ValueView<'a>>>,
+    features: Vec<RawFeature<'a>>,
+}
+
+/// Feature fields, with packed fields as raw slices
+#[derive(Clone, Debug)]
+struct RawFeature<'a> {
+    id: Option<u64>,
+    geom_type: Option<GeomType>,
+    tags: &'a [u8],
+    geometry: &'a [u8],
+}
+
+/// Borrowed view of a feature in a [LayerView].
+#[derive(Clone, Copy, Debug)]
+pub struct FeatureView<'v, 'a> {
+    layer: &'v LayerView<'a>,
+    feature: &'v RawFeature<'a>,
+}
+
+/// Borrowed tag value.
+///
+/// Convert to an owned [TagValue] with `TagValue::from`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueView<'a> {
+    /// String value
+    String(&'a str),
+
+    /// Double (64-bit float) value
+    F64(f64),
+
+    /// Float (32-bit) value
+    F32(f32),
+
+    /// Int (64-bit signed) value
+    I64(i64),
+
+    /// Uint (64-bit unsigned) value
+    U64(u64),
+
+    /// Sint (64-bit signed, zigzag encoded) value
+    Sint(i64),
+
+    /// Boolean value
+    Bool(bool),
+}
+
+impl From<ValueView<'_>> for TagValue {
+    fn from(value: ValueView<'_>) -> Self {
+        match value {
+            ValueView::String(v) => TagValue::String(v.to_string()),
+            ValueView::F64(v) => TagValue::F64(v),
+            ValueView::F32(v) => TagValue::F32(v),
+            ValueView::I64(v) => TagValue::I64(v),
+            ValueView::U64(v) => TagValue::U64(v),
+            ValueView::Sint(v) => TagValue::Sint(v),
+            ValueView::Bool(v) => TagValue::Bool(v),
+        }
+    }
+}
+
+/// Protobuf message reader
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Create a new reader
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Reader { buf }
+    }
+
+    /// Check if all data has been read
+    pub(crate) fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Read a varint
+    pub(crate) fn varint(&mut self) -> Result<u64> {
+        let mut v = 0;
+        for shift in (0..64).step_by(7) {
+            let (&b, rest) =
+                self.buf.split_first().ok_or(Error::InvalidData())?;
+            self.buf = rest;
+            v |= u64::from(b & 0x7F) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(Error::InvalidData())
+    }
+
+    /// Read a field key (number and wire type)
+    pub(crate) fn field(&mut self) -> Result<Option<(u64, u64)>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        if key >> 3 == 0 {
+            return Err(Error::InvalidData());
+        }
+        Ok(Some((key >> 3, key & 0x7)))
+    }
+
+    /// Read a number of bytes
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.buf.len() {
+            return Err(Error::InvalidData());
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    /// Read a length-delimited field
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = usize::try_from(self.varint()?)
+            .map_err(|_| Error::InvalidData())?;
+        self.take(len)
+    }
+
+    /// Read a string field
+    fn str(&mut self) -> Result<&'a str> {
+        std::str::from_utf8(self.bytes()?).map_err(|_| Error::InvalidData())
+    }
+
+    /// Read a 32-bit fixed field
+    fn fixed32(&mut self) -> Result<[u8; 4]> {
+        Ok(self.take(4)?.try_into().unwrap_or_default())
+    }
+
+    /// Read a 64-bit fixed field
+    pub(crate) fn fixed64(&mut self) -> Result<[u8; 8]> {
+        Ok(self.take(8)?.try_into().unwrap_or_default())
+    }
+
+    /// Skip a field
+    pub(crate) fn skip(&mut self, wire: u64) -> Result<()> {
+        match wire {
+            WIRE_VARINT => {
+                self.varint()?;
+            }
+            WIRE_FIXED64 => {
+                self.take(8)?;
+            }
+            WIRE_LEN => {
+                self.bytes()?;
+            }
+            WIRE_FIXED32 => {
+                self.take(4)?;
+            }
+            _ => return Err(Error::InvalidData()),
+        }
+        Ok(())
+    }
+
+    /// Read a packed repeated field, returning the number of values
+    fn packed(&mut self) -> Result<(&'a [u8], usize)> {
+        let bytes = self.bytes()?;
+        let mut reader = Reader::new(bytes);
+        let mut n = 0;
+        while !reader.is_empty() {
+            reader.varint()?;
+            n += 1;
+        }
+        Ok((bytes, n))
+    }
+}
+
+/// Check the wire type of a field
+fn check_wire(wire: u64, expected: u64) -> Result<()> {
+    if wire == expected {
+        Ok(())
+    } else {
+        Err(Error::InvalidData())
+    }
+}
+
+/// Iterate over values of a (checked) packed field
+fn packed_values(buf: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    let mut reader = Reader::new(buf);
+    std::iter::from_fn(move || {
+        if reader.is_empty() {
+            None
+        } else {
+            reader.varint().ok().map(|v| v as u32)
+        }
+    })
+}
+
+impl<'a> TileView<'a> {
+    /// Parse a view of an encoded tile.
+    ///
+    /// * `bytes` Encoded tile data.
+    ///
+    /// Returns `Error::InvalidData` if the protobuf data is malformed.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let mut layers = Vec::new();
+        while let Some((field, wire)) = reader.field()? {
+            match field {
+                3 => {
+                    check_wire(wire, WIRE_LEN)?;
+                    layers.push(LayerView::parse(reader.bytes()?)?);
+                }
+                _ => reader.skip(wire)?,
+            }
+        }
+        Ok(TileView { bytes, layers })
+    }
+
+    /// Get the number of layers.
+    pub fn num_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Get an iterator of layers.
+    pub fn layers(&self) -> impl Iterator<Item = &LayerView<'a>> {
+        self.layers.iter()
+    }
+
+    /// Get a layer by name.
+    pub fn layer(&self, name: &str) -> Option<&LayerView<'a>> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+
+    /// Decode an owned tile.
+    pub fn to_tile(&self) -> Result<Tile> {
+        Tile::from_bytes(self.bytes)
+    }
+}
+
+impl<'a> LayerView<'a> {
+    /// Parse a layer message
+    fn parse(buf: &'a [u8]) -> Result<Self> {
+        let mut reader = Reader::new(buf);
+        let mut name = None;
+        let mut layer = LayerView {
+            name: "",
+            version: 1,
+            extent: 4096,
+            keys: Vec::new(),
+            values: Vec::new(),
+            features: Vec::new(),
+        };
+        while let Some((field, wire)) = reader.field()? {
+            match field {
+                1 => {
+                    check_wire(wire, WIRE_LEN)?;
+                    name = Some(reader.str()?);
+                }
+                2 => {
+                    check_wire(wire, WIRE_LEN)?;
+                    layer.features.push(RawFeature::parse(reader.bytes()?)?);
+                }
+                3 => {
+                    check_wire(wire, WIRE_LEN)?;
+                    layer.keys.push(reader.str()?);
+                }
+                4 => {
+                    check_wire(wire, WIRE_LEN)?;
+                    layer.values.push(parse_value(reader.bytes()?)?);
+                }
+                5 => {
+                    check_wire(wire, WIRE_VARINT)?;
+                    layer.extent = reader.varint()? as u32;
+                }
+                15 => {
+                    check_wire(wire, WIRE_VARINT)?;
+                    layer.version = reader.varint()? as u32;
+                }
+                _ => reader.skip(wire)?,
+            }
+        }
+        // name is a required field
+        layer.name = name.ok_or(Error::InvalidData())?;
+        Ok(layer)
+    }
+
+    /// Get the layer name.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Get the layer version.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Get the layer extent.
+    pub fn extent(&self) -> u32 {
+        self.extent
+    }
+
+    /// Get the key table.
+    pub fn keys(&self) -> &[&'a str] {
+        &self.keys
+    }
+
+    /// Get number of features (count).
+    pub fn num_features(&self) -> usize {
+        self.features.len()
+    }
+
+    /// Get an iterator of features.
+    pub fn features(&self) -> impl Iterator<Item = FeatureView<'_, 'a>> {
+        self.features.iter().map(move |feature| FeatureView {
+            layer: self,
+            feature,
+        })
+    }
+}
+
+impl<'a> RawFeature<'a> {
+    /// Parse a feature message
+    fn parse(buf: &'a [u8]) -> Result<Self> {
+        let mut reader = Reader::new(buf);
+        let mut feature = RawFeature {
+            id: None,
+            geom_type: None,
+            tags: &[],
+            geometry: &[],
+        };
+        while let Some((field, wire)) = reader.field()? {
+            match field {
+                1 => {
+                    check_wire(wire, WIRE_VARINT)?;
+                    feature.id = Some(reader.varint()?);
+                }
+                2 => {
+                    // tags must be packed
+                    check_wire(wire, WIRE_LEN)?;
+                    let (tags, n) = reader.packed()?;
+                    if n % 2 != 0 {
+                        return Err(Error::InvalidData());
+                    }
+                    feature.tags = tags;
+                }
+                3 => {
+                    check_wire(wire, WIRE_VARINT)?;
+                    feature.geom_type = match reader.varint()? {
+                        1 => Some(GeomType::Point),
+                        2 => Some(GeomType::Linestring),
+                        3 => Some(GeomType::Polygon),
+                        _ => None,
+                    };
+                }
+                4 => {
+                    // geometry must be packed
+                    check_wire(wire, WIRE_LEN)?;
+                    feature.geometry = reader.packed()?.0;
+                }
+                _ => reader.skip(wire)?,
+            }
+        }
+        Ok(feature)
+    }
+}
+
+/// Parse a value message
+fn parse_value(buf: &[u8]) -> Result<Option<ValueView<'_>>> {
+    let mut reader = Reader::new(buf);
+    let mut values = [None; 7];
+    while let Some((field, wire)) = reader.field()? {
+        let value = match field {
+            1 => {
+                check_wire(wire, WIRE_LEN)?;
+                ValueView::String(reader.str()?)
+            }
+            2 => {
+                check_wire(wire, WIRE_FIXED32)?;
+                ValueView::F32(f32::from_le_bytes(reader.fixed32()?))
+            }
+            3 => {
+                check_wire(wire, WIRE_FIXED64)?;
+                ValueView::F64(f64::from_le_bytes(reader.fixed64()?))
+            }
+            4 => {
+                check_wire(wire, WIRE_VARINT)?;
+                ValueView::I64(reader.varint()? as i64)
+            }
+            5 => {
+                check_wire(wire, WIRE_VARINT)?;
+                ValueView::U64(reader.varint()?)
+            }
+            6 => {
+                check_wire(wire, WIRE_VARINT)?;
+                let v = reader.varint()?;
+                ValueView::Sint((v >> 1) as i64 ^ -((v & 1) as i64))
+            }
+            7 => {
+                check_wire(wire, WIRE_VARINT)?;
+                ValueView::Bool(reader.varint()? != 0)
+            }
+            _ => {
+                reader.skip(wire)?;
+                continue;
+            }
+        };
+        values[field as usize - 1] = Some(value);
+    }
+    // same precedence as owned decoding
+    Ok([0, 2, 1, 3, 4, 5, 6].into_iter().find_map(|i| values[i]))
+}
+
+impl<'v, 'a> FeatureView<'v, 'a> {
+    /// Get the feature ID (if set).
+    pub fn id(&self) -> Option<u64> {
+        self.feature.id
+    }
+
+    /// Get the geometry type.
+    ///
+    /// Returns `None` for unknown geometry.
+    pub fn geom_type(&self) -> Option<GeomType> {
+        self.feature.geom_type
+    }
+
+    /// Get an iterator of encoded geometry commands and parameters.
+    pub fn geometry(&self) -> impl Iterator<Item = u32> + 'a {
+        packed_values(self.feature.geometry)
+    }
+
+    /// Get the geometry data (allocated).
+    ///
+    /// Returns `None` for unknown geometry.
+    pub fn geom_data(&self) -> Option<GeomData> {
+        let geom_tp = self.geom_type()?;
+        Some(GeomData::from_vec(geom_tp, self.geometry().collect()))
+    }
+
+    /// Get the tags (key / value pairs).
+    ///
+    /// Tags with invalid key or value indices are skipped.
+    pub fn tags(&self) -> impl Iterator<Item = (&'a str, ValueView<'a>)> + 'v {
+        let layer = self.layer;
+        let mut values = packed_values(self.feature.tags);
+        std::iter::from_fn(move || {
+            let k = values.next()?;
+            let v = values.next()?;
+            Some((k, v))
+        })
+        .filter_map(move |(k, v)| {
+            let key = layer.keys.get(k as usize);
+            let value = layer.values.get(v as usize).copied().flatten();
+            match (key, value) {
+                (Some(key), Some(value)) => Some((*key, value)),
+                _ => {
+                    log::warn!("Invalid tag indices: [{k}, {v}]");
+                    None
+                }
+            }
+        })
+    }
+
+    /// Get the value of a tag.
+    ///
+    /// * `key` Tag key.
+    ///
+    /// If a key is repeated, the first value is used.
+    pub fn get(&self, key: &str) -> Option<ValueView<'a>> {
+        self.tags().find(|(k, _v)| *k == key).map(|(_k, v)| v)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::make_tile;
+
+    fn road_tile() -> Tile {
+        let pts = [(1.0, 2.0), (10.0, 20.0)];
+        let mut tile = make_tile("roads", GeomType::Linestring, &pts, |f| {
+            f.set_id(7);
+            f.add_tag_string("name", "Main St");
+            f.add_tag_double("d", 1.5);
+            f.add_tag_float("f", 2.5);
+            f.add_tag_int("i", -3);
+            f.add_tag_uint("u", 4);
+            f.add_tag_sint("s", -5);
+            f.add_tag_bool("b", true);
+        });
+        tile.add_layer(tile.create_layer("empty")).unwrap();
+        tile
+    }
+
+    #[test]
+    fn view() {
+        let tile = road_tile();
+        let bytes = tile.to_bytes().unwrap();
+        let view = TileView::parse(&bytes).unwrap();
+        assert_eq!(view.num_layers(), 2);
+        assert!(view.layer("missing").is_none());
+        let layer = view.layer("roads").unwrap();
+        assert_eq!(layer.version(), 2);
+        assert_eq!(layer.extent(), 4096);
+        assert_eq!(layer.keys().len(), 7);
+        let feature = layer.features().next().unwrap();
+        assert_eq!(feature.id(), Some(7));
+        assert_eq!(feature.geom_type(), Some(GeomType::Linestring));
+        let owned = tile.layer("roads").unwrap().features().next().unwrap();
+        assert_eq!(
+            feature.geometry().collect::<Vec<_>>(),
+            owned.geom_data().unwrap().into_vec()
+        );
+        let tags: Vec<_> = feature
+            .tags()
+            .map(|(k, v)| (k, TagValue::from(v)))
+            .collect();
+        assert_eq!(tags, owned.tags().collect::<Vec<_>>());
+        assert_eq!(feature.get("s"), Some(ValueView::Sint(-5)));
+        assert_eq!(feature.get("x"), None);
+        let owned = view.to_tile().unwrap();
+        assert_eq!(owned.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn malformed() {
+        let bytes = road_tile().to_bytes().unwrap();
+        for len in 1..bytes.len() {
+            match TileView::parse(&bytes[..len]) {
+                // truncated at the end of the first layer
+                Ok(view) => assert_eq!(view.num_layers(), 1),
+                Err(e) => assert!(matches!(e, Error::InvalidData()), "{len}"),
+            }
+        }
+        // group wire type
+        assert!(TileView::parse(&[0x1B]).is_err());
+        // field number 0
+        assert!(TileView::parse(&[0x02, 0x00]).is_err());
+        // layer without name
+        assert!(TileView::parse(&[0x1A, 0x02, 0x28, 0x01]).is_err());
+    }
+}
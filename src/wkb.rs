@@ -0,0 +1,373 @@
+// wkb.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Well-known binary (WKB / EWKB) geometry input.
+//!
+use crate::encoder::{GeomEncoder, GeomType};
+use crate::error::{Error, Result};
+use pointy::Float;
+
+/// EWKB flag for Z coördinates
+const EWKB_Z: u32 = 0x8000_0000;
+
+/// EWKB flag for M coördinates
+const EWKB_M: u32 = 0x4000_0000;
+
+/// EWKB flag for SRID
+const EWKB_SRID: u32 = 0x2000_0000;
+
+/// WKB reader
+struct WkbReader<'a> {
+    /// Remaining data
+    data: &'a [u8],
+    /// Little endian byte order
+    little: bool,
+}
+
+/// WKB geometry header
+struct Header {
+    /// Geometry type code (1 to 7)
+    code: u32,
+    /// Number of coördinate dimensions
+    dims: usize,
+}
+
+impl<'a> WkbReader<'a> {
+    /// Create a new WKB reader
+    fn new(data: &'a [u8]) -> Self {
+        WkbReader { data, little: true }
+    }
+
+    /// Read some bytes
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let Some((bytes, rest)) = self.data.split_first_chunk::<N>() else {
+            log::debug!("WKB: unexpected end of data");
+            return Err(Error::InvalidGeometry());
+        };
+        self.data = rest;
+        Ok(*bytes)
+    }
+
+    /// Read a u32 value
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes()?;
+        Ok(if self.little {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// Read an f64 value
+    fn f64(&mut self) -> Result<f64> {
+        let bytes = self.bytes()?;
+        Ok(if self.little {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    }
+
+    /// Read a geometry header
+    fn header(&mut self) -> Result<Header> {
+        self.little = match self.bytes::<1>()? {
+            [0] => false,
+            [1] => true,
+            [b] => {
+                log::debug!("WKB: invalid byte order {b}");
+                return Err(Error::InvalidGeometry());
+            }
+        };
+        let tp = self.u32()?;
+        if tp & EWKB_SRID != 0 {
+            let _srid = self.u32()?;
+        }
+        let mut dims = 2;
+        if tp & EWKB_Z != 0 {
+            dims += 1;
+        }
+        if tp & EWKB_M != 0 {
+            dims += 1;
+        }
+        // ISO WKB: 1000 (Z), 2000 (M) or 3000 (ZM) added to type
+        let iso = tp & 0x0FFF_FFFF;
+        dims += match iso / 1000 {
+            1 | 2 => 1,
+            3 => 2,
+            _ => 0,
+        };
+        Ok(Header {
+            code: iso % 1000,
+            dims,
+        })
+    }
+
+    /// Read a point, skipping extra dimensions
+    fn point(&mut self, dims: usize) -> Result<(f64, f64)> {
+        let x = self.f64()?;
+        let y = self.f64()?;
+        for _ in 2..dims {
+            self.f64()?;
+        }
+        Ok((x, y))
+    }
+}
+
+/// Get the geometry type for a WKB type code
+fn geom_type(code: u32) -> Option<GeomType> {
+    match code {
+        1 | 4 => Some(GeomType::Point),
+        2 | 5 => Some(GeomType::Linestring),
+        3 | 6 => Some(GeomType::Polygon),
+        _ => None,
+    }
+}
+
+/// Get the geometry type of WKB or EWKB geometry.
+///
+/// Returns `Error::InvalidGeometry` for malformed data or geometry
+/// collections.
+pub(crate) fn wkb_geom_type(wkb: &[u8]) -> Result<GeomType> {
+    let header = WkbReader::new(wkb).header()?;
+    geom_type(header.code).ok_or(Error::InvalidGeometry())
+}
+
+impl<F> GeomEncoder<F>
+where
+    F: Float,
+{
+    /// Create an encoder from WKB or EWKB geometry.
+    ///
+    /// The geometry type is determined from the geometry.  Since no
+    /// transform is set, coördinates must already be in tile units; use
+    /// [GeomEncoder::add_wkb] to set up the encoder first.
+    ///
+    /// Returns `Error::InvalidGeometry` for malformed data or geometry
+    /// collections.
+    pub fn from_wkb(wkb: &[u8]) -> Result<Self> {
+        GeomEncoder::new(wkb_geom_type(wkb)?).add_wkb(wkb)
+    }
+
+    /// Add WKB or EWKB geometry, taking ownership (for method chaining).
+    ///
+    /// Each part (linestring or polygon ring) is completed after it is
+    /// added.  Closing vertices of polygon rings are skipped, and Z / M
+    /// values are ignored.
+    ///
+    /// Returns `Error::InvalidGeometry` for malformed data, or
+    /// `Error::WrongGeomType` if the geometry does not match the encoder's
+    /// geometry type.
+    pub fn add_wkb(mut self, wkb: &[u8]) -> Result<Self> {
+        let mut rdr = WkbReader::new(wkb);
+        self.add_wkb_geometry(&mut rdr)?;
+        Ok(self)
+    }
+
+    /// Add one WKB geometry.
+    ///
+    /// Members of a multi geometry must be the matching single type, so
+    /// nested multi geometries are rejected (without recursion).
+    fn add_wkb_geometry(&mut self, rdr: &mut WkbReader) -> Result<()> {
+        let header = rdr.header()?;
+        match geom_type(header.code) {
+            Some(tp) if tp == self.geom_type() => (),
+            Some(_) => return Err(Error::WrongGeomType(self.geom_type())),
+            None => return Err(Error::InvalidGeometry()),
+        }
+        if header.code <= 3 {
+            return self.add_wkb_single(rdr, header);
+        }
+        let n = rdr.u32()?;
+        for _ in 0..n {
+            let member = rdr.header()?;
+            if member.code != header.code - 3 {
+                log::debug!("WKB: invalid member type {}", member.code);
+                return Err(Error::InvalidGeometry());
+            }
+            self.add_wkb_single(rdr, member)?;
+        }
+        Ok(())
+    }
+
+    /// Add a single (not multi) WKB geometry
+    fn add_wkb_single(
+        &mut self,
+        rdr: &mut WkbReader,
+        header: Header,
+    ) -> Result<()> {
+        match header.code {
+            1 => self.add_wkb_points(rdr, header.dims, 1),
+            2 => self.add_wkb_points(rdr, header.dims, 0),
+            _ => self.add_wkb_polygon(rdr, header.dims),
+        }
+    }
+
+    /// Add WKB points, reading the count if `n` is zero
+    fn add_wkb_points(
+        &mut self,
+        rdr: &mut WkbReader,
+        dims: usize,
+        n: u32,
+    ) -> Result<()> {
+        let n = if n > 0 { n } else { rdr.u32()? };
+        for _ in 0..n {
+            let (x, y) = rdr.point(dims)?;
+            self.add_point(float(x)?, float(y)?)?;
+        }
+        self.complete_geom()
+    }
+
+    /// Add WKB polygon rings
+    fn add_wkb_polygon(
+        &mut self,
+        rdr: &mut WkbReader,
+        dims: usize,
+    ) -> Result<()> {
+        let rings = rdr.u32()?;
+        for _ in 0..rings {
+            let n = rdr.u32()?;
+            let mut first = None;
+            for i in 0..n {
+                let pt = rdr.point(dims)?;
+                if i == 0 {
+                    first = Some(pt);
+                } else if i + 1 == n && Some(pt) == first {
+                    break;
+                }
+                self.add_point(float(pt.0)?, float(pt.1)?)?;
+            }
+            self.complete_geom()?;
+        }
+        Ok(())
+    }
+}
+
+/// Convert an f64 value to a float
+fn float<F: Float>(v: f64) -> Result<F> {
+    F::from(v).ok_or(Error::InvalidGeometry())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Build little endian WKB
+    fn wkb(tp: u32, body: &[f64], counts: &[(usize, u32)]) -> Vec<u8> {
+        let mut v = vec![1];
+        v.extend(tp.to_le_bytes());
+        let mut i = 0;
+        for (pos, count) in counts {
+            while i < *pos {
+                v.extend(body[i].to_le_bytes());
+                i += 1;
+            }
+            v.extend(count.to_le_bytes());
+        }
+        for x in &body[i..] {
+            v.extend(x.to_le_bytes());
+        }
+        v
+    }
+
+    #[test]
+    fn point() {
+        let data = wkb(1, &[25.0, 17.0], &[]);
+        let geom = GeomEncoder::<f64>::from_wkb(&data)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.into_vec(), vec![9, 50, 34]);
+        // big endian
+        let mut data = vec![0];
+        data.extend(1u32.to_be_bytes());
+        data.extend(25.0f64.to_be_bytes());
+        data.extend(17.0f64.to_be_bytes());
+        let geom = GeomEncoder::<f64>::from_wkb(&data)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.into_vec(), vec![9, 50, 34]);
+    }
+
+    #[test]
+    fn ewkb_linestring() {
+        // SRID 3857, with Z
+        let mut data = vec![1];
+        data.extend((2 | EWKB_Z | EWKB_SRID).to_le_bytes());
+        data.extend(3857u32.to_le_bytes());
+        data.extend(3u32.to_le_bytes());
+        for x in [2.0, 2.0, 9.0, 2.0, 10.0, 9.0, 10.0, 10.0, 9.0f64] {
+            data.extend(x.to_le_bytes());
+        }
+        let geom = GeomEncoder::<f64>::from_wkb(&data)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.into_vec(), vec![9, 4, 4, 18, 0, 16, 16, 0]);
+    }
+
+    #[test]
+    fn polygon() {
+        let data = wkb(
+            3,
+            &[3.0, 6.0, 8.0, 12.0, 20.0, 34.0, 3.0, 6.0],
+            &[(0, 1), (0, 4)],
+        );
+        let geom = GeomEncoder::<f64>::from_wkb(&data)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.into_vec(), vec![9, 6, 12, 18, 10, 12, 24, 44, 15]);
+    }
+
+    #[test]
+    fn multi_point() {
+        let mut data = vec![1];
+        data.extend(4u32.to_le_bytes());
+        data.extend(2u32.to_le_bytes());
+        data.extend(wkb(1, &[5.0, 7.0], &[]));
+        data.extend(wkb(1, &[3.0, 2.0], &[]));
+        let geom = GeomEncoder::<f64>::from_wkb(&data)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.into_vec(), vec![17, 10, 14, 3, 9]);
+    }
+
+    #[test]
+    fn nested_multi() {
+        let mut data = Vec::new();
+        for _ in 0..3 {
+            data.push(1);
+            data.extend(4u32.to_le_bytes());
+            data.extend(1u32.to_le_bytes());
+        }
+        data.extend(wkb(1, &[5.0, 7.0], &[]));
+        assert!(matches!(
+            GeomEncoder::<f64>::from_wkb(&data),
+            Err(Error::InvalidGeometry())
+        ));
+        // collection within multi geometry
+        let mut data = vec![1];
+        data.extend(4u32.to_le_bytes());
+        data.extend(1u32.to_le_bytes());
+        data.extend(wkb(7, &[], &[(0, 0)]));
+        assert!(matches!(
+            GeomEncoder::<f64>::from_wkb(&data),
+            Err(Error::InvalidGeometry())
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(GeomEncoder::<f64>::from_wkb(&[1, 1, 0]).is_err());
+        assert!(GeomEncoder::<f64>::from_wkb(&wkb(1, &[5.0], &[])).is_err());
+        assert!(GeomEncoder::<f64>::from_wkb(&wkb(7, &[], &[])).is_err());
+        let data = wkb(1, &[25.0, 17.0], &[]);
+        assert!(matches!(
+            GeomEncoder::<f64>::new(GeomType::Polygon).add_wkb(&data),
+            Err(Error::WrongGeomType(GeomType::Polygon))
+        ));
+    }
+}
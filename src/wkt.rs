@@ -0,0 +1,109 @@
+// wkt.rs
+//
+// Copyright (c) 2019-2024  Minnesota Department of Transportation
+//
+//! Well-known text (WKT) geometry input.
+//!
+use crate::encoder::GeomEncoder;
+use crate::error::{Error, Result};
+use geo_types::{CoordFloat, Geometry};
+use pointy::Float;
+use std::str::FromStr;
+use wkt::{TryFromWkt, WktNum};
+
+/// Parse WKT into `geo-types` geometry
+fn parse<F>(wkt: &str) -> Result<Geometry<F>>
+where
+    F: CoordFloat + FromStr + Default + WktNum,
+{
+    Geometry::try_from_wkt_str(wkt).map_err(|e| {
+        log::debug!("WKT: {e}");
+        Error::InvalidGeometry()
+    })
+}
+
+impl<F> GeomEncoder<F>
+where
+    F: Float + CoordFloat + FromStr + Default + WktNum,
+{
+    /// Create an encoder from WKT geometry.
+    ///
+    /// The geometry type is determined from the geometry.  Since no
+    /// transform is set, coördinates must already be in tile units; use
+    /// [GeomEncoder::add_wkt] to set up the encoder first.
+    ///
+    /// ```rust
+    /// # use mvt::GeomEncoder;
+    /// let geom = GeomEncoder::<f64>::from_wkt("POLYGON((3 6,8 12,20 34,3 6))")
+    ///     .unwrap()
+    ///     .encode()
+    ///     .unwrap();
+    /// assert_eq!(geom.len(), 9);
+    /// ```
+    ///
+    /// Returns `Error::InvalidGeometry` for invalid WKT or geometry
+    /// collections.
+    pub fn from_wkt(wkt: &str) -> Result<Self> {
+        GeomEncoder::from_geometry(&parse(wkt)?)
+    }
+
+    /// Add WKT geometry, taking ownership (for method chaining).
+    ///
+    /// Returns `Error::InvalidGeometry` for invalid WKT, or
+    /// `Error::WrongGeomType` if the geometry does not match the encoder's
+    /// geometry type.
+    pub fn add_wkt(self, wkt: &str) -> Result<Self> {
+        self.add_geometry(&parse(wkt)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomType;
+
+    // Examples from MVT spec:
+    #[test]
+    fn spec_examples() {
+        let cases: [(&str, &[u32]); 6] = [
+            ("POINT(25 17)", &[9, 50, 34]),
+            ("MULTIPOINT((5 7),(3 2))", &[17, 10, 14, 3, 9]),
+            ("LINESTRING(2 2,2 10,10 10)", &[9, 4, 4, 18, 0, 16, 16, 0]),
+            (
+                "MULTILINESTRING((2 2,2 10,10 10),(1 1,3 5))",
+                &[9, 4, 4, 18, 0, 16, 16, 0, 9, 17, 17, 10, 4, 8],
+            ),
+            (
+                "POLYGON((3 6,8 12,20 34,3 6))",
+                &[9, 6, 12, 18, 10, 12, 24, 44, 15],
+            ),
+            (
+                "MULTIPOLYGON(((0 0,10 0,10 10,0 10,0 0)),\
+                 ((11 11,20 11,20 20,11 20,11 11),\
+                 (13 13,13 17,17 17,17 13,13 13)))",
+                &[
+                    9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15, 9, 22, 2, 26, 18, 0,
+                    0, 18, 17, 0, 15, 9, 4, 13, 26, 0, 8, 8, 0, 0, 7, 15,
+                ],
+            ),
+        ];
+        for (wkt, data) in cases {
+            let geom =
+                GeomEncoder::<f64>::from_wkt(wkt).unwrap().encode().unwrap();
+            assert_eq!(geom.into_vec(), data, "{wkt}");
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(GeomEncoder::<f64>::from_wkt("POINT(1)").is_err());
+        assert!(
+            GeomEncoder::<f64>::from_wkt("GEOMETRYCOLLECTION EMPTY").is_err()
+        );
+        assert!(
+            GeomEncoder::<f64>::new(GeomType::Linestring)
+                .add_wkt("POINT(1 2)")
+                .is_err()
+        );
+    }
+}